@@ -0,0 +1,165 @@
+//! Local analysis stream over WebSocket
+//!
+//! Browser overlays, OBS sources and companion visualizers connect to
+//! `ws://127.0.0.1:<port>` and receive every bridge frame as a JSON
+//! `AnalysisResult`, without going through Tauri events. Incoming messages
+//! are ignored.
+
+use crate::audio::AnalysisResult;
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Frames buffered per client before a slow one starts skipping
+const CLIENT_BUFFER: usize = 32;
+
+/// Running analysis server; stops accepting and closes every client when
+/// dropped
+pub struct AnalysisServer {
+    port: u16,
+    frames: broadcast::Sender<String>,
+    accept_task: JoinHandle<()>,
+}
+
+impl AnalysisServer {
+    /// Listen on `127.0.0.1:port` (0 picks a free port)
+    pub async fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let port = listener.local_addr()?.port();
+        let (frames, _) = broadcast::channel(CLIENT_BUFFER);
+        let accept_task = tokio::spawn(accept_loop(listener, frames.clone()));
+        log::info!("Analysis server listening on ws://127.0.0.1:{}", port);
+        Ok(Self {
+            port,
+            frames,
+            accept_task,
+        })
+    }
+
+    /// Port actually listened on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Number of connected clients
+    pub fn client_count(&self) -> usize {
+        self.frames.receiver_count()
+    }
+
+    /// Send a frame to every connected client; skipped when nobody is
+    /// listening so idle servers cost nothing
+    pub fn publish(&self, analysis: &AnalysisResult) {
+        if self.frames.receiver_count() == 0 {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string(analysis) {
+            let _ = self.frames.send(json);
+        }
+    }
+}
+
+impl Drop for AnalysisServer {
+    fn drop(&mut self) {
+        // Client tasks see the channel close once the sender is gone
+        self.accept_task.abort();
+    }
+}
+
+async fn accept_loop(listener: TcpListener, frames: broadcast::Sender<String>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                log::debug!("Analysis client connected from {}", addr);
+                tokio::spawn(serve_client(stream, frames.subscribe()));
+            }
+            Err(e) => log::warn!("Analysis server accept failed: {}", e),
+        }
+    }
+}
+
+async fn serve_client(stream: TcpStream, mut frames: broadcast::Receiver<String>) {
+    let ws = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            log::debug!("Analysis client handshake failed: {}", e);
+            return;
+        }
+    };
+    let (mut write, mut read) = ws.split();
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(json) => {
+                    if write.send(Message::Text(json.into())).await.is_err() {
+                        break;
+                    }
+                }
+                // A slow client just misses frames
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => {
+                    let _ = write.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+            msg = read.next() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    log::debug!("Analysis client disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn streams_frames_to_connected_clients() {
+        let server = AnalysisServer::bind(0).await.unwrap();
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}", server.port()))
+                .await
+                .unwrap();
+
+        // The client is subscribed once the server has accepted it
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while server.client_count() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("client never subscribed");
+
+        server.publish(&AnalysisResult {
+            bands: [0.5, 0.0, 0.0, 0.0, 0.0],
+            bpm: 128.0,
+            ..Default::default()
+        });
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let frame: serde_json::Value = serde_json::from_str(msg.to_text().unwrap()).unwrap();
+        assert_eq!(frame["bpm"], 128.0);
+        assert_eq!(frame["bands"][0], 0.5);
+
+        // Dropping the server closes the stream
+        drop(server);
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match ws.next().await {
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        })
+        .await;
+        assert!(closed.is_ok());
+    }
+}
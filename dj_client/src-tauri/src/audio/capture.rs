@@ -1,11 +1,15 @@
 //! Audio capture implementation using a dedicated thread
 
+use super::fft::SILENCE_RMS;
+use super::file_source::{FILE_SOURCE_PREFIX, FileDecoder, FilePlayback, start_file_playback};
 use super::{AudioConfig, BassLane, FftAnalyzer};
 use crate::voice::VoiceStreamer;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Device, SampleFormat, StreamConfig};
 use parking_lot::Mutex;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::thread::{self, JoinHandle};
 use thiserror::Error;
@@ -33,10 +37,13 @@ pub enum CaptureError {
 
     #[error("Thread error: {0}")]
     ThreadError(String),
+
+    #[error("Can't mix in {0}: only audio devices can be added to a mix")]
+    NotMixable(String),
 }
 
 /// Capture mode indicator for UI feedback
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 #[serde(tag = "mode")]
 pub enum CaptureMode {
     /// Waiting for audio thread to report
@@ -48,20 +55,35 @@ pub enum CaptureMode {
         /// If this is a fallback from per-app capture, the reason
         fallback_reason: Option<String>,
     },
-    /// Per-process loopback capture (Windows only)
+    /// Per-process capture (Windows Process Loopback, macOS ScreenCaptureKit, Linux PipeWire)
     #[serde(rename = "process_loopback")]
     ProcessLoopback { pid: u32, name: String },
     /// Input device (microphone/line-in)
     #[serde(rename = "input_device")]
     InputDevice,
+    /// Audio file played back in real time
+    #[serde(rename = "file_playback")]
+    FilePlayback { path: String },
+    /// The capture device disappeared and no fallback device was available
+    #[serde(rename = "lost")]
+    Lost { reason: String },
 }
 
 /// FFT analysis result (Send-safe)
-#[derive(Debug, Clone, Default)]
+///
+/// Deserializable (missing fields default) so pattern previews can take
+/// hand-written analysis fixtures; serialized as-is by the local analysis
+/// server.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct AnalysisResult {
     /// Frequency bands (bass, low, mid, high, air)
     pub bands: [f32; 5],
 
+    /// Sub-bass rumble from 20Hz up to the bass band (0-1), smoothed and
+    /// scaled like `bands`
+    pub sub_bass: f32,
+
     /// Peak amplitude
     pub peak: f32,
 
@@ -80,11 +102,222 @@ pub struct AnalysisResult {
     /// Position within the beat cycle [0, 1)
     pub beat_phase: f32,
 
+    /// `beat_phase` from a phase-locked accumulator: advances continuously
+    /// at the estimated BPM and only eases toward detected beats, so it
+    /// never steps backward (other than wrapping from 1 to 0)
+    pub beat_phase_smooth: f32,
+
     /// Instant bass energy from bass lane IIR filter (0-1), ~1ms latency
     pub instant_bass: f32,
 
     /// Instant kick detected by bass lane onset detector
     pub instant_kick: bool,
+
+    /// Bass lane envelope before normalization (absolute filter output)
+    pub bass_raw_envelope: f32,
+
+    /// Bass lane running peak that `instant_bass` is normalized against
+    pub bass_running_peak: f32,
+
+    /// Capture is paused: the device stays open but results are silent
+    pub is_paused: bool,
+
+    /// Input level is below the silence floor (also set while paused)
+    pub is_silent: bool,
+
+    /// More than `CLIP_RATIO_THRESHOLD` of the analyzed samples sit at full
+    /// scale, after the mix and input gain
+    pub clipping: bool,
+
+    /// Pitch-class profile (C, C#, ... B), peak-normalized to 0-1
+    pub chroma: [f32; 12],
+
+    /// Strongest pitch class in `chroma` (0 = C ... 11 = B)
+    pub dominant_pitch_class: u8,
+}
+
+/// A source in the capture mix and its gain
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct MixSource {
+    pub source_id: String,
+    pub gain: f32,
+}
+
+/// Sample magnitude that counts as clipped
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Share of clipped samples in an analysis window that flags clipping
+/// (about 5 samples in a 1024-sample window)
+pub const CLIP_RATIO_THRESHOLD: f32 = 0.005;
+
+/// Share of `samples` at or beyond full scale
+fn clip_ratio(samples: &[f32]) -> f32 {
+    let clipped = samples.iter().filter(|s| s.abs() >= CLIP_LEVEL).count();
+    clipped as f32 / samples.len().max(1) as f32
+}
+
+/// RMS auto-normalize steers the input towards (-20 dBFS)
+const NORMALIZE_TARGET_RMS: f32 = 0.1;
+
+/// Limits of the auto-normalize gain (-12 dB to +24 dB)
+const NORMALIZE_GAIN_RANGE: std::ops::RangeInclusive<f32> = 0.25..=16.0;
+
+/// Share of the way to the target gain covered per analysis frame. At
+/// ~100 frames/s this settles over a few seconds, following songs rather
+/// than individual kicks.
+const NORMALIZE_RATE: f32 = 0.005;
+
+/// Linear gain shared with the analysis thread without a lock
+struct SharedGain(AtomicU32);
+
+impl SharedGain {
+    fn new(gain: f32) -> Self {
+        Self(AtomicU32::new(gain.to_bits()))
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, gain: f32) {
+        self.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// An extra source summed into the primary capture
+struct MixInput {
+    /// Mono samples, resampled to the analyzer's rate
+    buffer: Mutex<AudioBuffer>,
+    gain: SharedGain,
+    /// Cleared when the feed's device goes away, so its stale buffer isn't
+    /// mixed in forever
+    live: AtomicBool,
+}
+
+/// Everything summed into the buffer the analyzer sees.
+///
+/// The primary source fills its own buffer through the usual capture
+/// paths; extra sources are device streams run by `MixFeedHandle`s.
+struct MixBus {
+    primary_gain: SharedGain,
+    inputs: Vec<MixInput>,
+    /// Pre-gain on the summed signal, ahead of the FFT and bass lane
+    input_gain: SharedGain,
+    /// Slowly steer the summed level towards `NORMALIZE_TARGET_RMS`
+    auto_normalize: AtomicBool,
+    /// Analyzer sample rate the extra sources are resampled to
+    sample_rate: AtomicU32,
+}
+
+impl MixBus {
+    fn new(extra_sources: usize) -> Self {
+        Self {
+            primary_gain: SharedGain::new(1.0),
+            inputs: (0..extra_sources)
+                .map(|_| MixInput {
+                    buffer: Mutex::new(AudioBuffer::new(48000 * 2)),
+                    gain: SharedGain::new(1.0),
+                    live: AtomicBool::new(false),
+                })
+                .collect(),
+            input_gain: SharedGain::new(1.0),
+            auto_normalize: AtomicBool::new(false),
+            sample_rate: AtomicU32::new(AudioConfig::default().sample_rate),
+        }
+    }
+
+    /// Apply the input gain (and auto-normalize, if on) to the mixed samples.
+    ///
+    /// Auto-normalize measures the level before the manual gain, so the
+    /// manual gain still trims relative to the normalized level. Input
+    /// below the silence floor is left untouched, so paused players and
+    /// idle detection still read as silent.
+    fn apply_input_gain(&self, samples: &mut [f32], normalizer: &mut AutoNormalizer) {
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt();
+        if rms < SILENCE_RMS {
+            return;
+        }
+
+        let mut gain = self.input_gain.get();
+        if self.auto_normalize.load(Ordering::Relaxed) {
+            gain *= normalizer.update(rms);
+        } else {
+            normalizer.gain = 1.0;
+        }
+        if gain != 1.0 {
+            samples.iter_mut().for_each(|s| *s *= gain);
+        }
+    }
+
+    /// Apply the primary gain to `samples` and add the latest samples of
+    /// every live extra source on top
+    fn mix_into(&self, samples: &mut [f32], scratch: &mut Vec<f32>) {
+        let primary_gain = self.primary_gain.get();
+        if primary_gain != 1.0 {
+            samples.iter_mut().for_each(|s| *s *= primary_gain);
+        }
+
+        scratch.resize(samples.len(), 0.0);
+        for input in &self.inputs {
+            if !input.live.load(Ordering::Relaxed) {
+                continue;
+            }
+            let count = input.buffer.lock().get_latest_into(scratch);
+            let gain = input.gain.get();
+            for (s, x) in samples.iter_mut().zip(&scratch[..count]) {
+                *s += x * gain;
+            }
+        }
+    }
+}
+
+/// Slow automatic gain towards a reference RMS
+struct AutoNormalizer {
+    gain: f32,
+}
+
+impl AutoNormalizer {
+    fn new() -> Self {
+        Self { gain: 1.0 }
+    }
+
+    /// Step towards the gain that brings `rms` to the target and return it
+    fn update(&mut self, rms: f32) -> f32 {
+        let target = (NORMALIZE_TARGET_RMS / rms)
+            .clamp(*NORMALIZE_GAIN_RANGE.start(), *NORMALIZE_GAIN_RANGE.end());
+        self.gain += (target - self.gain) * NORMALIZE_RATE;
+        self.gain
+    }
+}
+
+/// Streaming linear-interpolation resampler for the mix feeds
+struct LinearResampler {
+    /// Input samples per output sample
+    step: f64,
+    /// Position of the next output between `prev` and the next input
+    pos: f64,
+    prev: f32,
+}
+
+impl LinearResampler {
+    fn new(from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            step: from_rate as f64 / to_rate.max(1) as f64,
+            pos: 0.0,
+            prev: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        for &x in input {
+            while self.pos < 1.0 {
+                out.push(self.prev + (x - self.prev) * self.pos as f32);
+                self.pos += self.step;
+            }
+            self.pos -= 1.0;
+            self.prev = x;
+        }
+    }
 }
 
 /// Commands sent to the audio thread
@@ -92,6 +325,18 @@ enum AudioCommand {
     Stop,
 }
 
+/// Why the analysis loop returned
+#[derive(Debug, PartialEq)]
+enum LoopExit {
+    /// Stop command received or the handle was dropped
+    Stopped,
+    /// The cpal stream reported that its device went away
+    DeviceLost,
+}
+
+/// Source of `AudioCaptureHandle::id`
+static NEXT_CAPTURE_ID: AtomicU64 = AtomicU64::new(1);
+
 /// Audio capture handle (Send + Sync safe)
 ///
 /// This struct doesn't contain the cpal::Stream directly.
@@ -111,6 +356,24 @@ pub struct AudioCaptureHandle {
 
     /// Current capture mode (set by audio thread)
     capture_mode: Arc<Mutex<CaptureMode>>,
+
+    /// Unique per capture session, so callers can tell restarts apart
+    id: u64,
+
+    /// While set, the audio thread publishes silence instead of analysis
+    paused: Arc<AtomicBool>,
+
+    /// Gains and buffers of the sources summed before analysis
+    mix: Arc<MixBus>,
+
+    /// Source ids in mix order; the primary source comes first
+    mix_source_ids: Vec<String>,
+
+    /// Streams of the extra mixed-in sources
+    mix_feeds: Mutex<Vec<MixFeedHandle>>,
+
+    /// Play/pause/seek controls when the primary source is a file
+    file_playback: Option<Arc<FilePlayback>>,
 }
 
 // AudioCaptureHandle is Send + Sync because all fields are:
@@ -129,6 +392,48 @@ impl AudioCaptureHandle {
         source_id: Option<String>,
         voice_streamer: Option<Arc<VoiceStreamer>>,
     ) -> Result<Self, CaptureError> {
+        Self::new_mixed(source_id, Vec::new(), voice_streamer)
+    }
+
+    /// Create a capture of `source_id` with more device sources summed into
+    /// it before analysis (e.g. a sampler or mic on top of the music app).
+    ///
+    /// Only the primary source feeds the voice streamer. Each source starts
+    /// at unity gain; see `set_source_gain`.
+    pub fn new_mixed(
+        source_id: Option<String>,
+        mix_with: Vec<String>,
+        voice_streamer: Option<Arc<VoiceStreamer>>,
+    ) -> Result<Self, CaptureError> {
+        if let Some(id) = mix_with.iter().find(|id| !is_mixable(id)) {
+            return Err(CaptureError::NotMixable(id.clone()));
+        }
+        // Open file sources up front so a bad path fails here, not in the
+        // thread; the decoder is handed to the thread as is
+        let (file_playback, file_decoder) = match source_id
+            .as_deref()
+            .and_then(|id| id.strip_prefix(FILE_SOURCE_PREFIX))
+        {
+            Some(path) => (
+                Some(Arc::new(FilePlayback::new(path.to_string()))),
+                Some(FileDecoder::open(Path::new(path))?),
+            ),
+            None => (None, None),
+        };
+
+        let mix = Arc::new(MixBus::new(mix_with.len()));
+        let mix_feeds = mix_with
+            .iter()
+            .enumerate()
+            .map(|(index, id)| MixFeedHandle::new(id.clone(), mix.clone(), index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut mix_source_ids = vec![
+            source_id
+                .clone()
+                .unwrap_or_else(|| "system_audio".to_string()),
+        ];
+        mix_source_ids.extend(mix_with);
+
         let (command_tx, command_rx) = mpsc::channel();
         let latest_result = Arc::new(Mutex::new(AnalysisResult::default()));
         let result_clone = latest_result.clone();
@@ -140,6 +445,10 @@ impl AudioCaptureHandle {
         let analyzer_clone = analyzer.clone();
         let capture_mode = Arc::new(Mutex::new(CaptureMode::Pending));
         let mode_clone = capture_mode.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let paused_clone = paused.clone();
+        let mix_clone = mix.clone();
+        let file_source = file_playback.clone().zip(file_decoder);
 
         // Spawn audio thread
         let thread_handle = thread::Builder::new()
@@ -152,6 +461,9 @@ impl AudioCaptureHandle {
                     voice_streamer,
                     analyzer_clone,
                     mode_clone,
+                    paused_clone,
+                    mix_clone,
+                    file_source,
                 ) {
                     log::error!("Audio thread error: {}", e);
                 }
@@ -164,9 +476,34 @@ impl AudioCaptureHandle {
             latest_result,
             analyzer,
             capture_mode,
+            id: NEXT_CAPTURE_ID.fetch_add(1, Ordering::Relaxed),
+            paused,
+            mix,
+            mix_source_ids,
+            mix_feeds: Mutex::new(mix_feeds),
+            file_playback,
         })
     }
 
+    /// Pause or resume analysis without closing the device.
+    ///
+    /// While paused the audio thread keeps the stream (and any Process
+    /// Loopback or ScreenCaptureKit session) running but publishes silent
+    /// results, so resuming is instant.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Whether analysis is currently paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Identifier of this capture session
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Get a reference to the shared analyzer for applying presets
     pub fn analyzer(&self) -> &Arc<Mutex<FftAnalyzer>> {
         &self.analyzer
@@ -177,17 +514,60 @@ impl AudioCaptureHandle {
         self.latest_result.lock().clone()
     }
 
+    /// Play/pause/seek controls, if this capture plays a file
+    pub fn file_playback(&self) -> Option<&Arc<FilePlayback>> {
+        self.file_playback.as_ref()
+    }
+
     /// Get the current capture mode
     pub fn get_capture_mode(&self) -> CaptureMode {
         self.capture_mode.lock().clone()
     }
 
+    /// Sources in the mix with their gains, primary first
+    pub fn mix_sources(&self) -> Vec<MixSource> {
+        let gains = std::iter::once(&self.mix.primary_gain)
+            .chain(self.mix.inputs.iter().map(|input| &input.gain));
+        self.mix_source_ids
+            .iter()
+            .zip(gains)
+            .map(|(id, gain)| MixSource {
+                source_id: id.clone(),
+                gain: gain.get(),
+            })
+            .collect()
+    }
+
+    /// Set the linear gain of one source in the mix; false if it isn't part
+    /// of this capture
+    pub fn set_source_gain(&self, source_id: &str, gain: f32) -> bool {
+        match self.mix_source_ids.iter().position(|id| id == source_id) {
+            Some(0) => self.mix.primary_gain.set(gain),
+            Some(index) => self.mix.inputs[index - 1].gain.set(gain),
+            None => return false,
+        }
+        true
+    }
+
+    /// Set the input gain in dB, applied to the mixed signal before analysis
+    pub fn set_input_gain_db(&self, db: f32) {
+        self.mix.input_gain.set(10f32.powf(db / 20.0));
+    }
+
+    /// Turn slow automatic level normalization before analysis on or off
+    pub fn set_auto_normalize(&self, enabled: bool) {
+        self.mix.auto_normalize.store(enabled, Ordering::Relaxed);
+    }
+
     /// Stop the audio capture
     pub fn stop(&self) {
         let _ = self.command_tx.send(AudioCommand::Stop);
         if let Some(handle) = self.thread_handle.lock().take() {
             let _ = handle.join();
         }
+        for feed in self.mix_feeds.lock().drain(..) {
+            feed.stop();
+        }
     }
 }
 
@@ -197,6 +577,110 @@ impl Drop for AudioCaptureHandle {
     }
 }
 
+/// Dedicated voice input handle (Send + Sync safe)
+///
+/// Owns a thread with a second cpal input stream (e.g. a headset mic) that
+/// feeds the voice streamer instead of the music mix. While the stream is
+/// running the music capture no longer pushes samples to the streamer.
+pub struct VoiceInputHandle {
+    /// Command sender to control the voice input thread
+    command_tx: mpsc::Sender<AudioCommand>,
+
+    /// Handle to the voice input thread (wrapped in Mutex for Sync safety)
+    thread_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl VoiceInputHandle {
+    /// Start capturing voice from the named input device.
+    pub fn new(
+        device_name: String,
+        voice_streamer: Arc<VoiceStreamer>,
+    ) -> Result<Self, CaptureError> {
+        let (command_tx, command_rx) = mpsc::channel();
+
+        let thread_handle = thread::Builder::new()
+            .name("voice-input".to_string())
+            .spawn(move || {
+                if let Err(e) = run_voice_input_thread(&device_name, command_rx, &voice_streamer) {
+                    log::error!("Voice input thread error: {}", e);
+                }
+                // Hand voice back to the music mix
+                voice_streamer.set_dedicated_input(None);
+            })
+            .map_err(|e| CaptureError::ThreadError(e.to_string()))?;
+
+        Ok(Self {
+            command_tx,
+            thread_handle: Mutex::new(Some(thread_handle)),
+        })
+    }
+
+    /// Stop the voice input
+    pub fn stop(&self) {
+        let _ = self.command_tx.send(AudioCommand::Stop);
+        if let Some(handle) = self.thread_handle.lock().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for VoiceInputHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Whether a source can be mixed in on top of the primary capture: plain
+/// cpal devices can, per-app and exclude-self captures can't
+fn is_mixable(source_id: &str) -> bool {
+    source_id == "system_audio"
+        || source_id.starts_with("output:")
+        || source_id.starts_with("input:")
+}
+
+/// Stream of one extra mixed-in source (Send + Sync safe)
+///
+/// Owns a thread with a cpal stream that downmixes, resamples to the
+/// analyzer's rate and fills its `MixInput` buffer.
+struct MixFeedHandle {
+    command_tx: mpsc::Sender<AudioCommand>,
+    thread_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl MixFeedHandle {
+    fn new(source_id: String, mix: Arc<MixBus>, index: usize) -> Result<Self, CaptureError> {
+        let (command_tx, command_rx) = mpsc::channel();
+
+        let thread_handle = thread::Builder::new()
+            .name("audio-mix-feed".to_string())
+            .spawn(move || {
+                if let Err(e) = run_mix_feed_thread(&source_id, command_rx, &mix, index) {
+                    log::error!("Mix source '{}' error: {}", source_id, e);
+                }
+                mix.inputs[index].live.store(false, Ordering::Relaxed);
+            })
+            .map_err(|e| CaptureError::ThreadError(e.to_string()))?;
+
+        Ok(Self {
+            command_tx,
+            thread_handle: Mutex::new(Some(thread_handle)),
+        })
+    }
+
+    fn stop(&self) {
+        let _ = self.command_tx.send(AudioCommand::Stop);
+        if let Some(handle) = self.thread_handle.lock().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MixFeedHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 /// Circular audio buffer
 pub struct AudioBuffer {
     samples: Vec<f32>,
@@ -263,6 +747,7 @@ impl AudioBuffer {
 }
 
 /// Run the audio capture in a dedicated thread
+#[allow(clippy::too_many_arguments)]
 fn run_audio_thread(
     source_id: Option<String>,
     command_rx: mpsc::Receiver<AudioCommand>,
@@ -270,14 +755,31 @@ fn run_audio_thread(
     voice_streamer: Option<Arc<VoiceStreamer>>,
     shared_analyzer: Arc<Mutex<FftAnalyzer>>,
     mode_out: Arc<Mutex<CaptureMode>>,
+    paused: Arc<AtomicBool>,
+    mix: Arc<MixBus>,
+    file_source: Option<(Arc<FilePlayback>, FileDecoder)>,
 ) -> Result<(), CaptureError> {
+    if let Some((playback, decoder)) = file_source {
+        return run_file_capture(
+            playback,
+            decoder,
+            &command_rx,
+            &result_out,
+            voice_streamer,
+            &shared_analyzer,
+            &mode_out,
+            &paused,
+            &mix,
+        );
+    }
+
     let host = cpal::default_host();
 
     // Track whether we're doing loopback capture (output device used as input)
     let mut is_loopback = false;
 
     // Get device based on source
-    let device = match &source_id {
+    let mut device = match &source_id {
         Some(id) if id == "system_audio" => {
             // WASAPI loopback: capture from the default OUTPUT device
             // On Windows, cpal/WASAPI allows building an input stream on an output device
@@ -290,6 +792,65 @@ fn run_audio_thread(
             host.default_output_device()
                 .ok_or(CaptureError::NoOutputDevice)?
         }
+        #[cfg(target_os = "windows")]
+        Some(id) if id == super::sources::EXCLUDE_SELF_SOURCE_ID => {
+            // System audio minus our own process tree, via Process Loopback
+            // in exclude mode so the client's UI sounds don't feed back
+            let pid = std::process::id();
+            let buffer = Arc::new(Mutex::new(AudioBuffer::new(48000 * 2)));
+
+            let fallback_reason = if super::platform::windows::supports_process_loopback() {
+                match super::platform::windows::start_process_loopback(
+                    pid,
+                    super::platform::windows::LoopbackTarget::ExcludeProcessTree,
+                    buffer.clone(),
+                    voice_streamer.clone(),
+                ) {
+                    Ok((mut loopback_handle, sample_rate, _channels)) => {
+                        log::info!(
+                            "System loopback excluding PID {} active ({}Hz)",
+                            pid,
+                            sample_rate
+                        );
+                        *mode_out.lock() = CaptureMode::SystemLoopback {
+                            fallback_reason: None,
+                        };
+
+                        run_with_sample_rate(
+                            sample_rate,
+                            &buffer,
+                            &command_rx,
+                            &shared_analyzer,
+                            &result_out,
+                            &paused,
+                            &mix,
+                        );
+
+                        loopback_handle.stop();
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Exclude-self loopback failed: {}. Falling back to system loopback.",
+                            e
+                        );
+                        e
+                    }
+                }
+            } else {
+                format!(
+                    "Process Loopback API not supported (Windows build < {})",
+                    20348
+                )
+            };
+
+            *mode_out.lock() = CaptureMode::SystemLoopback {
+                fallback_reason: Some(fallback_reason),
+            };
+            is_loopback = true;
+            host.default_output_device()
+                .ok_or(CaptureError::NoOutputDevice)?
+        }
         Some(id) if id.starts_with("output:") => {
             // Named output device for loopback
             let device_name = id.trim_start_matches("output:");
@@ -327,11 +888,12 @@ fn run_audio_thread(
             if super::platform::windows::supports_process_loopback() {
                 log::info!("Using Process Loopback API for PID {}", pid);
 
-                // Create buffer; reinitialize the shared analyzer once we know sample rate
+                // Sized for 48kHz until the capture reports its actual rate
                 let buffer = Arc::new(Mutex::new(AudioBuffer::new(48000 * 2)));
 
                 match super::platform::windows::start_process_loopback(
                     pid,
+                    super::platform::windows::LoopbackTarget::IncludeProcessTree,
                     buffer.clone(),
                     voice_streamer.clone(),
                 ) {
@@ -342,70 +904,15 @@ fn run_audio_thread(
                             name: app_name.clone(),
                         };
 
-                        // Resize buffer for actual sample rate
-                        {
-                            let mut buf = buffer.lock();
-                            *buf = AudioBuffer::new(sample_rate as usize * 2);
-                        }
-
-                        // Reinitialize analyzer with actual sample rate
-                        {
-                            let mut ana = shared_analyzer.lock();
-                            *ana = FftAnalyzer::new(AudioConfig {
-                                sample_rate,
-                                ..Default::default()
-                            });
-                        }
-                        let analyzer = shared_analyzer;
-                        let bass_lane = Arc::new(Mutex::new(BassLane::new(sample_rate as f32)));
-
-                        // Run analysis loop - copy samples under lock, release, then process
-                        let mut sample_buf = vec![0.0f32; 4096];
-                        loop {
-                            match command_rx.try_recv() {
-                                Ok(AudioCommand::Stop) => {
-                                    log::info!("Audio capture stopping (process loopback)");
-                                    break;
-                                }
-                                Err(mpsc::TryRecvError::Disconnected) => {
-                                    log::info!("Audio capture channel disconnected");
-                                    break;
-                                }
-                                Err(mpsc::TryRecvError::Empty) => {}
-                            }
-
-                            // Copy samples under lock, then release before expensive processing
-                            let fft_size = analyzer.lock().fft_size();
-                            if sample_buf.len() < fft_size {
-                                sample_buf.resize(fft_size, 0.0);
-                            }
-                            let count = buffer.lock().get_latest_into(&mut sample_buf[..fft_size]);
-
-                            if count >= fft_size {
-                                let samples = &sample_buf[..count];
-                                let (i_bass, i_kick) = {
-                                    let mut bl = bass_lane.lock();
-                                    bl.process(samples)
-                                };
-
-                                let mut result = {
-                                    let mut ana = analyzer.lock();
-                                    ana.analyze(samples)
-                                };
-
-                                result.instant_bass = i_bass;
-                                result.instant_kick = i_kick;
-
-                                if i_kick && !result.is_beat {
-                                    result.is_beat = true;
-                                    result.beat_intensity = result.beat_intensity.max(0.5);
-                                }
-
-                                *result_out.lock() = result;
-                            }
-
-                            std::thread::sleep(std::time::Duration::from_millis(10));
-                        }
+                        run_with_sample_rate(
+                            sample_rate,
+                            &buffer,
+                            &command_rx,
+                            &shared_analyzer,
+                            &result_out,
+                            &paused,
+                            &mix,
+                        );
 
                         loopback_handle.stop();
                         return Ok(());
@@ -440,7 +947,136 @@ fn run_audio_thread(
                     .ok_or(CaptureError::NoOutputDevice)?
             }
         }
-        #[cfg(not(target_os = "windows"))]
+        #[cfg(target_os = "macos")]
+        Some(id) if id.starts_with("app:") => {
+            // Per-app capture via ScreenCaptureKit (macOS 13+)
+            // Source ID format: "app:<pid>:<name>"
+            let parts: Vec<&str> = id.splitn(3, ':').collect();
+            let pid = parts
+                .get(1)
+                .and_then(|p| p.parse::<u32>().ok())
+                .ok_or_else(|| {
+                    CaptureError::SourceNotFound(format!("Invalid app source: {}", id))
+                })?;
+            let app_name = parts.get(2).unwrap_or(&"unknown").to_string();
+
+            let fallback_reason = if super::platform::macos::supports_app_capture() {
+                log::info!("Using ScreenCaptureKit for PID {}", pid);
+
+                let buffer = Arc::new(Mutex::new(AudioBuffer::new(48000 * 2)));
+
+                match super::platform::macos::start_app_capture(
+                    pid,
+                    buffer.clone(),
+                    voice_streamer.clone(),
+                ) {
+                    Ok((mut capture_handle, sample_rate, _channels)) => {
+                        log::info!("App capture active: PID {} ({}Hz)", pid, sample_rate);
+                        *mode_out.lock() = CaptureMode::ProcessLoopback {
+                            pid,
+                            name: app_name.clone(),
+                        };
+
+                        run_with_sample_rate(
+                            sample_rate,
+                            &buffer,
+                            &command_rx,
+                            &shared_analyzer,
+                            &result_out,
+                            &paused,
+                            &mix,
+                        );
+
+                        capture_handle.stop();
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "App capture failed for PID {}: {}. Falling back to system loopback.",
+                            pid,
+                            e
+                        );
+                        e
+                    }
+                }
+            } else {
+                log::warn!(
+                    "ScreenCaptureKit audio capture requires macOS 13+. Falling back to system loopback."
+                );
+                "ScreenCaptureKit audio capture requires macOS 13+".to_string()
+            };
+
+            *mode_out.lock() = CaptureMode::SystemLoopback {
+                fallback_reason: Some(fallback_reason),
+            };
+            is_loopback = true;
+            host.default_output_device()
+                .ok_or(CaptureError::NoOutputDevice)?
+        }
+        #[cfg(all(target_os = "linux", feature = "pipewire-capture"))]
+        Some(id) if id.starts_with("app:") => {
+            // Per-app capture via a native PipeWire stream
+            // Source ID format: "app:<pid>:<name>"
+            let parts: Vec<&str> = id.splitn(3, ':').collect();
+            let pid = parts
+                .get(1)
+                .and_then(|p| p.parse::<u32>().ok())
+                .ok_or_else(|| {
+                    CaptureError::SourceNotFound(format!("Invalid app source: {}", id))
+                })?;
+            let app_name = parts.get(2).unwrap_or(&"unknown").to_string();
+
+            log::info!("Using PipeWire capture for PID {}", pid);
+
+            let buffer = Arc::new(Mutex::new(AudioBuffer::new(48000 * 2)));
+
+            let fallback_reason = match super::platform::pipewire_native::start_app_capture(
+                pid,
+                buffer.clone(),
+                voice_streamer.clone(),
+            ) {
+                Ok((mut capture_handle, sample_rate, _channels)) => {
+                    log::info!("App capture active: PID {} ({}Hz)", pid, sample_rate);
+                    *mode_out.lock() = CaptureMode::ProcessLoopback {
+                        pid,
+                        name: app_name.clone(),
+                    };
+
+                    run_with_sample_rate(
+                        sample_rate,
+                        &buffer,
+                        &command_rx,
+                        &shared_analyzer,
+                        &result_out,
+                        &paused,
+                        &mix,
+                    );
+
+                    capture_handle.stop();
+                    return Ok(());
+                }
+                Err(e) => {
+                    log::warn!(
+                        "App capture failed for PID {}: {}. Falling back to system loopback.",
+                        pid,
+                        e
+                    );
+                    e
+                }
+            };
+
+            *mode_out.lock() = CaptureMode::SystemLoopback {
+                fallback_reason: Some(fallback_reason),
+            };
+            is_loopback = true;
+            host.default_output_device()
+                .ok_or(CaptureError::NoOutputDevice)?
+        }
+        #[cfg(not(any(
+            target_os = "windows",
+            target_os = "macos",
+            all(target_os = "linux", feature = "pipewire-capture")
+        )))]
         Some(id) if id.starts_with("app:") => {
             log::info!(
                 "Per-app capture not available on this platform, using system audio loopback"
@@ -464,6 +1100,110 @@ fn run_audio_thread(
         }
     };
 
+    loop {
+        if run_cpal_capture(
+            &device,
+            is_loopback,
+            &command_rx,
+            &result_out,
+            voice_streamer.clone(),
+            &shared_analyzer,
+            &paused,
+            &mix,
+        )? == LoopExit::Stopped
+        {
+            return Ok(());
+        }
+
+        // The device went away mid-session (e.g. USB interface unplugged).
+        // Fall back to the current default output unless that's what we lost.
+        let lost_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+        match host.default_output_device() {
+            Some(default)
+                if !(is_loopback && default.name().ok().as_deref() == Some(&lost_name)) =>
+            {
+                log::warn!(
+                    "Capture device '{}' disconnected, switching to default output",
+                    lost_name
+                );
+                *mode_out.lock() = CaptureMode::SystemLoopback {
+                    fallback_reason: Some(format!("Capture device '{}' disconnected", lost_name)),
+                };
+                device = default;
+                is_loopback = true;
+            }
+            _ => {
+                log::error!(
+                    "Capture device '{}' disconnected and no fallback is available",
+                    lost_name
+                );
+                *mode_out.lock() = CaptureMode::Lost {
+                    reason: format!("Capture device '{}' disconnected", lost_name),
+                };
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Play a file source, opened by `new_mixed`, until stopped.
+#[allow(clippy::too_many_arguments)]
+fn run_file_capture(
+    playback: Arc<FilePlayback>,
+    decoder: FileDecoder,
+    command_rx: &mpsc::Receiver<AudioCommand>,
+    result_out: &Arc<Mutex<AnalysisResult>>,
+    voice_streamer: Option<Arc<VoiceStreamer>>,
+    shared_analyzer: &Arc<Mutex<FftAnalyzer>>,
+    mode_out: &Mutex<CaptureMode>,
+    paused: &AtomicBool,
+    mix: &MixBus,
+) -> Result<(), CaptureError> {
+    let path = playback.status().path;
+    let sample_rate = decoder.sample_rate();
+    log::info!("Playing audio file {} ({}Hz)", path, sample_rate);
+
+    let buffer = Arc::new(Mutex::new(AudioBuffer::new(sample_rate as usize * 2)));
+    {
+        let mut ana = shared_analyzer.lock();
+        *ana = FftAnalyzer::new(AudioConfig {
+            sample_rate,
+            ..Default::default()
+        })
+        .with_tuning_of(&ana);
+    }
+    let bass_lane = Mutex::new(BassLane::new(sample_rate as f32));
+
+    let mut feed_handle = start_file_playback(decoder, playback, buffer.clone(), voice_streamer)?;
+    *mode_out.lock() = CaptureMode::FilePlayback { path };
+
+    run_analysis_loop(
+        command_rx,
+        &buffer,
+        shared_analyzer,
+        &bass_lane,
+        result_out,
+        paused,
+        mix,
+        None,
+    );
+
+    feed_handle.stop();
+    Ok(())
+}
+
+/// Capture from a cpal device until stopped or the device disappears.
+#[allow(clippy::too_many_arguments)]
+fn run_cpal_capture(
+    device: &Device,
+    is_loopback: bool,
+    command_rx: &mpsc::Receiver<AudioCommand>,
+    result_out: &Arc<Mutex<AnalysisResult>>,
+    voice_streamer: Option<Arc<VoiceStreamer>>,
+    shared_analyzer: &Arc<Mutex<FftAnalyzer>>,
+    paused: &AtomicBool,
+    mix: &MixBus,
+) -> Result<LoopExit, CaptureError> {
     // Get supported config
     // For loopback, we query the output config (which is what the device is producing)
     let config = if is_loopback {
@@ -488,38 +1228,45 @@ fn run_audio_thread(
         *ana = FftAnalyzer::new(AudioConfig {
             sample_rate,
             ..Default::default()
-        });
+        })
+        .with_tuning_of(&ana);
     }
-    let analyzer = shared_analyzer;
 
     // Create bass lane for ultra-fast kick detection (~1ms latency)
-    let bass_lane = Arc::new(Mutex::new(BassLane::new(sample_rate as f32)));
+    let bass_lane = Mutex::new(BassLane::new(sample_rate as f32));
+
+    // Set by the stream error callback when the device disappears
+    let device_lost = Arc::new(AtomicBool::new(false));
 
     // Clone for stream callback
     let buffer_clone = buffer.clone();
+    let lost_clone = device_lost.clone();
 
     // Build stream based on sample format
     let stream = match config.sample_format() {
         SampleFormat::F32 => build_stream::<f32>(
-            &device,
+            device,
             &config.into(),
             buffer_clone,
             channels,
             voice_streamer,
+            lost_clone,
         ),
         SampleFormat::I16 => build_stream::<i16>(
-            &device,
+            device,
             &config.into(),
             buffer_clone,
             channels,
             voice_streamer,
+            lost_clone,
         ),
         SampleFormat::U16 => build_stream::<u16>(
-            &device,
+            device,
             &config.into(),
             buffer_clone,
             channels,
             voice_streamer,
+            lost_clone,
         ),
         _ => {
             return Err(CaptureError::ConfigError(
@@ -536,27 +1283,116 @@ fn run_audio_thread(
     log::info!("Audio capture started");
 
     // Main loop - analyze audio and check for stop command
+    Ok(run_analysis_loop(
+        command_rx,
+        &buffer,
+        shared_analyzer,
+        &bass_lane,
+        result_out,
+        paused,
+        mix,
+        Some(&device_lost),
+    ))
+}
+
+/// Analyze what a platform capture (Windows Process Loopback, macOS
+/// ScreenCaptureKit or PipeWire) writes into `buffer` until stopped.
+///
+/// The buffer was sized before the capture reported its rate, so it is
+/// resized here, and the analyzer and bass lane are rebuilt for
+/// `sample_rate` with the analyzer's tuning carried over.
+fn run_with_sample_rate(
+    sample_rate: u32,
+    buffer: &Arc<Mutex<AudioBuffer>>,
+    command_rx: &mpsc::Receiver<AudioCommand>,
+    shared_analyzer: &Arc<Mutex<FftAnalyzer>>,
+    result_out: &Arc<Mutex<AnalysisResult>>,
+    paused: &AtomicBool,
+    mix: &MixBus,
+) {
+    *buffer.lock() = AudioBuffer::new(sample_rate as usize * 2);
+    {
+        let mut ana = shared_analyzer.lock();
+        *ana = FftAnalyzer::new(AudioConfig {
+            sample_rate,
+            ..Default::default()
+        })
+        .with_tuning_of(&ana);
+    }
+    let bass_lane = Mutex::new(BassLane::new(sample_rate as f32));
+
+    run_analysis_loop(
+        command_rx,
+        buffer,
+        shared_analyzer,
+        &bass_lane,
+        result_out,
+        paused,
+        mix,
+        None,
+    );
+}
+
+/// Analyze the shared buffer every 10ms until a stop command arrives.
+///
+/// Shared by every capture path: cpal streams, Windows Process Loopback and
+/// macOS ScreenCaptureKit all just fill `buffer`. cpal streams also pass
+/// `device_lost`, which ends the loop instead of analyzing a dead buffer.
+#[allow(clippy::too_many_arguments)]
+fn run_analysis_loop(
+    command_rx: &mpsc::Receiver<AudioCommand>,
+    buffer: &Arc<Mutex<AudioBuffer>>,
+    analyzer: &Arc<Mutex<FftAnalyzer>>,
+    bass_lane: &Mutex<BassLane>,
+    result_out: &Arc<Mutex<AnalysisResult>>,
+    paused: &AtomicBool,
+    mix: &MixBus,
+    device_lost: Option<&AtomicBool>,
+) -> LoopExit {
     let mut sample_buf = vec![0.0f32; 4096];
+    let mut mix_scratch = Vec::new();
+    let mut normalizer = AutoNormalizer::new();
     loop {
         // Check for stop command (non-blocking)
         match command_rx.try_recv() {
             Ok(AudioCommand::Stop) => {
                 log::info!("Audio capture stopping");
-                break;
+                return LoopExit::Stopped;
             }
             Err(mpsc::TryRecvError::Disconnected) => {
                 log::info!("Audio capture channel disconnected");
-                break;
+                return LoopExit::Stopped;
             }
             Err(mpsc::TryRecvError::Empty) => {
                 // No command, continue processing
             }
         }
 
+        if device_lost.is_some_and(|lost| lost.load(Ordering::Relaxed)) {
+            // Don't leave the last bands frozen on screen
+            *result_out.lock() = AnalysisResult::default();
+            return LoopExit::DeviceLost;
+        }
+
+        if paused.load(Ordering::Relaxed) {
+            // Keep the device open but publish silence until resumed
+            *result_out.lock() = AnalysisResult {
+                is_paused: true,
+                is_silent: true,
+                ..Default::default()
+            };
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            continue;
+        }
+
         // Analyze audio (FFT + merge bass lane results)
         // IMPORTANT: Copy samples under lock, then release lock before expensive FFT.
         // Holding the buffer lock during analyze() blocks the audio callback.
-        let fft_size = analyzer.lock().fft_size();
+        let (fft_size, sample_rate) = {
+            let ana = analyzer.lock();
+            (ana.fft_size(), ana.sample_rate())
+        };
+        mix.sample_rate.store(sample_rate, Ordering::Relaxed);
         if sample_buf.len() < fft_size {
             sample_buf.resize(fft_size, 0.0);
         }
@@ -564,22 +1400,29 @@ fn run_audio_thread(
         // All locks dropped - audio callback can push freely
 
         if count >= fft_size {
+            mix.mix_into(&mut sample_buf[..count], &mut mix_scratch);
+            mix.apply_input_gain(&mut sample_buf[..count], &mut normalizer);
             let samples = &sample_buf[..count];
             // Run bass lane on the same samples (moved out of audio callback to avoid contention)
-            let (i_bass, i_kick) = {
+            let (i_bass, i_kick, raw_envelope, running_peak) = {
                 let mut bl = bass_lane.lock();
-                bl.process(samples)
+                let (i_bass, i_kick) = bl.process(samples);
+                (i_bass, i_kick, bl.raw_envelope(), bl.running_peak())
             };
             // bass_lane lock dropped
 
             let mut result = {
                 let mut ana = analyzer.lock();
+                ana.set_lane_bass(i_bass);
                 ana.analyze(samples)
             };
             // analyzer lock dropped
 
             result.instant_bass = i_bass;
             result.instant_kick = i_kick;
+            result.bass_raw_envelope = raw_envelope;
+            result.bass_running_peak = running_peak;
+            result.clipping = clip_ratio(samples) > CLIP_RATIO_THRESHOLD;
 
             // If bass lane detects kick but FFT didn't, supplement beat detection
             if i_kick && !result.is_beat {
@@ -594,10 +1437,242 @@ fn run_audio_thread(
         // Sleep briefly to avoid spinning
         std::thread::sleep(std::time::Duration::from_millis(10));
     }
+}
+
+/// Run the dedicated voice input stream until told to stop
+fn run_voice_input_thread(
+    device_name: &str,
+    command_rx: mpsc::Receiver<AudioCommand>,
+    voice_streamer: &Arc<VoiceStreamer>,
+) -> Result<(), CaptureError> {
+    let host = cpal::default_host();
+    let device = host
+        .input_devices()
+        .map_err(|e| CaptureError::ConfigError(e.to_string()))?
+        .find(|d| d.name().map(|n| n == device_name).unwrap_or(false))
+        .ok_or_else(|| CaptureError::SourceNotFound(device_name.to_string()))?;
+
+    let config = device
+        .default_input_config()
+        .map_err(|e| CaptureError::ConfigError(e.to_string()))?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+
+    log::info!(
+        "Voice input '{}': {} Hz, {} channels",
+        device_name,
+        sample_rate,
+        channels
+    );
+
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => {
+            build_voice_stream::<f32>(&device, &config.into(), channels, voice_streamer.clone())
+        }
+        SampleFormat::I16 => {
+            build_voice_stream::<i16>(&device, &config.into(), channels, voice_streamer.clone())
+        }
+        SampleFormat::U16 => {
+            build_voice_stream::<u16>(&device, &config.into(), channels, voice_streamer.clone())
+        }
+        _ => {
+            return Err(CaptureError::ConfigError(
+                "Unsupported sample format".to_string(),
+            ));
+        }
+    }
+    .map_err(|e| CaptureError::StreamError(e.to_string()))?;
+
+    stream
+        .play()
+        .map_err(|e| CaptureError::PlayError(e.to_string()))?;
+
+    voice_streamer.set_dedicated_input(Some(sample_rate));
+    log::info!("Voice input started");
+
+    // Nothing to do here but wait: the stream callback does all the work.
+    // Both Stop and a dropped handle end the stream.
+    let _ = command_rx.recv();
+    log::info!("Voice input stopping");
+
+    Ok(())
+}
+
+/// Build a voice input stream that feeds the voice streamer directly
+fn build_voice_stream<T: cpal::Sample + cpal::SizedSample>(
+    device: &Device,
+    config: &StreamConfig,
+    channels: usize,
+    voice_streamer: Arc<VoiceStreamer>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    f32: cpal::FromSample<T>,
+{
+    let mut f32_scratch: Vec<f32> = Vec::with_capacity(8192);
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            f32_scratch.clear();
+            f32_scratch.extend(
+                data.iter()
+                    .map(|s| -> f32 { cpal::Sample::from_sample(*s) }),
+            );
+            voice_streamer.push_samples(&f32_scratch, channels);
+        },
+        |err| {
+            log::error!("Voice input stream error: {}", err);
+        },
+        None,
+    )
+}
+
+/// Run the stream of an extra mixed-in device until told to stop
+fn run_mix_feed_thread(
+    source_id: &str,
+    command_rx: mpsc::Receiver<AudioCommand>,
+    mix: &Arc<MixBus>,
+    index: usize,
+) -> Result<(), CaptureError> {
+    let host = cpal::default_host();
+    let (device, is_loopback) = if source_id == "system_audio" {
+        (
+            host.default_output_device()
+                .ok_or(CaptureError::NoOutputDevice)?,
+            true,
+        )
+    } else if let Some(name) = source_id.strip_prefix("output:") {
+        let device = host
+            .output_devices()
+            .map_err(|e| CaptureError::ConfigError(e.to_string()))?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| CaptureError::SourceNotFound(name.to_string()))?;
+        (device, true)
+    } else {
+        let name = source_id.trim_start_matches("input:");
+        let device = host
+            .input_devices()
+            .map_err(|e| CaptureError::ConfigError(e.to_string()))?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| CaptureError::SourceNotFound(name.to_string()))?;
+        (device, false)
+    };
+
+    let config = if is_loopback {
+        device
+            .default_output_config()
+            .map_err(|e| CaptureError::ConfigError(format!("Loopback config: {}", e)))?
+    } else {
+        device
+            .default_input_config()
+            .map_err(|e| CaptureError::ConfigError(e.to_string()))?
+    };
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+
+    log::info!(
+        "Mix source '{}': {} Hz, {} channels",
+        source_id,
+        sample_rate,
+        channels
+    );
+
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => build_mix_feed_stream::<f32>(
+            &device,
+            &config.into(),
+            channels,
+            sample_rate,
+            mix.clone(),
+            index,
+        ),
+        SampleFormat::I16 => build_mix_feed_stream::<i16>(
+            &device,
+            &config.into(),
+            channels,
+            sample_rate,
+            mix.clone(),
+            index,
+        ),
+        SampleFormat::U16 => build_mix_feed_stream::<u16>(
+            &device,
+            &config.into(),
+            channels,
+            sample_rate,
+            mix.clone(),
+            index,
+        ),
+        _ => {
+            return Err(CaptureError::ConfigError(
+                "Unsupported sample format".to_string(),
+            ));
+        }
+    }
+    .map_err(|e| CaptureError::StreamError(e.to_string()))?;
+
+    stream
+        .play()
+        .map_err(|e| CaptureError::PlayError(e.to_string()))?;
+    mix.inputs[index].live.store(true, Ordering::Relaxed);
+
+    // The stream callback does all the work; Stop or a dropped handle ends it
+    let _ = command_rx.recv();
+    log::info!("Mix source '{}' stopping", source_id);
 
     Ok(())
 }
 
+/// Build a stream that downmixes and resamples into a mix input's buffer
+fn build_mix_feed_stream<T: cpal::Sample + cpal::SizedSample>(
+    device: &Device,
+    config: &StreamConfig,
+    channels: usize,
+    sample_rate: u32,
+    mix: Arc<MixBus>,
+    index: usize,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    f32: cpal::FromSample<T>,
+{
+    let mut mono_scratch: Vec<f32> = Vec::with_capacity(4096);
+    let mut resampled: Vec<f32> = Vec::with_capacity(8192);
+    let mut target_rate = mix.sample_rate.load(Ordering::Relaxed);
+    let mut resampler = LinearResampler::new(sample_rate, target_rate);
+    let error_mix = mix.clone();
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            // Follow the analyzer if the primary source's rate changes
+            let rate = mix.sample_rate.load(Ordering::Relaxed);
+            if rate != target_rate {
+                target_rate = rate;
+                resampler = LinearResampler::new(sample_rate, target_rate);
+            }
+
+            mono_scratch.clear();
+            mono_scratch.extend(data.chunks(channels).map(|frame| {
+                let sum: f32 = frame
+                    .iter()
+                    .map(|s| -> f32 { cpal::Sample::from_sample(*s) })
+                    .sum();
+                sum / channels as f32
+            }));
+
+            resampled.clear();
+            resampler.process(&mono_scratch, &mut resampled);
+            mix.inputs[index].buffer.lock().push_samples(&resampled);
+        },
+        move |err| {
+            log::error!("Mix source stream error: {}", err);
+            if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                error_mix.inputs[index].live.store(false, Ordering::Relaxed);
+            }
+        },
+        None,
+    )
+}
+
 /// Build audio stream for given sample type
 fn build_stream<T: cpal::Sample + cpal::SizedSample>(
     device: &Device,
@@ -605,6 +1680,7 @@ fn build_stream<T: cpal::Sample + cpal::SizedSample>(
     buffer: Arc<Mutex<AudioBuffer>>,
     channels: usize,
     voice_streamer: Option<Arc<VoiceStreamer>>,
+    device_lost: Arc<AtomicBool>,
 ) -> Result<cpal::Stream, cpal::BuildStreamError>
 where
     f32: cpal::FromSample<T>,
@@ -626,7 +1702,7 @@ where
 
             // Feed raw interleaved f32 samples to voice streamer (before downmix)
             if let Some(ref streamer) = voice_streamer {
-                streamer.push_samples(&f32_scratch, channels);
+                streamer.push_mix_samples(&f32_scratch, channels);
             }
 
             // Downmix to mono (reuse scratch)
@@ -640,8 +1716,13 @@ where
             // Bass lane runs in the analysis thread (not callback) to avoid lock contention
             buffer.lock().push_samples(&mono_scratch);
         },
-        |err| {
+        move |err| {
             log::error!("Audio stream error: {}", err);
+            // Other errors (e.g. backend hiccups) can be transient; only a
+            // vanished device ends the capture
+            if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                device_lost.store(true, Ordering::Relaxed);
+            }
         },
         None,
     )
@@ -649,7 +1730,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::AudioBuffer;
+    use super::*;
 
     #[test]
     fn get_latest_returns_recent_samples_in_order() {
@@ -660,6 +1741,18 @@ mod tests {
         assert_eq!(latest, vec![2.0, 3.0, 4.0]);
     }
 
+    #[test]
+    fn clip_ratio_counts_full_scale_samples() {
+        let mut samples = vec![0.5; 1000];
+        assert_eq!(clip_ratio(&samples), 0.0);
+        samples[..4].fill(1.0);
+        samples[4] = -1.2;
+        assert!((clip_ratio(&samples) - 0.005).abs() < 1e-6);
+        samples[5] = -1.0;
+        assert!(clip_ratio(&samples) > CLIP_RATIO_THRESHOLD);
+        assert_eq!(clip_ratio(&[]), 0.0);
+    }
+
     #[test]
     fn circular_buffer_wraps_and_preserves_time_order() {
         let mut buffer = AudioBuffer::new(5);
@@ -678,4 +1771,158 @@ mod tests {
         let latest = buffer.get_latest(100);
         assert_eq!(latest, vec![1.0, 2.0, 3.0, 4.0]);
     }
+
+    #[test]
+    fn mix_bus_sums_live_inputs_with_gain() {
+        let mix = MixBus::new(2);
+        mix.primary_gain.set(0.5);
+        mix.inputs[0].buffer.lock().push_samples(&[0.2; 4]);
+        mix.inputs[0].gain.set(2.0);
+        mix.inputs[0].live.store(true, Ordering::Relaxed);
+        // Not live: its stale samples stay out of the mix
+        mix.inputs[1].buffer.lock().push_samples(&[1.0; 4]);
+
+        let mut samples = [0.4; 4];
+        mix.mix_into(&mut samples, &mut Vec::new());
+
+        for s in samples {
+            assert!((s - 0.6).abs() < 1e-6, "expected 0.6, got {s}");
+        }
+    }
+
+    #[test]
+    fn input_gain_skips_silence() {
+        let mix = MixBus::new(0);
+        mix.input_gain.set(2.0);
+        let mut normalizer = AutoNormalizer::new();
+
+        let mut quiet = [0.05; 8];
+        mix.apply_input_gain(&mut quiet, &mut normalizer);
+        assert_eq!(quiet, [0.1; 8]);
+
+        let mut silent = [1e-5; 8];
+        mix.apply_input_gain(&mut silent, &mut normalizer);
+        assert_eq!(silent, [1e-5; 8]);
+    }
+
+    #[test]
+    fn auto_normalize_converges_on_target_level() {
+        let mix = MixBus::new(0);
+        mix.auto_normalize.store(true, Ordering::Relaxed);
+        let mut normalizer = AutoNormalizer::new();
+
+        let mut samples = [0.0; 8];
+        for _ in 0..3000 {
+            samples = [0.025; 8];
+            mix.apply_input_gain(&mut samples, &mut normalizer);
+        }
+        assert!((samples[0] - NORMALIZE_TARGET_RMS).abs() < 0.005);
+    }
+
+    #[test]
+    fn linear_resampler_converts_rate() {
+        let mut resampler = LinearResampler::new(44100, 48000);
+        let mut out = Vec::new();
+        resampler.process(&[0.5; 4410], &mut out);
+
+        assert!((out.len() as i64 - 4800).abs() <= 1, "got {}", out.len());
+        // Ramps up from silence, then holds the input level
+        assert!((out[100] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn only_devices_can_be_mixed_in() {
+        assert!(is_mixable("system_audio"));
+        assert!(is_mixable("input:USB Mic"));
+        assert!(!is_mixable("app:1234:Spotify"));
+
+        let err = AudioCaptureHandle::new_mixed(None, vec!["app:1234:Spotify".into()], None)
+            .err()
+            .unwrap();
+        assert!(matches!(err, CaptureError::NotMixable(_)));
+    }
+
+    #[test]
+    fn analysis_loop_exits_and_clears_result_when_device_is_lost() {
+        let (_command_tx, command_rx) = mpsc::channel();
+        let buffer = Arc::new(Mutex::new(AudioBuffer::new(4096)));
+        let analyzer = Arc::new(Mutex::new(FftAnalyzer::new(AudioConfig::default())));
+        let bass_lane = Mutex::new(BassLane::new(48000.0));
+        let result = Arc::new(Mutex::new(AnalysisResult {
+            bands: [0.5; 5],
+            ..Default::default()
+        }));
+        let device_lost = AtomicBool::new(true);
+
+        let exit = run_analysis_loop(
+            &command_rx,
+            &buffer,
+            &analyzer,
+            &bass_lane,
+            &result,
+            &AtomicBool::new(false),
+            &MixBus::new(0),
+            Some(&device_lost),
+        );
+
+        assert_eq!(exit, LoopExit::DeviceLost);
+        assert_eq!(result.lock().bands, [0.0; 5]);
+    }
+
+    #[test]
+    fn analysis_loop_stops_on_command() {
+        let (command_tx, command_rx) = mpsc::channel();
+        command_tx.send(AudioCommand::Stop).unwrap();
+        let buffer = Arc::new(Mutex::new(AudioBuffer::new(4096)));
+        let analyzer = Arc::new(Mutex::new(FftAnalyzer::new(AudioConfig::default())));
+        let bass_lane = Mutex::new(BassLane::new(48000.0));
+        let result = Arc::new(Mutex::new(AnalysisResult::default()));
+
+        let exit = run_analysis_loop(
+            &command_rx,
+            &buffer,
+            &analyzer,
+            &bass_lane,
+            &result,
+            &AtomicBool::new(false),
+            &MixBus::new(0),
+            None,
+        );
+        assert_eq!(exit, LoopExit::Stopped);
+    }
+
+    #[test]
+    fn paused_analysis_loop_publishes_silence() {
+        let (command_tx, command_rx) = mpsc::channel();
+        let buffer = Arc::new(Mutex::new(AudioBuffer::new(4096)));
+        buffer.lock().push_samples(&[0.8; 4096]);
+        let analyzer = Arc::new(Mutex::new(FftAnalyzer::new(AudioConfig::default())));
+        let bass_lane = Mutex::new(BassLane::new(48000.0));
+        let result = Arc::new(Mutex::new(AnalysisResult {
+            bands: [0.5; 5],
+            ..Default::default()
+        }));
+        let paused = AtomicBool::new(true);
+
+        let stopper = thread::spawn(move || {
+            // Give the loop a few iterations before stopping it
+            thread::sleep(std::time::Duration::from_millis(50));
+            command_tx.send(AudioCommand::Stop).unwrap();
+        });
+        run_analysis_loop(
+            &command_rx,
+            &buffer,
+            &analyzer,
+            &bass_lane,
+            &result,
+            &paused,
+            &MixBus::new(0),
+            None,
+        );
+        stopper.join().unwrap();
+
+        let result = result.lock();
+        assert!(result.is_paused);
+        assert_eq!(result.bands, [0.0; 5]);
+    }
 }
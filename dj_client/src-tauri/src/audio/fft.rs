@@ -1,13 +1,81 @@
 //! FFT analysis - ported from Python fft_analyzer.py
 
-use super::{AudioConfig, capture::AnalysisResult};
+use super::{
+    AudioConfig, BandScale, DEFAULT_BAND_EDGES, band_edges_increasing, capture::AnalysisResult,
+};
 use rustfft::{FftPlanner, num_complex::Complex};
 use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Instant;
 
+/// RMS level of a frame below which the input counts as silent (-80 dBFS).
+/// Checked on the raw samples, since the per-band AGC would otherwise boost
+/// the noise floor to full scale.
+pub(super) const SILENCE_RMS: f32 = 1e-4;
+
+/// Band level (relative to a full-scale sine, -12 dBFS) that reads as 1.0
+/// when the per-band AGC is off
+const FIXED_BAND_REFERENCE: f32 = 0.251;
+
+/// Share of the phase error the smoothed phase corrects per second
+const PHASE_PULL_PER_SEC: f64 = 3.0;
+
+/// Time after a detected beat during which no new onset may fire.
+/// Matches the old 8-frame cooldown at the ~60Hz analysis rate.
+const BEAT_COOLDOWN_SECS: f64 = 0.13;
+
+/// Weight of the newest audible frame in the peak averages that
+/// `normalize_across_presets` matches (~2s at the ~60Hz analysis rate)
+const PRESET_PEAK_AVG_COEF: f32 = 0.01;
+
+/// Bounds of the gain that holds loudness steady across preset switches
+const PRESET_GAIN_MIN: f32 = 0.5;
+const PRESET_GAIN_MAX: f32 = 2.0;
+
+/// Average peak below which there is too little signal to match against
+const PRESET_MATCH_MIN_PEAK: f32 = 0.02;
+
+/// Frequency range folded into the chroma vector. Below ~130Hz a 1024-point
+/// FFT bin spans several semitones, so low notes would smear across classes.
+const CHROMA_MIN_HZ: f32 = 130.0;
+const CHROMA_MAX_HZ: f32 = 5000.0;
+
+/// Weight of the newest frame in the smoothed chroma (0-1)
+const CHROMA_SMOOTHING: f32 = 0.25;
+
+/// FFT bin range of each band between consecutive `edges`; edges past the
+/// Nyquist frequency are capped at the top bin
+fn band_boundaries(edges: &[f32; 6], fft_size: usize, sample_rate: u32) -> [(usize, usize); 5] {
+    let freq_to_bin = |freq: f32| -> usize {
+        (((freq * fft_size as f32) / sample_rate as f32).round() as usize).min(fft_size / 2)
+    };
+    std::array::from_fn(|i| (freq_to_bin(edges[i]), freq_to_bin(edges[i + 1])))
+}
+
+/// Lower edge of the sub-bass signal; the upper edge is the bass band's
+/// lower edge
+const SUB_BASS_MIN_HZ: f32 = 20.0;
+
+/// FFT bin range of the sub-bass below `low_edge`. It always keeps the
+/// lowest non-DC bin, so with 47Hz bins (1024 points at 48kHz) it is the
+/// one bin the bass band also starts on: a coarse rumble signal that only
+/// separates from the kick band at larger FFT sizes.
+fn sub_bass_bins(low_edge: f32, fft_size: usize, sample_rate: u32) -> (usize, usize) {
+    let freq_to_bin = |freq: f32| -> usize {
+        (((freq * fft_size as f32) / sample_rate as f32).round() as usize).min(fft_size / 2)
+    };
+    let start = freq_to_bin(SUB_BASS_MIN_HZ).max(1);
+    (start, freq_to_bin(low_edge).max(start + 1))
+}
+
+/// Pitch class (0 = C ... 11 = B) of the equal-tempered note nearest `freq`
+fn pitch_class(freq: f32) -> u8 {
+    let midi = 69.0 + 12.0 * (freq / 440.0).log2();
+    (midi.round() as i32).rem_euclid(12) as u8
+}
+
 /// Audio preset for tuning FFT analysis to different music styles
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct AudioPreset {
     pub name: String,
     pub attack: f32,
@@ -15,6 +83,51 @@ pub struct AudioPreset {
     pub beat_threshold: f32,
     pub bass_weight: f32,
     pub band_sensitivity: [f32; 5],
+    /// Per-band attack overriding `attack` (bass to air)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band_attack: Option<[f32; 5]>,
+    /// Per-band release overriding `release` (bass to air)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band_release: Option<[f32; 5]>,
+}
+
+impl AudioPreset {
+    /// Attack for each band, filled from `attack` unless overridden
+    pub fn attack_per_band(&self) -> [f32; 5] {
+        self.band_attack.unwrap_or([self.attack; 5])
+    }
+
+    /// Release for each band, filled from `release` unless overridden
+    pub fn release_per_band(&self) -> [f32; 5] {
+        self.band_release.unwrap_or([self.release; 5])
+    }
+
+    /// Check a preset from outside the app (e.g. an imported setup):
+    /// attack and release in 0-1, non-negative sensitivities and threshold
+    pub fn validate(&self) -> Result<(), String> {
+        let rates = self
+            .attack_per_band()
+            .into_iter()
+            .chain(self.release_per_band());
+        if !rates.into_iter().all(|r| (0.0..=1.0).contains(&r)) {
+            return Err("Preset attack and release must be between 0 and 1".to_string());
+        }
+        let gains = self
+            .band_sensitivity
+            .iter()
+            .chain([&self.beat_threshold, &self.bass_weight]);
+        if !gains.into_iter().all(|g| g.is_finite() && *g >= 0.0) {
+            return Err("Preset sensitivities must be non-negative numbers".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for AudioPreset {
+    /// The `auto` preset, first in `get_presets`
+    fn default() -> Self {
+        get_presets().remove(0)
+    }
 }
 
 /// Return all built-in presets
@@ -27,6 +140,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.3,
             bass_weight: 0.7,
             band_sensitivity: [1.0, 1.0, 1.0, 1.0, 1.0],
+            band_attack: None,
+            band_release: None,
         },
         AudioPreset {
             name: "edm".to_string(),
@@ -35,6 +150,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.1,
             bass_weight: 0.85,
             band_sensitivity: [1.5, 0.8, 0.9, 1.2, 1.0],
+            band_attack: None,
+            band_release: None,
         },
         AudioPreset {
             name: "chill".to_string(),
@@ -43,6 +160,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.6,
             bass_weight: 0.5,
             band_sensitivity: [0.9, 1.0, 1.1, 1.2, 1.3],
+            band_attack: None,
+            band_release: None,
         },
         AudioPreset {
             name: "rock".to_string(),
@@ -51,6 +170,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.3,
             bass_weight: 0.65,
             band_sensitivity: [1.2, 1.0, 1.0, 0.9, 0.8],
+            band_attack: None,
+            band_release: None,
         },
         AudioPreset {
             name: "hiphop".to_string(),
@@ -59,6 +180,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.2,
             bass_weight: 0.8,
             band_sensitivity: [1.4, 0.9, 1.0, 1.1, 0.9],
+            band_attack: None,
+            band_release: None,
         },
         AudioPreset {
             name: "folk".to_string(),
@@ -67,6 +190,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.5,
             bass_weight: 0.45,
             band_sensitivity: [0.8, 1.3, 1.4, 1.2, 0.9],
+            band_attack: None,
+            band_release: None,
         },
         AudioPreset {
             name: "classical".to_string(),
@@ -75,6 +200,8 @@ pub fn get_presets() -> Vec<AudioPreset> {
             beat_threshold: 1.8,
             bass_weight: 0.4,
             band_sensitivity: [0.8, 1.0, 1.2, 1.3, 1.4],
+            band_attack: None,
+            band_release: None,
         },
     ]
 }
@@ -235,6 +362,12 @@ impl BassLane {
         self.envelope
     }
 
+    /// Return the running peak the envelope is normalized against.
+    /// Onsets must rise by `onset_threshold` times this value.
+    pub fn running_peak(&self) -> f32 {
+        self.running_peak
+    }
+
     /// Reset all filter state.
     pub fn reset(&mut self) {
         self.prev_filtered = 0.0;
@@ -245,6 +378,57 @@ impl BassLane {
     }
 }
 
+/// What the last beat detector run decided
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BeatKind {
+    #[default]
+    None,
+    /// Bass flux onset
+    Onset,
+    /// Filled in from the tempo lock
+    Predicted,
+}
+
+/// Signal the beat detector listens to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BeatSource {
+    /// Smoothed FFT bass band (40-250Hz)
+    #[default]
+    BassBand,
+    /// Normalized IIR bass-lane envelope (<120Hz, ~1ms attack)
+    BassLane,
+    /// Average of the two, for kicks that sit between sub and low-mid
+    Combined,
+}
+
+/// Which way to move the tempo estimate by an octave
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TempoOctave {
+    /// Double time (x2)
+    Up,
+    /// Half time (x0.5)
+    Down,
+}
+
+/// Beat detector internals from the last frame, for threshold tuning
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct BeatDebug {
+    pub bass: f32,
+    /// Running bass average the threshold scales from
+    pub avg: f32,
+    pub bass_threshold: f32,
+    pub bass_flux: f32,
+    pub flux_mean: f32,
+    pub flux_std: f32,
+    pub flux_threshold: f32,
+    /// The preset's `beat_threshold` multiplier
+    pub beat_threshold: f32,
+    pub kind: BeatKind,
+}
+
 /// FFT analyzer for audio visualization
 pub struct FftAnalyzer {
     fft_size: usize,
@@ -253,26 +437,68 @@ pub struct FftAnalyzer {
     fft_buffer: Vec<Complex<f32>>,
     magnitudes: Vec<f32>,
 
-    // Band boundaries (bin indices for 5 bands)
+    // Band edges in Hz and the bin ranges they give (5 bands)
+    band_edges: [f32; 6],
     band_boundaries: [(usize, usize); 5],
 
+    // Sub-bass (20Hz up to the bass band) bins, running max, and smoothed
+    // level and dBFS; it shares the bass band's tuning
+    sub_bass_bins: (usize, usize),
+    sub_bass_max: f32,
+    sub_bass: f32,
+    sub_bass_db: f32,
+
+    // Pitch class of each FFT bin inside the chroma range
+    bin_pitch_class: Vec<Option<u8>>,
+
+    // Smoothed, peak-normalized chroma
+    chroma: [f32; 12],
+
     // Smoothed band values
     smoothed_bands: [f32; 5],
 
+    // Output scale, and smoothed band levels in dBFS for `BandScale::Db`
+    band_scale: BandScale,
+    db_floor: f32,
+    band_db: [f32; 5],
+
     // Per-band running max for normalization (decays slowly)
     band_max: [f32; 5],
 
-    // Preset-tunable parameters
-    attack: f32,
-    release: f32,
+    // Scale of the positive band flux added to the output, and last
+    // frame's unsmoothed bands (in the output scale) to take it from
+    transient_emphasis: f32,
+    prev_raw_bands: [f32; 5],
+    // Normalize against `band_max`; off reads bands against a fixed level
+    agc_enabled: bool,
+
+    // Preset-tunable parameters (attack/release per band, bass to air)
+    attack: [f32; 5],
+    release: [f32; 5],
     beat_threshold: f32,
     bass_weight: f32,
     band_sensitivity: [f32; 5],
+    /// User trim multiplied onto the preset's band sensitivity
+    band_trim: [f32; 5],
+
+    // Loudness matching across presets: the average output peak to hold
+    // (taken at the last preset switch), slow averages of the output and
+    // pre-gain peak, and the gain that closes the gap
+    normalize_across_presets: bool,
+    preset_target_peak: Option<f32>,
+    output_peak_avg: f32,
+    ungained_peak_avg: f32,
+    preset_gain: f32,
+
+    // Beat detection input and the latest bass-lane level from the capture loop
+    beat_source: BeatSource,
+    lane_bass: f32,
 
     // Beat detection
     beat_history: VecDeque<f32>,
     beat_sum: f32, // Running sum for O(1) mean
-    beat_cooldown: usize,
+    beat_cooldown_until: f64,
+    beat_debug: BeatDebug,
     last_beat_times: VecDeque<f64>,
     prev_bass: f32,
     flux_history: VecDeque<f32>,
@@ -283,14 +509,19 @@ pub struct FftAnalyzer {
     ioi_history: VecDeque<f64>,
     estimated_bpm: f32,
     tempo_confidence: f32,
+    /// Octave that gets the histogram bonus; moves with manual nudges
+    preferred_bpm: (f64, f64),
     start_time: Instant,
     last_output_beat_time: f64,
+    /// Beats counted by the phase-locked accumulator (fract is the phase)
+    smooth_beats: f64,
+    /// When `smooth_beats` was last advanced (seconds since `start_time`)
+    smooth_phase_time: Option<f64>,
 
     // Frame counter
     frame: u64,
 
     // Time tracking for BPM
-    #[allow(dead_code)]
     sample_rate: u32,
 }
 
@@ -315,19 +546,25 @@ impl FftAnalyzer {
         let fft_buffer = vec![Complex::new(0.0, 0.0); fft_size];
         let magnitudes = vec![0.0f32; fft_size / 2];
 
-        // Calculate band boundaries for 5 bands:
-        // Bass (40-250Hz), Low (250-500Hz), Mid (500-2000Hz), High (2-6kHz), Air (6-20kHz)
-        let freq_to_bin = |freq: f32| -> usize {
-            ((freq * fft_size as f32) / sample_rate as f32).round() as usize
+        let band_edges = if band_edges_increasing(&config.band_edges) {
+            config.band_edges
+        } else {
+            log::warn!(
+                "Ignoring invalid band edges {:?}, using the defaults",
+                config.band_edges
+            );
+            DEFAULT_BAND_EDGES
         };
-
-        let band_boundaries = [
-            (freq_to_bin(40.0), freq_to_bin(250.0)),    // Bass
-            (freq_to_bin(250.0), freq_to_bin(500.0)),   // Low
-            (freq_to_bin(500.0), freq_to_bin(2000.0)),  // Mid
-            (freq_to_bin(2000.0), freq_to_bin(6000.0)), // High
-            (freq_to_bin(6000.0), freq_to_bin(20000.0).min(fft_size / 2)), // Air
-        ];
+        let band_boundaries = band_boundaries(&band_edges, fft_size, sample_rate);
+
+        let bin_pitch_class = (0..fft_size / 2)
+            .map(|bin| {
+                let freq = bin as f32 * sample_rate as f32 / fft_size as f32;
+                (CHROMA_MIN_HZ..=CHROMA_MAX_HZ)
+                    .contains(&freq)
+                    .then(|| pitch_class(freq))
+            })
+            .collect();
 
         Self {
             fft_size,
@@ -335,17 +572,39 @@ impl FftAnalyzer {
             window,
             fft_buffer,
             magnitudes,
+            band_edges,
             band_boundaries,
+            sub_bass_bins: sub_bass_bins(band_edges[0], fft_size, sample_rate),
+            sub_bass_max: 0.001,
+            sub_bass: 0.0,
+            sub_bass_db: config.db_floor,
+            bin_pitch_class,
+            chroma: [0.0; 12],
             smoothed_bands: [0.0; 5],
+            band_scale: config.band_scale,
+            db_floor: config.db_floor,
+            band_db: [config.db_floor; 5],
             band_max: [0.001; 5],
-            attack: config.attack,
-            release: config.release,
+            transient_emphasis: config.transient_emphasis.max(0.0),
+            prev_raw_bands: [0.0; 5],
+            agc_enabled: config.agc_enabled,
+            attack: [config.attack; 5],
+            release: [config.release; 5],
             beat_threshold: config.beat_threshold,
             bass_weight: 0.7,
             band_sensitivity: [1.0; 5],
+            band_trim: [1.0; 5],
+            normalize_across_presets: false,
+            preset_target_peak: None,
+            output_peak_avg: 0.0,
+            ungained_peak_avg: 0.0,
+            preset_gain: 1.0,
+            beat_source: BeatSource::default(),
+            lane_bass: 0.0,
             beat_history: VecDeque::with_capacity(60),
             beat_sum: 0.0,
-            beat_cooldown: 0,
+            beat_cooldown_until: 0.0,
+            beat_debug: BeatDebug::default(),
             last_beat_times: VecDeque::with_capacity(20),
             prev_bass: 0.0,
             flux_history: VecDeque::with_capacity(120),
@@ -356,8 +615,11 @@ impl FftAnalyzer {
             ioi_history: VecDeque::with_capacity(32),
             estimated_bpm: 120.0,
             tempo_confidence: 0.0,
+            preferred_bpm: (80.0, 160.0),
             start_time: Instant::now(),
             last_output_beat_time: 0.0,
+            smooth_beats: 0.0,
+            smooth_phase_time: None,
             frame: 0,
             sample_rate,
         }
@@ -365,11 +627,118 @@ impl FftAnalyzer {
 
     /// Apply an audio preset, updating tunable parameters without resetting state
     pub fn apply_preset(&mut self, preset: &AudioPreset) {
-        self.attack = preset.attack;
-        self.release = preset.release;
+        self.attack = preset.attack_per_band();
+        self.release = preset.release_per_band();
         self.beat_threshold = preset.beat_threshold;
         self.bass_weight = preset.bass_weight;
         self.band_sensitivity = preset.band_sensitivity;
+        // Hold the level the show had under the previous preset
+        if self.normalize_across_presets && self.output_peak_avg > PRESET_MATCH_MIN_PEAK {
+            self.preset_target_peak = Some(self.output_peak_avg);
+        }
+    }
+
+    /// Scale the bands after a preset switch so the average peak stays
+    /// where the previous preset had it; off drops back to unity gain
+    pub fn set_normalize_across_presets(&mut self, enabled: bool) {
+        self.normalize_across_presets = enabled;
+        if !enabled {
+            self.preset_target_peak = None;
+            self.preset_gain = 1.0;
+        }
+    }
+
+    /// Track the average peak of an audible frame and retune the
+    /// cross-preset gain toward the target
+    fn update_preset_gain(&mut self, peak: f32) {
+        self.output_peak_avg += (peak - self.output_peak_avg) * PRESET_PEAK_AVG_COEF;
+        let ungained = peak / self.preset_gain;
+        self.ungained_peak_avg += (ungained - self.ungained_peak_avg) * PRESET_PEAK_AVG_COEF;
+        if let Some(target) = self.preset_target_peak
+            && self.ungained_peak_avg > PRESET_MATCH_MIN_PEAK
+        {
+            self.preset_gain =
+                (target / self.ungained_peak_avg).clamp(PRESET_GAIN_MIN, PRESET_GAIN_MAX);
+        }
+    }
+
+    /// Beat detector internals from the last analyzed frame
+    pub fn beat_debug(&self) -> BeatDebug {
+        self.beat_debug
+    }
+
+    /// Set the per-band trim; kept across preset changes
+    pub fn set_band_trim(&mut self, trim: [f32; 5]) {
+        self.band_trim = trim;
+    }
+
+    /// Choose the signal the beat detector listens to; kept across preset changes
+    pub fn set_beat_source(&mut self, source: BeatSource) {
+        self.beat_source = source;
+    }
+
+    /// Choose linear or dB output bands; `db_floor` is the dBFS level
+    /// shown as 0 in dB mode
+    pub fn set_band_scale(&mut self, scale: BandScale, db_floor: f32) {
+        self.band_scale = scale;
+        self.db_floor = db_floor;
+    }
+
+    /// Retune the band edges (Hz); ignored unless strictly increasing. Check
+    /// user input with `validate_band_edges` first.
+    pub fn set_band_edges(&mut self, edges: [f32; 6]) {
+        if !band_edges_increasing(&edges) {
+            return;
+        }
+        self.band_edges = edges;
+        self.band_boundaries = band_boundaries(&edges, self.fft_size, self.sample_rate);
+        self.sub_bass_bins = sub_bass_bins(edges[0], self.fft_size, self.sample_rate);
+    }
+
+    /// Scale the rise-since-last-frame added on top of each smoothed band
+    /// (0 turns it off)
+    pub fn set_transient_emphasis(&mut self, emphasis: f32) {
+        self.transient_emphasis = emphasis.max(0.0);
+    }
+
+    /// Turn the per-band AGC on (adaptive levels) or off (absolute levels,
+    /// for pre-mastered material)
+    pub fn set_agc(&mut self, enabled: bool) {
+        self.agc_enabled = enabled;
+    }
+
+    /// Signal the beat detector listens to
+    pub fn beat_source(&self) -> BeatSource {
+        self.beat_source
+    }
+
+    /// Latest normalized bass-lane level (0.0-1.0), read by the next
+    /// `analyze` when the beat source uses the bass lane
+    pub fn set_lane_bass(&mut self, level: f32) {
+        self.lane_bass = level.clamp(0.0, 1.0);
+    }
+
+    /// Carry preset parameters, band trim and beat source over from `other`, e.g. when
+    /// the audio thread swaps in an analyzer for the device's sample rate
+    pub fn with_tuning_of(mut self, other: &FftAnalyzer) -> Self {
+        self.attack = other.attack;
+        self.release = other.release;
+        self.beat_threshold = other.beat_threshold;
+        self.bass_weight = other.bass_weight;
+        self.band_sensitivity = other.band_sensitivity;
+        self.band_trim = other.band_trim;
+        self.beat_source = other.beat_source;
+        self.band_scale = other.band_scale;
+        self.db_floor = other.db_floor;
+        self.agc_enabled = other.agc_enabled;
+        self.transient_emphasis = other.transient_emphasis;
+        self.normalize_across_presets = other.normalize_across_presets;
+        self.preset_target_peak = other.preset_target_peak;
+        self.output_peak_avg = other.output_peak_avg;
+        self.ungained_peak_avg = other.ungained_peak_avg;
+        self.preset_gain = other.preset_gain;
+        self.set_band_edges(other.band_edges);
+        self
     }
 
     /// Get FFT size
@@ -377,6 +746,11 @@ impl FftAnalyzer {
         self.fft_size
     }
 
+    /// Sample rate the analyzer was configured for
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
     /// Analyze audio samples and return frequency bands
     pub fn analyze(&mut self, samples: &[f32]) -> AnalysisResult {
         self.frame += 1;
@@ -386,6 +760,9 @@ impl FftAnalyzer {
             return AnalysisResult::default();
         }
 
+        let input = &samples[..self.fft_size];
+        let rms = (input.iter().map(|s| s * s).sum::<f32>() / input.len() as f32).sqrt();
+
         // Apply window and prepare FFT input (reuse pre-allocated buffer)
         for (i, (&s, &w)) in samples
             .iter()
@@ -409,19 +786,31 @@ impl FftAnalyzer {
             self.magnitudes[i] = self.fft_buffer[i].norm();
         }
 
-        // Extract bands
+        // Extract bands, plus each band's level in dBFS: a full-scale sine
+        // puts N/4 in its Hann-windowed bin and N/8 in each neighbour, so the
+        // band energy is measured against 1.5 * (N/4)^2
+        let full_scale = 1.5 * (self.fft_size as f32 / 4.0).powi(2);
         let mut raw_bands = [0.0f32; 5];
+        let mut band_level = [0.0f32; 5];
+        let mut raw_db = [self.db_floor; 5];
         for (i, &(start, end)) in self.band_boundaries.iter().enumerate() {
             let start = start.max(1);
             let end = end.min(self.magnitudes.len());
             if start < end {
-                let sum: f32 = self.magnitudes[start..end].iter().sum();
+                let band = &self.magnitudes[start..end];
+                let sum: f32 = band.iter().sum();
                 raw_bands[i] = sum / (end - start) as f32;
+                let energy: f32 = band.iter().map(|m| m * m).sum();
+                band_level[i] = (energy / full_scale).sqrt();
+                raw_db[i] =
+                    (10.0 * (energy / full_scale + 1e-12).log10()).clamp(self.db_floor, 0.0);
             }
         }
 
         // Per-band AGC: each band tracks its own running max for normalization.
         // This prevents loud bass from crushing quiet high-frequency bands.
+        // With AGC off the max keeps tracking (so re-enabling is seamless) but
+        // bands are read against the fixed full-scale reference instead.
         for (i, band) in raw_bands.iter_mut().enumerate() {
             // Update running max (fast attack, slow decay)
             if *band > self.band_max[i] {
@@ -431,51 +820,214 @@ impl FftAnalyzer {
                 self.band_max[i] *= 0.997;
                 self.band_max[i] = self.band_max[i].max(0.001);
             }
-            *band = (*band / self.band_max[i]).min(1.0);
+            *band = if self.agc_enabled {
+                (*band / self.band_max[i]).min(1.0)
+            } else {
+                (band_level[i] / FIXED_BAND_REFERENCE).min(1.0)
+            };
 
-            // Apply per-band sensitivity from preset
-            *band = (*band * self.band_sensitivity[i]).min(1.0);
+            // Apply per-band sensitivity from preset, the user trim and the
+            // cross-preset loudness gain
+            *band =
+                (*band * self.band_sensitivity[i] * self.band_trim[i] * self.preset_gain).min(1.0);
         }
 
         // Apply envelope following (attack/release smoothing)
+        for (i, (current, raw)) in self.band_db.iter_mut().zip(raw_db).enumerate() {
+            let rate = if raw > *current {
+                self.attack[i]
+            } else {
+                self.release[i]
+            };
+            *current += (raw - *current) * rate;
+        }
         for (i, &raw) in raw_bands.iter().enumerate() {
             let current = self.smoothed_bands[i];
             if raw > current {
                 // Attack
-                self.smoothed_bands[i] = current + (raw - current) * self.attack;
+                self.smoothed_bands[i] = current + (raw - current) * self.attack[i];
             } else {
                 // Release
-                self.smoothed_bands[i] = current + (raw - current) * self.release;
+                self.smoothed_bands[i] = current + (raw - current) * self.release[i];
+            }
+        }
+        self.update_sub_bass(full_scale);
+
+        // Positive flux of the unsmoothed bands, for `transient_emphasis`.
+        // Taken in the output scale so it adds onto like units.
+        let raw_output = match self.band_scale {
+            BandScale::Linear => raw_bands,
+            BandScale::Db => raw_db.map(|db| self.db_to_unit(db)),
+        };
+        let mut transient = [0.0f32; 5];
+        for ((flux, &raw), prev) in transient
+            .iter_mut()
+            .zip(&raw_output)
+            .zip(&mut self.prev_raw_bands)
+        {
+            *flux = (raw - *prev).max(0.0);
+            *prev = raw;
+        }
+        let mut bands = self.output_bands();
+        if self.transient_emphasis > 0.0 {
+            for (band, flux) in bands.iter_mut().zip(transient) {
+                *band = (*band + flux * self.transient_emphasis).min(1.0);
             }
         }
 
         // Calculate peak
         let peak = self.smoothed_bands.iter().cloned().fold(0.0f32, f32::max);
+        if rms >= SILENCE_RMS {
+            self.update_preset_gain(peak);
+        }
+
+        let dominant_pitch_class = self.update_chroma();
 
         // Beat detection on bass
-        let bass = self.smoothed_bands[0];
+        let bass = self.beat_input();
         let (is_beat, beat_intensity) = self.detect_beat(bass);
 
         // Estimate BPM
         let bpm = self.estimate_bpm();
         let beat_phase = self.estimate_beat_phase();
+        let beat_phase_smooth =
+            self.advance_smooth_phase(beat_phase, self.start_time.elapsed().as_secs_f64());
 
         AnalysisResult {
-            bands: self.smoothed_bands,
+            bands,
+            sub_bass: self.output_sub_bass(),
             peak,
             is_beat,
             beat_intensity,
             bpm,
             tempo_confidence: self.tempo_confidence,
             beat_phase,
+            beat_phase_smooth,
             // Bass lane fields are populated by the capture loop, not by FFT analysis
             instant_bass: 0.0,
             instant_kick: false,
+            bass_raw_envelope: 0.0,
+            bass_running_peak: 0.0,
+            is_paused: false,
+            is_silent: rms < SILENCE_RMS,
+            // Set by the capture loop, which sees the samples after gain
+            clipping: false,
+            chroma: self.chroma,
+            dominant_pitch_class,
+        }
+    }
+
+    /// Fold the magnitude spectrum into the smoothed 12-bin chroma and
+    /// return the strongest pitch class (0 when there is no energy)
+    fn update_chroma(&mut self) -> u8 {
+        let mut frame = [0.0f32; 12];
+        for (magnitude, class) in self.magnitudes.iter().zip(&self.bin_pitch_class) {
+            if let Some(class) = class {
+                // Power, so the fundamental dominates window leakage
+                frame[*class as usize] += magnitude * magnitude;
+            }
+        }
+        let max = frame.iter().cloned().fold(0.0f32, f32::max);
+        if max > 0.0 {
+            for value in &mut frame {
+                *value /= max;
+            }
+        }
+        for (smoothed, value) in self.chroma.iter_mut().zip(frame) {
+            *smoothed += (value - *smoothed) * CHROMA_SMOOTHING;
+        }
+
+        self.chroma
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v > 0.0)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(class, _)| class as u8)
+    }
+
+    /// Bands as reported, per `band_scale`. Beat detection always runs on
+    /// the linear bands.
+    fn output_bands(&self) -> [f32; 5] {
+        match self.band_scale {
+            BandScale::Linear => self.smoothed_bands,
+            BandScale::Db => self.band_db.map(|db| self.db_to_unit(db)),
+        }
+    }
+
+    /// Map a level in dBFS onto 0-1 over `db_floor`..0
+    fn db_to_unit(&self, db: f32) -> f32 {
+        ((db - self.db_floor) / -self.db_floor).clamp(0.0, 1.0)
+    }
+
+    /// Normalize and smooth the sub-bass bins like the bass band: own
+    /// running max (or the fixed reference with AGC off), then the bass
+    /// band's sensitivity, trim, preset gain and attack/release
+    fn update_sub_bass(&mut self, full_scale: f32) {
+        let (start, end) = self.sub_bass_bins;
+        let end = end.min(self.magnitudes.len());
+        let (raw, level, raw_db) = if start < end {
+            let bins = &self.magnitudes[start..end];
+            let energy: f32 = bins.iter().map(|m| m * m).sum();
+            (
+                bins.iter().sum::<f32>() / (end - start) as f32,
+                (energy / full_scale).sqrt(),
+                (10.0 * (energy / full_scale + 1e-12).log10()).clamp(self.db_floor, 0.0),
+            )
+        } else {
+            (0.0, 0.0, self.db_floor)
+        };
+
+        if raw > self.sub_bass_max {
+            self.sub_bass_max = raw;
+        } else {
+            self.sub_bass_max = (self.sub_bass_max * 0.997).max(0.001);
+        }
+        let normalized = if self.agc_enabled {
+            (raw / self.sub_bass_max).min(1.0)
+        } else {
+            (level / FIXED_BAND_REFERENCE).min(1.0)
+        };
+        let target =
+            (normalized * self.band_sensitivity[0] * self.band_trim[0] * self.preset_gain).min(1.0);
+
+        let rate = |rising: bool| {
+            if rising {
+                self.attack[0]
+            } else {
+                self.release[0]
+            }
+        };
+        self.sub_bass += (target - self.sub_bass) * rate(target > self.sub_bass);
+        self.sub_bass_db += (raw_db - self.sub_bass_db) * rate(raw_db > self.sub_bass_db);
+    }
+
+    /// `sub_bass` in the output scale, like `output_bands`
+    fn output_sub_bass(&self) -> f32 {
+        match self.band_scale {
+            BandScale::Linear => self.sub_bass,
+            BandScale::Db => self.db_to_unit(self.sub_bass_db),
+        }
+    }
+
+    /// Bass level fed to the beat detector, per `beat_source`
+    fn beat_input(&self) -> f32 {
+        let band = self.smoothed_bands[0];
+        match self.beat_source {
+            BeatSource::BassBand => band,
+            BeatSource::BassLane => self.lane_bass,
+            BeatSource::Combined => (band + self.lane_bass) * 0.5,
         }
     }
 
     /// Detect beats based on bass energy
     fn detect_beat(&mut self, bass: f32) -> (bool, f32) {
+        let current_time = self.start_time.elapsed().as_secs_f64();
+        self.detect_beat_at(bass, current_time)
+    }
+
+    /// `detect_beat` at an explicit time (seconds since `start_time`).
+    /// All timing here is wall-clock based, so it holds at any analysis rate.
+    fn detect_beat_at(&mut self, bass: f32, current_time: f64) -> (bool, f32) {
         // Update beat history with running sum (O(1) mean)
         self.beat_history.push_back(bass);
         self.beat_sum += bass;
@@ -495,12 +1047,7 @@ impl FftAnalyzer {
             self.flux_sum_sq -= removed * removed;
         }
 
-        let current_time = self.start_time.elapsed().as_secs_f64();
-
-        // Decrement cooldown
-        if self.beat_cooldown > 0 {
-            self.beat_cooldown -= 1;
-        }
+        let cooling_down = current_time < self.beat_cooldown_until;
 
         // Dynamic bass threshold (O(1) via running sum)
         let avg = if self.beat_history.is_empty() {
@@ -520,6 +1067,17 @@ impl FftAnalyzer {
             (mean, var.sqrt())
         };
         let flux_threshold = (flux_mean + flux_std * self.beat_threshold).max(0.015);
+        self.beat_debug = BeatDebug {
+            bass,
+            avg,
+            bass_threshold,
+            bass_flux,
+            flux_mean,
+            flux_std,
+            flux_threshold,
+            beat_threshold: self.beat_threshold,
+            kind: BeatKind::None,
+        };
 
         // Onset candidate if bass jump is strong and bass is meaningfully above floor.
         let mut is_onset = bass_flux >= flux_threshold && bass > bass_threshold;
@@ -534,13 +1092,13 @@ impl FftAnalyzer {
             .map(|last| current_time - last >= min_interval)
             .unwrap_or(true);
 
-        if self.beat_cooldown == 0 && is_onset && can_fire {
+        if !cooling_down && is_onset && can_fire {
             self._update_bpm_from_onset(current_time);
             self.last_onset_time = Some(current_time);
             self.last_output_beat_time = current_time;
 
-            // Soft cooldown by frame count; preserves legacy anti-chatter behavior.
-            self.beat_cooldown = 8;
+            // Soft cooldown; preserves legacy anti-chatter behavior.
+            self.beat_cooldown_until = current_time + BEAT_COOLDOWN_SECS;
 
             // Keep raw onset times for debug/tests/legacy behavior.
             self.last_beat_times.push_back(current_time);
@@ -554,6 +1112,7 @@ impl FftAnalyzer {
             let bass_w = self.bass_weight;
             let flux_w = 1.0 - bass_w * 0.5; // flux always contributes at least 50%
             let intensity = (flux_score * flux_w + bass_score * (1.0 - flux_w)).min(1.0);
+            self.beat_debug.kind = BeatKind::Onset;
             return (true, intensity);
         }
 
@@ -567,6 +1126,7 @@ impl FftAnalyzer {
                 let near_boundary = !(0.10..=0.90).contains(&phase);
                 if near_boundary && bass > avg * 0.85 && bass_flux > flux_mean * 0.6 {
                     self.last_output_beat_time = current_time;
+                    self.beat_debug.kind = BeatKind::Predicted;
                     return (true, 0.55);
                 }
             }
@@ -628,7 +1188,7 @@ impl FftAnalyzer {
 
                 let x = offset as f32 / 1.5;
                 let mut weight = (-0.5 * x * x).exp();
-                if (80.0..=160.0).contains(&candidate) {
+                if (self.preferred_bpm.0..=self.preferred_bpm.1).contains(&candidate) {
                     weight *= 1.4;
                 }
                 self.tempo_histogram[idx as usize] += weight;
@@ -706,11 +1266,63 @@ impl FftAnalyzer {
         }
     }
 
+    /// Move the tempo estimate up or down an octave when the detector has
+    /// latched onto the wrong one, and return the new BPM.
+    ///
+    /// The histogram is rescaled rather than cleared so the lock holds, and
+    /// the preferred octave follows so new onsets don't pull it back. A
+    /// nudge that would leave 60-200 BPM is ignored.
+    pub fn nudge_tempo_octave(&mut self, direction: TempoOctave) -> f32 {
+        let factor = match direction {
+            TempoOctave::Up => 2.0,
+            TempoOctave::Down => 0.5,
+        };
+        let target = self.estimated_bpm * factor as f32;
+        if !(60.0..=200.0).contains(&target) {
+            return self.estimated_bpm;
+        }
+        self.estimated_bpm = target;
+
+        let len = self.tempo_histogram.len();
+        let old = std::mem::replace(&mut self.tempo_histogram, vec![0.0; len]);
+        for (i, weight) in old.into_iter().enumerate() {
+            let idx = (((i + 40) as f64 * factor).round() as i64) - 40;
+            if (0..len as i64).contains(&idx) {
+                self.tempo_histogram[idx as usize] += weight;
+            }
+        }
+        self.preferred_bpm = (self.preferred_bpm.0 * factor, self.preferred_bpm.1 * factor);
+
+        self.estimated_bpm
+    }
+
     /// Estimate BPM from beat history
     fn estimate_bpm(&self) -> f32 {
         self.estimated_bpm
     }
 
+    /// Advance the phase-locked accumulator to `now` at the estimated BPM,
+    /// easing it toward `raw_phase` once beats have been seen, and return
+    /// its phase in [0, 1). The correction never outruns the advance, so the
+    /// accumulator only moves forward.
+    fn advance_smooth_phase(&mut self, raw_phase: f32, now: f64) -> f32 {
+        let dt = self
+            .smooth_phase_time
+            .map_or(0.0, |last| (now - last).max(0.0));
+        self.smooth_phase_time = Some(now);
+
+        let advance = dt * self.estimated_bpm.max(0.0) as f64 / 60.0;
+        let correction = if self.last_output_beat_time > 0.0 {
+            // Shortest way round the cycle to the detected phase
+            let error = (raw_phase as f64 - self.smooth_beats.fract() + 0.5).rem_euclid(1.0) - 0.5;
+            error * (PHASE_PULL_PER_SEC * dt).min(1.0)
+        } else {
+            0.0
+        };
+        self.smooth_beats += (advance + correction).max(0.0);
+        self.smooth_beats.fract() as f32
+    }
+
     /// Estimate current beat phase in [0, 1).
     fn estimate_beat_phase(&self) -> f32 {
         if self.last_output_beat_time <= 0.0 || self.estimated_bpm <= 0.0 {
@@ -728,6 +1340,65 @@ impl FftAnalyzer {
     }
 }
 
+/// Per-call timing of `analyze` plus the bass lane on a synthetic signal
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct AnalysisBenchmark {
+    pub iterations: usize,
+    pub fft_size: usize,
+    pub sample_rate: u32,
+    /// Mean time per call in microseconds
+    pub mean_us: f64,
+    /// 99th percentile time per call in microseconds
+    pub p99_us: f64,
+    /// Slowest call in microseconds
+    pub max_us: f64,
+}
+
+/// Time `iterations` frames through `analyzer` and a fresh bass lane, the
+/// same work the analysis loop does per tick. The input is a 60Hz kick on
+/// every 30th frame over a 1kHz tone, so the beat and tempo paths run too.
+pub fn benchmark_analysis(mut analyzer: FftAnalyzer, iterations: usize) -> AnalysisBenchmark {
+    let fft_size = analyzer.fft_size();
+    let sample_rate = analyzer.sample_rate();
+    let mut bass_lane = BassLane::new(sample_rate as f32);
+    let mut frame = vec![0.0f32; fft_size];
+    let mut timings = Vec::with_capacity(iterations);
+
+    for i in 0..iterations {
+        let kick = if i % 30 == 0 { 0.6 } else { 0.0 };
+        for (n, sample) in frame.iter_mut().enumerate() {
+            let t = (i * fft_size + n) as f32 / sample_rate as f32;
+            *sample = kick * (2.0 * std::f32::consts::PI * 60.0 * t).sin()
+                + 0.2 * (2.0 * std::f32::consts::PI * 1000.0 * t).sin();
+        }
+
+        let started = Instant::now();
+        let (lane_bass, _) = bass_lane.process(&frame);
+        analyzer.set_lane_bass(lane_bass);
+        std::hint::black_box(analyzer.analyze(&frame));
+        timings.push(started.elapsed().as_secs_f64() * 1e6);
+    }
+
+    if timings.is_empty() {
+        return AnalysisBenchmark {
+            fft_size,
+            sample_rate,
+            ..Default::default()
+        };
+    }
+    let mean_us = timings.iter().sum::<f64>() / timings.len() as f64;
+    timings.sort_by(f64::total_cmp);
+    let p99_index = ((timings.len() as f64 * 0.99).ceil() as usize).clamp(1, timings.len()) - 1;
+    AnalysisBenchmark {
+        iterations,
+        fft_size,
+        sample_rate,
+        mean_us,
+        p99_us: timings[p99_index],
+        max_us: timings[timings.len() - 1],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -765,6 +1436,68 @@ mod tests {
         assert!(!result.is_beat);
         assert_eq!(result.beat_intensity, 0.0);
         assert_eq!(result.bpm, 120.0);
+        assert!(result.is_silent);
+    }
+
+    #[test]
+    fn benchmark_reports_ordered_timings() {
+        let stats = benchmark_analysis(FftAnalyzer::new(AudioConfig::default()), 50);
+
+        assert_eq!(stats.iterations, 50);
+        assert_eq!(stats.fft_size, 1024);
+        assert!(stats.mean_us > 0.0);
+        assert!(stats.p99_us <= stats.max_us);
+        assert!(stats.mean_us <= stats.max_us);
+
+        let empty = benchmark_analysis(FftAnalyzer::new(AudioConfig::default()), 0);
+        assert_eq!(empty.iterations, 0);
+        assert_eq!(empty.mean_us, 0.0);
+    }
+
+    #[test]
+    fn pitch_class_maps_notes() {
+        assert_eq!(pitch_class(440.0), 9); // A4
+        assert_eq!(pitch_class(261.63), 0); // C4
+        assert_eq!(pitch_class(277.18), 1); // C#4
+        assert_eq!(pitch_class(987.77), 11); // B5
+    }
+
+    #[test]
+    fn chroma_follows_a_tone() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        // 890.625Hz sits exactly on bin 19, about a fifth of a semitone above A5
+        let freq = 19.0 * 48000.0 / 1024.0;
+        let tone: Vec<f32> = (0..analyzer.fft_size())
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * freq * i as f32 / 48000.0).sin())
+            .collect();
+
+        let mut result = analyzer.analyze(&tone);
+        for _ in 0..10 {
+            result = analyzer.analyze(&tone);
+        }
+
+        assert_eq!(result.dominant_pitch_class, 9);
+        assert!(result.chroma[9] > 0.9);
+        assert!(result.chroma[0] < 0.1);
+    }
+
+    #[test]
+    fn chroma_is_empty_for_silence() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        let result = analyzer.analyze(&vec![0.0; analyzer.fft_size()]);
+
+        assert_eq!(result.chroma, [0.0; 12]);
+        assert_eq!(result.dominant_pitch_class, 0);
+    }
+
+    #[test]
+    fn analyze_tone_is_not_silent() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        let tone: Vec<f32> = (0..analyzer.fft_size())
+            .map(|i| 0.01 * (i as f32 * 0.05).sin())
+            .collect();
+
+        assert!(!analyzer.analyze(&tone).is_silent);
     }
 
     #[test]
@@ -775,17 +1508,40 @@ mod tests {
             analyzer.beat_history.push_back(0.1);
         }
 
+        // The cooldown is time based: 130ms after the beat, however many
+        // frames are analyzed in between
         analyzer.frame = 120;
-        let (first_beat, first_intensity) = analyzer.detect_beat(0.6);
+        let (first_beat, first_intensity) = analyzer.detect_beat_at(0.6, 2.0);
         assert!(first_beat);
         assert!(first_intensity > 0.0);
-        assert_eq!(analyzer.beat_cooldown, 8);
+        assert_approx(analyzer.beat_cooldown_until as f32, 2.13, 1e-6);
 
         analyzer.frame += 1;
-        let (second_beat, second_intensity) = analyzer.detect_beat(0.8);
+        let (second_beat, second_intensity) = analyzer.detect_beat_at(0.8, 2.05);
         assert!(!second_beat);
         assert_eq!(second_intensity, 0.0);
-        assert_eq!(analyzer.beat_cooldown, 7);
+        assert_approx(analyzer.beat_cooldown_until as f32, 2.13, 1e-6);
+    }
+
+    #[test]
+    fn beat_debug_reports_last_decision() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        assert_eq!(analyzer.beat_debug().kind, BeatKind::None);
+
+        for _ in 0..60 {
+            analyzer.beat_history.push_back(0.1);
+        }
+
+        analyzer.frame = 120;
+        assert!(analyzer.detect_beat_at(0.6, 2.0).0);
+        let debug = analyzer.beat_debug();
+        assert_eq!(debug.kind, BeatKind::Onset);
+        assert_approx(debug.bass, 0.6, 1e-6);
+        assert!(debug.bass_threshold >= 0.12);
+
+        analyzer.frame += 1;
+        assert!(!analyzer.detect_beat_at(0.8, 2.05).0);
+        assert_eq!(analyzer.beat_debug().kind, BeatKind::None);
     }
 
     #[test]
@@ -846,13 +1602,360 @@ mod tests {
         let edm = get_preset("edm").unwrap();
         analyzer.apply_preset(&edm);
 
-        assert_approx(analyzer.attack, 0.7, 0.001);
-        assert_approx(analyzer.release, 0.15, 0.001);
+        assert_eq!(analyzer.attack, [0.7; 5]);
+        assert_eq!(analyzer.release, [0.15; 5]);
         assert_approx(analyzer.beat_threshold, 1.1, 0.001);
         assert_approx(analyzer.bass_weight, 0.85, 0.001);
         assert_approx(analyzer.band_sensitivity[0], 1.5, 0.001);
     }
 
+    #[test]
+    fn preset_validation_rejects_out_of_range_tuning() {
+        assert!(get_presets().iter().all(|p| p.validate().is_ok()));
+
+        let mut preset = get_preset("edm").unwrap();
+        preset.band_release = Some([0.1, 0.1, 1.5, 0.1, 0.1]);
+        assert!(preset.validate().is_err());
+
+        let mut preset = get_preset("edm").unwrap();
+        preset.band_sensitivity[2] = f32::NAN;
+        assert!(preset.validate().is_err());
+    }
+
+    #[test]
+    fn per_band_attack_and_release_override_the_scalars() {
+        let preset: AudioPreset = serde_json::from_value(serde_json::json!({
+            "name": "custom",
+            "attack": 0.3,
+            "release": 0.1,
+            "beat_threshold": 1.3,
+            "bass_weight": 0.7,
+            "band_sensitivity": [1.0, 1.0, 1.0, 1.0, 1.0],
+            "band_attack": [0.9, 0.5, 0.3, 0.3, 0.3],
+        }))
+        .unwrap();
+        assert_eq!(preset.attack_per_band(), [0.9, 0.5, 0.3, 0.3, 0.3]);
+        assert_eq!(preset.release_per_band(), [0.1; 5]);
+
+        // Built-in presets stay uniform and serialize without the overrides
+        let edm = get_preset("edm").unwrap();
+        assert_eq!(edm.attack_per_band(), [0.7; 5]);
+        assert!(
+            serde_json::to_value(&edm)
+                .unwrap()
+                .get("band_attack")
+                .is_none()
+        );
+
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        analyzer.apply_preset(&preset);
+        assert_eq!(analyzer.attack, [0.9, 0.5, 0.3, 0.3, 0.3]);
+    }
+
+    #[test]
+    fn smooth_phase_never_steps_back_and_locks_to_beats() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        analyzer.estimated_bpm = 120.0;
+        analyzer.last_output_beat_time = 0.01;
+
+        let mut last_beats = 0.0;
+        let mut raw = 0.0f32;
+        let mut smooth = 0.0f32;
+        for frame in 0..600 {
+            let t = frame as f64 / 60.0;
+            // Detected phase at 120 BPM, re-anchored 50ms late every 2s, which
+            // makes the raw phase jump backward
+            let anchor = (t / 2.0).floor() * 2.0 + if t >= 2.0 { 0.05 } else { 0.0 };
+            raw = ((t - anchor).max(0.0) * 2.0).fract() as f32;
+            smooth = analyzer.advance_smooth_phase(raw, t);
+            assert!(
+                analyzer.smooth_beats >= last_beats,
+                "stepped back at frame {frame}"
+            );
+            last_beats = analyzer.smooth_beats;
+        }
+        let error = (smooth - raw + 0.5).rem_euclid(1.0) - 0.5;
+        assert!(error.abs() < 0.05, "smooth {smooth} vs raw {raw}");
+    }
+
+    #[test]
+    fn band_edges_set_the_bin_ranges() {
+        // 46.875 Hz bins at 48kHz / 1024
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        assert_eq!(analyzer.band_boundaries[0], (1, 5));
+        assert_eq!(analyzer.band_boundaries[4], (128, 427));
+
+        // Split the bass into sub and low bass
+        analyzer.set_band_edges([20.0, 90.0, 250.0, 2000.0, 8000.0, 20000.0]);
+        assert_eq!(analyzer.band_boundaries[0], (0, 2));
+        assert_eq!(analyzer.band_boundaries[1], (2, 5));
+
+        // Out of order edges are ignored
+        analyzer.set_band_edges([40.0, 30.0, 500.0, 2000.0, 6000.0, 20000.0]);
+        assert_eq!(analyzer.band_edges[1], 90.0);
+
+        // The top edge is capped at the top bin at low sample rates
+        let low_rate = FftAnalyzer::new(AudioConfig {
+            sample_rate: 16000,
+            ..Default::default()
+        })
+        .with_tuning_of(&analyzer);
+        assert_eq!(low_rate.band_edges[0], 20.0);
+        assert_eq!(low_rate.band_boundaries[4].1, 512);
+
+        assert!(crate::audio::validate_band_edges(&DEFAULT_BAND_EDGES, 48000).is_ok());
+        assert!(crate::audio::validate_band_edges(&DEFAULT_BAND_EDGES, 32000).is_err());
+    }
+
+    #[test]
+    fn sub_bass_follows_rumble_below_the_bass_band() {
+        // 8192 points give 5.9Hz bins, enough to split 30Hz from the kick
+        // band. AGC off so window leakage isn't normalized up to full scale.
+        let config = AudioConfig {
+            fft_size: 8192,
+            agc_enabled: false,
+            ..Default::default()
+        };
+        let tone = |freq: f32| -> Vec<f32> {
+            (0..8192)
+                .map(|i| 0.5 * (2.0 * std::f32::consts::PI * freq * i as f32 / 48000.0).sin())
+                .collect()
+        };
+
+        let mut analyzer = FftAnalyzer::new(config.clone());
+        assert_eq!(analyzer.sub_bass_bins, (3, 7));
+        let rumble = tone(30.0);
+        let mut result = analyzer.analyze(&rumble);
+        for _ in 0..20 {
+            result = analyzer.analyze(&rumble);
+        }
+        assert!(result.sub_bass > 0.9, "sub_bass {}", result.sub_bass);
+
+        let mut analyzer = FftAnalyzer::new(config);
+        let kick = tone(120.0);
+        let mut result = analyzer.analyze(&kick);
+        for _ in 0..20 {
+            result = analyzer.analyze(&kick);
+        }
+        assert!(result.bands[0] > 0.9);
+        assert!(result.sub_bass < 0.1, "sub_bass {}", result.sub_bass);
+
+        // At the default size the lowest non-DC bin stands in for it
+        assert_eq!(
+            FftAnalyzer::new(AudioConfig::default()).sub_bass_bins,
+            (1, 2)
+        );
+    }
+
+    #[test]
+    fn transient_emphasis_pops_band_onsets() {
+        let config = AudioConfig {
+            agc_enabled: false,
+            ..Default::default()
+        };
+        let silence = vec![0.0; 1024];
+        let kick: Vec<f32> = (0..1024)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 93.75 * i as f32 / 48000.0).sin())
+            .collect();
+
+        let mut plain = FftAnalyzer::new(config.clone());
+        let mut punchy = FftAnalyzer::new(AudioConfig {
+            transient_emphasis: 1.0,
+            ..config
+        });
+        plain.analyze(&silence);
+        punchy.analyze(&silence);
+
+        // The onset frame jumps past the attack curve
+        let onset_plain = plain.analyze(&kick).bands[0];
+        let onset_punchy = punchy.analyze(&kick).bands[0];
+        assert!(onset_plain < 0.5, "plain onset {onset_plain}");
+        assert!(onset_punchy > 0.9, "emphasized onset {onset_punchy}");
+
+        // A held note has no flux, so both settle on the smoothed value
+        let held_plain = plain.analyze(&kick).bands[0];
+        let held_punchy = punchy.analyze(&kick).bands[0];
+        assert_approx(held_punchy, held_plain, 1e-6);
+    }
+
+    #[test]
+    fn transient_emphasis_follows_db_scale() {
+        // -40 dBFS kick: a third of the way up a 60dB range
+        let silence = vec![0.0; 1024];
+        let kick: Vec<f32> = (0..1024)
+            .map(|i| 0.01 * (2.0 * std::f32::consts::PI * 93.75 * i as f32 / 48000.0).sin())
+            .collect();
+
+        let mut plain = FftAnalyzer::new(AudioConfig::default());
+        let mut punchy = FftAnalyzer::new(AudioConfig {
+            transient_emphasis: 1.0,
+            ..Default::default()
+        });
+        for analyzer in [&mut plain, &mut punchy] {
+            analyzer.set_band_scale(BandScale::Db, -60.0);
+            analyzer.analyze(&silence);
+        }
+
+        // The onset adds the jump in dB-mapped level, not the AGC-normalized
+        // linear jump (which would be the full 1.0 after silence)
+        let onset_plain = plain.analyze(&kick).bands[0];
+        let onset_punchy = punchy.analyze(&kick).bands[0];
+        assert_approx(onset_punchy - onset_plain, 1.0 / 3.0, 0.03);
+    }
+
+    #[test]
+    fn tuning_and_trim_survive_sample_rate_reinit() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        analyzer.apply_preset(&get_preset("edm").unwrap());
+        analyzer.set_band_trim([0.8, 1.0, 1.0, 1.0, 1.0]);
+
+        let fresh = FftAnalyzer::new(AudioConfig {
+            sample_rate: 44100,
+            ..Default::default()
+        })
+        .with_tuning_of(&analyzer);
+
+        assert_approx(fresh.band_sensitivity[0], 1.5, 0.001);
+        assert_approx(fresh.band_trim[0], 0.8, 0.001);
+        assert_approx(fresh.beat_threshold, 1.1, 0.001);
+    }
+
+    #[test]
+    fn normalize_across_presets_holds_the_average_peak() {
+        // -20 dBFS sine at bin 3, inside the bass band; AGC off so the
+        // preset's bass sensitivity shows in the level
+        let samples: Vec<f32> = (0..1024)
+            .map(|i| 0.1 * (2.0 * std::f32::consts::PI * 140.625 * i as f32 / 48000.0).sin())
+            .collect();
+        let run = |analyzer: &mut FftAnalyzer, frames: usize| {
+            let mut peak = 0.0;
+            for _ in 0..frames {
+                peak = analyzer.analyze(&samples).peak;
+            }
+            peak
+        };
+        let config = AudioConfig {
+            agc_enabled: false,
+            ..Default::default()
+        };
+
+        let mut plain = FftAnalyzer::new(config.clone());
+        plain.apply_preset(&get_preset("classical").unwrap());
+        let classical = run(&mut plain, 600);
+        plain.apply_preset(&get_preset("edm").unwrap());
+        assert!(run(&mut plain, 800) > classical * 1.5);
+
+        let mut matched = FftAnalyzer::new(config);
+        matched.set_normalize_across_presets(true);
+        matched.apply_preset(&get_preset("classical").unwrap());
+        assert_approx(run(&mut matched, 600), classical, 0.01);
+        matched.apply_preset(&get_preset("edm").unwrap());
+        assert_approx(run(&mut matched, 800), classical, 0.03);
+
+        matched.set_normalize_across_presets(false);
+        assert!(run(&mut matched, 100) > classical * 1.5);
+    }
+
+    #[test]
+    fn db_scale_maps_band_level_from_floor() {
+        let sine = |amplitude: f32| -> Vec<f32> {
+            // Bin 3 at 48kHz / 1024, inside the bass band with both neighbours
+            (0..1024)
+                .map(|i| {
+                    amplitude * (2.0 * std::f32::consts::PI * 140.625 * i as f32 / 48000.0).sin()
+                })
+                .collect()
+        };
+        let settle = |analyzer: &mut FftAnalyzer, samples: &[f32]| {
+            let mut result = AnalysisResult::default();
+            for _ in 0..60 {
+                result = analyzer.analyze(samples);
+            }
+            result
+        };
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        analyzer.set_band_scale(BandScale::Db, -60.0);
+
+        // Full scale reads 0 dBFS, -20 dBFS sits two thirds up a 60dB range
+        assert_approx(settle(&mut analyzer, &sine(1.0)).bands[0], 1.0, 0.02);
+        assert_approx(settle(&mut analyzer, &sine(0.1)).bands[0], 2.0 / 3.0, 0.02);
+        assert_approx(settle(&mut analyzer, &[0.0; 1024]).bands[0], 0.0, 0.01);
+
+        // Linear output stays AGC-normalized whatever the level
+        let mut linear = FftAnalyzer::new(AudioConfig::default());
+        assert!(settle(&mut linear, &sine(0.1)).bands[0] > 0.9);
+    }
+
+    #[test]
+    fn agc_off_reads_bands_against_fixed_reference() {
+        // -20 dBFS sine at bin 3, inside the bass band
+        let samples: Vec<f32> = (0..1024)
+            .map(|i| 0.1 * (2.0 * std::f32::consts::PI * 140.625 * i as f32 / 48000.0).sin())
+            .collect();
+        let settle = |analyzer: &mut FftAnalyzer| {
+            let mut bass = 0.0;
+            for _ in 0..60 {
+                bass = analyzer.analyze(&samples).bands[0];
+            }
+            bass
+        };
+
+        let mut adaptive = FftAnalyzer::new(AudioConfig::default());
+        assert!(settle(&mut adaptive) > 0.9);
+
+        let mut fixed = FftAnalyzer::new(AudioConfig {
+            agc_enabled: false,
+            ..Default::default()
+        });
+        assert_approx(settle(&mut fixed), 0.1 / FIXED_BAND_REFERENCE, 0.02);
+    }
+
+    #[test]
+    fn beat_input_follows_beat_source() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        analyzer.smoothed_bands[0] = 0.2;
+        analyzer.set_lane_bass(0.8);
+
+        assert_approx(analyzer.beat_input(), 0.2, 0.001);
+        analyzer.set_beat_source(BeatSource::BassLane);
+        assert_approx(analyzer.beat_input(), 0.8, 0.001);
+        analyzer.set_beat_source(BeatSource::Combined);
+        assert_approx(analyzer.beat_input(), 0.5, 0.001);
+
+        let fresh = FftAnalyzer::new(AudioConfig::default()).with_tuning_of(&analyzer);
+        assert_eq!(fresh.beat_source(), BeatSource::Combined);
+    }
+
+    #[test]
+    fn nudge_tempo_octave_holds_double_time() {
+        let mut analyzer = FftAnalyzer::new(AudioConfig::default());
+        let period = 60.0 / 87.0;
+        let feed = |analyzer: &mut FftAnalyzer, onsets: std::ops::Range<usize>| {
+            for i in onsets {
+                let t = i as f64 * period;
+                analyzer._update_bpm_from_onset(t);
+                analyzer.last_onset_time = Some(t);
+            }
+        };
+
+        // Drum and bass kicks the detector has latched at half time
+        analyzer.estimated_bpm = 87.0;
+        feed(&mut analyzer, 0..32);
+        let bpm = analyzer.estimate_bpm();
+        assert!((bpm - 87.0).abs() < 4.0, "expected ~87 BPM, got {bpm}");
+
+        let bpm = analyzer.nudge_tempo_octave(TempoOctave::Up);
+        assert!((bpm - 174.0).abs() < 8.0, "expected ~174 BPM, got {bpm}");
+
+        // More of the same kicks must not drag it back to 87
+        feed(&mut analyzer, 32..96);
+        let bpm = analyzer.estimate_bpm();
+        assert!((bpm - 174.0).abs() < 8.0, "expected ~174 BPM, got {bpm}");
+
+        // Out of range nudges are ignored
+        assert_approx(analyzer.nudge_tempo_octave(TempoOctave::Up), bpm, 1e-6);
+    }
+
     #[test]
     fn tempo_histogram_handles_half_time_ioi() {
         let mut analyzer = FftAnalyzer::new(AudioConfig::default());
@@ -980,6 +2083,20 @@ mod tests {
         assert!(!kick, "reset should clear kick state");
     }
 
+    #[test]
+    fn bass_lane_running_peak_tracks_envelope() {
+        let mut bl = BassLane::new(48000.0);
+        assert_eq!(bl.running_peak(), 0.001);
+
+        let samples: Vec<f32> = (0..4800)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 80.0 * i as f32 / 48000.0).sin())
+            .collect();
+        bl.process(&samples);
+
+        assert!(bl.running_peak() > 0.001);
+        assert!(bl.running_peak() >= bl.raw_envelope());
+    }
+
     #[test]
     fn bass_lane_lowpass_attenuates_high_frequency() {
         let sample_rate = 48000.0;
@@ -0,0 +1,504 @@
+//! Audio file playback source
+//!
+//! `file:<path>` sources decode a file with symphonia and feed it, downmixed
+//! to mono, into the capture buffer at real-time pace. Analysis then runs
+//! exactly as for a live device, so a pre-rendered set visualizes the same
+//! way every time.
+
+use super::capture::{AudioBuffer, CaptureError};
+use crate::voice::VoiceStreamer;
+use parking_lot::Mutex;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+
+/// Source id prefix of file playback sources
+pub const FILE_SOURCE_PREFIX: &str = "file:";
+
+/// How often the feeder tops up the capture buffer
+const FEED_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Most audio fed in one go after the feeder was held up, in seconds
+const MAX_FEED_SECS: f64 = 0.25;
+
+/// Playback controls and position, shared between the capture handle and
+/// the feeder thread
+#[derive(Debug)]
+pub struct FilePlayback {
+    path: String,
+    paused: AtomicBool,
+    looping: AtomicBool,
+    /// Reached the end with looping off; silence is fed until a seek
+    ended: AtomicBool,
+    /// Seek request in seconds, taken by the feeder
+    seek_to: Mutex<Option<f64>>,
+    /// Seconds into the file (f64 bits)
+    position: AtomicU64,
+    /// Length of the file in seconds (f64 bits, NaN if unknown)
+    duration: AtomicU64,
+}
+
+/// File playback state for the UI
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FilePlaybackStatus {
+    pub path: String,
+    pub position_secs: f64,
+    pub duration_secs: Option<f64>,
+    pub paused: bool,
+    pub looping: bool,
+    pub ended: bool,
+}
+
+impl FilePlayback {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            paused: AtomicBool::new(false),
+            looping: AtomicBool::new(false),
+            ended: AtomicBool::new(false),
+            seek_to: Mutex::new(None),
+            position: AtomicU64::new(0f64.to_bits()),
+            duration: AtomicU64::new(f64::NAN.to_bits()),
+        }
+    }
+
+    /// Pause or resume; resuming a file that played to the end starts it over
+    pub fn set_paused(&self, paused: bool) {
+        if !paused && self.ended.load(Ordering::Relaxed) {
+            self.seek(0.0);
+        }
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Start over at the end instead of stopping
+    pub fn set_looping(&self, looping: bool) {
+        self.looping.store(looping, Ordering::Relaxed);
+    }
+
+    /// Jump to `secs` (clamped to the file) on the next feed
+    pub fn seek(&self, secs: f64) {
+        *self.seek_to.lock() = Some(secs.max(0.0));
+    }
+
+    pub fn status(&self) -> FilePlaybackStatus {
+        let duration = f64::from_bits(self.duration.load(Ordering::Relaxed));
+        FilePlaybackStatus {
+            path: self.path.clone(),
+            position_secs: f64::from_bits(self.position.load(Ordering::Relaxed)),
+            duration_secs: (!duration.is_nan()).then_some(duration),
+            paused: self.paused.load(Ordering::Relaxed),
+            looping: self.looping.load(Ordering::Relaxed),
+            ended: self.ended.load(Ordering::Relaxed),
+        }
+    }
+
+    fn set_position(&self, secs: f64) {
+        self.position.store(secs.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Streaming decoder for the first audio track of a file, downmixing to mono
+pub struct FileDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    time_base: Option<TimeBase>,
+    duration_secs: Option<f64>,
+    /// Decoded mono samples not handed out yet
+    pending: Vec<f32>,
+    pending_pos: usize,
+    /// Samples to drop after an accurate seek landed early
+    skip: usize,
+    interleaved: Option<SampleBuffer<f32>>,
+}
+
+impl FileDecoder {
+    pub fn open(path: &Path) -> Result<Self, CaptureError> {
+        let file = File::open(path)
+            .map_err(|e| CaptureError::SourceNotFound(format!("{}: {}", path.display(), e)))?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext);
+        }
+        let unsupported =
+            |e: SymphoniaError| CaptureError::ConfigError(format!("{}: {}", path.display(), e));
+
+        let format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(unsupported)?
+            .format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| {
+                CaptureError::ConfigError(format!("{}: no audio track", path.display()))
+            })?;
+        let sample_rate = track.codec_params.sample_rate.ok_or_else(|| {
+            CaptureError::ConfigError(format!("{}: unknown sample rate", path.display()))
+        })?;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(unsupported)?;
+
+        Ok(Self {
+            track_id: track.id,
+            sample_rate,
+            time_base: track.codec_params.time_base,
+            duration_secs: track
+                .codec_params
+                .n_frames
+                .map(|frames| frames as f64 / sample_rate as f64),
+            format,
+            decoder,
+            pending: Vec::new(),
+            pending_pos: 0,
+            skip: 0,
+            interleaved: None,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn duration_secs(&self) -> Option<f64> {
+        self.duration_secs
+    }
+
+    /// Fill `out` with the next mono samples and return how many were
+    /// written; fewer than `out.len()` only at the end of the file
+    pub fn read(&mut self, out: &mut [f32]) -> Result<usize, CaptureError> {
+        let mut written = 0;
+        while written < out.len() {
+            if self.pending_pos == self.pending.len() && !self.decode_next()? {
+                break;
+            }
+            let dropped = self.skip.min(self.pending.len() - self.pending_pos);
+            self.skip -= dropped;
+            self.pending_pos += dropped;
+
+            let count = (out.len() - written).min(self.pending.len() - self.pending_pos);
+            out[written..written + count]
+                .copy_from_slice(&self.pending[self.pending_pos..self.pending_pos + count]);
+            written += count;
+            self.pending_pos += count;
+        }
+        Ok(written)
+    }
+
+    /// Seek to `secs` (clamped to the file) and return where playback now is
+    pub fn seek(&mut self, secs: f64) -> Result<f64, CaptureError> {
+        let secs = match self.duration_secs {
+            Some(duration) => secs.clamp(0.0, duration),
+            None => secs.max(0.0),
+        };
+        let seeked = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::from(secs),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(|e| CaptureError::StreamError(format!("Seek failed: {}", e)))?;
+        self.decoder.reset();
+        self.pending.clear();
+        self.pending_pos = 0;
+
+        let actual = self.ts_secs(seeked.actual_ts);
+        let required = self.ts_secs(seeked.required_ts);
+        self.skip = ((required - actual).max(0.0) * self.sample_rate as f64) as usize;
+        Ok(required)
+    }
+
+    fn ts_secs(&self, ts: u64) -> f64 {
+        match self.time_base {
+            Some(tb) => {
+                let time = tb.calc_time(ts);
+                time.seconds as f64 + time.frac
+            }
+            None => ts as f64 / self.sample_rate as f64,
+        }
+    }
+
+    /// Decode the next packet of our track into `pending`; false at the end
+    /// of the file
+    fn decode_next(&mut self) -> Result<bool, CaptureError> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false);
+                }
+                // A new chained stream; treat it as the end of this one
+                Err(SymphoniaError::ResetRequired) => return Ok(false),
+                Err(e) => return Err(CaptureError::StreamError(e.to_string())),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Skip a corrupt packet rather than ending playback
+                Err(SymphoniaError::DecodeError(e)) => {
+                    log::debug!("Skipping undecodable packet: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(CaptureError::StreamError(e.to_string())),
+            };
+
+            let spec = *decoded.spec();
+            let channels = spec.channels.count().max(1);
+            let needed = decoded.capacity() * channels;
+            if self
+                .interleaved
+                .as_ref()
+                .is_none_or(|buf| buf.capacity() < needed)
+            {
+                self.interleaved = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+            }
+            let interleaved = self.interleaved.as_mut().expect("allocated above");
+            interleaved.copy_interleaved_ref(decoded);
+
+            self.pending.clear();
+            self.pending_pos = 0;
+            self.pending.extend(
+                interleaved
+                    .samples()
+                    .chunks(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            );
+            return Ok(true);
+        }
+    }
+}
+
+/// Feeder thread of a file source
+pub struct FilePlaybackHandle {
+    stop: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl FilePlaybackHandle {
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for FilePlaybackHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Start feeding `decoder` into `buffer` (and the voice streamer) at
+/// real-time pace, under the controls in `playback`
+pub fn start_file_playback(
+    mut decoder: FileDecoder,
+    playback: Arc<FilePlayback>,
+    buffer: Arc<Mutex<AudioBuffer>>,
+    voice_streamer: Option<Arc<VoiceStreamer>>,
+) -> Result<FilePlaybackHandle, CaptureError> {
+    let duration = decoder.duration_secs().unwrap_or(f64::NAN);
+    playback
+        .duration
+        .store(duration.to_bits(), Ordering::Relaxed);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_clone = stop.clone();
+    let thread_handle = thread::Builder::new()
+        .name("audio-file-feed".to_string())
+        .spawn(move || {
+            run_feed_loop(
+                &mut decoder,
+                &playback,
+                &buffer,
+                voice_streamer.as_deref(),
+                &stop_clone,
+            )
+        })
+        .map_err(|e| CaptureError::ThreadError(e.to_string()))?;
+
+    Ok(FilePlaybackHandle {
+        stop,
+        thread_handle: Some(thread_handle),
+    })
+}
+
+/// Every `FEED_INTERVAL`, push the samples that came due since the last
+/// round: file audio while playing, silence while paused or ended
+fn run_feed_loop(
+    decoder: &mut FileDecoder,
+    playback: &FilePlayback,
+    buffer: &Mutex<AudioBuffer>,
+    voice_streamer: Option<&VoiceStreamer>,
+    stop: &AtomicBool,
+) {
+    let rate = decoder.sample_rate() as f64;
+    let mut position = 0usize;
+    let mut chunk = Vec::new();
+    let mut last = Instant::now();
+    // Frames due but not fed yet (the fraction carries over)
+    let mut owed = 0.0f64;
+
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(FEED_INTERVAL);
+        let now = Instant::now();
+        owed = (owed + now.duration_since(last).as_secs_f64() * rate).min(MAX_FEED_SECS * rate);
+        last = now;
+        let frames = owed as usize;
+        owed -= frames as f64;
+
+        let seek = playback.seek_to.lock().take();
+        if let Some(secs) = seek {
+            match decoder.seek(secs) {
+                Ok(secs) => {
+                    position = (secs * rate) as usize;
+                    playback.ended.store(false, Ordering::Relaxed);
+                }
+                Err(e) => log::warn!("File playback seek failed: {}", e),
+            }
+        }
+
+        chunk.clear();
+        chunk.resize(frames, 0.0);
+        if !playback.paused.load(Ordering::Relaxed) && !playback.ended.load(Ordering::Relaxed) {
+            let mut filled = 0;
+            while filled < frames {
+                match decoder.read(&mut chunk[filled..]) {
+                    Ok(0) if playback.looping.load(Ordering::Relaxed) && position > 0 => {
+                        if let Err(e) = decoder.seek(0.0) {
+                            log::warn!("File playback can't loop: {}", e);
+                            playback.ended.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        position = 0;
+                    }
+                    Ok(0) => {
+                        log::info!("File playback reached the end");
+                        playback.ended.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    Ok(count) => {
+                        filled += count;
+                        position += count;
+                    }
+                    Err(e) => {
+                        log::error!("File playback stopped: {}", e);
+                        playback.ended.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+            playback.set_position(position as f64 / rate);
+        }
+
+        buffer.lock().push_samples(&chunk);
+        if let Some(streamer) = voice_streamer {
+            streamer.push_mix_samples(&chunk, 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Write a 16-bit PCM WAV whose left channel holds `left` and right
+    /// channel `right` for every frame
+    fn write_wav(name: &str, sample_rate: u32, frames: usize, left: i16, right: i16) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "mcav-file-source-test-{}-{}.wav",
+            name,
+            std::process::id()
+        ));
+        let data_len = (frames * 4) as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&2u16.to_le_bytes()); // stereo
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for _ in 0..frames {
+            bytes.extend_from_slice(&left.to_le_bytes());
+            bytes.extend_from_slice(&right.to_le_bytes());
+        }
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn decodes_whole_file_downmixed_to_mono() {
+        let path = write_wav("decode", 8000, 8000, 16384, 0);
+        let mut decoder = FileDecoder::open(&path).unwrap();
+        assert_eq!(decoder.sample_rate(), 8000);
+        assert_eq!(decoder.duration_secs(), Some(1.0));
+
+        let mut out = vec![0.0; 10_000];
+        let count = decoder.read(&mut out).unwrap();
+        assert_eq!(count, 8000);
+        assert!(out[..count].iter().all(|&s| (s - 0.25).abs() < 1e-3));
+        assert_eq!(decoder.read(&mut out).unwrap(), 0);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn seek_resumes_from_the_requested_time() {
+        let path = write_wav("seek", 8000, 8000, 0, 0);
+        let mut decoder = FileDecoder::open(&path).unwrap();
+
+        assert_eq!(decoder.seek(0.75).unwrap(), 0.75);
+        let mut out = vec![0.0; 8000];
+        assert_eq!(decoder.read(&mut out).unwrap(), 2000);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn missing_file_is_source_not_found() {
+        let result = FileDecoder::open(Path::new("/no/such/mcav-set.wav"));
+        assert!(matches!(result, Err(CaptureError::SourceNotFound(_))));
+    }
+
+    #[test]
+    fn resuming_an_ended_file_starts_over() {
+        let playback = FilePlayback::new("set.wav".to_string());
+        playback.ended.store(true, Ordering::Relaxed);
+        playback.set_paused(false);
+        assert_eq!(*playback.seek_to.lock(), Some(0.0));
+        assert!(!playback.status().paused);
+        assert_eq!(playback.status().duration_secs, None);
+    }
+}
@@ -2,13 +2,50 @@
 
 mod capture;
 mod fft;
+mod file_source;
 mod sources;
 
 mod platform;
 
-pub use capture::{AnalysisResult, AudioCaptureHandle, CaptureMode};
-pub use fft::{AudioPreset, BassLane, FftAnalyzer, get_preset, get_presets};
-pub use sources::{AudioSource, list_sources};
+pub use capture::{
+    AnalysisResult, AudioCaptureHandle, CaptureError, CaptureMode, MixSource, VoiceInputHandle,
+};
+pub use fft::{
+    AnalysisBenchmark, AudioPreset, BassLane, BeatDebug, BeatKind, BeatSource, FftAnalyzer,
+    TempoOctave, benchmark_analysis, get_preset, get_presets,
+};
+pub use file_source::{FILE_SOURCE_PREFIX, FilePlayback, FilePlaybackStatus};
+pub use sources::{
+    AudioSource, SourceCache, SourceType, filter_by_type, list_sources, list_sources_of_type,
+    spawn_device_watcher, supports_per_app_capture, supports_process_loopback,
+};
+
+/// Band edge frequencies in Hz: bass, low, mid, high and air lie between
+/// consecutive edges
+pub const DEFAULT_BAND_EDGES: [f32; 6] = [40.0, 250.0, 500.0, 2000.0, 6000.0, 20000.0];
+
+/// Whether band edges are finite, positive and strictly increasing
+fn band_edges_increasing(edges: &[f32; 6]) -> bool {
+    edges[0].is_finite()
+        && edges[0] > 0.0
+        && edges.windows(2).all(|w| w[1].is_finite() && w[1] > w[0])
+}
+
+/// Check user band edges: strictly increasing and no higher than the
+/// Nyquist frequency of `sample_rate`
+pub fn validate_band_edges(edges: &[f32; 6], sample_rate: u32) -> Result<(), String> {
+    if !band_edges_increasing(edges) {
+        return Err("Band edges must be positive and strictly increasing".to_string());
+    }
+    let nyquist = sample_rate as f32 / 2.0;
+    if edges[5] > nyquist {
+        return Err(format!(
+            "Top band edge {} Hz is above the Nyquist frequency ({} Hz)",
+            edges[5], nyquist
+        ));
+    }
+    Ok(())
+}
 
 /// Audio processing configuration
 #[derive(Debug, Clone)]
@@ -27,6 +64,34 @@ pub struct AudioConfig {
 
     /// Beat detection threshold
     pub beat_threshold: f32,
+
+    /// Scale of the output bands
+    pub band_scale: BandScale,
+
+    /// dBFS mapped to 0 when `band_scale` is `Db` (0 dBFS maps to 1)
+    pub db_floor: f32,
+
+    /// Normalize each band against its own running max; off uses a fixed
+    /// reference so pre-mastered material keeps absolute levels
+    pub agc_enabled: bool,
+
+    /// Band edge frequencies in Hz (see `DEFAULT_BAND_EDGES`)
+    pub band_edges: [f32; 6],
+
+    /// Share of each band's rise since the last frame added on top of the
+    /// smoothed value, so hits pop before the release settles them (0 = off)
+    pub transient_emphasis: f32,
+}
+
+/// How output bands are scaled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BandScale {
+    /// Per-band AGC-normalized magnitude (0-1)
+    #[default]
+    Linear,
+    /// Band level in dBFS, mapped from `db_floor`..0 onto 0-1
+    Db,
 }
 
 impl Default for AudioConfig {
@@ -37,6 +102,11 @@ impl Default for AudioConfig {
             attack: 0.35,
             release: 0.08,
             beat_threshold: 1.3,
+            band_scale: BandScale::Linear,
+            db_floor: -60.0,
+            agc_enabled: true,
+            band_edges: DEFAULT_BAND_EDGES,
+            transient_emphasis: 0.0,
         }
     }
 }
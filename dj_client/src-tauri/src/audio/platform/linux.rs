@@ -8,19 +8,63 @@
 //! - PipeWire: Modern replacement, backward-compatible with PulseAudio CLI tools
 //! - ALSA: Low-level, no per-app concept
 //!
-//! This module uses `pactl` (PulseAudio CLI) which works with both PulseAudio
-//! and PipeWire (via pipewire-pulse compatibility layer). For systems using
-//! only ALSA, per-app enumeration is not available.
+//! With the `pipewire-capture` feature, streams are enumerated natively via
+//! `pipewire_native`. Otherwise (or when the PipeWire daemon can't be
+//! reached) this module falls back to `pactl` (PulseAudio CLI), which works
+//! with both PulseAudio and PipeWire (via the pipewire-pulse compatibility
+//! layer). For systems using only ALSA, per-app enumeration is not available.
 
 use crate::audio::sources::{AudioSource, SourceType};
 use std::process::Command;
 
 /// List audio applications currently producing audio on Linux.
 ///
-/// Uses `pactl list sink-inputs` to enumerate PulseAudio/PipeWire streams.
+/// Prefers native PipeWire enumeration when built with `pipewire-capture`,
+/// falling back to `pactl list sink-inputs`.
+pub fn list_audio_applications() -> Result<Vec<AudioSource>, String> {
+    #[cfg(feature = "pipewire-capture")]
+    {
+        match super::pipewire_native::list_output_streams() {
+            Ok(streams) => return Ok(sources_from_pipewire(streams)),
+            Err(e) => log::info!("Native PipeWire enumeration failed ({}), trying pactl", e),
+        }
+    }
+
+    list_audio_applications_pactl()
+}
+
+/// Convert native PipeWire playback streams into `app:<pid>:<binary>` sources.
+///
+/// Streams without a PID can't be targeted for capture and are skipped;
+/// multiple streams from one process collapse into a single source.
+#[cfg(feature = "pipewire-capture")]
+fn sources_from_pipewire(streams: Vec<super::pipewire_native::OutputStream>) -> Vec<AudioSource> {
+    let mut seen_pids = std::collections::HashSet::new();
+    let mut sources: Vec<AudioSource> = streams
+        .into_iter()
+        .filter_map(|stream| {
+            let pid = stream.pid?;
+            if !seen_pids.insert(pid) {
+                return None;
+            }
+            let binary = stream.binary.as_deref().unwrap_or("unknown");
+            Some(AudioSource {
+                id: format!("app:{}:{}", pid, binary),
+                name: stream.app_name,
+                source_type: SourceType::Application,
+            })
+        })
+        .collect();
+
+    sources.sort_by_key(|a| a.name.to_lowercase());
+    sources
+}
+
+/// Enumerate streams by parsing `pactl list sink-inputs`.
+///
 /// Each sink-input represents an application's audio stream to a particular
 /// output device (sink).
-pub fn list_audio_applications() -> Result<Vec<AudioSource>, String> {
+fn list_audio_applications_pactl() -> Result<Vec<AudioSource>, String> {
     let mut sources = Vec::new();
 
     // Try pactl first (works with both PulseAudio and PipeWire)
@@ -135,7 +179,7 @@ pub fn list_audio_applications() -> Result<Vec<AudioSource>, String> {
     }
 
     // Sort by name
-    sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    sources.sort_by_key(|a| a.name.to_lowercase());
 
     Ok(sources)
 }
@@ -4,7 +4,9 @@
 //! - `list_audio_applications()` - enumerate apps currently producing audio
 //!
 //! Audio capture itself is handled cross-platform by cpal in capture.rs.
-//! These modules provide platform-specific enumeration features.
+//! Windows (`start_process_loopback`) and macOS (`start_app_capture`) also
+//! provide per-application capture that feeds the same `AudioBuffer`, as
+//! does Linux with the `pipewire-capture` feature (`pipewire_native`).
 
 #[cfg(target_os = "windows")]
 pub mod windows;
@@ -14,3 +16,6 @@ pub mod macos;
 
 #[cfg(target_os = "linux")]
 pub mod linux;
+
+#[cfg(all(target_os = "linux", feature = "pipewire-capture"))]
+pub mod pipewire_native;
@@ -0,0 +1,355 @@
+//! Native PipeWire stream enumeration and per-application capture
+//!
+//! Talks to the PipeWire daemon through `libpipewire` instead of shelling
+//! out to `pactl`, so enumeration doesn't depend on CLI tools or on the
+//! locale of their output. `start_app_capture` connects an input stream
+//! directly to one application's output node, giving the same per-app
+//! capture as the Windows Process Loopback path.
+//!
+//! Only built with the `pipewire-capture` feature; `linux.rs` keeps the
+//! `pactl` parser as a fallback when the daemon can't be reached.
+
+use crate::audio::capture::AudioBuffer;
+use crate::voice::VoiceStreamer;
+use parking_lot::Mutex;
+use pipewire as pw;
+use pw::properties::properties;
+use pw::spa;
+use pw::spa::pod::Pod;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::mpsc as std_mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Sample rate requested from PipeWire (the graph resamples if needed)
+const APP_CAPTURE_SAMPLE_RATE: u32 = 48_000;
+
+/// Channel count requested from PipeWire
+const APP_CAPTURE_CHANNELS: u16 = 2;
+
+/// How long enumeration waits for the daemon to answer its sync
+const ENUMERATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An application playback stream (`Stream/Output/Audio` node)
+#[derive(Debug, Clone)]
+pub struct OutputStream {
+    pub node_id: u32,
+    pub serial: Option<String>,
+    pub app_name: String,
+    pub pid: Option<u32>,
+    pub binary: Option<String>,
+}
+
+/// Enumerate application playback streams via a registry roundtrip.
+pub fn list_output_streams() -> Result<Vec<OutputStream>, String> {
+    let mainloop =
+        pw::main_loop::MainLoop::new(None).map_err(|e| format!("PipeWire main loop: {}", e))?;
+    let context =
+        pw::context::Context::new(&mainloop).map_err(|e| format!("PipeWire context: {}", e))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| format!("PipeWire connect: {}", e))?;
+    let registry = core
+        .get_registry()
+        .map_err(|e| format!("PipeWire registry: {}", e))?;
+
+    let streams = Rc::new(RefCell::new(Vec::new()));
+    let outcome: Rc<RefCell<Option<Result<(), String>>>> = Rc::new(RefCell::new(None));
+
+    let streams_clone = streams.clone();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            if global.type_ != pw::types::ObjectType::Node {
+                return;
+            }
+            let Some(props) = global.props else {
+                return;
+            };
+            if props.get(*pw::keys::MEDIA_CLASS) != Some("Stream/Output/Audio") {
+                return;
+            }
+
+            let app_name = props
+                .get(*pw::keys::APP_NAME)
+                .or_else(|| props.get(*pw::keys::NODE_NAME))
+                .unwrap_or("Unknown")
+                .to_string();
+
+            streams_clone.borrow_mut().push(OutputStream {
+                node_id: global.id,
+                serial: props.get(*pw::keys::OBJECT_SERIAL).map(str::to_string),
+                app_name,
+                pid: props
+                    .get(*pw::keys::APP_PROCESS_ID)
+                    .and_then(|p| p.parse().ok()),
+                binary: props.get(*pw::keys::APP_PROCESS_BINARY).map(str::to_string),
+            });
+        })
+        .register();
+
+    // The registry has announced every existing global once the core
+    // answers our sync. A core error (e.g. the daemon restarting) or the
+    // timeout ends the wait too, so listing sources can't hang.
+    let pending = core.sync(0).map_err(|e| format!("PipeWire sync: {}", e))?;
+    let finish = {
+        let outcome = outcome.clone();
+        let mainloop = mainloop.clone();
+        move |result: Result<(), String>| {
+            outcome.borrow_mut().get_or_insert(result);
+            mainloop.quit();
+        }
+    };
+    let _core_listener = core
+        .add_listener_local()
+        .done({
+            let finish = finish.clone();
+            move |id, seq| {
+                if id == pw::core::PW_ID_CORE && seq == pending {
+                    finish(Ok(()));
+                }
+            }
+        })
+        .error({
+            let finish = finish.clone();
+            move |id, _seq, res, message| {
+                finish(Err(format!(
+                    "PipeWire error on object {}: {} ({})",
+                    id, message, res
+                )));
+            }
+        })
+        .register();
+    let timer = mainloop.loop_().add_timer(move |_| {
+        finish(Err(format!(
+            "PipeWire did not answer within {}s",
+            ENUMERATE_TIMEOUT.as_secs()
+        )));
+    });
+    timer
+        .update_timer(Some(ENUMERATE_TIMEOUT), None)
+        .into_result()
+        .map_err(|e| format!("PipeWire timer: {}", e))?;
+
+    while outcome.borrow().is_none() {
+        mainloop.run();
+    }
+    outcome.take().unwrap_or(Ok(()))?;
+
+    Ok(streams.take())
+}
+
+/// Handle to an active PipeWire capture stream.
+/// Quits the capture thread's main loop when stopped or dropped.
+pub struct AppCaptureHandle {
+    quit_tx: Option<pw::channel::Sender<()>>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl AppCaptureHandle {
+    pub fn stop(&mut self) {
+        if let Some(tx) = self.quit_tx.take() {
+            let _ = tx.send(());
+        }
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for AppCaptureHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Per-stream state owned by the `process` callback
+struct CaptureData {
+    buffer: Arc<Mutex<AudioBuffer>>,
+    voice_streamer: Option<Arc<VoiceStreamer>>,
+    samples: Vec<f32>,
+    mono: Vec<f32>,
+}
+
+/// Start capturing the audio of the application with the given PID.
+///
+/// Connects to the first playback stream owned by `pid` and pushes
+/// mono-downmixed samples into `buffer`. Returns the capture handle
+/// together with the sample rate and channel count of the stream.
+pub fn start_app_capture(
+    pid: u32,
+    buffer: Arc<Mutex<AudioBuffer>>,
+    voice_streamer: Option<Arc<VoiceStreamer>>,
+) -> Result<(AppCaptureHandle, u32, u16), String> {
+    let target = list_output_streams()?
+        .into_iter()
+        .find(|s| s.pid == Some(pid))
+        .ok_or_else(|| format!("No PipeWire playback stream for PID {}", pid))?;
+    // `target.object` matches on serial; older daemons only know node ids
+    let target_object = target
+        .serial
+        .clone()
+        .unwrap_or_else(|| target.node_id.to_string());
+
+    let (quit_tx, quit_rx) = pw::channel::channel::<()>();
+    let (init_tx, init_rx) = std_mpsc::channel::<Result<(), String>>();
+
+    let thread_handle = thread::Builder::new()
+        .name("pipewire-capture".to_string())
+        .spawn(move || {
+            if let Err(e) =
+                run_capture_loop(&target_object, buffer, voice_streamer, quit_rx, &init_tx)
+            {
+                let _ = init_tx.send(Err(e));
+            }
+        })
+        .map_err(|e| format!("Failed to spawn PipeWire capture thread: {}", e))?;
+
+    init_rx
+        .recv()
+        .map_err(|_| "PipeWire capture thread exited during setup".to_string())??;
+
+    log::info!(
+        "PipeWire capture connected to {} (node {})",
+        target.app_name,
+        target.node_id
+    );
+
+    Ok((
+        AppCaptureHandle {
+            quit_tx: Some(quit_tx),
+            thread_handle: Some(thread_handle),
+        },
+        APP_CAPTURE_SAMPLE_RATE,
+        APP_CAPTURE_CHANNELS,
+    ))
+}
+
+/// Body of the capture thread: owns the PipeWire main loop until quit.
+fn run_capture_loop(
+    target_object: &str,
+    buffer: Arc<Mutex<AudioBuffer>>,
+    voice_streamer: Option<Arc<VoiceStreamer>>,
+    quit_rx: pw::channel::Receiver<()>,
+    init_tx: &std_mpsc::Sender<Result<(), String>>,
+) -> Result<(), String> {
+    let mainloop =
+        pw::main_loop::MainLoop::new(None).map_err(|e| format!("PipeWire main loop: {}", e))?;
+    let context =
+        pw::context::Context::new(&mainloop).map_err(|e| format!("PipeWire context: {}", e))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| format!("PipeWire connect: {}", e))?;
+
+    let props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Capture",
+        *pw::keys::MEDIA_ROLE => "Music",
+        *pw::keys::TARGET_OBJECT => target_object,
+    };
+    let stream = pw::stream::Stream::new(&core, "mcav-app-capture", props)
+        .map_err(|e| format!("PipeWire stream: {}", e))?;
+
+    let data = CaptureData {
+        buffer,
+        voice_streamer,
+        samples: Vec::new(),
+        mono: Vec::new(),
+    };
+
+    // Setup succeeds once the link to the app's node is streaming; an error
+    // before that fails the capture and ends this thread
+    let mut pending_init = Some(init_tx.clone());
+    let loop_on_error = mainloop.clone();
+    let _listener = stream
+        .add_local_listener_with_user_data(data)
+        .state_changed(move |_, _, _old, new| match new {
+            pw::stream::StreamState::Streaming => {
+                if let Some(tx) = pending_init.take() {
+                    let _ = tx.send(Ok(()));
+                }
+            }
+            pw::stream::StreamState::Error(msg) => {
+                log::error!("PipeWire capture stream error: {}", msg);
+                if let Some(tx) = pending_init.take() {
+                    let _ = tx.send(Err(format!("PipeWire capture stream error: {}", msg)));
+                    loop_on_error.quit();
+                }
+            }
+            _ => {}
+        })
+        .process(|stream, data| {
+            let Some(mut pw_buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = pw_buffer.datas_mut();
+            let Some(first) = datas.first_mut() else {
+                return;
+            };
+            let size = first.chunk().size() as usize;
+            let Some(bytes) = first.data() else {
+                return;
+            };
+            let bytes = &bytes[..size.min(bytes.len())];
+
+            data.samples.clear();
+            data.samples.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            );
+
+            let channels = APP_CAPTURE_CHANNELS as usize;
+            if let Some(ref streamer) = data.voice_streamer {
+                streamer.push_mix_samples(&data.samples, channels);
+            }
+
+            data.mono.clear();
+            data.mono.extend(
+                data.samples
+                    .chunks(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+            );
+            data.buffer.lock().push_samples(&data.mono);
+        })
+        .register()
+        .map_err(|e| format!("PipeWire stream listener: {}", e))?;
+
+    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
+    audio_info.set_rate(APP_CAPTURE_SAMPLE_RATE);
+    audio_info.set_channels(APP_CAPTURE_CHANNELS as u32);
+    let obj = spa::pod::Object {
+        type_: spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: spa::param::ParamType::EnumFormat.as_raw(),
+        properties: audio_info.into(),
+    };
+    let values: Vec<u8> = spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(obj),
+    )
+    .map_err(|e| format!("PipeWire format pod: {:?}", e))?
+    .0
+    .into_inner();
+    let mut params = [Pod::from_bytes(&values).ok_or("PipeWire format pod is invalid")?];
+
+    stream
+        .connect(
+            spa::utils::Direction::Input,
+            None,
+            pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )
+        .map_err(|e| format!("PipeWire stream connect: {}", e))?;
+
+    let _quit = quit_rx.attach(mainloop.loop_(), {
+        let mainloop = mainloop.clone();
+        move |()| mainloop.quit()
+    });
+
+    mainloop.run();
+
+    Ok(())
+}
@@ -14,17 +14,19 @@ use std::sync::Arc;
 use std::sync::mpsc as std_mpsc;
 use std::thread;
 
+use windows::Win32::Foundation::PROPERTYKEY;
 use windows::Win32::Foundation::{CloseHandle, E_FAIL, S_OK};
 use windows::Win32::Media::Audio::{
     AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM, AUDCLNT_STREAMFLAGS_LOOPBACK,
     AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY, AUDIOCLIENT_ACTIVATION_PARAMS,
     AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK, AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS,
-    ActivateAudioInterfaceAsync, AudioSessionStateActive, IActivateAudioInterfaceAsyncOperation,
-    IActivateAudioInterfaceCompletionHandler, IAudioCaptureClient, IAudioClient,
-    IAudioSessionControl, IAudioSessionControl2, IAudioSessionEnumerator, IAudioSessionManager2,
-    IMMDeviceEnumerator, MMDeviceEnumerator, PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
-    VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK, WAVEFORMATEX, WAVEFORMATEXTENSIBLE,
-    WAVEFORMATEXTENSIBLE_0, eConsole, eRender,
+    ActivateAudioInterfaceAsync, AudioSessionStateActive, DEVICE_STATE, EDataFlow, ERole,
+    IActivateAudioInterfaceAsyncOperation, IActivateAudioInterfaceCompletionHandler,
+    IAudioCaptureClient, IAudioClient, IAudioSessionControl, IAudioSessionControl2,
+    IAudioSessionEnumerator, IAudioSessionManager2, IMMDeviceEnumerator, IMMNotificationClient,
+    MMDeviceEnumerator, PROCESS_LOOPBACK_MODE, PROCESS_LOOPBACK_MODE_EXCLUDE_TARGET_PROCESS_TREE,
+    PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE, VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK,
+    WAVEFORMATEX, WAVEFORMATEXTENSIBLE, WAVEFORMATEXTENSIBLE_0, eConsole, eRender,
 };
 use windows::Win32::Media::KernelStreaming::{
     SPEAKER_FRONT_LEFT, SPEAKER_FRONT_RIGHT, WAVE_FORMAT_EXTENSIBLE,
@@ -42,7 +44,7 @@ use windows::Win32::System::Threading::{
     CreateEventW, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, SetEvent, WaitForSingleObject,
 };
 use windows::Win32::System::Variant::VT_BLOB;
-use windows::core::{HRESULT, Interface};
+use windows::core::{HRESULT, Interface, PCWSTR};
 
 /// Minimum Windows build number that supports Process Loopback API
 const MIN_PROCESS_LOOPBACK_BUILD: u32 = 20348;
@@ -209,6 +211,215 @@ unsafe fn create_completion_handler(event: HANDLE) -> IActivateAudioInterfaceCom
 
 // --- End completion handler ---
 
+// --- Device notification client COM implementation (manual vtable) ---
+//
+// Same approach as the completion handler above: a minimal
+// IMMNotificationClient that forwards endpoint changes to a channel.
+
+#[repr(C)]
+struct NotificationClientVtbl {
+    query_interface: unsafe extern "system" fn(
+        *mut NotificationClient,
+        *const windows::core::GUID,
+        *mut *mut std::ffi::c_void,
+    ) -> HRESULT,
+    add_ref: unsafe extern "system" fn(*mut NotificationClient) -> u32,
+    release: unsafe extern "system" fn(*mut NotificationClient) -> u32,
+    on_device_state_changed:
+        unsafe extern "system" fn(*mut NotificationClient, PCWSTR, DEVICE_STATE) -> HRESULT,
+    on_device_added: unsafe extern "system" fn(*mut NotificationClient, PCWSTR) -> HRESULT,
+    on_device_removed: unsafe extern "system" fn(*mut NotificationClient, PCWSTR) -> HRESULT,
+    on_default_device_changed:
+        unsafe extern "system" fn(*mut NotificationClient, EDataFlow, ERole, PCWSTR) -> HRESULT,
+    on_property_value_changed:
+        unsafe extern "system" fn(*mut NotificationClient, PCWSTR, PROPERTYKEY) -> HRESULT,
+}
+
+#[repr(C)]
+struct NotificationClient {
+    vtbl: *const NotificationClientVtbl,
+    ref_count: std::sync::atomic::AtomicU32,
+    event_tx: std_mpsc::Sender<()>,
+}
+
+unsafe extern "system" fn nc_query_interface(
+    this: *mut NotificationClient,
+    riid: *const windows::core::GUID,
+    ppv: *mut *mut std::ffi::c_void,
+) -> HRESULT {
+    let iid = &*riid;
+    if *iid == IMMNotificationClient::IID || *iid == windows::core::IUnknown::IID {
+        (*this)
+            .ref_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        *ppv = this as *mut std::ffi::c_void;
+        S_OK
+    } else {
+        *ppv = std::ptr::null_mut();
+        windows::Win32::Foundation::E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn nc_add_ref(this: *mut NotificationClient) -> u32 {
+    (*this)
+        .ref_count
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        + 1
+}
+
+unsafe extern "system" fn nc_release(this: *mut NotificationClient) -> u32 {
+    let prev = (*this)
+        .ref_count
+        .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    if prev == 1 {
+        let _ = Box::from_raw(this);
+        0
+    } else {
+        prev - 1
+    }
+}
+
+/// Notify the watcher; callbacks must return quickly, so no work happens here.
+unsafe fn nc_notify(this: *mut NotificationClient) -> HRESULT {
+    let _ = (*this).event_tx.send(());
+    S_OK
+}
+
+unsafe extern "system" fn nc_on_device_state_changed(
+    this: *mut NotificationClient,
+    _device_id: PCWSTR,
+    _new_state: DEVICE_STATE,
+) -> HRESULT {
+    nc_notify(this)
+}
+
+unsafe extern "system" fn nc_on_device_added(
+    this: *mut NotificationClient,
+    _device_id: PCWSTR,
+) -> HRESULT {
+    nc_notify(this)
+}
+
+unsafe extern "system" fn nc_on_device_removed(
+    this: *mut NotificationClient,
+    _device_id: PCWSTR,
+) -> HRESULT {
+    nc_notify(this)
+}
+
+unsafe extern "system" fn nc_on_default_device_changed(
+    this: *mut NotificationClient,
+    _flow: EDataFlow,
+    _role: ERole,
+    _device_id: PCWSTR,
+) -> HRESULT {
+    nc_notify(this)
+}
+
+unsafe extern "system" fn nc_on_property_value_changed(
+    _this: *mut NotificationClient,
+    _device_id: PCWSTR,
+    _key: PROPERTYKEY,
+) -> HRESULT {
+    // Property changes (volume, icons, ...) don't change the source list
+    S_OK
+}
+
+static NOTIFICATION_CLIENT_VTBL: NotificationClientVtbl = NotificationClientVtbl {
+    query_interface: nc_query_interface,
+    add_ref: nc_add_ref,
+    release: nc_release,
+    on_device_state_changed: nc_on_device_state_changed,
+    on_device_added: nc_on_device_added,
+    on_device_removed: nc_on_device_removed,
+    on_default_device_changed: nc_on_default_device_changed,
+    on_property_value_changed: nc_on_property_value_changed,
+};
+
+/// Create a notification client that sends on `event_tx` for each endpoint change.
+unsafe fn create_notification_client(event_tx: std_mpsc::Sender<()>) -> IMMNotificationClient {
+    let client = Box::new(NotificationClient {
+        vtbl: &NOTIFICATION_CLIENT_VTBL,
+        ref_count: std::sync::atomic::AtomicU32::new(1),
+        event_tx,
+    });
+    let raw = Box::into_raw(client);
+    // Safety: same layout argument as create_completion_handler.
+    IMMNotificationClient::from_raw(raw as *mut std::ffi::c_void)
+}
+
+// --- End device notification client ---
+
+/// Active endpoint notification registration; unregisters when dropped.
+///
+/// Must be dropped on the thread that created it, since it owns that
+/// thread's COM initialization.
+pub struct DeviceNotificationRegistration {
+    enumerator: Option<IMMDeviceEnumerator>,
+    client: Option<IMMNotificationClient>,
+    com_initialized: bool,
+}
+
+impl Drop for DeviceNotificationRegistration {
+    fn drop(&mut self) {
+        if let (Some(enumerator), Some(client)) = (self.enumerator.take(), self.client.take()) {
+            unsafe {
+                let _ = enumerator.UnregisterEndpointNotificationCallback(&client);
+            }
+        }
+        if self.com_initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+/// Register for audio endpoint add/remove/state/default-device changes.
+///
+/// Each change sends `()` on `event_tx` from a COM worker thread.
+pub fn register_device_notifications(
+    event_tx: std_mpsc::Sender<()>,
+) -> Result<DeviceNotificationRegistration, String> {
+    unsafe {
+        let com_initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        // Constructed first so an early return still uninitializes COM
+        let mut registration = DeviceNotificationRegistration {
+            enumerator: None,
+            client: None,
+            com_initialized,
+        };
+
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+                .map_err(|e| format!("Failed to create device enumerator: {}", e))?;
+        let client = create_notification_client(event_tx);
+        enumerator
+            .RegisterEndpointNotificationCallback(&client)
+            .map_err(|e| format!("Failed to register device notifications: {}", e))?;
+
+        registration.enumerator = Some(enumerator);
+        registration.client = Some(client);
+        Ok(registration)
+    }
+}
+
+/// Which audio a Process Loopback stream captures relative to its target PID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopbackTarget {
+    /// Only the target process and its children (per-app capture)
+    IncludeProcessTree,
+    /// Everything except the target process and its children
+    ExcludeProcessTree,
+}
+
+impl LoopbackTarget {
+    fn mode(self) -> PROCESS_LOOPBACK_MODE {
+        match self {
+            LoopbackTarget::IncludeProcessTree => PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+            LoopbackTarget::ExcludeProcessTree => PROCESS_LOOPBACK_MODE_EXCLUDE_TARGET_PROCESS_TREE,
+        }
+    }
+}
+
 /// Handle to a process loopback capture stream running on a dedicated thread.
 pub struct ProcessLoopbackHandle {
     stop_tx: std_mpsc::Sender<()>,
@@ -232,6 +443,9 @@ impl Drop for ProcessLoopbackHandle {
 
 /// Start capturing audio from a specific process via the Process Loopback API.
 ///
+/// With `LoopbackTarget::ExcludeProcessTree` the stream instead captures all
+/// system audio except that process tree.
+///
 /// Returns a `ProcessLoopbackHandle` plus the sample rate and channel count.
 ///
 /// Both the activation and capture loop run on a dedicated thread with guaranteed
@@ -240,6 +454,7 @@ impl Drop for ProcessLoopbackHandle {
 /// ActivateAudioInterfaceAsync to fail with 0x8000000E / RPC_E_WRONG_THREAD).
 pub fn start_process_loopback(
     pid: u32,
+    target: LoopbackTarget,
     buffer: Arc<Mutex<super::super::capture::AudioBuffer>>,
     voice_streamer: Option<Arc<VoiceStreamer>>,
 ) -> Result<(ProcessLoopbackHandle, u32, u16), String> {
@@ -274,7 +489,7 @@ pub fn start_process_loopback(
 
             // Activate the process loopback audio client on this clean MTA thread
             let (audio_client, _sample_rate, channels) =
-                match unsafe { activate_process_loopback(pid) } {
+                match unsafe { activate_process_loopback(pid, target) } {
                     Ok(result) => {
                         let _ = init_tx.send(Ok((result.1, result.2)));
                         result
@@ -324,7 +539,10 @@ pub fn start_process_loopback(
 /// Activate an IAudioClient for process loopback capture.
 ///
 /// MUST be called from a thread with MTA COM initialization.
-unsafe fn activate_process_loopback(pid: u32) -> Result<(IAudioClient, u32, u16), String> {
+unsafe fn activate_process_loopback(
+    pid: u32,
+    target: LoopbackTarget,
+) -> Result<(IAudioClient, u32, u16), String> {
     // COM must already be initialized as MTA by the caller (start_process_loopback).
 
     let event =
@@ -336,7 +554,7 @@ unsafe fn activate_process_loopback(pid: u32) -> Result<(IAudioClient, u32, u16)
         Anonymous: windows::Win32::Media::Audio::AUDIOCLIENT_ACTIVATION_PARAMS_0 {
             ProcessLoopbackParams: AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS {
                 TargetProcessId: pid,
-                ProcessLoopbackMode: PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+                ProcessLoopbackMode: target.mode(),
             },
         },
     };
@@ -558,7 +776,7 @@ fn run_process_capture_loop(
                     unsafe { std::slice::from_raw_parts(data_ptr as *const f32, total_samples) };
 
                 if let Some(ref streamer) = voice_streamer {
-                    streamer.push_samples(f32_slice, channels);
+                    streamer.push_mix_samples(f32_slice, channels);
                 }
 
                 let mono: Vec<f32> = f32_slice
@@ -685,7 +903,7 @@ unsafe fn enumerate_sessions() -> Result<Vec<AudioSource>, String> {
         });
     }
 
-    sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    sources.sort_by_key(|a| a.name.to_lowercase());
 
     Ok(sources)
 }
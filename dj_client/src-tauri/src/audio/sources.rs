@@ -2,10 +2,22 @@
 
 use cpal::traits::{DeviceTrait, HostTrait};
 use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// How long a source scan is reused before `SourceCache::get` rescans
+pub const SOURCE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Events arriving within this window of each other trigger one rescan
+const DEVICE_CHANGE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Device polling interval on platforms without change notifications
+#[cfg(not(target_os = "windows"))]
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
 /// Audio source information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AudioSource {
     /// Unique identifier for the source
     pub id: String,
@@ -18,7 +30,7 @@ pub struct AudioSource {
 }
 
 /// Type of audio source
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum SourceType {
     /// System-wide audio (loopback)
@@ -44,6 +56,37 @@ pub enum SourceError {
     DeviceNotFound(String),
 }
 
+/// Source id for system audio with the DJ client's own process tree excluded
+#[cfg(target_os = "windows")]
+pub(crate) const EXCLUDE_SELF_SOURCE_ID: &str = "system_audio_exclude_self";
+
+/// Whether Windows Process Loopback is available (build 20348+)
+pub fn supports_process_loopback() -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        super::platform::windows::supports_process_loopback()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        false
+    }
+}
+
+/// Whether single-application capture works here: Process Loopback on
+/// Windows, ScreenCaptureKit on macOS 13+, or native PipeWire on Linux
+/// builds with `pipewire-capture`. Elsewhere app sources fall back to
+/// system audio.
+pub fn supports_per_app_capture() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        super::platform::macos::supports_app_capture()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        supports_process_loopback() || cfg!(all(target_os = "linux", feature = "pipewire-capture"))
+    }
+}
+
 /// List available audio sources
 pub fn list_sources() -> Result<Vec<AudioSource>, SourceError> {
     let mut sources = Vec::new();
@@ -60,6 +103,16 @@ pub fn list_sources() -> Result<Vec<AudioSource>, SourceError> {
         });
     }
 
+    // Process Loopback in exclude mode keeps our own UI sounds out of the mix
+    #[cfg(target_os = "windows")]
+    if super::platform::windows::supports_process_loopback() {
+        sources.push(AudioSource {
+            id: EXCLUDE_SELF_SOURCE_ID.to_string(),
+            name: "System Audio (exclude this app)".to_string(),
+            source_type: SourceType::SystemAudio,
+        });
+    }
+
     // List per-application audio sources (platform-specific)
     #[cfg(target_os = "windows")]
     {
@@ -140,3 +193,204 @@ pub fn list_sources() -> Result<Vec<AudioSource>, SourceError> {
 
     Ok(sources)
 }
+
+/// List available audio sources of a single type, e.g. only applications.
+pub fn list_sources_of_type(kind: SourceType) -> Result<Vec<AudioSource>, SourceError> {
+    list_sources().map(|sources| filter_by_type(sources, kind))
+}
+
+/// Keep only the sources of the given type, preserving order.
+pub fn filter_by_type(sources: Vec<AudioSource>, kind: SourceType) -> Vec<AudioSource> {
+    sources
+        .into_iter()
+        .filter(|source| source.source_type == kind)
+        .collect()
+}
+
+/// Caches the result of `list_sources` for a short TTL.
+///
+/// A full scan spins up COM and enumerates sessions on Windows, so UI
+/// refreshes reuse the previous result until it goes stale or `refresh`
+/// forces a rescan.
+pub struct SourceCache {
+    ttl: Duration,
+    entry: Option<(Instant, Vec<AudioSource>)>,
+}
+
+impl SourceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entry: None }
+    }
+
+    /// Return the cached sources, rescanning with `scan` if they are stale.
+    pub fn get(
+        &mut self,
+        scan: impl FnOnce() -> Result<Vec<AudioSource>, SourceError>,
+    ) -> Result<Vec<AudioSource>, SourceError> {
+        match &self.entry {
+            Some((scanned_at, sources)) if scanned_at.elapsed() < self.ttl => Ok(sources.clone()),
+            _ => self.refresh(scan),
+        }
+    }
+
+    /// Rescan with `scan` regardless of age and cache the result.
+    pub fn refresh(
+        &mut self,
+        scan: impl FnOnce() -> Result<Vec<AudioSource>, SourceError>,
+    ) -> Result<Vec<AudioSource>, SourceError> {
+        let sources = scan()?;
+        self.entry = Some((Instant::now(), sources.clone()));
+        Ok(sources)
+    }
+}
+
+impl Default for SourceCache {
+    fn default() -> Self {
+        Self::new(SOURCE_CACHE_TTL)
+    }
+}
+
+/// Watch for audio devices being added, removed or changed.
+///
+/// Runs for the lifetime of the process and calls `on_change` (debounced)
+/// whenever the device set changes. Windows uses `IMMNotificationClient`
+/// notifications; other platforms poll the device list.
+pub fn spawn_device_watcher(on_change: impl Fn() + Send + 'static) -> Result<(), SourceError> {
+    thread::Builder::new()
+        .name("device-watcher".to_string())
+        .spawn(move || run_device_watcher(on_change))
+        .map(|_| ())
+        .map_err(|e| SourceError::EnumerationError(format!("device watcher: {}", e)))
+}
+
+#[cfg(target_os = "windows")]
+fn run_device_watcher(on_change: impl Fn()) {
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
+    // Keep the registration alive for as long as this thread runs
+    let _registration = match super::platform::windows::register_device_notifications(event_tx) {
+        Ok(registration) => registration,
+        Err(e) => {
+            log::warn!("Device change notifications unavailable: {}", e);
+            return;
+        }
+    };
+
+    while event_rx.recv().is_ok() {
+        // Devices usually fire several notifications at once
+        while event_rx.recv_timeout(DEVICE_CHANGE_DEBOUNCE).is_ok() {}
+        on_change();
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run_device_watcher(on_change: impl Fn()) {
+    let mut known = device_fingerprint();
+    loop {
+        thread::sleep(DEVICE_POLL_INTERVAL);
+        let current = device_fingerprint();
+        if current != known {
+            // Let a device finish appearing before rescanning
+            thread::sleep(DEVICE_CHANGE_DEBOUNCE);
+            known = device_fingerprint();
+            on_change();
+        }
+    }
+}
+
+/// Names of all input and output devices plus the default output, which
+/// is cheap to query compared to a full source scan.
+#[cfg(not(target_os = "windows"))]
+fn device_fingerprint() -> Vec<String> {
+    let host = cpal::default_host();
+    let mut names: Vec<String> = host
+        .default_output_device()
+        .and_then(|d| d.name().ok())
+        .into_iter()
+        .collect();
+    if let Ok(devices) = host.output_devices() {
+        names.extend(devices.filter_map(|d| d.name().ok().map(|n| format!("output:{}", n))));
+    }
+    if let Ok(devices) = host.input_devices() {
+        names.extend(devices.filter_map(|d| d.name().ok().map(|n| format!("input:{}", n))));
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn source(id: &str) -> AudioSource {
+        AudioSource {
+            id: id.to_string(),
+            name: id.to_string(),
+            source_type: SourceType::SystemAudio,
+        }
+    }
+
+    #[test]
+    fn filter_by_type_keeps_matching_sources_in_order() {
+        let app = |id: &str| AudioSource {
+            id: id.to_string(),
+            name: id.to_string(),
+            source_type: SourceType::Application,
+        };
+        let sources = vec![
+            source("system_audio"),
+            app("app:1:spotify"),
+            source("output:Speakers"),
+            app("app:2:vlc"),
+        ];
+
+        assert_eq!(
+            filter_by_type(sources.clone(), SourceType::Application),
+            vec![app("app:1:spotify"), app("app:2:vlc")]
+        );
+        assert!(filter_by_type(sources, SourceType::InputDevice).is_empty());
+    }
+
+    #[test]
+    fn cache_reuses_fresh_scan() {
+        let mut cache = SourceCache::new(Duration::from_secs(60));
+        let scans = Cell::new(0);
+        let scan = || {
+            scans.set(scans.get() + 1);
+            Ok(vec![source("system_audio")])
+        };
+
+        assert_eq!(cache.get(scan).unwrap(), vec![source("system_audio")]);
+        assert_eq!(cache.get(scan).unwrap(), vec![source("system_audio")]);
+        assert_eq!(scans.get(), 1);
+    }
+
+    #[test]
+    fn cache_rescans_when_stale_or_forced() {
+        let mut cache = SourceCache::new(Duration::ZERO);
+        let scans = Cell::new(0);
+        let scan = || {
+            scans.set(scans.get() + 1);
+            Ok(vec![source(&format!("output:{}", scans.get()))])
+        };
+
+        cache.get(scan).unwrap();
+        assert_eq!(cache.get(scan).unwrap(), vec![source("output:2")]);
+
+        let mut cache = SourceCache::new(Duration::from_secs(60));
+        cache.get(scan).unwrap();
+        assert_eq!(cache.refresh(scan).unwrap(), vec![source("output:4")]);
+    }
+
+    #[test]
+    fn failed_scan_keeps_previous_entry() {
+        let mut cache = SourceCache::new(Duration::from_secs(60));
+        cache.get(|| Ok(vec![source("system_audio")])).unwrap();
+
+        let err = cache.refresh(|| Err(SourceError::NoHost));
+        assert!(err.is_err());
+        assert_eq!(
+            cache.get(|| Ok(Vec::new())).unwrap(),
+            vec![source("system_audio")]
+        );
+    }
+}
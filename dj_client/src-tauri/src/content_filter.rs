@@ -1,15 +1,210 @@
 //! Slur detection for user-facing text in the DJ client.
 //!
 //! Uses the `rustrict` crate with `Type::OFFENSIVE & Type::SEVERE` to catch
-//! severe slurs while ignoring general profanity.
+//! severe slurs while ignoring general profanity. Spaced-out and leetspeak
+//! spellings are normalized before checking. Operators can extend that
+//! with their own blocked terms and whitelist words rustrict gets wrong;
+//! those lists live on the app-wide filter and are saved with the settings.
 
+use parking_lot::RwLock;
 use rustrict::{CensorStr, Type};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
 
-/// Return `true` if *text* contains a severe slur.
+/// App-wide filter used by `contains_slur` and `validate_no_slurs`
+static GLOBAL_FILTER: LazyLock<RwLock<ContentFilter>> =
+    LazyLock::new(|| RwLock::new(ContentFilter::default()));
+
+/// Custom word lists layered on top of rustrict
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WordLists {
+    /// Extra terms that are always rejected
+    pub blocked: Vec<String>,
+    /// Terms that are never flagged, even if rustrict would flag them
+    pub allowed: Vec<String>,
+}
+
+/// Content filter: a rustrict threshold plus custom word lists.
+///
+/// Words are matched case-insensitively after stripping invisible
+/// characters. Blocked words match anywhere in the text; allowed words only
+/// count as whole words, which are removed before any other check, so they
+/// override the blocked list without being able to split a blocked word.
+#[derive(Debug, Clone)]
+pub struct ContentFilter {
+    threshold: Type,
+    words: WordLists,
+}
+
+impl Default for ContentFilter {
+    fn default() -> Self {
+        Self::new(Type::OFFENSIVE & Type::SEVERE)
+    }
+}
+
+impl ContentFilter {
+    /// Create a filter flagging anything rustrict rates as `threshold`
+    pub fn new(threshold: Type) -> Self {
+        Self {
+            threshold,
+            words: WordLists::default(),
+        }
+    }
+
+    /// Create a default filter with the given custom lists
+    pub fn with_word_lists(words: &WordLists) -> Self {
+        let mut filter = Self::default();
+        filter.set_word_lists(words);
+        filter
+    }
+
+    /// Block an extra term. Returns `false` if it was empty or already blocked.
+    pub fn add_blocked_word(&mut self, word: &str) -> bool {
+        add_word(&mut self.words.blocked, word)
+    }
+
+    /// Never flag this term. Returns `false` if it was empty or already allowed.
+    pub fn add_allowed_word(&mut self, word: &str) -> bool {
+        add_word(&mut self.words.allowed, word)
+    }
+
+    /// The custom word lists
+    pub fn word_lists(&self) -> &WordLists {
+        &self.words
+    }
+
+    /// Replace the custom word lists
+    pub fn set_word_lists(&mut self, words: &WordLists) {
+        self.words = WordLists::default();
+        for word in &words.blocked {
+            self.add_blocked_word(word);
+        }
+        for word in &words.allowed {
+            self.add_allowed_word(word);
+        }
+    }
+
+    /// Return `true` if *text* should be rejected.
+    ///
+    /// The text is checked as written and in its de-spaced, de-leeted forms
+    /// (see `normalized_variants`), so `b 4 d` is caught wherever `bad` is.
+    pub fn check(&self, text: &str) -> bool {
+        // Strip zero-width / invisible Unicode before checking
+        let cleaned = strip_invisible(text).to_lowercase();
+        let [as_i, as_l] = normalized_variants(&cleaned);
+        [cleaned, as_i, as_l]
+            .into_iter()
+            .any(|variant| self.check_variant(variant))
+    }
+
+    fn check_variant(&self, text: String) -> bool {
+        let text = mask_allowed(&text, &self.words.allowed);
+        self.words
+            .blocked
+            .iter()
+            .any(|word| text.contains(word.as_str()))
+            || text.is(self.threshold)
+    }
+}
+
+/// Blank out whole-word occurrences of the allowed words. Matches inside a
+/// longer word are kept, so allowing `hits` can't turn `shits` into `s `.
+fn mask_allowed(text: &str, allowed: &[String]) -> String {
+    let mut masked = vec![false; text.len()];
+    for word in allowed {
+        for (start, _) in text.match_indices(word.as_str()) {
+            let end = start + word.len();
+            if is_whole_word(text, start, end) {
+                masked[start..end].fill(true);
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut in_mask = false;
+    for (i, c) in text.char_indices() {
+        if !masked[i] {
+            out.push(c);
+        } else if !in_mask {
+            out.push(' ');
+        }
+        in_mask = masked[i];
+    }
+    out
+}
+
+/// `true` if `text[start..end]` isn't part of a longer word
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+/// Characters used to space letters out (`b.a.s.s`, `b-a-s-s`)
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '.' | ',' | '-' | '_' | '*' | '+' | '~' | '|')
+}
+
+/// Undo a leetspeak substitution; `1` is handled by the caller since it can
+/// stand for either `i` or `l`
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '0' => 'o',
+        '$' => 's',
+        c => c,
+    }
+}
+
+/// Collapse runs of separators to a single space and join runs of
+/// single-character tokens, so `b  a - s s` becomes `bass`.
+fn collapse_separators(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut prev_single = false;
+    for token in text.split(is_separator).filter(|t| !t.is_empty()) {
+        let single = token.chars().count() == 1;
+        let spelled_out = single && prev_single;
+        if !spelled_out && !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(token);
+        prev_single = single;
+    }
+    out
+}
+
+/// The collapsed, de-leeted spellings of lowercase *text*: one reading `1`
+/// as `i`, the other as `l`.
+fn normalized_variants(text: &str) -> [String; 2] {
+    let collapsed = collapse_separators(text);
+    ['i', 'l'].map(|one| {
+        collapsed
+            .chars()
+            .map(|c| if c == '1' { one } else { unleet(c) })
+            .collect()
+    })
+}
+
+/// Normalize and insert a custom word, skipping duplicates
+fn add_word(list: &mut Vec<String>, word: &str) -> bool {
+    let word = strip_invisible(word.trim()).to_lowercase();
+    if word.is_empty() || list.contains(&word) {
+        return false;
+    }
+    list.push(word);
+    true
+}
+
+/// The app-wide filter, for adding words at runtime
+pub fn global() -> &'static RwLock<ContentFilter> {
+    &GLOBAL_FILTER
+}
+
+/// Return `true` if *text* contains a severe slur or a blocked term.
 pub fn contains_slur(text: &str) -> bool {
-    // Strip zero-width / invisible Unicode before checking
-    let cleaned = strip_invisible(text);
-    cleaned.is(Type::OFFENSIVE & Type::SEVERE)
+    GLOBAL_FILTER.read().check(text)
 }
 
 /// Validate that *text* does not contain a severe slur.
@@ -85,11 +280,83 @@ mod tests {
         assert!(!contains_slur("classic"));
     }
 
+    #[test]
+    fn separators_and_leet_are_normalized() {
+        assert_eq!(collapse_separators("b  a - s.s"), "bass");
+        assert_eq!(collapse_separators("DJ --  b a s s"), "DJ bass");
+        assert_eq!(
+            normalized_variants("b4$$ 1in3"),
+            ["bass iine".to_string(), "bass line".to_string()]
+        );
+    }
+
+    #[test]
+    fn spaced_and_leet_benign_words_pass() {
+        assert!(!contains_slur("S c u n t h o r p e"));
+        assert!(!contains_slur("$cunth0rp3"));
+        assert!(!contains_slur("c.l.a.s.s.i.c"));
+        assert!(!contains_slur("c1a$$1c"));
+        assert!(!contains_slur("B 4 S S L 1 N E"));
+        assert!(!contains_slur("CoolBeats42"));
+    }
+
+    #[test]
+    fn spaced_and_leet_blocked_words_are_caught() {
+        let mut filter = ContentFilter::default();
+        filter.add_blocked_word("velvet");
+        assert!(filter.check("v e l v e t"));
+        assert!(filter.check("v3lv3t"));
+        assert!(filter.check("ve1vet"));
+        assert!(filter.check("f a g g o t"));
+        assert!(filter.check("f4gg0t"));
+    }
+
     #[test]
     fn validate_clean_ok() {
         assert!(validate_no_slurs("DJ Cool", "DJ name").is_ok());
     }
 
+    #[test]
+    fn validate_rejects_flagged_name() {
+        let err = validate_no_slurs("DJ faggot", "DJ name").unwrap_err();
+        assert_eq!(err, "DJ name contains language that is not allowed");
+    }
+
+    #[test]
+    fn blocked_word_is_flagged() {
+        let mut filter = ContentFilter::default();
+        assert!(!filter.check("DJ Velvet Rope"));
+        assert!(filter.add_blocked_word("  Velvet "));
+        assert!(!filter.add_blocked_word("velvet"));
+        assert!(filter.check("DJ VELVET Rope"));
+        assert!(filter.check("DJ vel\u{200b}vet"));
+    }
+
+    #[test]
+    fn allowed_word_overrides_filter() {
+        let mut filter = ContentFilter::with_word_lists(&WordLists {
+            blocked: vec!["bass".to_string()],
+            allowed: Vec::new(),
+        });
+        assert!(filter.check("Bassline Society"));
+        filter.add_allowed_word("bassline");
+        assert!(!filter.check("Bassline Society"));
+        assert!(filter.check("Bass Society"));
+        assert!(filter.check("DJ faggot"));
+    }
+
+    #[test]
+    fn allowed_word_does_not_hide_overlapping_blocked_word() {
+        let filter = ContentFilter::with_word_lists(&WordLists {
+            blocked: vec!["shit".to_string()],
+            allowed: vec!["hits".to_string(), "shitake".to_string()],
+        });
+        assert!(!filter.check("Shitake Hits"));
+        assert!(filter.check("shits"));
+        assert!(filter.check("s h i t s"));
+        assert!(filter.check("$hit$"));
+    }
+
     #[test]
     fn validate_vague_error() {
         // We don't test specific slurs here, just that the error message
@@ -0,0 +1,3023 @@
+//! Headless DJ pipeline
+//!
+//! `McavEngine` owns audio capture, the VJ server client and the bridge task
+//! that streams analysis frames between them. It has no Tauri dependency:
+//! the desktop app's commands are thin wrappers around it, and a headless
+//! front end (a kiosk CLI, tests) can drive it directly and consume its
+//! `EngineEvent` stream.
+
+use crate::analysis_server::AnalysisServer;
+use crate::audio::{
+    self, AnalysisResult, AudioCaptureHandle, AudioSource, CaptureMode, FilePlayback,
+    FilePlaybackStatus, MixSource, SourceCache, VoiceInputHandle,
+};
+use crate::content_filter;
+use crate::error::DjError;
+use crate::midi::{self, MidiAction, MidiEvent, MidiMapping};
+use crate::osc::OscSender;
+use crate::patterns;
+use crate::protocol::{self, AudioFrameMessage, DjClient, DjClientConfig};
+use crate::settings::{Settings, SharedSetup};
+use crate::state::{self, AppState, BeatAssistConfig, BridgeControl, ConnectionStatus};
+use crate::voice::{self, VoiceConfig, VoiceOverrun, VoiceStatus, VoiceStreamer};
+
+use parking_lot::Mutex;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Sequence counter for audio frames
+static FRAME_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Accepted per-band trim multipliers
+pub const BAND_TRIM_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+
+/// Accepted input gains in dB
+pub const INPUT_GAIN_DB_RANGE: std::ops::RangeInclusive<f32> = -24.0..=24.0;
+
+/// Accepted dB-scale floors, in dBFS
+pub const DB_FLOOR_RANGE: std::ops::RangeInclusive<f32> = -120.0..=-12.0;
+
+/// Accepted linear gains for a source in the capture mix
+pub const SOURCE_GAIN_RANGE: std::ops::RangeInclusive<f32> = 0.0..=4.0;
+
+/// Accepted transient emphasis factors
+pub const TRANSIENT_EMPHASIS_RANGE: std::ops::RangeInclusive<f32> = 0.0..=4.0;
+
+/// Accepted beat intensity release times in ms
+pub const BEAT_RELEASE_MS_RANGE: std::ops::RangeInclusive<u32> = 20..=2000;
+
+/// Accepted iteration counts for `benchmark_analysis`
+pub const BENCHMARK_ITERATIONS_RANGE: std::ops::RangeInclusive<usize> = 1..=10_000;
+
+/// Locally fired effects queued between bridge ticks before extras are dropped
+const MAX_LOCAL_EFFECTS: usize = 16;
+
+/// How long `list_zones` waits for the server's `zone_list`
+const ZONE_LIST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Events buffered per subscriber before a slow one starts lagging
+const EVENT_CAPACITY: usize = 256;
+
+/// Something the UI (or any other subscriber) should know about.
+///
+/// Serializes as `{"event": <name>, "payload": ...}`, where the name is the
+/// Tauri event the desktop app forwards it as.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "event", content = "payload", rename_all = "kebab-case")]
+pub enum EngineEvent {
+    AudioLevels(AudioLevels),
+    Beat(BeatEvent),
+    DjStatus(ConnectionStatus),
+    VoiceStatus(VoiceStatus),
+    VoiceOverrun(VoiceOverrun),
+    PresetChanged(String),
+    DjRoster(serde_json::Value),
+    BridgeIdle(bool),
+    BridgeStats(state::BridgeStats),
+    CaptureMode(CaptureMode),
+    CaptureLost(String),
+    EffectTriggered(String),
+    SourcesChanged(Vec<AudioSource>),
+    SourceMissing(String),
+    MidiEvent(MidiEvent),
+    TempoChanged(state::TempoChange),
+    ClippingDetected(ClippingWarning),
+    EntityCountClamped(state::EntityCountClamp),
+    /// The Lua pattern engine couldn't start; entity mirroring stays off
+    /// until the next connection
+    PatternEngineFailed(String),
+}
+
+/// Audio levels response
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioLevels {
+    pub bands: [f32; 5],
+    pub peak: f32,
+    pub is_beat: bool,
+    pub beat_intensity: f32,
+    pub bpm: f32,
+    pub is_paused: bool,
+}
+
+/// A beat as the bridge sent it (detected, kick-assisted or phase-predicted),
+/// emitted on its own so UI flashes don't wait for the next levels emit
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BeatEvent {
+    pub intensity: f32,
+    pub bpm: f32,
+    pub beat_phase: f32,
+    /// Unix time in seconds
+    pub ts: f64,
+}
+
+/// The analyzed input is clipping; lowering the input gain usually fixes it
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ClippingWarning {
+    /// Input gain in dB at the time, so the UI can suggest a lower one
+    pub input_gain_db: f32,
+}
+
+/// Capture status response
+#[derive(Clone, serde::Serialize)]
+pub struct CaptureStatus {
+    pub active: bool,
+    pub source_id: Option<String>,
+    pub capture_mode: Option<CaptureMode>,
+    /// Sources summed before analysis with their gains, primary first
+    /// (empty when not capturing)
+    pub mix: Vec<MixSource>,
+    /// Why the chosen app couldn't be captured alone, when capture fell
+    /// back to all system audio
+    pub capture_fallback: Option<String>,
+}
+
+/// Everything the UI shows, read under one lock so fields agree with each other
+#[derive(Clone, serde::Serialize)]
+pub struct AppSnapshot {
+    pub status: ConnectionStatus,
+    pub audio_levels: AudioLevels,
+    pub voice_status: VoiceStatus,
+    pub active_preset: String,
+    pub capture_info: CaptureStatus,
+}
+
+/// What this build can do on this machine, so the UI can hide controls
+/// that would silently fall back
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Capabilities {
+    /// Voice is sent as Opus (built with `voice-opus`) rather than PCM
+    pub opus: bool,
+    /// Single-application capture instead of the system mix
+    pub per_app_capture: bool,
+    /// Windows Process Loopback, incl. "System Audio (exclude this app)"
+    pub process_loopback: bool,
+    /// `std::env::consts::OS`, e.g. "windows", "macos" or "linux"
+    pub platform: String,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        Self {
+            opus: cfg!(feature = "voice-opus"),
+            per_app_capture: audio::supports_per_app_capture(),
+            process_loopback: audio::supports_process_loopback(),
+            platform: std::env::consts::OS.to_string(),
+        }
+    }
+}
+
+/// Capture, client and bridge behind plain async methods.
+///
+/// Cheap to clone; clones share the same state and event stream.
+#[derive(Clone)]
+pub struct McavEngine {
+    state: Arc<Mutex<AppState>>,
+    /// Kept apart from `AppState` so a slow scan doesn't block the bridge task
+    sources: Arc<Mutex<SourceCache>>,
+    events: broadcast::Sender<EngineEvent>,
+    /// Where settings changes are persisted; `None` keeps them in memory
+    settings_path: Option<PathBuf>,
+}
+
+impl McavEngine {
+    pub fn new(settings_path: Option<PathBuf>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            state: Arc::new(Mutex::new(AppState::default())),
+            sources: Arc::new(Mutex::new(SourceCache::default())),
+            events,
+            settings_path,
+        }
+    }
+
+    /// Shared app state, for reads and settings that need no orchestration
+    pub fn state(&self) -> &Arc<Mutex<AppState>> {
+        &self.state
+    }
+
+    /// Subscribe to engine events from this point on
+    pub fn subscribe(&self) -> broadcast::Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: EngineEvent) {
+        // No subscribers is fine: nobody is watching
+        let _ = self.events.send(event);
+    }
+
+    /// Write settings to disk; failures are logged rather than surfaced,
+    /// since the change itself has already been applied
+    pub fn persist_settings(&self, settings: &Settings) {
+        let Some(ref path) = self.settings_path else {
+            return;
+        };
+        if let Err(e) = settings.save(path) {
+            log::warn!("Failed to save settings: {}", e);
+        }
+    }
+
+    /// Reload the settings file (the defaults when there is none), apply
+    /// it and return the settings as applied
+    pub fn load_settings(&self) -> Result<Settings, DjError> {
+        let settings = match self.settings_path {
+            Some(ref path) => Settings::load(path)?,
+            None => Settings::default(),
+        };
+        Ok(self.apply_settings(settings))
+    }
+
+    /// Apply settings and write them to disk as applied
+    pub fn save_settings(&self, settings: Settings) -> Result<Settings, DjError> {
+        let settings = self.apply_settings(settings);
+        if let Some(ref path) = self.settings_path {
+            settings.save(path)?;
+        }
+        Ok(settings)
+    }
+
+    /// Apply settings to the app state and a running capture, returning
+    /// them as applied. Values are clamped to their setters' ranges; an
+    /// invalid preset or band edges keep the current ones.
+    fn apply_settings(&self, settings: Settings) -> Settings {
+        let mut app_state = self.state.lock();
+        let sample_rate = app_state
+            .audio_capture
+            .as_ref()
+            .map_or(audio::AudioConfig::default().sample_rate, |c| {
+                c.analyzer().lock().sample_rate()
+            });
+        let active_preset = match settings.active_preset.validate() {
+            Ok(()) => settings.active_preset,
+            Err(e) => {
+                log::warn!("Keeping the current preset: {}", e);
+                app_state.active_preset.clone()
+            }
+        };
+        let band_edges = match audio::validate_band_edges(&settings.band_edges, sample_rate) {
+            Ok(()) => settings.band_edges,
+            Err(e) => {
+                log::warn!("Keeping the current band edges: {}", e);
+                app_state.band_edges
+            }
+        };
+        let settings = Settings {
+            active_preset,
+            band_edges,
+            band_trim: settings
+                .band_trim
+                .map(|t| t.clamp(*BAND_TRIM_RANGE.start(), *BAND_TRIM_RANGE.end())),
+            input_gain_db: settings
+                .input_gain_db
+                .clamp(*INPUT_GAIN_DB_RANGE.start(), *INPUT_GAIN_DB_RANGE.end()),
+            db_floor: settings
+                .db_floor
+                .clamp(*DB_FLOOR_RANGE.start(), *DB_FLOOR_RANGE.end()),
+            transient_emphasis: settings.transient_emphasis.clamp(
+                *TRANSIENT_EMPHASIS_RANGE.start(),
+                *TRANSIENT_EMPHASIS_RANGE.end(),
+            ),
+            beat_release_ms: settings
+                .beat_release_ms
+                .map(|ms| ms.clamp(*BEAT_RELEASE_MS_RANGE.start(), *BEAT_RELEASE_MS_RANGE.end())),
+            beat_assist: settings.beat_assist.clamped(),
+            max_entity_count: settings
+                .max_entity_count
+                .clamp(1, state::ENTITY_COUNT_LIMIT),
+            visibility_threshold: settings.visibility_threshold.map(|t| t.clamp(0.0, 1.0)),
+            voice_config: settings.voice_config.clamped(),
+            ..settings
+        };
+
+        settings.apply_to(&mut app_state);
+        if let Some(ref streamer) = app_state.voice_streamer {
+            streamer.apply_config(&app_state.voice_config);
+        }
+        if let Some(ref capture) = app_state.audio_capture {
+            push_tuning(&app_state, capture);
+        }
+        Settings::from_state(&app_state)
+    }
+
+    /// Available audio sources (cached for a few seconds)
+    pub fn list_sources(&self) -> Result<Vec<AudioSource>, String> {
+        self.sources
+            .lock()
+            .get(audio::list_sources)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Force a fresh source scan and emit `SourcesChanged`
+    pub fn refresh_sources(&self) -> Result<Vec<AudioSource>, String> {
+        let sources = self
+            .sources
+            .lock()
+            .refresh(audio::list_sources)
+            .map_err(|e| e.to_string())?;
+        self.emit(EngineEvent::SourcesChanged(sources.clone()));
+        Ok(sources)
+    }
+
+    /// Connect to a VJ server and start the bridge task.
+    ///
+    /// Validates the DJ name and normalizes the connect code (if any), shuts
+    /// down any existing bridge task, connects the client, optionally sends a
+    /// block palette, and spawns a new bridge task.
+    pub async fn connect(
+        &self,
+        mut config: DjClientConfig,
+        block_palette: Option<Vec<Option<String>>>,
+    ) -> Result<(), DjError> {
+        content_filter::validate_no_slurs(&config.dj_name, "DJ name")
+            .map_err(DjError::ContentRejected)?;
+        config.connect_code = config
+            .connect_code
+            .as_deref()
+            .map(protocol::normalize_connect_code)
+            .transpose()
+            .map_err(DjError::InvalidInput)?;
+        if config
+            .auth_token
+            .as_deref()
+            .is_some_and(|t| t.trim().is_empty())
+        {
+            return Err(DjError::InvalidInput("Auth token is empty".to_string()));
+        }
+
+        // If a previous bridge task is still running (e.g. reconnecting after
+        // server restart), shut it down and await completion before starting a new
+        // connection to avoid two bridge tasks running concurrently.
+        {
+            let (old_tx, old_handle) = {
+                let mut app_state = self.state.lock();
+                app_state.bridge_control_tx = None;
+                (
+                    app_state.bridge_shutdown_tx.take(),
+                    app_state.bridge_task_handle.take(),
+                )
+            };
+            if let Some(tx) = old_tx {
+                log::info!("Shutting down existing bridge task before reconnecting");
+                let _ = tx.send(()).await;
+            }
+            if let Some(handle) = old_handle {
+                match tokio::time::timeout(Duration::from_millis(500), handle).await {
+                    Ok(_) => log::info!("Old bridge task stopped cleanly"),
+                    Err(_) => {
+                        log::warn!("Old bridge task did not stop within 500ms, proceeding anyway")
+                    }
+                }
+            }
+            // Clean up old client
+            let old_client = self.state.lock().client.take();
+            if let Some(c) = old_client {
+                let _ = c.disconnect().await;
+            }
+        }
+
+        let settings = {
+            let mut app_state = self.state.lock();
+            app_state.connect_code = config.connect_code.clone();
+            app_state.auth_token = config.auth_token.clone();
+            app_state.dj_name = config.dj_name.clone();
+            app_state.server_host = config.server_host.clone();
+            app_state.server_port = config.server_port;
+            Settings::from_state(&app_state)
+        };
+        self.persist_settings(&settings);
+
+        // Create and connect client (async, no mutex held)
+        let mut client = DjClient::new(config);
+        client.connect().await?;
+
+        // Send block palette if provided
+        if let Some(palette) = block_palette
+            && palette.iter().any(|m| m.is_some())
+            && let Err(e) = client.send_palette(palette).await
+        {
+            log::warn!("Failed to send block palette: {}", e);
+        }
+
+        // Create shutdown and reconnect control channels for bridge task
+        let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
+        let (control_tx, control_rx) = mpsc::channel::<BridgeControl>(4);
+
+        // Store connected client and shutdown channel
+        {
+            let mut app_state = self.state.lock();
+            app_state.client = Some(client);
+            app_state.bridge_shutdown_tx = Some(shutdown_tx);
+            app_state.bridge_control_tx = Some(control_tx);
+            app_state.status.connected = true;
+            app_state.status.error = None;
+        }
+
+        // Spawn bridge task and store its handle
+        let handle = tokio::spawn(run_bridge(self.clone(), shutdown_rx, control_rx));
+        self.state.lock().bridge_task_handle = Some(handle);
+
+        Ok(())
+    }
+
+    /// Stop the bridge, disconnect from the server and stop capture
+    pub async fn disconnect(&self) {
+        // Signal bridge task to stop (it handles client disconnect)
+        let (shutdown_tx, bridge_handle, capture, voice_streamer, voice_input) = {
+            let mut app_state = self.state.lock();
+            app_state.bridge_control_tx = None;
+            (
+                app_state.bridge_shutdown_tx.take(),
+                app_state.bridge_task_handle.take(),
+                app_state.audio_capture.take(),
+                app_state.voice_streamer.take(),
+                app_state.voice_input.take(),
+            )
+        };
+
+        // Disable voice streaming
+        if let Some(ref streamer) = voice_streamer {
+            streamer.set_enabled(false);
+        }
+
+        if let Some(tx) = shutdown_tx {
+            let _ = tx.send(()).await;
+            // Await the bridge task handle instead of a fixed sleep
+            if let Some(handle) = bridge_handle {
+                let _ = tokio::time::timeout(BRIDGE_SHUTDOWN_TIMEOUT, handle).await;
+            }
+        } else {
+            // No bridge task running, disconnect client directly
+            let client = {
+                let mut app_state = self.state.lock();
+                app_state.client.take()
+            };
+            if let Some(client) = client {
+                let _ = client.disconnect().await;
+            }
+        }
+
+        // Stop audio capture
+        if let Some(capture) = capture {
+            capture.stop();
+        }
+        if let Some(input) = voice_input {
+            input.stop();
+        }
+
+        // Reset status
+        {
+            let mut app_state = self.state.lock();
+            app_state.status.connected = false;
+            app_state.status.is_active = false;
+            app_state.status.latency_ms = 0.0;
+            app_state.status.server_time_offset_ms = None;
+            app_state.status.route_mode = String::new();
+            app_state.status.queue_position = 0;
+            app_state.status.total_djs = 0;
+            app_state.status.active_dj_name = None;
+            app_state.status.mc_connected = false;
+            app_state.status.health = 0;
+            app_state.status.error = None;
+            app_state.capture_fallback = None;
+            app_state.voice_config.enabled = false;
+            app_state.voice_status = VoiceStatus::default();
+        }
+    }
+
+    /// Cut the reconnect backoff short and retry straight away (no-op while
+    /// connected)
+    pub fn force_reconnect(&self) -> Result<(), DjError> {
+        self.send_bridge_control(BridgeControl::ReconnectNow)
+    }
+
+    /// Stop the reconnect loop and stay disconnected; capture keeps running
+    pub fn cancel_reconnect(&self) -> Result<(), DjError> {
+        self.send_bridge_control(BridgeControl::CancelReconnect)
+    }
+
+    fn send_bridge_control(&self, control: BridgeControl) -> Result<(), DjError> {
+        let tx = self
+            .state
+            .lock()
+            .bridge_control_tx
+            .clone()
+            .ok_or(DjError::NotConnected)?;
+        match tx.try_send(control) {
+            // A full queue already holds a pending retry or cancel
+            Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => Ok(()),
+            Err(mpsc::error::TrySendError::Closed(_)) => Err(DjError::NotConnected),
+        }
+    }
+
+    /// Start audio capture from a source.
+    ///
+    /// Without an explicit source, picks up the remembered source from the
+    /// last session (or default loopback). `run_capture_monitor` emits the
+    /// capture mode once the audio thread reports it, and remembers the
+    /// source if capture succeeded.
+    ///
+    /// Devices in `mix_with` are summed into the source before analysis.
+    pub fn start_capture(
+        &self,
+        source_id: Option<String>,
+        mix_with: Vec<String>,
+    ) -> Result<(), DjError> {
+        let source_id = source_id.or_else(|| self.remembered_source());
+        self.open_capture(source_id, mix_with)
+    }
+
+    /// Hot-swap capture to another source (e.g. while connected), keeping
+    /// the mixed-in devices
+    pub fn change_source(&self, source_id: Option<String>) -> Result<(), DjError> {
+        let mix_with = {
+            let mut app_state = self.state.lock();
+            if let Some(capture) = app_state.audio_capture.take() {
+                capture.stop();
+            }
+            app_state.mix_source_ids.clone()
+        };
+        let mix_with = mix_with
+            .into_iter()
+            .filter(|id| Some(id) != source_id.as_ref())
+            .collect();
+        self.open_capture(source_id, mix_with)
+    }
+
+    fn open_capture(
+        &self,
+        source_id: Option<String>,
+        mix_with: Vec<String>,
+    ) -> Result<(), DjError> {
+        // Create voice streamer (48kHz, stereo assumed; resampling handles mismatches)
+        let voice_streamer = Arc::new(VoiceStreamer::new(48000, 2));
+
+        // Propagate current voice config
+        {
+            let app_state = self.state.lock();
+            voice_streamer.set_enabled(app_state.voice_config.enabled);
+            voice_streamer.apply_config(&app_state.voice_config);
+        }
+
+        let capture = AudioCaptureHandle::new_mixed(
+            source_id.clone(),
+            mix_with.clone(),
+            Some(voice_streamer.clone()),
+        )?;
+
+        let mut app_state = self.state.lock();
+        for source in capture.mix_sources() {
+            if let Some(&gain) = app_state.source_gains.get(&source.source_id) {
+                capture.set_source_gain(&source.source_id, gain);
+            }
+        }
+
+        push_tuning(&app_state, &capture);
+
+        app_state.audio_source_id = source_id;
+        app_state.mix_source_ids = mix_with;
+        app_state.audio_capture = Some(capture);
+        app_state.capture_fallback = None;
+        app_state.voice_status.codec = voice_streamer.codec().to_string();
+        app_state.voice_streamer = Some(voice_streamer);
+        if let Err(e) = restart_voice_input(&mut app_state) {
+            log::warn!("Voice input unavailable, sending the music mix: {}", e);
+        }
+        Ok(())
+    }
+
+    /// The remembered source id, if that source is still available.
+    ///
+    /// Emits `SourceMissing` with the id when it has disappeared (e.g. the
+    /// app was closed or the device unplugged), in which case capture falls
+    /// back to default loopback.
+    fn remembered_source(&self) -> Option<String> {
+        let remembered = self.state.lock().remembered_source_id.clone()?;
+        let present = self
+            .list_sources()
+            .is_ok_and(|sources| sources.iter().any(|s| s.id == remembered));
+        if present {
+            return Some(remembered);
+        }
+
+        log::warn!(
+            "Remembered audio source '{}' is no longer available",
+            remembered
+        );
+        self.emit(EngineEvent::SourceMissing(remembered));
+        None
+    }
+
+    /// Stop audio capture
+    pub fn stop_capture(&self) {
+        let mut app_state = self.state.lock();
+        if let Some(capture) = app_state.audio_capture.take() {
+            capture.stop();
+        }
+        app_state.capture_fallback = None;
+        if let Some(input) = app_state.voice_input.take() {
+            input.stop();
+        }
+        // Clean up voice streamer so it stops buffering frames
+        if let Some(ref streamer) = app_state.voice_streamer {
+            streamer.set_enabled(false);
+        }
+        app_state.voice_streamer = None;
+    }
+
+    /// Pause analysis (silent frames go out) while keeping the device open,
+    /// or resume it
+    pub fn set_capture_paused(&self, paused: bool) -> Result<(), DjError> {
+        let app_state = self.state.lock();
+        let capture = app_state
+            .audio_capture
+            .as_ref()
+            .ok_or(DjError::CaptureNotRunning)?;
+        capture.set_paused(paused);
+        Ok(())
+    }
+
+    /// Play or pause a file source; playing a file that reached the end
+    /// starts it over
+    pub fn set_file_paused(&self, paused: bool) -> Result<(), DjError> {
+        self.with_file_playback(|playback| playback.set_paused(paused))
+    }
+
+    /// Jump to a time (seconds) in a file source
+    pub fn seek_file(&self, position_secs: f64) -> Result<(), DjError> {
+        if !position_secs.is_finite() {
+            return Err(DjError::InvalidInput(format!(
+                "Invalid seek position: {}",
+                position_secs
+            )));
+        }
+        self.with_file_playback(|playback| playback.seek(position_secs))
+    }
+
+    /// Loop a file source at its end instead of stopping
+    pub fn set_file_looping(&self, looping: bool) -> Result<(), DjError> {
+        self.with_file_playback(|playback| playback.set_looping(looping))
+    }
+
+    /// Position and controls of the file source, if capture is playing one
+    pub fn file_playback(&self) -> Option<FilePlaybackStatus> {
+        self.with_file_playback(|playback| playback.status()).ok()
+    }
+
+    fn with_file_playback<T>(&self, f: impl FnOnce(&FilePlayback) -> T) -> Result<T, DjError> {
+        let app_state = self.state.lock();
+        let capture = app_state
+            .audio_capture
+            .as_ref()
+            .ok_or(DjError::CaptureNotRunning)?;
+        let playback = capture.file_playback().ok_or_else(|| {
+            DjError::InvalidInput("Capture is not playing an audio file".to_string())
+        })?;
+        Ok(f(playback))
+    }
+
+    /// Set the input gain in dB (clamped to `INPUT_GAIN_DB_RANGE`) and
+    /// return it as applied
+    pub fn set_input_gain(&self, db: f32) -> f32 {
+        let db = db.clamp(*INPUT_GAIN_DB_RANGE.start(), *INPUT_GAIN_DB_RANGE.end());
+        let mut app_state = self.state.lock();
+        app_state.input_gain_db = db;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.set_input_gain_db(db);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        db
+    }
+
+    /// Turn automatic input level normalization on or off
+    pub fn set_auto_normalize(&self, enabled: bool) {
+        let mut app_state = self.state.lock();
+        app_state.auto_normalize = enabled;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.set_auto_normalize(enabled);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+    }
+
+    /// Choose the signal the beat detector listens to; survives preset
+    /// changes and restarts
+    pub fn set_beat_source(&self, source: audio::BeatSource) {
+        let mut app_state = self.state.lock();
+        app_state.beat_source = source;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().set_beat_source(source);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+    }
+
+    /// Report bands linearly or in dB, with `db_floor` (clamped to
+    /// `DB_FLOOR_RANGE`, kept when `None`) shown as 0; returns the floor as
+    /// applied
+    pub fn set_band_scale(&self, scale: audio::BandScale, db_floor: Option<f32>) -> f32 {
+        let mut app_state = self.state.lock();
+        let db_floor = db_floor
+            .unwrap_or(app_state.db_floor)
+            .clamp(*DB_FLOOR_RANGE.start(), *DB_FLOOR_RANGE.end());
+        app_state.band_scale = scale;
+        app_state.db_floor = db_floor;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().set_band_scale(scale, db_floor);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        db_floor
+    }
+
+    /// Retune the band edges (Hz, six strictly increasing values). Checked
+    /// against the running capture's sample rate, or 48kHz when idle.
+    pub fn set_band_edges(&self, edges: [f32; 6]) -> Result<(), DjError> {
+        let mut app_state = self.state.lock();
+        let sample_rate = app_state
+            .audio_capture
+            .as_ref()
+            .map_or(audio::AudioConfig::default().sample_rate, |c| {
+                c.analyzer().lock().sample_rate()
+            });
+        audio::validate_band_edges(&edges, sample_rate).map_err(DjError::InvalidInput)?;
+        app_state.band_edges = edges;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().set_band_edges(edges);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        Ok(())
+    }
+
+    /// Turn the analyzer's per-band AGC on or off
+    pub fn set_agc(&self, enabled: bool) {
+        let mut app_state = self.state.lock();
+        app_state.agc_enabled = enabled;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().set_agc(enabled);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+    }
+
+    /// Add each band's rise since the last frame, times `emphasis`, on top
+    /// of the smoothed level so hits pop before settling (0 turns it off)
+    pub fn set_transient_emphasis(&self, emphasis: f32) -> Result<(), DjError> {
+        if !TRANSIENT_EMPHASIS_RANGE.contains(&emphasis) {
+            return Err(DjError::InvalidInput(format!(
+                "Transient emphasis must be between {} and {}",
+                TRANSIENT_EMPHASIS_RANGE.start(),
+                TRANSIENT_EMPHASIS_RANGE.end()
+            )));
+        }
+        let mut app_state = self.state.lock();
+        app_state.transient_emphasis = emphasis;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().set_transient_emphasis(emphasis);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        Ok(())
+    }
+
+    /// Hold the average peak steady when switching presets, so a mid-set
+    /// switch doesn't jump the show's intensity
+    pub fn set_normalize_across_presets(&self, enabled: bool) {
+        let mut app_state = self.state.lock();
+        app_state.normalize_across_presets = enabled;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture
+                .analyzer()
+                .lock()
+                .set_normalize_across_presets(enabled);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+    }
+
+    /// The current reactive setup for sharing: the active preset (built-in,
+    /// imported or server-sent) with band trim, beat assist and capture
+    /// options
+    pub fn export_setup(&self) -> SharedSetup {
+        SharedSetup::from_state(&self.state.lock())
+    }
+
+    /// Apply a setup another DJ exported. The preset name goes through the
+    /// content filter; band trim, gain, dB floor and transient emphasis are
+    /// clamped to their setters' ranges.
+    pub fn import_setup(&self, setup: SharedSetup) -> Result<(), DjError> {
+        content_filter::validate_no_slurs(&setup.preset.name, "Preset name")
+            .map_err(DjError::ContentRejected)?;
+        if setup.preset.name.trim().is_empty() {
+            return Err(DjError::InvalidInput("Preset name is empty".to_string()));
+        }
+        setup.preset.validate().map_err(DjError::InvalidInput)?;
+        let setup = SharedSetup {
+            band_trim: setup
+                .band_trim
+                .map(|t| t.clamp(*BAND_TRIM_RANGE.start(), *BAND_TRIM_RANGE.end())),
+            input_gain_db: setup
+                .input_gain_db
+                .clamp(*INPUT_GAIN_DB_RANGE.start(), *INPUT_GAIN_DB_RANGE.end()),
+            db_floor: setup
+                .db_floor
+                .clamp(*DB_FLOOR_RANGE.start(), *DB_FLOOR_RANGE.end()),
+            beat_assist: setup.beat_assist.clamped(),
+            transient_emphasis: setup.transient_emphasis.clamp(
+                *TRANSIENT_EMPHASIS_RANGE.start(),
+                *TRANSIENT_EMPHASIS_RANGE.end(),
+            ),
+            ..setup
+        };
+
+        let mut app_state = self.state.lock();
+        let sample_rate = app_state
+            .audio_capture
+            .as_ref()
+            .map_or(audio::AudioConfig::default().sample_rate, |c| {
+                c.analyzer().lock().sample_rate()
+            });
+        audio::validate_band_edges(&setup.band_edges, sample_rate)
+            .map_err(DjError::InvalidInput)?;
+        setup.apply_to(&mut app_state);
+        if let Some(ref capture) = app_state.audio_capture {
+            push_tuning(&app_state, capture);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        log::info!("Imported setup with preset '{}'", setup.preset.name);
+        Ok(())
+    }
+
+    /// Punch a one-shot beat (intensity clamped to 0-1) into the next bridge
+    /// frame, whatever the analyzer says
+    pub fn trigger_beat(&self, intensity: f32) -> Result<(), DjError> {
+        let mut app_state = self.state.lock();
+        if app_state.audio_capture.is_none() {
+            return Err(DjError::CaptureNotRunning);
+        }
+        app_state.forced_beat = Some(intensity.clamp(0.0, 1.0));
+        Ok(())
+    }
+
+    /// Send one synthetic audio frame (bands and peak clamped to 0-1) to the
+    /// VJ server, which renders it to Minecraft like any other frame. Meant
+    /// for checking the in-game render on site with capture stopped; while
+    /// capture runs the next bridge frame replaces it straight away.
+    pub async fn send_test_frame(
+        &self,
+        bands: [f32; 5],
+        peak: f32,
+        beat: bool,
+    ) -> Result<(), DjError> {
+        let tx = self
+            .state
+            .lock()
+            .client
+            .as_ref()
+            .and_then(|c| c.get_tx_clone())
+            .ok_or(DjError::NotConnected)?;
+        let msg = AudioFrameMessage::new(
+            FRAME_SEQ.fetch_add(1, Ordering::Relaxed),
+            bands.map(|b| b.clamp(0.0, 1.0)),
+            peak.clamp(0.0, 1.0),
+            beat,
+            if beat { 1.0 } else { 0.0 },
+            0.0,
+            0.0,
+            0.0,
+            bands[0].clamp(0.0, 1.0),
+            beat,
+        );
+        let json = serde_json::to_string(&msg).map_err(|e| DjError::Internal(e.to_string()))?;
+        tx.send(Message::Text(json.into()))
+            .await
+            .map_err(|_| DjError::NotConnected)?;
+        log::info!("Sent test frame (bands {:?}, beat {})", msg.bands, beat);
+        Ok(())
+    }
+
+    /// Fire a pattern effect (e.g. `"strobe"`) on the next bridge frame, as
+    /// if the VJ panel had sent it
+    pub fn fire_effect(&self, effect: &str) -> Result<(), DjError> {
+        let effect = effect.trim();
+        if effect.is_empty() {
+            return Err(DjError::InvalidInput("Effect name is empty".to_string()));
+        }
+        let mut app_state = self.state.lock();
+        if app_state.local_effects.len() < MAX_LOCAL_EFFECTS {
+            app_state.local_effects.push(effect.to_string());
+        }
+        Ok(())
+    }
+
+    /// Set the gain of one source in the capture mix (clamped to
+    /// `SOURCE_GAIN_RANGE`) and return it as applied. Kept across source
+    /// changes for the rest of the session.
+    pub fn set_source_gain(&self, source_id: &str, gain: f32) -> Result<f32, DjError> {
+        let gain = gain.clamp(*SOURCE_GAIN_RANGE.start(), *SOURCE_GAIN_RANGE.end());
+        let mut app_state = self.state.lock();
+        let capture = app_state
+            .audio_capture
+            .as_ref()
+            .ok_or(DjError::CaptureNotRunning)?;
+        if !capture.set_source_gain(source_id, gain) {
+            return Err(DjError::SourceNotFound(source_id.to_string()));
+        }
+        app_state.source_gains.insert(source_id.to_string(), gain);
+        Ok(gain)
+    }
+
+    /// Apply an audio preset by name and return its canonical name
+    pub fn set_preset(&self, name: &str) -> Result<String, String> {
+        let preset = audio::get_preset(name).ok_or_else(|| format!("Unknown preset: {}", name))?;
+        let mut app_state = self.state.lock();
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().apply_preset(&preset);
+        }
+        app_state.active_preset = preset.clone();
+
+        // Send preferred preset to VJ server so it persists across DJ swaps
+        if let Some(tx) = app_state.client.as_ref().and_then(|c| c.get_tx_clone())
+            && let Ok(json) = serde_json::to_string(&serde_json::json!({
+                "type": "set_my_preset",
+                "preset": &preset.name
+            }))
+        {
+            let _ = tx.try_send(Message::Text(json.into()));
+        }
+
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+
+        Ok(preset.name)
+    }
+
+    /// Apply the preset `step` places from the active one in
+    /// `audio::get_presets()`, wrapping around, and return its name.
+    ///
+    /// A server-sent custom preset isn't in the list, so stepping from it
+    /// starts at the first (forward) or last (backward) preset.
+    pub fn step_preset(&self, step: isize) -> Result<String, String> {
+        let presets = audio::get_presets();
+        let active = self.state.lock().active_preset.name.clone();
+        let index = match presets.iter().position(|p| p.name == active) {
+            Some(i) => i as isize + step,
+            None if step > 0 => step - 1,
+            None => step,
+        };
+        self.set_preset_by_index(index.rem_euclid(presets.len() as isize) as usize)
+    }
+
+    /// Apply the preset at `index` in `audio::get_presets()` and return its name
+    pub fn set_preset_by_index(&self, index: usize) -> Result<String, String> {
+        let preset = audio::get_presets()
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| format!("No preset at index {}", index))?;
+        self.set_preset(&preset.name)
+    }
+
+    /// Trim each band on top of the active preset's sensitivity (values are
+    /// clamped to `BAND_TRIM_RANGE`) and return the trim as applied.
+    ///
+    /// Survives preset changes. Patterns see the trimmed bands, so the
+    /// pattern engine's own sensitivity is left alone.
+    pub fn set_band_trim(&self, trim: [f32; 5]) -> [f32; 5] {
+        let trim = trim.map(|t| t.clamp(*BAND_TRIM_RANGE.start(), *BAND_TRIM_RANGE.end()));
+        let mut app_state = self.state.lock();
+        app_state.band_trim = trim;
+        if let Some(ref capture) = app_state.audio_capture {
+            capture.analyzer().lock().set_band_trim(trim);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        trim
+    }
+
+    /// Move the tempo estimate to double or half time and return the new
+    /// BPM, for when the detector has locked the wrong octave
+    pub fn nudge_tempo_octave(&self, direction: audio::TempoOctave) -> Result<f32, DjError> {
+        let app_state = self.state.lock();
+        let capture = app_state
+            .audio_capture
+            .as_ref()
+            .ok_or(DjError::CaptureNotRunning)?;
+        Ok(capture.analyzer().lock().nudge_tempo_octave(direction))
+    }
+
+    /// Enable or disable voice audio streaming
+    pub async fn set_voice_streaming(&self, enabled: bool) {
+        let (voice_streamer, tx, voice_config) = {
+            let mut app_state = self.state.lock();
+            app_state.voice_config.enabled = enabled;
+
+            let streamer = app_state.voice_streamer.clone();
+            let tx = app_state.client.as_ref().and_then(|c| c.get_tx_clone());
+            let config = app_state.voice_config.clone();
+            (streamer, tx, config)
+        };
+
+        // Update streamer state
+        if let Some(ref streamer) = voice_streamer {
+            streamer.set_enabled(enabled);
+        }
+
+        // Send voice_config message to VJ server
+        if let Some(tx) = tx {
+            let msg = protocol::VoiceConfigMessage::new(
+                enabled,
+                voice_config.channel_type.clone(),
+                voice_config.distance,
+                voice_config.zone.clone(),
+            );
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = tx.send(Message::Text(json.into())).await;
+            }
+        }
+
+        // Emit updated voice status
+        let voice_status = self.state.lock().voice_status.clone();
+        self.emit(EngineEvent::VoiceStatus(voice_status));
+    }
+
+    /// Ask the server for its zones and wait for the answer
+    pub async fn list_zones(&self) -> Result<Vec<protocol::ZoneInfo>, DjError> {
+        let (tx, seen) = {
+            let app_state = self.state.lock();
+            let client = app_state.client.as_ref().ok_or(DjError::NotConnected)?;
+            let tx = client.get_tx_clone().ok_or(DjError::NotConnected)?;
+            (tx, client.get_state().zone_lists_received)
+        };
+        let json = serde_json::to_string(&protocol::ListZonesMessage::new())
+            .map_err(|e| DjError::Internal(e.to_string()))?;
+        tx.send(Message::Text(json.into()))
+            .await
+            .map_err(|_| DjError::NotConnected)?;
+
+        tokio::time::timeout(ZONE_LIST_TIMEOUT, async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let app_state = self.state.lock();
+                let Some(ref client) = app_state.client else {
+                    return Err(DjError::NotConnected);
+                };
+                let conn = client.get_state();
+                if conn.zone_lists_received != seen {
+                    return Ok(conn.zones.unwrap_or_default());
+                }
+            }
+        })
+        .await
+        .map_err(|_| DjError::Timeout)?
+    }
+
+    /// Check `zone` against the zones the server last reported; passes
+    /// when disconnected or before any list has arrived
+    pub fn check_zone(&self, zone: &str) -> Result<(), DjError> {
+        match self.state.lock().client {
+            Some(ref client) => client
+                .get_state()
+                .check_zone(zone)
+                .map_err(DjError::InvalidInput),
+            None => Ok(()),
+        }
+    }
+
+    /// Replace the voice streaming options and send the new channel,
+    /// distance and zone to the server. Whether voice is on and the input
+    /// device are left alone.
+    ///
+    /// A changed zone is checked with `check_zone` and `frame_ms` must be
+    /// one of `voice::VOICE_FRAME_SIZES_MS`; levels are clamped.
+    pub async fn set_voice_config(&self, config: VoiceConfig) -> Result<(), DjError> {
+        if voice::frame_samples_for(config.frame_ms).is_none() {
+            return Err(DjError::InvalidInput(format!(
+                "Voice frame size must be one of {:?} ms",
+                voice::VOICE_FRAME_SIZES_MS
+            )));
+        }
+        if config.zone != self.state.lock().voice_config.zone {
+            self.check_zone(&config.zone)?;
+        }
+        let (tx, config, settings) = {
+            let mut app_state = self.state.lock();
+            app_state.voice_config = VoiceConfig {
+                enabled: app_state.voice_config.enabled,
+                voice_input_device: app_state.voice_config.voice_input_device.clone(),
+                ..config.clamped()
+            };
+            if let Some(ref streamer) = app_state.voice_streamer {
+                streamer.apply_config(&app_state.voice_config);
+            }
+            (
+                app_state.client.as_ref().and_then(|c| c.get_tx_clone()),
+                app_state.voice_config.clone(),
+                Settings::from_state(&app_state),
+            )
+        };
+        self.persist_settings(&settings);
+
+        if let Some(tx) = tx {
+            let msg = protocol::VoiceConfigMessage::new(
+                config.enabled,
+                config.channel_type,
+                config.distance,
+                config.zone,
+            );
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = tx.send(Message::Text(json.into())).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Capture voice from `device` (a device name or `input:` source id);
+    /// `None` or an empty name goes back to sending the music mix. Takes
+    /// effect immediately if capture is running, and is saved even when the
+    /// device fails to open.
+    pub fn set_voice_input_device(&self, device: Option<String>) -> Result<(), DjError> {
+        let device = device
+            .map(|d| d.trim_start_matches("input:").to_string())
+            .filter(|d| !d.is_empty());
+        let mut app_state = self.state.lock();
+        app_state.voice_config.voice_input_device = device;
+        let result = restart_voice_input(&mut app_state).map_err(DjError::AudioDevice);
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        result
+    }
+
+    /// Shape the music ducking applied while the DJ talks: attack and
+    /// release in ms, `depth` the maximum attenuation (0-1, 0 turns it off)
+    pub fn set_voice_ducking(
+        &self,
+        attack_ms: u32,
+        release_ms: u32,
+        depth: f32,
+    ) -> Result<(), DjError> {
+        if !(0.0..=1.0).contains(&depth) {
+            return Err(DjError::InvalidInput(
+                "Ducking depth must be between 0 and 1".to_string(),
+            ));
+        }
+        let mut app_state = self.state.lock();
+        app_state.voice_config.duck_attack_ms = attack_ms;
+        app_state.voice_config.duck_release_ms = release_ms;
+        app_state.voice_config.duck_depth = depth;
+        if let Some(ref streamer) = app_state.voice_streamer {
+            streamer.apply_config(&app_state.voice_config);
+        }
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        Ok(())
+    }
+
+    /// The synthetic beat assist settings
+    pub fn beat_assist_config(&self) -> BeatAssistConfig {
+        self.state.lock().beat_assist
+    }
+
+    /// Update the synthetic beat assist and save it; out-of-range values are
+    /// clamped. Returns the config as applied.
+    pub fn set_beat_assist_config(&self, config: BeatAssistConfig) -> BeatAssistConfig {
+        let config = config.clamped();
+        let mut app_state = self.state.lock();
+        app_state.beat_assist = config;
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        config
+    }
+
+    /// Start sending analysis over OSC to `host:port` at `rate_hz`
+    /// (`osc::OSC_RATE_RANGE`), replacing any running sender. Frames come
+    /// from the bridge, so they flow while connected to a VJ server.
+    pub fn enable_osc(&self, host: &str, port: u16, rate_hz: u32) -> Result<(), DjError> {
+        let sender = OscSender::new(host, port, rate_hz).map_err(DjError::InvalidInput)?;
+        log::info!("OSC output to {} at {} Hz", sender.target(), rate_hz);
+        self.state.lock().osc_sender = Some(Arc::new(sender));
+        Ok(())
+    }
+
+    /// Stop sending OSC
+    pub fn disable_osc(&self) {
+        if self.state.lock().osc_sender.take().is_some() {
+            log::info!("OSC output stopped");
+        }
+    }
+
+    /// Serve the bridge's analysis frames as JSON over a WebSocket on
+    /// `127.0.0.1:port` (0 picks a free port), replacing any running server,
+    /// and return the port. Frames flow while connected to a VJ server.
+    pub async fn enable_analysis_server(&self, port: u16) -> Result<u16, DjError> {
+        // Free the port first so the same one can be reused
+        self.disable_analysis_server();
+        let server = AnalysisServer::bind(port)
+            .await
+            .map_err(|e| DjError::Internal(format!("Couldn't listen on port {}: {}", port, e)))?;
+        let port = server.port();
+        self.state.lock().analysis_server = Some(Arc::new(server));
+        Ok(port)
+    }
+
+    /// Stop the analysis server and disconnect its clients
+    pub fn disable_analysis_server(&self) {
+        if self.state.lock().analysis_server.take().is_some() {
+            log::info!("Analysis server stopped");
+        }
+    }
+
+    /// Replace the MIDI controller mapping
+    pub fn set_midi_mapping(&self, mapping: MidiMapping) {
+        let mut app_state = self.state.lock();
+        app_state.midi_mapping = mapping;
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+    }
+
+    /// Open a MIDI input (the first available one when `port` is `None`),
+    /// replacing any open one, and return the port's name.
+    ///
+    /// Every note and CC is emitted as `EngineEvent::MidiEvent` for learn
+    /// mode; presses of mapped controls run their action.
+    pub async fn enable_midi(&self, port: Option<&str>) -> Result<String, DjError> {
+        // Close the old port first so the same device can be reopened
+        self.disable_midi();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let listener = midi::open(port, move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(DjError::AudioDevice)?;
+        let name = listener.port().to_string();
+        self.state.lock().midi_listener = Some(listener);
+        log::info!("MIDI input '{}' opened", name);
+
+        // Ends when the listener (and with it the sender) is dropped
+        let engine = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let action = engine.state.lock().midi_mapping.action_for(&event).cloned();
+                engine.emit(EngineEvent::MidiEvent(event));
+                if let Some(action) = action {
+                    engine.run_midi_action(action).await;
+                }
+            }
+        });
+        Ok(name)
+    }
+
+    /// Close the MIDI input, if one is open
+    pub fn disable_midi(&self) {
+        if let Some(listener) = self.state.lock().midi_listener.take() {
+            log::info!("MIDI input '{}' closed", listener.port());
+        }
+    }
+
+    async fn run_midi_action(&self, action: MidiAction) {
+        let result = match action {
+            MidiAction::NextPreset => self.step_preset(1).map(Some),
+            MidiAction::PrevPreset => self.step_preset(-1).map(Some),
+            MidiAction::SetPreset { name } => self.set_preset(&name).map(Some),
+            MidiAction::TriggerBeat { intensity } => self
+                .trigger_beat(intensity)
+                .map(|_| None)
+                .map_err(|e| e.to_string()),
+            MidiAction::ToggleVoice => {
+                let enabled = !self.state.lock().voice_config.enabled;
+                self.set_voice_streaming(enabled).await;
+                Ok(None)
+            }
+            MidiAction::FireEffect { effect } => self
+                .fire_effect(&effect)
+                .map(|_| None)
+                .map_err(|e| e.to_string()),
+        };
+        match result {
+            // Tell the UI, which didn't ask for the switch itself
+            Ok(Some(preset)) => self.emit(EngineEvent::PresetChanged(preset)),
+            Ok(None) => {}
+            Err(e) => log::warn!("MIDI action failed: {}", e),
+        }
+    }
+
+    /// Set the bridge frame rate (20-120fps); a running bridge picks it up
+    /// on its next tick
+    pub fn set_bridge_fps(&self, fps: u32) -> Result<(), DjError> {
+        if !BRIDGE_FPS_RANGE.contains(&fps) {
+            return Err(DjError::InvalidInput(format!(
+                "Frame rate must be between {} and {} fps",
+                BRIDGE_FPS_RANGE.start(),
+                BRIDGE_FPS_RANGE.end()
+            )));
+        }
+        self.state.lock().bridge_tick = Duration::from_secs_f64(1.0 / fps as f64);
+        Ok(())
+    }
+
+    /// Cap the server's entity count (1-512) for machines that can't keep
+    /// up; a running bridge applies it on its next tick
+    pub fn set_max_entity_count(&self, max: u32) -> Result<(), DjError> {
+        if !(1..=state::ENTITY_COUNT_LIMIT).contains(&max) {
+            return Err(DjError::InvalidInput(format!(
+                "Entity cap must be between 1 and {}",
+                state::ENTITY_COUNT_LIMIT
+            )));
+        }
+        let mut app_state = self.state.lock();
+        app_state.max_entity_count = max;
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        Ok(())
+    }
+
+    /// Hide mirrored entities whose band is below `threshold` (0-1), or
+    /// leave visibility to the pattern with `None`
+    pub fn set_visibility_threshold(&self, threshold: Option<f32>) -> Result<(), DjError> {
+        if let Some(t) = threshold
+            && !(0.0..=1.0).contains(&t)
+        {
+            return Err(DjError::InvalidInput(
+                "Visibility threshold must be between 0 and 1".to_string(),
+            ));
+        }
+        let mut app_state = self.state.lock();
+        app_state.visibility_threshold = threshold;
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        Ok(())
+    }
+
+    /// Hold the sent beat intensity with an instant attack and a release of
+    /// `release_ms`, so it fades between beats instead of strobing; `None`
+    /// sends the raw per-frame intensity
+    pub fn set_beat_release(&self, release_ms: Option<u32>) -> Result<(), DjError> {
+        if let Some(ms) = release_ms
+            && !BEAT_RELEASE_MS_RANGE.contains(&ms)
+        {
+            return Err(DjError::InvalidInput(format!(
+                "Beat release must be between {} and {} ms",
+                BEAT_RELEASE_MS_RANGE.start(),
+                BEAT_RELEASE_MS_RANGE.end()
+            )));
+        }
+        let mut app_state = self.state.lock();
+        app_state.beat_release_ms = release_ms;
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+        Ok(())
+    }
+
+    /// Leave only `band` (0 = bass ... 4 = air) active in the analysis the
+    /// bridge uses, or restore all bands with `None`. Not persisted.
+    pub fn set_solo_band(&self, band: Option<usize>) -> Result<(), DjError> {
+        if band.is_some_and(|b| b >= 5) {
+            return Err(DjError::InvalidInput(
+                "Band must be between 0 and 4".to_string(),
+            ));
+        }
+        self.state.lock().solo_band = band;
+        Ok(())
+    }
+
+    /// Note whether the main window is showing; while hidden the bridge
+    /// emits UI events at `HIDDEN_UI_EMIT_INTERVAL` (frames still go out at
+    /// full rate)
+    pub fn set_window_visible(&self, visible: bool) {
+        let mut app_state = self.state.lock();
+        if app_state.ui_hidden == visible {
+            log::debug!("Main window {}", if visible { "shown" } else { "hidden" });
+            app_state.ui_hidden = !visible;
+        }
+    }
+
+    /// Halve the bridge's frame rate and UI emit rates to save battery
+    pub fn set_low_power(&self, enabled: bool) {
+        let mut app_state = self.state.lock();
+        app_state.low_power = enabled;
+        let settings = Settings::from_state(&app_state);
+        drop(app_state);
+        self.persist_settings(&settings);
+    }
+
+    /// Current capture status
+    pub fn capture_status(&self) -> CaptureStatus {
+        capture_status(&self.state.lock())
+    }
+
+    /// Current audio levels (5 bands + peak + beat info)
+    pub fn audio_levels(&self) -> AudioLevels {
+        audio_levels(&mut self.state.lock())
+    }
+
+    /// Beat detector internals from the last frame, if capturing
+    pub fn beat_debug(&self) -> Option<audio::BeatDebug> {
+        let app_state = self.state.lock();
+        let capture = app_state.audio_capture.as_ref()?;
+        Some(capture.analyzer().lock().beat_debug())
+    }
+
+    /// Time the analyzer on a synthetic signal. Runs on a fresh analyzer
+    /// with the live capture's sample rate and tuning (or the defaults when
+    /// not capturing), so the live capture is never touched. Blocks for the
+    /// whole run; call it off the async runtime.
+    pub fn benchmark_analysis(
+        &self,
+        iterations: usize,
+    ) -> Result<audio::AnalysisBenchmark, DjError> {
+        if !BENCHMARK_ITERATIONS_RANGE.contains(&iterations) {
+            return Err(DjError::InvalidInput(format!(
+                "Iterations must be between {} and {}",
+                BENCHMARK_ITERATIONS_RANGE.start(),
+                BENCHMARK_ITERATIONS_RANGE.end()
+            )));
+        }
+        let analyzer = {
+            let app_state = self.state.lock();
+            match app_state.audio_capture {
+                Some(ref capture) => {
+                    let live = capture.analyzer().lock();
+                    audio::FftAnalyzer::new(audio::AudioConfig {
+                        sample_rate: live.sample_rate(),
+                        fft_size: live.fft_size(),
+                        ..Default::default()
+                    })
+                    .with_tuning_of(&live)
+                }
+                None => audio::FftAnalyzer::new(audio::AudioConfig::default()),
+            }
+        };
+        Ok(audio::benchmark_analysis(analyzer, iterations))
+    }
+
+    /// Patterns loaded into the bridge's Lua engine and the active one
+    pub fn patterns(&self) -> state::PatternList {
+        let app_state = self.state.lock();
+        state::PatternList {
+            loaded: app_state.loaded_patterns.clone(),
+            active: app_state.local_pattern.clone(),
+        }
+    }
+
+    /// Status, levels, voice, preset and capture info under one lock
+    pub fn snapshot(&self) -> AppSnapshot {
+        let mut app_state = self.state.lock();
+        AppSnapshot {
+            audio_levels: audio_levels(&mut app_state),
+            capture_info: capture_status(&app_state),
+            status: app_state.status.clone(),
+            voice_status: app_state.voice_status.clone(),
+            active_preset: app_state.active_preset.name.clone(),
+        }
+    }
+
+    /// Watch the active capture for the lifetime of the engine.
+    ///
+    /// Emits `CaptureMode` whenever a capture session reports a new mode
+    /// (including after falling back from an unplugged device) and
+    /// `CaptureLost` with the reason when the device vanished with no fallback.
+    pub async fn run_capture_monitor(self) {
+        let mut last: Option<(u64, CaptureMode)> = None;
+        loop {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+
+            let (current, source_id) = {
+                let app_state = self.state.lock();
+                (
+                    app_state
+                        .audio_capture
+                        .as_ref()
+                        .map(|c| (c.id(), c.get_capture_mode())),
+                    app_state.audio_source_id.clone(),
+                )
+            };
+            let Some(current) = current else {
+                last = None;
+                continue;
+            };
+            if current.1 == CaptureMode::Pending || last.as_ref() == Some(&current) {
+                continue;
+            }
+
+            log::info!("Capture mode: {:?}", current.1);
+            // Per-app capture that fell back to all system audio
+            self.state.lock().capture_fallback = match &current.1 {
+                CaptureMode::SystemLoopback { fallback_reason } => fallback_reason.clone(),
+                _ => None,
+            };
+            self.emit(EngineEvent::CaptureMode(current.1.clone()));
+            if let CaptureMode::Lost { reason } = &current.1 {
+                self.emit(EngineEvent::CaptureLost(reason.clone()));
+            }
+
+            // Only a source that captured as requested is worth remembering.
+            // Files aren't listed sources, so they couldn't be restored.
+            let succeeded = !matches!(
+                current.1,
+                CaptureMode::Lost { .. }
+                    | CaptureMode::FilePlayback { .. }
+                    | CaptureMode::SystemLoopback {
+                        fallback_reason: Some(_)
+                    }
+            );
+            if succeeded && source_id.is_some() {
+                let settings = {
+                    let mut app_state = self.state.lock();
+                    (app_state.remembered_source_id != source_id).then(|| {
+                        app_state.remembered_source_id = source_id;
+                        Settings::from_state(&app_state)
+                    })
+                };
+                if let Some(settings) = settings {
+                    self.persist_settings(&settings);
+                }
+            }
+            last = Some(current);
+        }
+    }
+}
+
+/// (Re)start the dedicated voice input for the configured device, if any
+fn restart_voice_input(app_state: &mut AppState) -> Result<(), String> {
+    if let Some(input) = app_state.voice_input.take() {
+        input.stop();
+    }
+    let (Some(device), Some(streamer)) = (
+        app_state.voice_config.voice_input_device.clone(),
+        app_state.voice_streamer.clone(),
+    ) else {
+        return Ok(());
+    };
+    app_state.voice_input =
+        Some(VoiceInputHandle::new(device, streamer).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// Bring `capture` in line with the app state: the active preset first,
+/// then the band and beat options on its analyzer, the input gain and
+/// auto-normalize
+fn push_tuning(app_state: &AppState, capture: &AudioCaptureHandle) {
+    let mut analyzer = capture.analyzer().lock();
+    analyzer.apply_preset(&app_state.active_preset);
+    analyzer.set_band_trim(app_state.band_trim);
+    analyzer.set_beat_source(app_state.beat_source);
+    analyzer.set_band_scale(app_state.band_scale, app_state.db_floor);
+    analyzer.set_agc(app_state.agc_enabled);
+    analyzer.set_normalize_across_presets(app_state.normalize_across_presets);
+    analyzer.set_transient_emphasis(app_state.transient_emphasis);
+    analyzer.set_band_edges(app_state.band_edges);
+    drop(analyzer);
+    capture.set_input_gain_db(app_state.input_gain_db);
+    capture.set_auto_normalize(app_state.auto_normalize);
+}
+
+fn capture_status(app_state: &AppState) -> CaptureStatus {
+    let capture_mode = app_state
+        .audio_capture
+        .as_ref()
+        .map(|c| c.get_capture_mode());
+    CaptureStatus {
+        // A lost capture keeps its handle (and mode) until the user picks a new source
+        active: capture_mode
+            .as_ref()
+            .is_some_and(|m| !matches!(m, CaptureMode::Lost { .. })),
+        source_id: app_state.audio_source_id.clone(),
+        capture_mode,
+        mix: app_state
+            .audio_capture
+            .as_ref()
+            .map(|c| c.mix_sources())
+            .unwrap_or_default(),
+        capture_fallback: app_state.capture_fallback.clone(),
+    }
+}
+
+fn audio_levels(app_state: &mut AppState) -> AudioLevels {
+    // Get latest analysis from capture if running
+    if let Some(ref capture) = app_state.audio_capture {
+        let result = capture.get_analysis();
+        app_state.bands = result.bands;
+        app_state.peak = result.peak;
+        app_state.is_beat = result.is_beat;
+        app_state.beat_intensity = result.beat_intensity;
+        app_state.bpm = result.bpm;
+    }
+
+    AudioLevels {
+        bands: app_state.bands,
+        peak: app_state.peak,
+        is_beat: app_state.is_beat,
+        beat_intensity: app_state.beat_intensity,
+        bpm: app_state.bpm,
+        is_paused: app_state
+            .audio_capture
+            .as_ref()
+            .is_some_and(|c| c.is_paused()),
+    }
+}
+
+/// Maximum number of automatic reconnection attempts before giving up.
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+/// Maximum backoff delay between reconnection attempts in seconds.
+const MAX_RECONNECT_DELAY_SECS: u64 = 30;
+/// Random share (±) of each backoff delay, so clients dropped by a server
+/// restart don't all reconnect at the same instant
+const RECONNECT_JITTER: f64 = 0.2;
+/// Longest `disconnect` waits for the bridge to shut the client down.
+const BRIDGE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the input must stay silent before the bridge goes idle.
+const BRIDGE_IDLE_AFTER: Duration = Duration::from_secs(1);
+/// Audio frame interval while idle (keepalive trickle, 2fps).
+const BRIDGE_IDLE_FRAME_INTERVAL: Duration = Duration::from_millis(500);
+/// Accepted range for `set_bridge_fps`.
+pub const BRIDGE_FPS_RANGE: std::ops::RangeInclusive<u32> = 20..=120;
+/// Fastest `audio-levels` emit rate (~30fps); slower bridges emit every tick.
+const AUDIO_EMIT_MIN_INTERVAL: Duration = Duration::from_millis(33);
+/// Throttle for `dj-status`/`voice-status` emits when nothing changed (~4fps).
+const STATUS_EMIT_INTERVAL: Duration = Duration::from_millis(250);
+/// Slowest UI emit rate while the main window is hidden (1fps).
+const HIDDEN_UI_EMIT_INTERVAL: Duration = Duration::from_secs(1);
+/// Least time between `clipping-detected` events while the input keeps clipping.
+const CLIPPING_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+/// How long a reconnect keeps counting against the health score.
+const HEALTH_RECONNECT_WINDOW: Duration = Duration::from_secs(60);
+/// Server effects that also punch a full beat into the next frame.
+const BEAT_EFFECTS: &[&str] = &["flash", "strobe", "pulse", "explode"];
+
+/// Audio-levels and status emit intervals for a bridge ticking every
+/// `tick`: slowed to `HIDDEN_UI_EMIT_INTERVAL` while the window is hidden
+/// and doubled in low-power mode
+fn ui_emit_intervals(tick: Duration, ui_hidden: bool, low_power: bool) -> (Duration, Duration) {
+    let mut audio = AUDIO_EMIT_MIN_INTERVAL.max(tick);
+    let mut status = STATUS_EMIT_INTERVAL;
+    if ui_hidden {
+        audio = audio.max(HIDDEN_UI_EMIT_INTERVAL);
+        status = status.max(HIDDEN_UI_EMIT_INTERVAL);
+    }
+    if low_power {
+        audio *= 2;
+        status *= 2;
+    }
+    (audio, status)
+}
+
+/// Backoff before reconnect attempt `attempt` (from 1): doubling from 1s up
+/// to `MAX_RECONNECT_DELAY_SECS`, moved by `jitter` (-1 to 1) times
+/// `RECONNECT_JITTER` and still capped
+fn reconnect_delay(attempt: u32, jitter: f64) -> Duration {
+    let base = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX)
+        .min(MAX_RECONNECT_DELAY_SECS) as f64;
+    let secs = base * (1.0 + RECONNECT_JITTER * jitter.clamp(-1.0, 1.0));
+    Duration::from_secs_f64(secs.min(MAX_RECONNECT_DELAY_SECS as f64))
+}
+
+/// Bridge task: reads audio analysis and sends frames to VJ server at ~60fps
+/// (see `set_bridge_fps`).
+/// Automatically reconnects with exponential backoff when the connection drops.
+///
+/// After `BRIDGE_IDLE_AFTER` of silence the frame rate drops to a 2fps
+/// keepalive until audio returns; `bridge-idle` is emitted on each change.
+async fn run_bridge(
+    engine: McavEngine,
+    mut shutdown_rx: mpsc::Receiver<()>,
+    mut control_rx: mpsc::Receiver<BridgeControl>,
+) {
+    let state_arc = &engine.state;
+    let mut reconnect_count: u32 = 0;
+    let mut reconnected_at: Option<Instant> = None;
+
+    'reconnect: loop {
+        let mut tick = state_arc.lock().bridge_tick;
+        let mut interval = tokio::time::interval(tick);
+        // Direct MC publish is disabled: the VJ server's pattern engine handles
+        // all zones (multi-zone, transitions, crossfades). The DJ client sends
+        // audio frames to the VJ server which relays to Minecraft authoritatively.
+        let mut last_phase_predicted_beat_at = 0.0_f64;
+        let mut tempo_tracker = state::TempoTracker::default();
+        let mut beat_envelope = state::BeatEnvelope::default();
+        // Requested count last warned about, so a clamp is reported once
+        let mut clamp_warned: Option<u32> = None;
+        let mut last_clipping_warning: Option<Instant> = None;
+        let mut pattern_engine: Option<patterns::PatternEngine> = None;
+        // Set when Lua fails to start, so the bridge stops retrying and
+        // leaves the entities to the VJ server
+        let mut pattern_engine_failed = false;
+        // Throttle UI events: audio-levels ~30fps, status/voice ~4fps
+        let mut last_audio_emit = Instant::now() - Duration::from_secs(1);
+        let mut last_status_emit = Instant::now() - Duration::from_secs(1);
+        let mut pending_voice_drops: u64 = 0;
+        let mut prev_status_hash: u64 = 0;
+        let mut prev_voice_hash: u64 = 0;
+        // Idle detection: when silence started, and when the last frame went out
+        let mut silent_since: Option<Instant> = None;
+        let mut bridge_idle = false;
+        let mut last_frame_sent = Instant::now() - Duration::from_secs(1);
+        let mut stats_window = state::BridgeStatsWindow::new(Instant::now());
+        // Track whether this iteration exited due to explicit shutdown
+        let mut shutdown_requested = false;
+
+        log::info!("Bridge task started (reconnect #{})", reconnect_count);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => {
+                    log::info!("Bridge task received shutdown signal");
+                    shutdown_requested = true;
+                    break;
+                }
+                Some(control) = control_rx.recv() => {
+                    // Only the reconnect backoff acts on these
+                    log::debug!("Ignoring {:?} while connected", control);
+                }
+                _ = interval.tick() => {
+                    // 1. Read audio analysis + VJ sender + connection state (brief lock)
+                    let tick_start = Instant::now();
+                    let (mut analysis, tx, conn_state_opt, beat_assist, bridge_tick, forced_beat, osc, analysis_server, ui_hidden, low_power, solo, beat_release_ms) = {
+                        let mut app_state = state_arc.lock();
+                        stats_window.record_tick(tick_start, tick_start.elapsed());
+                        let analysis = app_state.audio_capture.as_ref()
+                            .map(|c| c.get_analysis());
+                        // A manual beat waits for a frame that can carry it
+                        let forced_beat = analysis.as_ref()
+                            .and_then(|_| app_state.forced_beat.take());
+                        let tx = app_state.client.as_ref()
+                            .and_then(|c| c.get_tx_clone());
+                        let conn_state = app_state.client.as_ref()
+                            .map(|c| c.get_state());
+                        // Low power halves the frame rate too
+                        let bridge_tick = if app_state.low_power {
+                            app_state.bridge_tick * 2
+                        } else {
+                            app_state.bridge_tick
+                        };
+                        (analysis, tx, conn_state, app_state.beat_assist, bridge_tick, forced_beat,
+                         app_state.osc_sender.clone(), app_state.analysis_server.clone(),
+                         app_state.ui_hidden, app_state.low_power, app_state.solo_band,
+                         app_state.beat_release_ms)
+                    };
+
+                    // Manual beat: land it on this frame as if the analyzer
+                    // had detected it
+                    if let (Some(intensity), Some(analysis)) = (forced_beat, analysis.as_mut()) {
+                        analysis.is_beat = true;
+                        analysis.beat_intensity = analysis.beat_intensity.max(intensity);
+                    }
+                    // Solo: one band drives the frames, patterns and UI alike
+                    if let (Some(band), Some(analysis)) = (solo, analysis.as_mut()) {
+                        state::solo_band(analysis, band);
+                    }
+                    // Lock dropped
+
+                    // Frame rate changed via set_bridge_fps or low power
+                    if bridge_tick != tick {
+                        tick = bridge_tick;
+                        interval = tokio::time::interval(tick);
+                    }
+
+                    // If no client tx, connection is lost
+                    let tx = match tx {
+                        Some(tx) => tx,
+                        None => {
+                            // Client disconnected externally
+                            let mut app_state = state_arc.lock();
+                            app_state.status.connected = false;
+                            app_state.status.error = Some("Connection lost".to_string());
+                            break;
+                        }
+                    };
+                    let conn_state = match conn_state_opt {
+                        Some(s) => s,
+                        None => {
+                            let mut app_state = state_arc.lock();
+                            app_state.status.connected = false;
+                            app_state.status.error = Some("Connection lost".to_string());
+                            break;
+                        }
+                    };
+
+                    // 2. Send audio frame if we have analysis data
+                    // Idle while silent: only a keepalive frame every 500ms
+                    let is_silent = analysis.as_ref().is_some_and(|a| a.is_silent);
+                    silent_since = if is_silent { silent_since.or(Some(Instant::now())) } else { None };
+                    let idle_now = silent_since.is_some_and(|t| t.elapsed() >= BRIDGE_IDLE_AFTER);
+                    if idle_now != bridge_idle {
+                        bridge_idle = idle_now;
+                        log::info!("Bridge {}", if idle_now { "idle (input silent)" } else { "active" });
+                        engine.emit(EngineEvent::BridgeIdle(idle_now));
+                    }
+                    let send_frame = !bridge_idle
+                        || forced_beat.is_some()
+                        || last_frame_sent.elapsed() >= BRIDGE_IDLE_FRAME_INTERVAL;
+                    // No capture yet: a zeroed frame at the idle keepalive rate
+                    // stops the server timing the DJ out before start_capture
+                    let keepalive = (analysis.is_none()
+                        && last_frame_sent.elapsed() >= BRIDGE_IDLE_FRAME_INTERVAL)
+                        .then(AnalysisResult::default);
+                    if analysis.is_none() && keepalive.is_none() {
+                        stats_window.counts.skipped_no_analysis += 1;
+                    } else if !send_frame {
+                        stats_window.counts.skipped_idle += 1;
+                    }
+
+                    // Hoist beat output vars for use in UI event emission (section 3)
+                    let mut out_is_beat = analysis.as_ref().is_some_and(|a| a.is_beat);
+                    let mut out_beat_intensity = analysis.as_ref().map_or(0.0, |a| a.beat_intensity);
+                    if let Some(analysis) = analysis.as_ref().or(keepalive.as_ref())
+                        && send_frame {
+                        let seq = FRAME_SEQ.fetch_add(1, Ordering::Relaxed);
+                        let now_secs = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs_f64();
+
+                        (out_is_beat, out_beat_intensity) =
+                            beat_assist.apply(analysis, now_secs, &mut last_phase_predicted_beat_at);
+                        if let Some(release_ms) = beat_release_ms {
+                            out_beat_intensity = beat_envelope.apply(
+                                out_beat_intensity,
+                                Duration::from_millis(release_ms as u64),
+                                Instant::now(),
+                            );
+                        }
+
+                        let mut msg = AudioFrameMessage::new(
+                            seq,
+                            analysis.bands,
+                            analysis.peak,
+                            out_is_beat,
+                            out_beat_intensity,
+                            analysis.bpm,
+                            analysis.tempo_confidence,
+                            analysis.beat_phase,
+                            analysis.instant_bass,
+                            analysis.instant_kick,
+                        );
+                        msg.sub_bass = analysis.sub_bass;
+                        // Mirror the local pattern's entities so the browser
+                        // preview renders exactly what Minecraft gets
+                        if conn_state.mirror_entities
+                            && let Some(ref engine) = pattern_engine {
+                            msg.entities = Some(engine.calculate_entities(analysis, seq));
+                        }
+                        // A triggered effect is seen by one frame only
+                        if let Some(ref mut engine) = pattern_engine {
+                            engine.set_effect(None);
+                        }
+
+                        if let Ok(json) = serde_json::to_string(&msg) {
+                            match tx.try_send(Message::Text(json.into())) {
+                                Ok(()) => {
+                                    last_frame_sent = Instant::now();
+                                    stats_window.counts.vj_frames_sent += 1;
+                                }
+                                Err(mpsc::error::TrySendError::Full(_)) => {
+                                    // Channel full — drop this frame rather than
+                                    // stalling the bridge loop. The consumer will
+                                    // catch up on the next tick.
+                                    log::debug!("Audio frame dropped (send channel full)");
+                                    stats_window.counts.vj_frames_dropped += 1;
+                                }
+                                Err(mpsc::error::TrySendError::Closed(_)) => {
+                                    log::error!("Failed to send audio frame - channel closed");
+                                    let mut app_state = state_arc.lock();
+                                    app_state.status.connected = false;
+                                    app_state.status.error = Some("Connection lost".to_string());
+                                    break;
+                                }
+                            }
+                        }
+
+                    }
+
+                    if out_is_beat && let Some(ref analysis) = analysis {
+                        engine.emit(EngineEvent::Beat(BeatEvent {
+                            intensity: out_beat_intensity,
+                            bpm: analysis.bpm,
+                            beat_phase: analysis.beat_phase,
+                            ts: std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs_f64(),
+                        }));
+                    }
+
+                    if let Some(change) = analysis.as_ref().and_then(|a| tempo_tracker.update(a)) {
+                        log::info!("Tempo changed: {:.1} -> {:.1} BPM", change.old_bpm, change.new_bpm);
+                        engine.emit(EngineEvent::TempoChanged(change));
+                    }
+
+                    if analysis.as_ref().is_some_and(|a| a.clipping)
+                        && last_clipping_warning.is_none_or(|t| t.elapsed() >= CLIPPING_WARNING_INTERVAL)
+                    {
+                        let input_gain_db = state_arc.lock().input_gain_db;
+                        log::warn!("Input is clipping at {:+.1} dB gain; try lowering the input gain", input_gain_db);
+                        engine.emit(EngineEvent::ClippingDetected(ClippingWarning { input_gain_db }));
+                        last_clipping_warning = Some(Instant::now());
+                    }
+
+                    // OSC gets the same beats the VJ server does, at its own rate
+                    if let (Some(osc), Some(analysis)) = (&osc, &analysis) {
+                        osc.send(analysis, out_is_beat, out_beat_intensity);
+                    }
+                    if let (Some(server), Some(analysis)) = (&analysis_server, &analysis) {
+                        server.publish(&AnalysisResult {
+                            is_beat: out_is_beat,
+                            beat_intensity: out_beat_intensity,
+                            ..analysis.clone()
+                        });
+                    }
+
+                    // 2.5 Send voice audio frames if streaming is enabled
+                    {
+                        let voice_streamer = {
+                            let app_state = state_arc.lock();
+                            app_state.voice_streamer.clone()
+                        };
+
+                        if let Some(ref streamer) = voice_streamer
+                            && streamer.is_enabled() {
+                                // Paced by queue depth: ~1 frame per tick normally,
+                                // more while catching up after a stall
+                                for frame in streamer.drain_frames_paced() {
+                                    let voice_msg = protocol::VoiceAudioMessage::new(
+                                        frame.data, frame.seq, frame.codec, frame.duck, frame.channels,
+                                    );
+                                    if let Ok(json) = serde_json::to_string(&voice_msg) {
+                                        match tx.try_send(Message::Text(json.into())) {
+                                            Ok(()) => {}
+                                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                                log::debug!("Voice frame dropped (send channel full)");
+                                                break;
+                                            }
+                                            Err(mpsc::error::TrySendError::Closed(_)) => {
+                                                log::error!("Failed to send voice frame - channel closed");
+                                                break;
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                    }
+
+                    // 3. Update connection state from DjClient (brief lock, no events)
+                    let (status_snapshot, voice_snapshot, preset_changed, roster_update, effects, server_gone, entity_clamp, pattern_failure) = {
+                        let mut app_state = state_arc.lock();
+                        // Report mc_connected=false so VJ server always relays to MC
+                        if let Some(ref client) = app_state.client {
+                            client.set_mc_connected(false);
+                        }
+
+                        // Check for preset_sync from server
+                        let mut preset_event: Option<String> = None;
+                        if let Some(ref client) = app_state.client
+                            && let Some(preset) = client.take_pending_preset() {
+                                if let Some(ref capture) = app_state.audio_capture {
+                                    capture.analyzer().lock().apply_preset(&preset);
+                                }
+                                preset_event = Some(preset.name.clone());
+                                app_state.active_preset = preset;
+                            }
+
+                        // Consume pending pattern data from server
+                        let mut pattern_failure = None;
+                        if let Some(ref client) = app_state.client {
+                            // Load pattern scripts
+                            if let Some(scripts) = client.take_pending_pattern_scripts() {
+                                if pattern_engine.is_none() && !pattern_engine_failed {
+                                    match patterns::PatternEngine::try_new() {
+                                        Ok(engine) => pattern_engine = Some(engine),
+                                        Err(e) => {
+                                            pattern_engine_failed = true;
+                                            pattern_failure = Some(e);
+                                        }
+                                    }
+                                }
+                                if let Some(ref mut engine) = pattern_engine {
+                                    // Look for lib script first
+                                    if let Some(lib_src) = scripts.get("lib")
+                                        && let Err(e) = engine.load_lib(lib_src) {
+                                            log::warn!("Failed to load lib.lua: {}", e);
+                                        }
+                                    for (name, src) in &scripts {
+                                        if name != "lib" {
+                                            engine.load_pattern(name, src);
+                                        }
+                                    }
+                                    log::info!("Loaded {} pattern scripts from server", scripts.len());
+                                    app_state.loaded_patterns = engine.loaded_pattern_names();
+                                }
+                            }
+
+                            // Switch pattern
+                            if let Some(pattern_name) = client.take_pending_pattern_change()
+                                && let Some(ref mut engine) = pattern_engine
+                                    && let Err(e) = engine.set_pattern(&pattern_name) {
+                                        log::warn!("Failed to switch pattern: {}", e);
+                                    }
+
+                            // Update band sensitivity
+                            if let Some(sensitivity) = client.take_pending_band_sensitivity()
+                                && let Some(ref mut engine) = pattern_engine {
+                                    engine.set_band_sensitivity(sensitivity);
+                                }
+
+                            // The entity count from config_sync also lands in
+                            // mc_entity_count, applied below under the local cap
+                            let _ = client.take_pending_config_change();
+                        }
+                        if let Some(ref mut engine) = pattern_engine {
+                            engine.set_visibility_threshold(app_state.visibility_threshold);
+                        }
+                        app_state.local_pattern = pattern_engine.as_ref()
+                            .map(|e| e.current_pattern().to_string())
+                            .filter(|name| !name.is_empty());
+
+                        // Consume pending DJ roster
+                        let roster = app_state.client.as_ref()
+                            .and_then(|c| c.take_pending_dj_roster());
+
+                        // Effects from the VJ panel: hand the latest to the
+                        // pattern and land hit-style ones as a full beat on
+                        // the next frame
+                        let mut effects = app_state.client.as_ref()
+                            .map(|c| c.take_pending_effects())
+                            .unwrap_or_default();
+                        effects.append(&mut app_state.local_effects);
+                        if let Some(effect) = effects.last() {
+                            if let Some(ref mut engine) = pattern_engine {
+                                engine.set_effect(Some(effect.clone()));
+                            }
+                            if effects.iter().any(|e| BEAT_EFFECTS.contains(&e.as_str())) {
+                                app_state.forced_beat = Some(1.0);
+                            }
+                        }
+
+                        let mut server_gone = false;
+                        let mut entity_clamp = None;
+                        if let Some(ref client) = app_state.client {
+                            let latest = client.get_state();
+                            app_state.status.is_active = latest.is_active;
+                            app_state.status.latency_ms = latest.latency_ms;
+                            app_state.status.server_time_offset_ms = latest.clock.offset_ms();
+                            app_state.status.route_mode = latest.route_mode;
+                            app_state.status.queue_position = latest.queue_position;
+                            app_state.status.total_djs = latest.total_djs;
+                            app_state.status.active_dj_name = latest.active_dj_name;
+                            app_state.status.mc_connected = false;
+                            let effective = latest
+                                .mc_entity_count
+                                .map(|n| state::effective_entity_count(n, app_state.max_entity_count));
+                            app_state.status.requested_entity_count = latest.mc_entity_count;
+                            app_state.status.effective_entity_count = effective;
+                            if let (Some(count), Some(engine)) = (effective, pattern_engine.as_mut())
+                                && engine.config().entity_count != count
+                            {
+                                engine.set_config(patterns::PatternConfig {
+                                    entity_count: count,
+                                    ..Default::default()
+                                });
+                            }
+                            match (latest.mc_entity_count, effective) {
+                                (Some(requested), Some(effective)) if requested > effective => {
+                                    if clamp_warned != Some(requested) {
+                                        clamp_warned = Some(requested);
+                                        entity_clamp = Some(state::EntityCountClamp { requested, effective });
+                                    }
+                                }
+                                _ => clamp_warned = None,
+                            }
+                            if !latest.connected {
+                                app_state.status.connected = false;
+                                app_state.status.error = Some("Server disconnected".to_string());
+                                server_gone = true;
+                            }
+                            let stats = &app_state.bridge_stats;
+                            let frames = stats.vj_frames_sent + stats.vj_frames_dropped;
+                            let drop_ratio = if frames > 0 {
+                                stats.vj_frames_dropped as f32 / frames as f32
+                            } else {
+                                0.0
+                            };
+                            app_state.status.health = if app_state.status.connected {
+                                state::health_score(
+                                    latest.latency_ms,
+                                    latest.latency_jitter_ms,
+                                    drop_ratio,
+                                    reconnected_at.is_some_and(|t| t.elapsed() < HEALTH_RECONNECT_WINDOW),
+                                )
+                            } else {
+                                0
+                            };
+
+                            // Sync voice status from server messages
+                            app_state.voice_status.available = latest.voice_available;
+                            app_state.voice_status.streaming = latest.voice_streaming;
+                            if let Some(ref ct) = latest.voice_channel_type {
+                                app_state.voice_status.channel_type = ct.clone();
+                            }
+                            if let Some(players) = latest.voice_connected_players {
+                                app_state.voice_status.connected_players = players;
+                            }
+                        }
+
+                        let (voice_level, voice_queue_depth, voice_drops, voice_codec) = app_state
+                            .voice_streamer
+                            .as_ref()
+                            .map_or((0.0, 0, 0, None), |s| {
+                                (s.current_level(), s.queue_depth() as u32, s.take_dropped_frames(), Some(s.codec()))
+                            });
+                        // Switching to stereo recreates the encoder, which can fall back to PCM
+                        if let Some(codec) = voice_codec
+                            && app_state.voice_status.codec != codec
+                        {
+                            app_state.voice_status.codec = codec.to_string();
+                        }
+                        app_state.voice_status.level = voice_level;
+                        app_state.voice_status.queue_depth = voice_queue_depth;
+                        pending_voice_drops += voice_drops;
+
+                        // Clone data for events — lock is released after this block
+                        (app_state.status.clone(), app_state.voice_status.clone(), preset_event, roster, effects, server_gone, entity_clamp, pattern_failure)
+                    };
+                    // state_arc lock dropped — emit events without holding any lock
+
+                    if let Some(ref preset_name) = preset_changed {
+                        engine.emit(EngineEvent::PresetChanged(preset_name.clone()));
+                    }
+
+                    // Emit DJ roster if available
+                    if let Some(ref roster) = roster_update {
+                        engine.emit(EngineEvent::DjRoster(roster.clone()));
+                    }
+
+                    for effect in effects {
+                        engine.emit(EngineEvent::EffectTriggered(effect));
+                    }
+
+                    if let Some(clamp) = entity_clamp {
+                        log::warn!(
+                            "Server asked for {} entities; rendering {} (local cap)",
+                            clamp.requested,
+                            clamp.effective
+                        );
+                        engine.emit(EngineEvent::EntityCountClamped(clamp));
+                    }
+
+                    if let Some(error) = pattern_failure {
+                        log::warn!("Pattern engine unavailable, leaving entities to the server: {}", error);
+                        engine.emit(EngineEvent::PatternEngineFailed(error));
+                    }
+
+                    // The socket closed: reconnect now rather than waiting for a
+                    // frame send to fail (nothing is sent without capture)
+                    if server_gone {
+                        engine.emit(EngineEvent::DjStatus(status_snapshot));
+                        break;
+                    }
+
+                    // Bridge stats: once per second
+                    if let Some(stats) = stats_window.finish(Instant::now()) {
+                        state_arc.lock().bridge_stats = stats.clone();
+                        engine.emit(EngineEvent::BridgeStats(stats));
+                    }
+
+                    // Audio levels: emit at ~30fps (or every tick on slower bridges),
+                    // but always emit immediately on beat unless nobody can see it
+                    let (audio_emit_interval, status_emit_interval) =
+                        ui_emit_intervals(tick, ui_hidden, low_power);
+                    if let Some(ref analysis) = analysis {
+                        let is_beat_frame = (analysis.is_beat || out_is_beat) && !ui_hidden;
+                        if is_beat_frame || last_audio_emit.elapsed() >= audio_emit_interval {
+                            engine.emit(EngineEvent::AudioLevels(AudioLevels {
+                                bands: analysis.bands,
+                                peak: analysis.peak,
+                                is_beat: out_is_beat,
+                                beat_intensity: out_beat_intensity,
+                                bpm: analysis.bpm,
+                                is_paused: analysis.is_paused,
+                            }));
+                            last_audio_emit = Instant::now();
+                        }
+                    }
+
+                    // Status + voice: emit at ~4fps (slower while hidden or in low
+                    // power) OR immediately on change
+                    {
+                        use std::hash::{Hash, Hasher};
+                        let mut h = std::collections::hash_map::DefaultHasher::new();
+                        format!("{:?}", status_snapshot).hash(&mut h);
+                        let s_hash = h.finish();
+
+                        // The mic level and queue depth change every tick, so leave
+                        // them out of the change check and let them ride the throttle
+                        let mut h2 = std::collections::hash_map::DefaultHasher::new();
+                        format!(
+                            "{:?}",
+                            VoiceStatus { level: 0.0, queue_depth: 0, ..voice_snapshot.clone() }
+                        )
+                        .hash(&mut h2);
+                        let v_hash = h2.finish();
+
+                        let status_changed = s_hash != prev_status_hash;
+                        let voice_changed = v_hash != prev_voice_hash;
+                        let throttle_elapsed = last_status_emit.elapsed() >= status_emit_interval;
+
+                        if status_changed || throttle_elapsed {
+                            engine.emit(EngineEvent::DjStatus(status_snapshot.clone()));
+                            prev_status_hash = s_hash;
+                        }
+                        if voice_changed || throttle_elapsed {
+                            engine.emit(EngineEvent::VoiceStatus(voice_snapshot.clone()));
+                            prev_voice_hash = v_hash;
+                        }
+                        if throttle_elapsed && pending_voice_drops > 0 {
+                            log::warn!("Voice queue overrun: {} frames dropped", pending_voice_drops);
+                            engine.emit(EngineEvent::VoiceOverrun(VoiceOverrun {
+                                dropped_frames: pending_voice_drops,
+                                queue_depth: voice_snapshot.queue_depth,
+                            }));
+                            pending_voice_drops = 0;
+                        }
+                        if status_changed || voice_changed || throttle_elapsed {
+                            last_status_emit = Instant::now();
+                        }
+                    }
+                }
+            }
+        }
+
+        // Cleanup current connection
+        log::info!("Bridge task cleaning up");
+        let client = {
+            let mut app_state = state_arc.lock();
+            app_state.client.take()
+        };
+        if let Some(client) = client {
+            let _ = client.disconnect().await;
+        }
+        {
+            let mut app_state = state_arc.lock();
+            app_state.status.connected = false;
+            app_state.status.mc_connected = false;
+            app_state.bridge_stats = state::BridgeStats::default();
+            app_state.local_pattern = None;
+            app_state.loaded_patterns.clear();
+        }
+
+        // If shutdown was explicitly requested, do not reconnect
+        if shutdown_requested {
+            let mut app_state = state_arc.lock();
+            app_state.bridge_shutdown_tx = None;
+            app_state.bridge_control_tx = None;
+            app_state.bridge_task_handle = None;
+            log::info!("Bridge task stopped (user disconnect)");
+            break 'reconnect;
+        }
+
+        // Auto-reconnect with exponential backoff
+        reconnect_count += 1;
+        if reconnect_count > MAX_RECONNECT_ATTEMPTS {
+            let mut app_state = state_arc.lock();
+            app_state.bridge_shutdown_tx = None;
+            app_state.bridge_control_tx = None;
+            app_state.bridge_task_handle = None;
+            app_state.status.error = Some("Connection lost (max retries reached)".to_string());
+            engine.emit(EngineEvent::DjStatus(app_state.status.clone()));
+            log::error!(
+                "Bridge task gave up after {} reconnect attempts",
+                MAX_RECONNECT_ATTEMPTS
+            );
+            break 'reconnect;
+        }
+
+        let delay = reconnect_delay(reconnect_count, rand::random_range(-1.0..=1.0));
+        log::info!(
+            "Reconnecting in {:.1}s (attempt {}/{})",
+            delay.as_secs_f64(),
+            reconnect_count,
+            MAX_RECONNECT_ATTEMPTS
+        );
+        {
+            let mut app_state = state_arc.lock();
+            app_state.status.error = Some(format!(
+                "Reconnecting in {:.1}s ({}/{})",
+                delay.as_secs_f64(),
+                reconnect_count,
+                MAX_RECONNECT_ATTEMPTS
+            ));
+            engine.emit(EngineEvent::DjStatus(app_state.status.clone()));
+        }
+
+        // Wait for backoff delay, a reconnect control or shutdown signal
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            Some(control) = control_rx.recv() => match control {
+                BridgeControl::ReconnectNow => {
+                    log::info!("Reconnecting now (backoff skipped)");
+                }
+                BridgeControl::CancelReconnect => {
+                    let mut app_state = state_arc.lock();
+                    app_state.bridge_shutdown_tx = None;
+                    app_state.bridge_control_tx = None;
+                    app_state.bridge_task_handle = None;
+                    app_state.status.error = None;
+                    engine.emit(EngineEvent::DjStatus(app_state.status.clone()));
+                    log::info!("Bridge task stopped during reconnect backoff (reconnect cancelled)");
+                    break 'reconnect;
+                }
+            },
+            _ = shutdown_rx.recv() => {
+                let mut app_state = state_arc.lock();
+                app_state.bridge_shutdown_tx = None;
+                app_state.bridge_control_tx = None;
+                app_state.bridge_task_handle = None;
+                log::info!("Bridge task stopped during reconnect backoff (user disconnect)");
+                break 'reconnect;
+            }
+        }
+
+        // Attempt to reconnect using stored config
+        let reconnect_result = {
+            let app_state = state_arc.lock();
+
+            DjClientConfig {
+                server_host: app_state.server_host.clone(),
+                server_port: app_state.server_port,
+                dj_name: app_state.dj_name.clone(),
+                connect_code: app_state.connect_code.clone(),
+                auth_token: app_state.auth_token.clone(),
+                dj_id: Some(format!("tauri_dj_{:08x}", rand::random::<u32>())),
+                dj_key: if app_state.connect_code.is_none() {
+                    Some(String::new())
+                } else {
+                    None
+                },
+                ..Default::default()
+            }
+        };
+
+        let mut client = DjClient::new(reconnect_result);
+        match client.connect().await {
+            Ok(()) => {
+                let mut app_state = state_arc.lock();
+                app_state.client = Some(client);
+                app_state.status.connected = true;
+                app_state.status.error = None;
+                engine.emit(EngineEvent::DjStatus(app_state.status.clone()));
+                log::info!("Reconnected successfully");
+                reconnect_count = 0;
+                reconnected_at = Some(Instant::now());
+                continue 'reconnect;
+            }
+            Err(e) => {
+                log::warn!("Reconnect failed: {}", e);
+                continue 'reconnect;
+            }
+        }
+    } // end 'reconnect loop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_serialize_with_tauri_event_names() {
+        let json = serde_json::to_value(EngineEvent::BridgeIdle(true)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"event": "bridge-idle", "payload": true})
+        );
+
+        let json = serde_json::to_value(EngineEvent::PresetChanged("edm".into())).unwrap();
+        assert_eq!(json["event"], "preset-changed");
+
+        let json = serde_json::to_value(EngineEvent::Beat(BeatEvent {
+            intensity: 0.8,
+            bpm: 128.0,
+            beat_phase: 0.0,
+            ts: 1.0,
+        }))
+        .unwrap();
+        assert_eq!(json["event"], "beat");
+        assert_eq!(json["payload"]["bpm"], 128.0);
+    }
+
+    #[test]
+    fn trigger_beat_needs_a_running_capture() {
+        let engine = McavEngine::new(None);
+        assert_eq!(engine.trigger_beat(0.9), Err(DjError::CaptureNotRunning));
+        assert_eq!(engine.state().lock().forced_beat, None);
+    }
+
+    #[test]
+    fn band_scale_floor_is_clamped_and_kept() {
+        let engine = McavEngine::new(None);
+        assert_eq!(
+            engine.set_band_scale(audio::BandScale::Db, Some(-200.0)),
+            -120.0
+        );
+        assert_eq!(engine.set_band_scale(audio::BandScale::Db, None), -120.0);
+        assert_eq!(engine.state().lock().band_scale, audio::BandScale::Db);
+    }
+
+    #[test]
+    fn set_band_edges_validates_before_storing() {
+        let engine = McavEngine::new(None);
+        assert!(matches!(
+            engine.set_band_edges([40.0, 30.0, 500.0, 2000.0, 6000.0, 20000.0]),
+            Err(DjError::InvalidInput(_))
+        ));
+        assert!(
+            engine
+                .set_band_edges([40.0, 250.0, 500.0, 2000.0, 6000.0, 30000.0])
+                .is_err()
+        );
+        engine
+            .set_band_edges([20.0, 90.0, 250.0, 2000.0, 8000.0, 20000.0])
+            .unwrap();
+        assert_eq!(engine.state().lock().band_edges[1], 90.0);
+    }
+
+    #[test]
+    fn fire_effect_queues_for_the_bridge() {
+        let engine = McavEngine::new(None);
+        assert!(matches!(
+            engine.fire_effect("  "),
+            Err(DjError::InvalidInput(_))
+        ));
+        for _ in 0..MAX_LOCAL_EFFECTS + 4 {
+            engine.fire_effect(" strobe ").unwrap();
+        }
+        let queued = engine.state().lock().local_effects.clone();
+        assert_eq!(queued.len(), MAX_LOCAL_EFFECTS);
+        assert_eq!(queued[0], "strobe");
+    }
+
+    #[test]
+    fn set_preset_updates_state_without_capture() {
+        let engine = McavEngine::new(None);
+        let name = audio::get_presets()[0].name.clone();
+        assert_eq!(engine.set_preset(&name).unwrap(), name);
+        assert_eq!(engine.state().lock().active_preset.name, name);
+        assert!(engine.set_preset("no-such-preset").is_err());
+    }
+
+    #[test]
+    fn step_preset_wraps_around_the_list() {
+        let engine = McavEngine::new(None);
+        let names: Vec<String> = audio::get_presets().into_iter().map(|p| p.name).collect();
+        let last = names.len() - 1;
+
+        assert_eq!(engine.set_preset_by_index(0).unwrap(), names[0]);
+        assert_eq!(engine.step_preset(-1).unwrap(), names[last]);
+        assert_eq!(engine.step_preset(1).unwrap(), names[0]);
+        assert_eq!(engine.step_preset(1).unwrap(), names[1]);
+        assert!(engine.set_preset_by_index(names.len()).is_err());
+        assert_eq!(engine.state().lock().active_preset.name, names[1]);
+
+        engine.state().lock().active_preset.name = "custom".to_string();
+        assert_eq!(engine.step_preset(1).unwrap(), names[0]);
+        engine.state().lock().active_preset.name = "custom".to_string();
+        assert_eq!(engine.step_preset(-1).unwrap(), names[last]);
+    }
+
+    #[test]
+    fn band_trim_is_clamped_and_kept_across_presets() {
+        let engine = McavEngine::new(None);
+        assert_eq!(
+            engine.set_band_trim([0.8, 3.0, 1.0, -1.0, 1.0]),
+            [0.8, 2.0, 1.0, 0.0, 1.0]
+        );
+        engine.set_preset("edm").unwrap();
+        assert_eq!(engine.state().lock().band_trim[0], 0.8);
+    }
+
+    #[test]
+    fn set_bridge_fps_rejects_out_of_range() {
+        let engine = McavEngine::new(None);
+        assert!(engine.set_bridge_fps(10).is_err());
+        engine.set_bridge_fps(30).unwrap();
+        let tick = engine.state().lock().bridge_tick;
+        assert!((tick.as_secs_f64() - 1.0 / 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn capabilities_match_the_build() {
+        let caps = Capabilities::detect();
+        assert_eq!(caps.opus, cfg!(feature = "voice-opus"));
+        assert_eq!(caps.platform, std::env::consts::OS);
+        if !cfg!(target_os = "windows") {
+            assert!(!caps.process_loopback);
+        }
+    }
+
+    #[test]
+    fn reconnect_delay_jitters_within_the_cap() {
+        assert_eq!(reconnect_delay(1, 0.0), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(4, 0.0), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(4, 1.0), Duration::from_secs_f64(9.6));
+        assert_eq!(reconnect_delay(4, -1.0), Duration::from_secs_f64(6.4));
+        // Capped before and after the jitter
+        assert_eq!(reconnect_delay(10, 0.0), Duration::from_secs(30));
+        assert_eq!(reconnect_delay(10, 1.0), Duration::from_secs(30));
+        assert_eq!(reconnect_delay(10, -1.0), Duration::from_secs(24));
+        assert_eq!(reconnect_delay(100, 0.0), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn ui_emits_slow_down_while_hidden_and_in_low_power() {
+        let tick = Duration::from_millis(16);
+        assert_eq!(
+            ui_emit_intervals(tick, false, false),
+            (AUDIO_EMIT_MIN_INTERVAL, STATUS_EMIT_INTERVAL)
+        );
+        assert_eq!(
+            ui_emit_intervals(tick, true, false),
+            (HIDDEN_UI_EMIT_INTERVAL, HIDDEN_UI_EMIT_INTERVAL)
+        );
+        assert_eq!(
+            ui_emit_intervals(tick, false, true),
+            (AUDIO_EMIT_MIN_INTERVAL * 2, STATUS_EMIT_INTERVAL * 2)
+        );
+        // Slow bridges still emit every tick
+        let slow = Duration::from_millis(50);
+        assert_eq!(ui_emit_intervals(slow, false, false).0, slow);
+    }
+
+    #[test]
+    fn import_setup_applies_and_clamps() {
+        let engine = McavEngine::new(None);
+        let mut setup = engine.export_setup();
+        setup.preset.name = "my techno".to_string();
+        setup.input_gain_db = 60.0;
+        setup.band_trim = [1.2, 1.0, 1.0, 1.0, 0.9];
+        setup.transient_emphasis = 1.5;
+        engine.import_setup(setup.clone()).unwrap();
+        {
+            let app_state = engine.state().lock();
+            assert_eq!(app_state.active_preset.name, "my techno");
+            assert_eq!(app_state.input_gain_db, *INPUT_GAIN_DB_RANGE.end());
+            assert_eq!(app_state.band_trim, [1.2, 1.0, 1.0, 1.0, 0.9]);
+            assert_eq!(app_state.transient_emphasis, 1.5);
+        }
+
+        setup.band_trim = [5.0, -1.0, 1.0, 1.0, 1.0];
+        setup.transient_emphasis = 10.0;
+        engine.import_setup(setup.clone()).unwrap();
+        {
+            let app_state = engine.state().lock();
+            assert_eq!(app_state.band_trim, [2.0, 0.0, 1.0, 1.0, 1.0]);
+            assert_eq!(
+                app_state.transient_emphasis,
+                *TRANSIENT_EMPHASIS_RANGE.end()
+            );
+        }
+
+        setup.band_edges = [100.0, 50.0, 250.0, 2000.0, 6000.0, 20000.0];
+        assert!(matches!(
+            engine.import_setup(setup.clone()),
+            Err(DjError::InvalidInput(_))
+        ));
+        setup.band_edges = engine.state().lock().band_edges;
+        setup.preset.name = " ".to_string();
+        assert!(engine.import_setup(setup).is_err());
+    }
+
+    #[test]
+    fn imported_setup_exports_unchanged_without_capture() {
+        let engine = McavEngine::new(None);
+        let mut setup = engine.export_setup();
+        setup.preset.name = "my techno".to_string();
+        setup.preset.beat_threshold = 1.7;
+        setup.preset.band_release = Some([0.05, 0.1, 0.1, 0.15, 0.2]);
+        setup.band_trim = [0.9, 1.0, 1.1, 1.0, 1.0];
+        engine.import_setup(setup.clone()).unwrap();
+        assert_eq!(engine.export_setup(), setup);
+    }
+
+    #[test]
+    fn saved_settings_are_clamped_when_applied() {
+        let engine = McavEngine::new(None);
+        let mut invalid_preset = audio::get_preset("edm").unwrap();
+        invalid_preset.attack = 3.0;
+        let settings = Settings {
+            active_preset: invalid_preset,
+            band_trim: [3.0, 1.0, 1.0, 1.0, 1.0],
+            input_gain_db: 100.0,
+            db_floor: 0.0,
+            transient_emphasis: 9.0,
+            beat_release_ms: Some(5),
+            band_edges: [100.0, 50.0, 250.0, 2000.0, 6000.0, 20000.0],
+            max_entity_count: 0,
+            visibility_threshold: Some(3.0),
+            voice_config: VoiceConfig {
+                duck_depth: 2.0,
+                frame_ms: 7.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let applied = engine.save_settings(settings).unwrap();
+        assert_eq!(applied, Settings::from_state(&engine.state().lock()));
+        assert_eq!(applied.active_preset, AppState::default().active_preset);
+        assert_eq!(applied.band_trim, [2.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(applied.input_gain_db, *INPUT_GAIN_DB_RANGE.end());
+        assert_eq!(applied.db_floor, *DB_FLOOR_RANGE.end());
+        assert_eq!(applied.transient_emphasis, *TRANSIENT_EMPHASIS_RANGE.end());
+        assert_eq!(
+            applied.beat_release_ms,
+            Some(*BEAT_RELEASE_MS_RANGE.start())
+        );
+        assert_eq!(applied.band_edges, AppState::default().band_edges);
+        assert_eq!(applied.max_entity_count, 1);
+        assert_eq!(applied.visibility_threshold, Some(1.0));
+        assert_eq!(applied.voice_config.duck_depth, 1.0);
+        assert_eq!(applied.voice_config.frame_ms, 20.0);
+    }
+
+    #[tokio::test]
+    async fn voice_config_keeps_streaming_state_and_rejects_bad_frames() {
+        let engine = McavEngine::new(None);
+        engine.state().lock().voice_config.enabled = true;
+        let config = VoiceConfig {
+            enabled: false,
+            distance: 42.0,
+            vad_threshold: 2.0,
+            frame_ms: 10.0,
+            ..Default::default()
+        };
+        engine.set_voice_config(config.clone()).await.unwrap();
+        {
+            let app_state = engine.state().lock();
+            assert!(app_state.voice_config.enabled);
+            assert_eq!(app_state.voice_config.distance, 42.0);
+            assert_eq!(app_state.voice_config.vad_threshold, 1.0);
+            assert_eq!(app_state.voice_config.frame_ms, 10.0);
+        }
+
+        let bad_frame = VoiceConfig {
+            frame_ms: 7.0,
+            ..config
+        };
+        assert!(matches!(
+            engine.set_voice_config(bad_frame).await,
+            Err(DjError::InvalidInput(_))
+        ));
+        assert_eq!(engine.state().lock().voice_config.frame_ms, 10.0);
+    }
+
+    #[test]
+    fn voice_ducking_rejects_out_of_range_depth() {
+        let engine = McavEngine::new(None);
+        assert!(matches!(
+            engine.set_voice_ducking(10, 300, 1.5),
+            Err(DjError::InvalidInput(_))
+        ));
+        engine.set_voice_ducking(10, 300, 0.7).unwrap();
+        let config = engine.state().lock().voice_config.clone();
+        assert_eq!(
+            (
+                config.duck_attack_ms,
+                config.duck_release_ms,
+                config.duck_depth
+            ),
+            (10, 300, 0.7)
+        );
+    }
+
+    #[test]
+    fn beat_assist_config_is_clamped() {
+        let engine = McavEngine::new(None);
+        let applied = engine.set_beat_assist_config(BeatAssistConfig {
+            phase_window: 0.9,
+            ..Default::default()
+        });
+        assert_eq!(applied.phase_window, 0.5);
+        assert_eq!(engine.beat_assist_config(), applied);
+    }
+
+    #[test]
+    fn set_max_entity_count_rejects_out_of_range() {
+        let engine = McavEngine::new(None);
+        assert!(matches!(
+            engine.set_max_entity_count(0),
+            Err(DjError::InvalidInput(_))
+        ));
+        assert!(
+            engine
+                .set_max_entity_count(state::ENTITY_COUNT_LIMIT + 1)
+                .is_err()
+        );
+        engine.set_max_entity_count(128).unwrap();
+        assert_eq!(engine.state().lock().max_entity_count, 128);
+    }
+
+    #[test]
+    fn set_visibility_threshold_rejects_out_of_range() {
+        let engine = McavEngine::new(None);
+        assert!(engine.set_visibility_threshold(Some(1.5)).is_err());
+        assert!(engine.set_visibility_threshold(Some(f32::NAN)).is_err());
+        engine.set_visibility_threshold(Some(0.1)).unwrap();
+        assert_eq!(engine.state().lock().visibility_threshold, Some(0.1));
+        engine.set_visibility_threshold(None).unwrap();
+        assert_eq!(engine.state().lock().visibility_threshold, None);
+    }
+
+    #[test]
+    fn set_transient_emphasis_rejects_out_of_range() {
+        let engine = McavEngine::new(None);
+        assert!(engine.set_transient_emphasis(-0.1).is_err());
+        assert!(engine.set_transient_emphasis(f32::NAN).is_err());
+        engine.set_transient_emphasis(1.5).unwrap();
+        assert_eq!(engine.state().lock().transient_emphasis, 1.5);
+    }
+
+    #[test]
+    fn set_beat_release_rejects_out_of_range() {
+        let engine = McavEngine::new(None);
+        assert!(engine.set_beat_release(Some(5)).is_err());
+        assert!(engine.set_beat_release(Some(10_000)).is_err());
+        engine.set_beat_release(Some(300)).unwrap();
+        assert_eq!(engine.state().lock().beat_release_ms, Some(300));
+        engine.set_beat_release(None).unwrap();
+        assert_eq!(engine.state().lock().beat_release_ms, None);
+    }
+
+    #[test]
+    fn set_solo_band_rejects_unknown_bands() {
+        let engine = McavEngine::new(None);
+        assert!(engine.set_solo_band(Some(5)).is_err());
+        engine.set_solo_band(Some(0)).unwrap();
+        assert_eq!(engine.state().lock().solo_band, Some(0));
+        engine.set_solo_band(None).unwrap();
+        assert_eq!(engine.state().lock().solo_band, None);
+    }
+
+    #[test]
+    fn benchmark_analysis_rejects_out_of_range() {
+        let engine = McavEngine::new(None);
+        assert!(engine.benchmark_analysis(0).is_err());
+        assert!(engine.benchmark_analysis(20_000).is_err());
+        assert_eq!(engine.benchmark_analysis(5).unwrap().iterations, 5);
+    }
+
+    #[tokio::test]
+    async fn connect_rejects_bad_code_before_dialing() {
+        let engine = McavEngine::new(None);
+        let config = DjClientConfig {
+            connect_code: Some("nope".into()),
+            ..mock_client_config(0)
+        };
+        assert!(matches!(
+            engine.connect(config, None).await,
+            Err(DjError::InvalidInput(_))
+        ));
+        assert!(engine.state().lock().client.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_frame_needs_a_connection() {
+        let engine = McavEngine::new(None);
+        assert_eq!(
+            engine
+                .send_test_frame([1.0, 0.0, 0.0, 0.0, 0.0], 1.0, true)
+                .await,
+            Err(DjError::NotConnected)
+        );
+    }
+
+    #[tokio::test]
+    async fn connect_rejects_empty_token_before_dialing() {
+        let engine = McavEngine::new(None);
+        let config = DjClientConfig {
+            auth_token: Some("  ".into()),
+            ..mock_client_config(0)
+        };
+        assert!(matches!(
+            engine.connect(config, None).await,
+            Err(DjError::InvalidInput(_))
+        ));
+        assert!(engine.state().lock().client.is_none());
+    }
+
+    /// In-process VJ server: runs the auth handshake for each DJ session.
+    /// The first session is then sent `first_session` and held open until
+    /// `drop_rx` fires; later sessions just stay open. Reports each accepted
+    /// session number on the returned channel.
+    async fn mock_vj_server(
+        first_session: Vec<serde_json::Value>,
+        drop_rx: tokio::sync::oneshot::Receiver<()>,
+    ) -> (u16, mpsc::UnboundedReceiver<u32>) {
+        use futures_util::{SinkExt, StreamExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (accepted_tx, accepted_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut drop_rx = Some(drop_rx);
+            for session in 0.. {
+                let Ok((stream, _)) = listener.accept().await else {
+                    break;
+                };
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let _auth = ws.next().await;
+                let mut messages = vec![
+                    serde_json::json!({
+                        "type": "auth_success",
+                        "dj_id": "dj_1",
+                        "dj_name": "DJ Test",
+                        "is_active": true,
+                    }),
+                    serde_json::json!({"type": "clock_sync_request", "server_time": 0.0}),
+                ];
+                if session == 0 {
+                    messages.extend(first_session.clone());
+                }
+                for msg in messages {
+                    ws.send(Message::Text(msg.to_string().into()))
+                        .await
+                        .unwrap();
+                }
+                let _ = accepted_tx.send(session);
+
+                if let Some(drop_rx) = drop_rx.take() {
+                    let _ = drop_rx.await;
+                    let _ = ws.close(None).await;
+                } else {
+                    tokio::spawn(async move { while ws.next().await.is_some() {} });
+                }
+            }
+        });
+        (port, accepted_rx)
+    }
+
+    /// Config for a DJ connecting to `mock_vj_server` on `port`
+    fn mock_client_config(port: u16) -> DjClientConfig {
+        DjClientConfig {
+            server_host: "127.0.0.1".into(),
+            server_port: port,
+            dj_name: "DJ Test".into(),
+            dj_id: Some("dj_1".into()),
+            dj_key: Some(String::new()),
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for(engine: &McavEngine, what: &str, done: impl Fn(&AppState) -> bool) {
+        let poll = async {
+            while !done(&engine.state().lock()) {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), poll)
+            .await
+            .unwrap_or_else(|_| panic!("timed out waiting for {}", what));
+    }
+
+    #[tokio::test]
+    async fn bridge_syncs_route_and_reconnects_after_server_drop() {
+        let (drop_tx, drop_rx) = tokio::sync::oneshot::channel();
+        let (port, mut accepted) = mock_vj_server(
+            vec![
+                serde_json::json!({
+                    "type": "stream_route",
+                    "route_mode": "dual",
+                    "minecraft_host": "127.0.0.1",
+                    "minecraft_port": 8765,
+                    "current_pattern": "pulse",
+                    "pattern_scripts": {"pulse": "function calculate(audio, config, dt) return {} end"},
+                }),
+                serde_json::json!({"type": "preset_sync", "preset": {"name": "chill"}}),
+            ],
+            drop_rx,
+        )
+        .await;
+
+        let engine = McavEngine::new(None);
+        let mut events = engine.subscribe();
+        engine
+            .connect(mock_client_config(port), None)
+            .await
+            .unwrap();
+        assert_eq!(accepted.recv().await, Some(0));
+
+        wait_for(&engine, "pattern load", |s| {
+            s.local_pattern.as_deref() == Some("pulse") && s.active_preset.name == "chill"
+        })
+        .await;
+        assert_eq!(
+            engine.patterns(),
+            state::PatternList {
+                loaded: vec!["pulse".to_string()],
+                active: Some("pulse".to_string()),
+            }
+        );
+        {
+            let app_state = engine.state().lock();
+            assert!(app_state.status.connected);
+            assert_eq!(app_state.status.route_mode, "dual");
+            // Direct MC publish is off: the VJ server relays even on a dual route
+            assert!(!app_state.status.mc_connected);
+        }
+        let mut saw_preset_event = false;
+        while let Ok(event) = events.try_recv() {
+            saw_preset_event |= matches!(event, EngineEvent::PresetChanged(ref p) if p == "chill");
+        }
+        assert!(saw_preset_event);
+
+        drop_tx.send(()).unwrap();
+        wait_for(&engine, "drop detection", |s| !s.status.connected).await;
+        assert_eq!(accepted.recv().await, Some(1));
+        wait_for(&engine, "reconnect", |s| {
+            s.status.connected && s.client.is_some() && s.status.error.is_none()
+        })
+        .await;
+
+        engine.disconnect().await;
+        assert!(engine.state().lock().bridge_task_handle.is_none());
+    }
+
+    #[tokio::test]
+    async fn bridge_sends_keepalive_frames_before_capture() {
+        let (_drop_tx, drop_rx) = tokio::sync::oneshot::channel();
+        let (port, mut accepted) = mock_vj_server(Vec::new(), drop_rx).await;
+
+        let engine = McavEngine::new(None);
+        engine
+            .connect(mock_client_config(port), None)
+            .await
+            .unwrap();
+        assert_eq!(accepted.recv().await, Some(0));
+
+        wait_for(&engine, "keepalive frames", |s| {
+            s.bridge_stats.vj_frames_sent > 0
+        })
+        .await;
+        {
+            let app_state = engine.state().lock();
+            assert!(app_state.audio_capture.is_none());
+            // One frame per keepalive interval, not one per tick
+            assert!(app_state.bridge_stats.vj_frames_sent <= 3);
+        }
+
+        engine.disconnect().await;
+    }
+
+    #[tokio::test]
+    async fn cancel_reconnect_stops_the_retry_loop() {
+        let (drop_tx, drop_rx) = tokio::sync::oneshot::channel();
+        let (port, mut accepted) = mock_vj_server(Vec::new(), drop_rx).await;
+
+        let engine = McavEngine::new(None);
+        engine
+            .connect(mock_client_config(port), None)
+            .await
+            .unwrap();
+        assert_eq!(accepted.recv().await, Some(0));
+
+        drop_tx.send(()).unwrap();
+        wait_for(&engine, "reconnect backoff", |s| {
+            s.status
+                .error
+                .as_deref()
+                .is_some_and(|e| e.starts_with("Reconnecting"))
+        })
+        .await;
+        engine.cancel_reconnect().unwrap();
+        wait_for(&engine, "bridge stop", |s| s.bridge_task_handle.is_none()).await;
+
+        let app_state = engine.state().lock();
+        assert!(!app_state.status.connected);
+        assert!(app_state.status.error.is_none());
+        assert!(app_state.bridge_control_tx.is_none());
+        drop(app_state);
+        assert!(accepted.try_recv().is_err());
+        assert_eq!(engine.force_reconnect(), Err(DjError::NotConnected));
+    }
+}
@@ -0,0 +1,146 @@
+//! Structured errors returned to the frontend
+//!
+//! `DjError` serializes as `{ "code": "...", "message": "..." }` so the UI
+//! can branch on `code` and show `message` as-is.
+
+use crate::audio::CaptureError;
+use crate::protocol::ClientError;
+use crate::settings::SettingsError;
+use serde::ser::SerializeStruct;
+use thiserror::Error;
+
+/// Error from the connect and capture commands
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DjError {
+    /// The server rejected the connect code or DJ credentials
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+
+    /// The server didn't answer in time
+    #[error("Connection timed out")]
+    Timeout,
+
+    /// The server couldn't be reached or dropped the socket
+    #[error("Connection failed: {0}")]
+    Network(String),
+
+    /// The DJ name (or other text) tripped the content filter
+    #[error("{0}")]
+    ContentRejected(String),
+
+    /// A malformed argument, e.g. a connect code in the wrong shape
+    #[error("{0}")]
+    InvalidInput(String),
+
+    #[error("Already connected")]
+    AlreadyConnected,
+
+    #[error("Not connected")]
+    NotConnected,
+
+    /// No usable output or input device, or the device refused the stream
+    #[error("Audio device error: {0}")]
+    AudioDevice(String),
+
+    /// The requested audio source doesn't exist (any more)
+    #[error("Source not found: {0}")]
+    SourceNotFound(String),
+
+    #[error("Audio capture is not running")]
+    CaptureNotRunning,
+
+    /// Anything else (send failures, thread spawn errors)
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl DjError {
+    /// Stable machine-readable code for the frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            DjError::Auth(_) => "auth",
+            DjError::Timeout => "timeout",
+            DjError::Network(_) => "network",
+            DjError::ContentRejected(_) => "content_rejected",
+            DjError::InvalidInput(_) => "invalid_input",
+            DjError::AlreadyConnected => "already_connected",
+            DjError::NotConnected => "not_connected",
+            DjError::AudioDevice(_) => "audio_device",
+            DjError::SourceNotFound(_) => "source_not_found",
+            DjError::CaptureNotRunning => "capture_not_running",
+            DjError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl serde::Serialize for DjError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("DjError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+impl From<ClientError> for DjError {
+    fn from(e: ClientError) -> Self {
+        match e {
+            ClientError::AuthenticationFailed(msg) => DjError::Auth(msg),
+            ClientError::Timeout => DjError::Timeout,
+            ClientError::ConnectionFailed(msg) | ClientError::WebSocketError(msg) => {
+                DjError::Network(msg)
+            }
+            ClientError::AlreadyConnected => DjError::AlreadyConnected,
+            ClientError::NotConnected => DjError::NotConnected,
+            ClientError::SendError(msg) => DjError::Internal(msg),
+        }
+    }
+}
+
+impl From<CaptureError> for DjError {
+    fn from(e: CaptureError) -> Self {
+        match e {
+            CaptureError::SourceNotFound(id) => DjError::SourceNotFound(id),
+            CaptureError::ThreadError(msg) => DjError::Internal(msg),
+            e @ CaptureError::NotMixable(_) => DjError::InvalidInput(e.to_string()),
+            e @ (CaptureError::NoHost
+            | CaptureError::NoOutputDevice
+            | CaptureError::ConfigError(_)
+            | CaptureError::StreamError(_)
+            | CaptureError::PlayError(_)) => DjError::AudioDevice(e.to_string()),
+        }
+    }
+}
+
+impl From<SettingsError> for DjError {
+    fn from(e: SettingsError) -> Self {
+        DjError::Internal(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_code_and_message() {
+        let json = serde_json::to_value(DjError::Auth("bad code".into())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"code": "auth", "message": "Authentication failed: bad code"})
+        );
+    }
+
+    #[test]
+    fn maps_client_errors() {
+        assert_eq!(DjError::from(ClientError::Timeout), DjError::Timeout);
+        assert_eq!(
+            DjError::from(ClientError::ConnectionFailed("refused".into())).code(),
+            "network"
+        );
+        assert_eq!(
+            DjError::from(CaptureError::NoOutputDevice).code(),
+            "audio_device"
+        );
+    }
+}
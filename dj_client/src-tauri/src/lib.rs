@@ -7,34 +7,42 @@
 //! Cross-platform DJ client for connecting to VJ servers and streaming
 //! audio visualizations to Minecraft.
 
+pub mod analysis_server;
 pub mod audio;
 pub mod content_filter;
+pub mod engine;
+pub mod error;
+pub mod logs;
+pub mod midi;
+pub mod osc;
 pub mod patterns;
 pub mod protocol;
+pub mod settings;
 pub mod state;
 pub mod voice;
 
-use audio::{AudioCaptureHandle, AudioPreset, AudioSource, CaptureMode};
-use protocol::{AudioFrameMessage, DjClient, DjClientConfig};
+pub use engine::{AppSnapshot, AudioLevels, BeatEvent, CaptureStatus, EngineEvent, McavEngine};
+pub use error::DjError;
+
+use audio::{AudioPreset, AudioSource, SourceType};
+use protocol::{ClientError, DjClient, DjClientConfig};
+use settings::Settings;
 use state::AppState;
-use voice::{VoiceStatus, VoiceStreamer};
+use voice::VoiceStatus;
 
 use parking_lot::Mutex;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::{TrayIconBuilder, TrayIconEvent};
 use tauri::{AppHandle, Emitter, Manager, State};
-use tokio::sync::mpsc;
-use tokio_tungstenite::tungstenite::Message;
+use tokio::sync::broadcast;
 
-/// Application state wrapper
+/// Application state wrapper (the engine's state, for plain reads and
+/// settings that need no orchestration)
 pub struct AppStateWrapper(pub Arc<Mutex<AppState>>);
 
-/// Sequence counter for audio frames (shared between bridge task and get_audio_levels)
-static FRAME_SEQ: AtomicU64 = AtomicU64::new(0);
-
 /// Returns true if the host is a private/local IP address that should use ws:// instead of wss://.
 pub(crate) fn is_local_host(host: &str) -> bool {
     if host == "localhost" {
@@ -49,906 +57,705 @@ pub(crate) fn is_local_host(host: &str) -> bool {
     false
 }
 
-/// List available audio sources
+/// List available audio sources (cached for a few seconds)
 #[tauri::command]
-async fn list_audio_sources() -> Result<Vec<AudioSource>, String> {
-    audio::list_sources().map_err(|e| e.to_string())
+async fn list_audio_sources(engine: State<'_, McavEngine>) -> Result<Vec<AudioSource>, String> {
+    engine.list_sources()
 }
 
-/// Shared connection logic used by both `connect_with_code` and `connect_direct`.
-///
-/// Shuts down any existing bridge task, connects the client, optionally sends a
-/// block palette, and spawns a new bridge task.
-async fn connect_common(
-    app_handle: AppHandle,
-    state_arc: Arc<Mutex<AppState>>,
-    config: DjClientConfig,
-    connect_code: Option<String>,
-    block_palette: Option<Vec<Option<String>>>,
-) -> Result<(), String> {
-    // If a previous bridge task is still running (e.g. reconnecting after
-    // server restart), shut it down and await completion before starting a new
-    // connection to avoid two bridge tasks running concurrently.
-    {
-        let (old_tx, old_handle) = {
-            let mut app_state = state_arc.lock();
-            (
-                app_state.bridge_shutdown_tx.take(),
-                app_state.bridge_task_handle.take(),
-            )
-        };
-        if let Some(tx) = old_tx {
-            log::info!("Shutting down existing bridge task before reconnecting");
-            let _ = tx.send(()).await;
-        }
-        if let Some(handle) = old_handle {
-            match tokio::time::timeout(Duration::from_millis(500), handle).await {
-                Ok(_) => log::info!("Old bridge task stopped cleanly"),
-                Err(_) => {
-                    log::warn!("Old bridge task did not stop within 500ms, proceeding anyway")
-                }
-            }
-        }
-        // Clean up old client
-        let old_client = state_arc.lock().client.take();
-        if let Some(c) = old_client {
-            let _ = c.disconnect().await;
-        }
-    }
-
-    {
-        let mut app_state = state_arc.lock();
-        app_state.connect_code = connect_code;
-        app_state.dj_name = config.dj_name.clone();
-        app_state.server_host = config.server_host.clone();
-        app_state.server_port = config.server_port;
-    }
-
-    // Create and connect client (async, no mutex held)
-    let mut client = DjClient::new(config);
-    client.connect().await.map_err(|e| e.to_string())?;
-
-    // Send block palette if provided
-    if let Some(palette) = block_palette
-        && palette.iter().any(|m| m.is_some())
-        && let Err(e) = client.send_palette(palette).await
-    {
-        log::warn!("Failed to send block palette: {}", e);
-    }
-
-    // Create shutdown channel for bridge task
-    let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
-
-    // Store connected client and shutdown channel
-    {
-        let mut app_state = state_arc.lock();
-        app_state.client = Some(client);
-        app_state.bridge_shutdown_tx = Some(shutdown_tx);
-        app_state.status.connected = true;
-        app_state.status.error = None;
-    }
-
-    // Spawn bridge task and store its handle
-    let bridge_state = state_arc.clone();
-    let handle = tokio::spawn(async move {
-        run_bridge(bridge_state, shutdown_rx, app_handle).await;
-    });
-    state_arc.lock().bridge_task_handle = Some(handle);
+/// List only the sources of one type (e.g. applications for an app picker)
+#[tauri::command]
+async fn list_sources_filtered(
+    engine: State<'_, McavEngine>,
+    kind: SourceType,
+) -> Result<Vec<AudioSource>, String> {
+    engine
+        .list_sources()
+        .map(|sources| audio::filter_by_type(sources, kind))
+}
 
-    Ok(())
+/// Force a fresh source scan and broadcast it as `sources-changed`
+#[tauri::command]
+async fn refresh_sources(engine: State<'_, McavEngine>) -> Result<Vec<AudioSource>, String> {
+    engine.refresh_sources()
 }
 
 /// Connect to VJ server with connect code
-#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn connect_with_code(
-    app_handle: AppHandle,
-    state: State<'_, AppStateWrapper>,
+    engine: State<'_, McavEngine>,
     code: String,
     dj_name: String,
     server_host: String,
     server_port: u16,
     block_palette: Option<Vec<Option<String>>>,
     dj_session_id: Option<String>,
-) -> Result<(), String> {
-    content_filter::validate_no_slurs(&dj_name, "DJ name")?;
-
+) -> Result<(), DjError> {
     let config = DjClientConfig {
-        server_host: server_host.clone(),
+        server_host,
         server_port,
-        dj_name: dj_name.clone(),
-        connect_code: Some(code.clone()),
+        dj_name,
+        connect_code: Some(code),
         dj_session_id,
         ..Default::default()
     };
+    engine.connect(config, block_palette).await
+}
 
-    connect_common(
-        app_handle,
-        state.0.clone(),
-        config,
-        Some(code),
-        block_palette,
-    )
-    .await
+/// Connect to a VJ server that issues bearer tokens (e.g. JWTs from an SSO
+/// login) instead of connect codes
+#[tauri::command]
+async fn connect_with_token(
+    engine: State<'_, McavEngine>,
+    token: String,
+    dj_name: String,
+    server_host: String,
+    server_port: u16,
+    block_palette: Option<Vec<Option<String>>>,
+) -> Result<(), DjError> {
+    let config = DjClientConfig {
+        server_host,
+        server_port,
+        dj_name,
+        auth_token: Some(token),
+        ..Default::default()
+    };
+    engine.connect(config, block_palette).await
 }
 
 /// Connect to VJ server directly (no connect code needed, for testing)
 #[tauri::command]
 async fn connect_direct(
-    app_handle: AppHandle,
-    state: State<'_, AppStateWrapper>,
+    engine: State<'_, McavEngine>,
     dj_name: String,
     server_host: String,
     server_port: u16,
-) -> Result<(), String> {
-    content_filter::validate_no_slurs(&dj_name, "DJ name")?;
-
+) -> Result<(), DjError> {
     let config = DjClientConfig {
-        server_host: server_host.clone(),
+        server_host,
         server_port,
-        dj_name: dj_name.clone(),
+        dj_name,
         dj_id: Some(format!("tauri_dj_{:08x}", rand::random::<u32>())),
         dj_key: Some(String::new()),
         ..Default::default()
     };
+    engine.connect(config, None).await
+}
 
-    connect_common(app_handle, state.0.clone(), config, None, None).await
-}
-
-/// Maximum number of automatic reconnection attempts before giving up.
-const MAX_RECONNECT_ATTEMPTS: u32 = 10;
-/// Maximum backoff delay between reconnection attempts in seconds.
-const MAX_RECONNECT_DELAY_SECS: u64 = 30;
-
-/// Bridge task: reads audio analysis and sends frames to VJ server at ~60fps.
-/// Automatically reconnects with exponential backoff when the connection drops.
-async fn run_bridge(
-    state_arc: Arc<Mutex<AppState>>,
-    mut shutdown_rx: mpsc::Receiver<()>,
-    app_handle: AppHandle,
-) {
-    let mut reconnect_count: u32 = 0;
-
-    'reconnect: loop {
-        let mut interval = tokio::time::interval(Duration::from_millis(16));
-        // Direct MC publish is disabled: the VJ server's pattern engine handles
-        // all zones (multi-zone, transitions, crossfades). The DJ client sends
-        // audio frames to the VJ server which relays to Minecraft authoritatively.
-        let mut last_phase_predicted_beat_at = 0.0_f64;
-        let mut pattern_engine: Option<patterns::PatternEngine> = None;
-        // Throttle UI events: audio-levels ~30fps, status/voice ~4fps
-        let mut last_audio_emit = Instant::now() - Duration::from_secs(1);
-        let mut last_status_emit = Instant::now() - Duration::from_secs(1);
-        let mut prev_status_hash: u64 = 0;
-        let mut prev_voice_hash: u64 = 0;
-        // Track whether this iteration exited due to explicit shutdown
-        let mut shutdown_requested = false;
-
-        log::info!("Bridge task started (reconnect #{})", reconnect_count);
-
-        loop {
-            tokio::select! {
-                _ = shutdown_rx.recv() => {
-                    log::info!("Bridge task received shutdown signal");
-                    shutdown_requested = true;
-                    break;
-                }
-                _ = interval.tick() => {
-                    // 1. Read audio analysis + VJ sender + connection state (brief lock)
-                    let (analysis, tx, conn_state_opt) = {
-                        let app_state = state_arc.lock();
-                        let analysis = app_state.audio_capture.as_ref()
-                            .map(|c| c.get_analysis());
-                        let tx = app_state.client.as_ref()
-                            .and_then(|c| c.get_tx_clone());
-                        let conn_state = app_state.client.as_ref()
-                            .map(|c| c.get_state());
-                        (analysis, tx, conn_state)
-                    };
-                    // Lock dropped
-
-                    // If no client tx, connection is lost
-                    let tx = match tx {
-                        Some(tx) => tx,
-                        None => {
-                            // Client disconnected externally
-                            let mut app_state = state_arc.lock();
-                            app_state.status.connected = false;
-                            app_state.status.error = Some("Connection lost".to_string());
-                            break;
-                        }
-                    };
-                    let _conn_state = match conn_state_opt {
-                        Some(s) => s,
-                        None => {
-                            let mut app_state = state_arc.lock();
-                            app_state.status.connected = false;
-                            app_state.status.error = Some("Connection lost".to_string());
-                            break;
-                        }
-                    };
-
-                    // 2. Send audio frame if we have analysis data
-                    // Hoist beat output vars for use in UI event emission (section 3)
-                    let mut out_is_beat = analysis.as_ref().is_some_and(|a| a.is_beat);
-                    let mut out_beat_intensity = analysis.as_ref().map_or(0.0, |a| a.beat_intensity);
-                    if let Some(ref analysis) = analysis {
-                        let seq = FRAME_SEQ.fetch_add(1, Ordering::Relaxed);
-                        let now_secs = std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs_f64();
-
-                        // Phase-aware beat assist: when tempo lock is strong and phase is near
-                        // the beat boundary, emit a conservative predicted beat so both VJ and
-                        // direct MC routes stay visually tight.
-                        if !out_is_beat && analysis.tempo_confidence >= 0.60 && analysis.bpm >= 60.0 {
-                            let beat_period = 60.0_f64 / analysis.bpm as f64;
-                            let phase = analysis.beat_phase.clamp(0.0, 1.0);
-                            let near_boundary = !(0.08..=0.92).contains(&phase);
-                            let can_fire = last_phase_predicted_beat_at <= 0.0
-                                || (now_secs - last_phase_predicted_beat_at) >= (beat_period * 0.60);
-                            if near_boundary && can_fire {
-                                out_is_beat = true;
-                                out_beat_intensity =
-                                    out_beat_intensity.max((0.50 + analysis.tempo_confidence * 0.25).clamp(0.0, 1.0));
-                                last_phase_predicted_beat_at = now_secs;
-                            }
-                        }
-
-                        // Use bass lane kick to supplement beat detection
-                        if !out_is_beat && analysis.instant_kick {
-                            out_is_beat = true;
-                            out_beat_intensity = out_beat_intensity.max(0.5);
-                        }
-
-                        let msg = AudioFrameMessage::new(
-                            seq,
-                            analysis.bands,
-                            analysis.peak,
-                            out_is_beat,
-                            out_beat_intensity,
-                            analysis.bpm,
-                            analysis.tempo_confidence,
-                            analysis.beat_phase,
-                            analysis.instant_bass,
-                            analysis.instant_kick,
-                        );
-
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            match tx.try_send(Message::Text(json.into())) {
-                                Ok(()) => {}
-                                Err(mpsc::error::TrySendError::Full(_)) => {
-                                    // Channel full — drop this frame rather than
-                                    // stalling the bridge loop. The consumer will
-                                    // catch up on the next tick.
-                                    log::debug!("Audio frame dropped (send channel full)");
-                                }
-                                Err(mpsc::error::TrySendError::Closed(_)) => {
-                                    log::error!("Failed to send audio frame - channel closed");
-                                    let mut app_state = state_arc.lock();
-                                    app_state.status.connected = false;
-                                    app_state.status.error = Some("Connection lost".to_string());
-                                    break;
-                                }
-                            }
-                        }
+/// Result of a `test_connection` probe
+#[derive(Clone, serde::Serialize)]
+pub struct ConnectionTestResult {
+    /// The WebSocket connection was established
+    pub reachable: bool,
+    /// The server accepted the connect code
+    pub code_accepted: bool,
+    /// Time from opening the socket to the end of the handshake
+    pub connect_ms: u64,
+    /// Protocol version reported by the server, if it sends one
+    pub protocol_version: Option<String>,
+    pub error: Option<String>,
+}
 
-                    }
+/// Check that a server is reachable and accepts a connect code.
+///
+/// Runs the same connect + auth handshake as `connect_with_code`, then
+/// disconnects straight away; no bridge is spawned and `AppState` is left
+/// untouched.
+#[tauri::command]
+async fn test_connection(
+    server_host: String,
+    server_port: u16,
+    code: String,
+    dj_name: String,
+) -> Result<ConnectionTestResult, DjError> {
+    content_filter::validate_no_slurs(&dj_name, "DJ name").map_err(DjError::ContentRejected)?;
+    let code = protocol::normalize_connect_code(&code).map_err(DjError::InvalidInput)?;
 
-                    // 2.5 Send voice audio frames if streaming is enabled
-                    {
-                        let voice_streamer = {
-                            let app_state = state_arc.lock();
-                            app_state.voice_streamer.clone()
-                        };
-
-                        if let Some(ref streamer) = voice_streamer
-                            && streamer.is_enabled() {
-                                let frames = streamer.drain_frames();
-                                // Send at most 3 frames per tick (~48ms at 16ms ticks)
-                                // to avoid flooding the WebSocket
-                                for (data, seq, codec) in frames.into_iter().take(3) {
-                                    let voice_msg = protocol::VoiceAudioMessage::new(data, seq, codec);
-                                    if let Ok(json) = serde_json::to_string(&voice_msg) {
-                                        match tx.try_send(Message::Text(json.into())) {
-                                            Ok(()) => {}
-                                            Err(mpsc::error::TrySendError::Full(_)) => {
-                                                log::debug!("Voice frame dropped (send channel full)");
-                                                break;
-                                            }
-                                            Err(mpsc::error::TrySendError::Closed(_)) => {
-                                                log::error!("Failed to send voice frame - channel closed");
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                    }
+    let config = DjClientConfig {
+        server_host,
+        server_port,
+        dj_name,
+        connect_code: Some(code),
+        ..Default::default()
+    };
 
-                    // 3. Update connection state from DjClient (brief lock, no events)
-                    let (status_snapshot, voice_snapshot, preset_changed, roster_update) = {
-                        let mut app_state = state_arc.lock();
-                        // Report mc_connected=false so VJ server always relays to MC
-                        if let Some(ref client) = app_state.client {
-                            client.set_mc_connected(false);
-                        }
+    let mut client = DjClient::new(config);
+    let started = Instant::now();
+    let outcome = client.connect().await;
+    let connect_ms = started.elapsed().as_millis() as u64;
 
-                        // Check for preset_sync from server
-                        let mut preset_event: Option<String> = None;
-                        if let Some(ref client) = app_state.client
-                            && let Some(preset_name) = client.take_pending_preset()
-                                && let Some(preset) = audio::get_preset(&preset_name) {
-                                    if let Some(ref capture) = app_state.audio_capture {
-                                        capture.analyzer().lock().apply_preset(&preset);
-                                    }
-                                    app_state.active_preset = preset.name.clone();
-                                    preset_event = Some(preset.name.clone());
-                                }
-
-                        // Consume pending pattern data from server
-                        if let Some(ref client) = app_state.client {
-                            // Load pattern scripts
-                            if let Some(scripts) = client.take_pending_pattern_scripts() {
-                                let engine = pattern_engine.get_or_insert_with(patterns::PatternEngine::new);
-                                // Look for lib script first
-                                if let Some(lib_src) = scripts.get("lib")
-                                    && let Err(e) = engine.load_lib(lib_src) {
-                                        log::warn!("Failed to load lib.lua: {}", e);
-                                    }
-                                for (name, src) in &scripts {
-                                    if name != "lib" {
-                                        engine.load_pattern(name, src);
-                                    }
-                                }
-                                log::info!("Loaded {} pattern scripts from server", scripts.len());
-                            }
-
-                            // Switch pattern
-                            if let Some(pattern_name) = client.take_pending_pattern_change()
-                                && let Some(ref mut engine) = pattern_engine
-                                    && let Err(e) = engine.set_pattern(&pattern_name) {
-                                        log::warn!("Failed to switch pattern: {}", e);
-                                    }
-
-                            // Update band sensitivity
-                            if let Some(sensitivity) = client.take_pending_band_sensitivity()
-                                && let Some(ref mut engine) = pattern_engine {
-                                    engine.set_band_sensitivity(sensitivity);
-                                }
-
-                            // Update config (entity_count, zone)
-                            if let Some((entity_count, _zone)) = client.take_pending_config_change()
-                                && let Some(ref mut engine) = pattern_engine {
-                                    engine.set_config(patterns::PatternConfig {
-                                        entity_count,
-                                        ..Default::default()
-                                    });
-                                }
-                        }
+    let result = match outcome {
+        Ok(()) => {
+            let conn = client.get_state();
+            let _ = client.disconnect().await;
+            ConnectionTestResult {
+                reachable: true,
+                code_accepted: conn.authenticated,
+                connect_ms,
+                protocol_version: conn.server_protocol_version,
+                error: (!conn.authenticated)
+                    .then(|| "Server did not confirm authentication".to_string()),
+            }
+        }
+        Err(e @ ClientError::AuthenticationFailed(_)) => ConnectionTestResult {
+            reachable: true,
+            code_accepted: false,
+            connect_ms,
+            protocol_version: None,
+            error: Some(e.to_string()),
+        },
+        Err(e) => ConnectionTestResult {
+            reachable: false,
+            code_accepted: false,
+            connect_ms,
+            protocol_version: None,
+            error: Some(e.to_string()),
+        },
+    };
+    Ok(result)
+}
 
-                        // Consume pending DJ roster
-                        let roster = app_state.client.as_ref()
-                            .and_then(|c| c.take_pending_dj_roster());
-
-                        if let Some(ref client) = app_state.client {
-                            let latest = client.get_state();
-                            app_state.status.is_active = latest.is_active;
-                            app_state.status.latency_ms = latest.latency_ms;
-                            app_state.status.route_mode = latest.route_mode;
-                            app_state.status.mc_connected = false;
-                            if !latest.connected {
-                                app_state.status.connected = false;
-                                app_state.status.error = Some("Server disconnected".to_string());
-                            }
-
-                            // Sync voice status from server messages
-                            app_state.voice_status.available = latest.voice_available;
-                            app_state.voice_status.streaming = latest.voice_streaming;
-                            if let Some(ref ct) = latest.voice_channel_type {
-                                app_state.voice_status.channel_type = ct.clone();
-                            }
-                            if let Some(players) = latest.voice_connected_players {
-                                app_state.voice_status.connected_players = players;
-                            }
-                        }
+/// Start audio capture from selected source
+#[tauri::command]
+async fn start_capture(
+    engine: State<'_, McavEngine>,
+    source_id: Option<String>,
+    mix_with: Option<Vec<String>>,
+) -> Result<(), DjError> {
+    engine.start_capture(source_id, mix_with.unwrap_or_default())
+}
 
-                        // Clone data for events — lock is released after this block
-                        (app_state.status.clone(), app_state.voice_status.clone(), preset_event, roster)
-                    };
-                    // state_arc lock dropped — emit events without holding any lock
+/// Stop audio capture
+#[tauri::command]
+async fn stop_capture(engine: State<'_, McavEngine>) -> Result<(), String> {
+    engine.stop_capture();
+    Ok(())
+}
 
-                    if let Some(ref preset_name) = preset_changed {
-                        let _ = app_handle.emit("preset-changed", preset_name);
-                    }
+/// Pause analysis (silent frames go out) while keeping the device open
+#[tauri::command]
+fn pause_capture(engine: State<'_, McavEngine>) -> Result<(), DjError> {
+    engine.set_capture_paused(true)
+}
 
-                    // Emit DJ roster if available
-                    if let Some(ref roster) = roster_update {
-                        let _ = app_handle.emit("dj-roster", roster);
-                    }
+/// Resume analysis after `pause_capture`
+#[tauri::command]
+fn resume_capture(engine: State<'_, McavEngine>) -> Result<(), DjError> {
+    engine.set_capture_paused(false)
+}
 
-                    // Audio levels: emit at ~30fps, but always emit immediately on beat
-                    if let Some(ref analysis) = analysis {
-                        let is_beat_frame = analysis.is_beat || out_is_beat;
-                        if is_beat_frame || last_audio_emit.elapsed() >= Duration::from_millis(33) {
-                            let _ = app_handle.emit("audio-levels", AudioLevels {
-                                bands: analysis.bands,
-                                peak: analysis.peak,
-                                is_beat: out_is_beat,
-                                beat_intensity: out_beat_intensity,
-                                bpm: analysis.bpm,
-                            });
-                            last_audio_emit = Instant::now();
-                        }
-                    }
+/// Resume a `file:` source
+#[tauri::command]
+fn play_file(engine: State<'_, McavEngine>) -> Result<(), DjError> {
+    engine.set_file_paused(false)
+}
 
-                    // Status + voice: emit at ~4fps OR immediately on change
-                    {
-                        use std::hash::{Hash, Hasher};
-                        let mut h = std::collections::hash_map::DefaultHasher::new();
-                        format!("{:?}", status_snapshot).hash(&mut h);
-                        let s_hash = h.finish();
-
-                        let mut h2 = std::collections::hash_map::DefaultHasher::new();
-                        format!("{:?}", voice_snapshot).hash(&mut h2);
-                        let v_hash = h2.finish();
-
-                        let status_changed = s_hash != prev_status_hash;
-                        let voice_changed = v_hash != prev_voice_hash;
-                        let throttle_elapsed = last_status_emit.elapsed() >= Duration::from_millis(250);
-
-                        if status_changed || throttle_elapsed {
-                            let _ = app_handle.emit("dj-status", &status_snapshot);
-                            // Update tray tooltip when connection status changes
-                            if status_changed {
-                                update_tray_tooltip(&app_handle, status_snapshot.connected);
-                            }
-                            prev_status_hash = s_hash;
-                        }
-                        if voice_changed || throttle_elapsed {
-                            let _ = app_handle.emit("voice-status", &voice_snapshot);
-                            prev_voice_hash = v_hash;
-                        }
-                        if status_changed || voice_changed || throttle_elapsed {
-                            last_status_emit = Instant::now();
-                        }
-                    }
-                }
-            }
-        }
+/// Pause a `file:` source (silence goes out until it plays again)
+#[tauri::command]
+fn pause_file(engine: State<'_, McavEngine>) -> Result<(), DjError> {
+    engine.set_file_paused(true)
+}
 
-        // Cleanup current connection
-        log::info!("Bridge task cleaning up");
-        let client = {
-            let mut app_state = state_arc.lock();
-            app_state.client.take()
-        };
-        if let Some(client) = client {
-            let _ = client.disconnect().await;
-        }
-        {
-            let mut app_state = state_arc.lock();
-            app_state.status.connected = false;
-            app_state.status.mc_connected = false;
-        }
+/// Jump to a time in seconds in a `file:` source
+#[tauri::command]
+fn seek_file(engine: State<'_, McavEngine>, position_secs: f64) -> Result<(), DjError> {
+    engine.seek_file(position_secs)
+}
 
-        // If shutdown was explicitly requested, do not reconnect
-        if shutdown_requested {
-            let mut app_state = state_arc.lock();
-            app_state.bridge_shutdown_tx = None;
-            app_state.bridge_task_handle = None;
-            log::info!("Bridge task stopped (user disconnect)");
-            break 'reconnect;
-        }
+/// Loop a `file:` source at its end instead of stopping
+#[tauri::command]
+fn set_file_looping(engine: State<'_, McavEngine>, enabled: bool) -> Result<(), DjError> {
+    engine.set_file_looping(enabled)
+}
 
-        // Auto-reconnect with exponential backoff
-        reconnect_count += 1;
-        if reconnect_count > MAX_RECONNECT_ATTEMPTS {
-            let mut app_state = state_arc.lock();
-            app_state.bridge_shutdown_tx = None;
-            app_state.bridge_task_handle = None;
-            app_state.status.error = Some("Connection lost (max retries reached)".to_string());
-            let _ = app_handle.emit("dj-status", &app_state.status);
-            log::error!(
-                "Bridge task gave up after {} reconnect attempts",
-                MAX_RECONNECT_ATTEMPTS
-            );
-            break 'reconnect;
-        }
+/// Get the position and controls of the `file:` source, if one is playing
+#[tauri::command]
+fn get_file_playback(engine: State<'_, McavEngine>) -> Option<audio::FilePlaybackStatus> {
+    engine.file_playback()
+}
 
-        let delay_secs = std::cmp::min(1u64 << (reconnect_count - 1), MAX_RECONNECT_DELAY_SECS);
-        log::info!(
-            "Reconnecting in {}s (attempt {}/{})",
-            delay_secs,
-            reconnect_count,
-            MAX_RECONNECT_ATTEMPTS
-        );
-        {
-            let mut app_state = state_arc.lock();
-            app_state.status.error = Some(format!(
-                "Reconnecting in {}s ({}/{})",
-                delay_secs, reconnect_count, MAX_RECONNECT_ATTEMPTS
-            ));
-            let _ = app_handle.emit("dj-status", &app_state.status);
-        }
+/// Change audio source while connected (hot-swap capture)
+#[tauri::command]
+async fn change_audio_source(
+    engine: State<'_, McavEngine>,
+    source_id: Option<String>,
+) -> Result<(), DjError> {
+    engine.change_source(source_id)
+}
 
-        // Wait for backoff delay or shutdown signal
-        tokio::select! {
-            _ = tokio::time::sleep(Duration::from_secs(delay_secs)) => {}
-            _ = shutdown_rx.recv() => {
-                let mut app_state = state_arc.lock();
-                app_state.bridge_shutdown_tx = None;
-                app_state.bridge_task_handle = None;
-                log::info!("Bridge task stopped during reconnect backoff (user disconnect)");
-                break 'reconnect;
-            }
-        }
+/// Fire a manual beat (intensity 0-1) on the next frame, like a sampler pad
+#[tauri::command]
+fn trigger_beat(engine: State<'_, McavEngine>, intensity: f32) -> Result<(), DjError> {
+    engine.trigger_beat(intensity)
+}
 
-        // Attempt to reconnect using stored config
-        let reconnect_result = {
-            let app_state = state_arc.lock();
-
-            DjClientConfig {
-                server_host: app_state.server_host.clone(),
-                server_port: app_state.server_port,
-                dj_name: app_state.dj_name.clone(),
-                connect_code: app_state.connect_code.clone(),
-                dj_id: Some(format!("tauri_dj_{:08x}", rand::random::<u32>())),
-                dj_key: if app_state.connect_code.is_none() {
-                    Some(String::new())
-                } else {
-                    None
-                },
-                ..Default::default()
-            }
-        };
+/// Push one synthetic frame (e.g. full bass, beat on) through the VJ server
+/// to check the Minecraft render without any audio
+#[tauri::command]
+async fn send_test_frame(
+    engine: State<'_, McavEngine>,
+    bands: [f32; 5],
+    peak: f32,
+    beat: bool,
+) -> Result<(), DjError> {
+    engine.send_test_frame(bands, peak, beat).await
+}
 
-        let mut client = DjClient::new(reconnect_result);
-        match client.connect().await {
-            Ok(()) => {
-                let mut app_state = state_arc.lock();
-                app_state.client = Some(client);
-                app_state.status.connected = true;
-                app_state.status.error = None;
-                let _ = app_handle.emit("dj-status", &app_state.status);
-                log::info!("Reconnected successfully");
-                reconnect_count = 0;
-                continue 'reconnect;
-            }
-            Err(e) => {
-                log::warn!("Reconnect failed: {}", e);
-                continue 'reconnect;
-            }
-        }
-    } // end 'reconnect loop
+/// Set the gain (linear, 0-4) of a source in the capture mix; returns the
+/// gain as applied
+#[tauri::command]
+fn set_source_gain(
+    engine: State<'_, McavEngine>,
+    source_id: String,
+    gain: f32,
+) -> Result<f32, DjError> {
+    engine.set_source_gain(&source_id, gain)
 }
 
-/// Start audio capture from selected source
+/// Get current capture status
 #[tauri::command]
-async fn start_capture(
-    app_handle: AppHandle,
-    state: State<'_, AppStateWrapper>,
-    source_id: Option<String>,
-) -> Result<(), String> {
-    // Create voice streamer (48kHz, stereo assumed; resampling handles mismatches)
-    let voice_streamer = Arc::new(VoiceStreamer::new(48000, 2));
-
-    // Propagate current voice config
-    {
-        let app_state = state.0.lock();
-        voice_streamer.set_enabled(app_state.voice_config.enabled);
-    }
+fn get_capture_status(engine: State<'_, McavEngine>) -> CaptureStatus {
+    engine.capture_status()
+}
 
-    let capture =
-        AudioCaptureHandle::new_with_voice(source_id.clone(), Some(voice_streamer.clone()))
-            .map_err(|e| e.to_string())?;
+/// Disconnect from VJ server
+#[tauri::command]
+async fn disconnect(engine: State<'_, McavEngine>) -> Result<(), String> {
+    engine.disconnect().await;
+    Ok(())
+}
 
-    let mut app_state = state.0.lock();
+/// Skip the reconnect backoff and retry now
+#[tauri::command]
+fn force_reconnect(engine: State<'_, McavEngine>) -> Result<(), DjError> {
+    engine.force_reconnect()
+}
 
-    // Apply the active preset to the new analyzer
-    if let Some(preset) = audio::get_preset(&app_state.active_preset) {
-        capture.analyzer().lock().apply_preset(&preset);
-    }
+/// Give up reconnecting and stay disconnected
+#[tauri::command]
+fn cancel_reconnect(engine: State<'_, McavEngine>) -> Result<(), DjError> {
+    engine.cancel_reconnect()
+}
 
-    app_state.audio_source_id = source_id;
-    app_state.audio_capture = Some(capture);
-    app_state.voice_streamer = Some(voice_streamer);
-    drop(app_state);
-
-    // Emit capture mode after a brief delay for the audio thread to initialize
-    let state_for_mode = state.0.clone();
-    tokio::spawn(async move {
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        let mode = {
-            let app_state = state_for_mode.lock();
-            app_state
-                .audio_capture
-                .as_ref()
-                .map(|c| c.get_capture_mode())
-        };
-        if let Some(mode) = mode {
-            let _ = app_handle.emit("capture-mode", &mode);
-        }
-    });
+/// Zones the connected server can visualize, for the voice zone picker
+#[tauri::command]
+async fn get_zones(engine: State<'_, McavEngine>) -> Result<Vec<protocol::ZoneInfo>, DjError> {
+    engine.list_zones().await
+}
 
+/// Enable or disable voice audio streaming
+#[tauri::command]
+async fn set_voice_streaming(engine: State<'_, McavEngine>, enabled: bool) -> Result<(), String> {
+    engine.set_voice_streaming(enabled).await;
     Ok(())
 }
 
-/// Stop audio capture
+/// Get current voice streaming status
 #[tauri::command]
-async fn stop_capture(state: State<'_, AppStateWrapper>) -> Result<(), String> {
-    let mut app_state = state.0.lock();
-    if let Some(capture) = app_state.audio_capture.take() {
-        capture.stop();
-    }
-    // Clean up voice streamer so it stops buffering frames
+fn get_voice_status(state: State<'_, AppStateWrapper>) -> VoiceStatus {
+    let app_state = state.0.lock();
+    let mut status = app_state.voice_status.clone();
+    // The bridge only refreshes the codec while connected
     if let Some(ref streamer) = app_state.voice_streamer {
-        streamer.set_enabled(false);
+        status.codec = streamer.codec().to_string();
     }
-    app_state.voice_streamer = None;
-    Ok(())
+    status
 }
 
-/// Change audio source while connected (hot-swap capture)
+/// Update voice streaming configuration
+///
+/// `zone` is checked against the server's zone list (see `get_zones`)
+/// and `frame_ms` must be one of 2.5, 5, 10, 20 or 40 before anything
+/// changes.
+/// `stereo` keeps both channels, for streaming the music mix rather than
+/// speech. `fec`, `packet_loss_perc` and `dtx` trade bandwidth and quality
+/// for resilience on lossy networks (see `VoiceConfig`).
 #[tauri::command]
-async fn change_audio_source(
-    app_handle: AppHandle,
-    state: State<'_, AppStateWrapper>,
-    source_id: Option<String>,
-) -> Result<(), String> {
-    // Stop existing capture
-    {
-        let mut app_state = state.0.lock();
-        if let Some(capture) = app_state.audio_capture.take() {
-            capture.stop();
-        }
-    }
+#[allow(clippy::too_many_arguments)]
+async fn set_voice_config(
+    engine: State<'_, McavEngine>,
+    channel_type: String,
+    distance: f64,
+    zone: Option<String>,
+    vad_threshold: Option<f32>,
+    vad_hold_ms: Option<u32>,
+    frame_ms: Option<f32>,
+    stereo: Option<bool>,
+    fec: Option<bool>,
+    packet_loss_perc: Option<u8>,
+    dtx: Option<bool>,
+) -> Result<(), DjError> {
+    let mut config = engine.state().lock().voice_config.clone();
+    config.channel_type = channel_type;
+    config.distance = distance;
+    config.zone = zone.unwrap_or(config.zone);
+    config.vad_threshold = vad_threshold.unwrap_or(config.vad_threshold);
+    config.vad_hold_ms = vad_hold_ms.unwrap_or(config.vad_hold_ms);
+    config.frame_ms = frame_ms.unwrap_or(config.frame_ms);
+    config.stereo = stereo.unwrap_or(config.stereo);
+    config.fec = fec.unwrap_or(config.fec);
+    config.packet_loss_perc = packet_loss_perc.unwrap_or(config.packet_loss_perc);
+    config.dtx = dtx.unwrap_or(config.dtx);
+    engine.set_voice_config(config).await
+}
 
-    // Create new voice streamer
-    let voice_streamer = Arc::new(VoiceStreamer::new(48000, 2));
+/// Select the input device voice is captured from.
+///
+/// Accepts a device name or an `input:` source id; `None` (or an empty name)
+/// goes back to sending the music mix. Takes effect immediately if capture
+/// is running.
+#[tauri::command]
+fn set_voice_input_device(
+    engine: State<'_, McavEngine>,
+    device: Option<String>,
+) -> Result<(), DjError> {
+    engine.set_voice_input_device(device)
+}
 
-    // Propagate voice config
-    {
-        let app_state = state.0.lock();
-        voice_streamer.set_enabled(app_state.voice_config.enabled);
-    }
+/// Update the music ducking envelope applied while the DJ is talking;
+/// `depth` must be between 0 and 1
+#[tauri::command]
+fn set_voice_ducking(
+    engine: State<'_, McavEngine>,
+    attack_ms: u32,
+    release_ms: u32,
+    depth: f32,
+) -> Result<(), DjError> {
+    engine.set_voice_ducking(attack_ms, release_ms, depth)
+}
+
+/// List available audio presets
+#[tauri::command]
+fn list_presets() -> Vec<AudioPreset> {
+    audio::get_presets()
+}
+
+/// Get the currently active preset name
+#[tauri::command]
+fn get_current_preset(state: State<'_, AppStateWrapper>) -> String {
+    state.0.lock().active_preset.name.clone()
+}
 
-    // Start new capture
-    let capture =
-        AudioCaptureHandle::new_with_voice(source_id.clone(), Some(voice_streamer.clone()))
-            .map_err(|e| e.to_string())?;
+/// Apply an audio preset by name
+#[tauri::command]
+fn set_preset(engine: State<'_, McavEngine>, name: String) -> Result<String, String> {
+    engine.set_preset(&name)
+}
 
-    let mut app_state = state.0.lock();
+/// Apply the next preset in `list_presets` order, wrapping around; returns
+/// its name
+#[tauri::command]
+fn next_preset(engine: State<'_, McavEngine>) -> Result<String, String> {
+    engine.step_preset(1)
+}
 
-    // Apply active preset
-    if let Some(preset) = audio::get_preset(&app_state.active_preset) {
-        capture.analyzer().lock().apply_preset(&preset);
-    }
+/// Apply the previous preset in `list_presets` order, wrapping around;
+/// returns its name
+#[tauri::command]
+fn prev_preset(engine: State<'_, McavEngine>) -> Result<String, String> {
+    engine.step_preset(-1)
+}
 
-    app_state.audio_source_id = source_id;
-    app_state.audio_capture = Some(capture);
-    app_state.voice_streamer = Some(voice_streamer);
-    drop(app_state);
-
-    // Emit capture mode after a brief delay for the audio thread to initialize
-    let state_for_mode = state.0.clone();
-    tokio::spawn(async move {
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        let mode = {
-            let app_state = state_for_mode.lock();
-            app_state
-                .audio_capture
-                .as_ref()
-                .map(|c| c.get_capture_mode())
-        };
-        if let Some(mode) = mode {
-            let _ = app_handle.emit("capture-mode", &mode);
-        }
-    });
+/// Apply the preset at `index` in `list_presets` order; returns its name
+#[tauri::command]
+fn set_preset_by_index(engine: State<'_, McavEngine>, index: usize) -> Result<String, String> {
+    engine.set_preset_by_index(index)
+}
 
-    Ok(())
+/// Trim the bands on top of the active preset (e.g. `[0.8, 1, 1, 1, 1]`
+/// pulls the bass down 20%); returns the trim as applied
+#[tauri::command]
+fn set_band_trim(engine: State<'_, McavEngine>, trim: [f32; 5]) -> [f32; 5] {
+    engine.set_band_trim(trim)
 }
 
-/// Capture status response
-#[derive(Clone, serde::Serialize)]
-pub struct CaptureStatus {
-    pub active: bool,
-    pub source_id: Option<String>,
-    pub capture_mode: Option<CaptureMode>,
+/// Clear the band trim back to the preset's own sensitivity
+#[tauri::command]
+fn reset_band_trim(engine: State<'_, McavEngine>) -> [f32; 5] {
+    engine.set_band_trim([1.0; 5])
 }
 
-/// Get current capture status
+/// Double (`"up"`) or halve (`"down"`) the detected tempo without
+/// resetting beat detection; returns the new BPM
 #[tauri::command]
-fn get_capture_status(state: State<'_, AppStateWrapper>) -> CaptureStatus {
-    let app_state = state.0.lock();
-    let capture_mode = app_state
-        .audio_capture
-        .as_ref()
-        .map(|c| c.get_capture_mode());
-    CaptureStatus {
-        active: app_state.audio_capture.is_some(),
-        source_id: app_state.audio_source_id.clone(),
-        capture_mode,
-    }
+fn nudge_tempo_octave(
+    engine: State<'_, McavEngine>,
+    direction: audio::TempoOctave,
+) -> Result<f32, DjError> {
+    engine.nudge_tempo_octave(direction)
 }
 
-/// Disconnect from VJ server
+/// Set the pre-analysis input gain in dB (-24 to +24); returns the gain as
+/// applied
 #[tauri::command]
-async fn disconnect(state: State<'_, AppStateWrapper>) -> Result<(), String> {
-    // Signal bridge task to stop (it handles client disconnect)
-    let (shutdown_tx, bridge_handle, capture, voice_streamer) = {
-        let mut app_state = state.0.lock();
-        (
-            app_state.bridge_shutdown_tx.take(),
-            app_state.bridge_task_handle.take(),
-            app_state.audio_capture.take(),
-            app_state.voice_streamer.take(),
-        )
-    };
+fn set_input_gain(engine: State<'_, McavEngine>, db: f32) -> f32 {
+    engine.set_input_gain(db)
+}
 
-    // Disable voice streaming
-    if let Some(ref streamer) = voice_streamer {
-        streamer.set_enabled(false);
-    }
+/// Turn automatic input level normalization on or off
+#[tauri::command]
+fn set_auto_normalize(engine: State<'_, McavEngine>, enabled: bool) {
+    engine.set_auto_normalize(enabled)
+}
 
-    if let Some(tx) = shutdown_tx {
-        let _ = tx.send(()).await;
-        // Await the bridge task handle instead of a fixed sleep
-        if let Some(handle) = bridge_handle {
-            let _ = tokio::time::timeout(Duration::from_millis(500), handle).await;
-        }
-    } else {
-        // No bridge task running, disconnect client directly
-        let client = {
-            let mut app_state = state.0.lock();
-            app_state.client.take()
-        };
-        if let Some(client) = client {
-            let _ = client.disconnect().await;
-        }
-    }
+/// Choose the beat detection input: `"bass_band"`, `"bass_lane"` or
+/// `"combined"`
+#[tauri::command]
+fn set_beat_source(engine: State<'_, McavEngine>, source: audio::BeatSource) {
+    engine.set_beat_source(source)
+}
 
-    // Stop audio capture
-    if let Some(capture) = capture {
-        capture.stop();
-    }
+/// Report bands as `"linear"` or `"db"`; in dB mode `floor_db` (default
+/// -60, kept when omitted) is shown as 0. Returns the floor as applied.
+#[tauri::command]
+fn set_band_scale(
+    engine: State<'_, McavEngine>,
+    scale: audio::BandScale,
+    floor_db: Option<f32>,
+) -> f32 {
+    engine.set_band_scale(scale, floor_db)
+}
 
-    // Reset status
-    {
-        let mut app_state = state.0.lock();
-        app_state.status.connected = false;
-        app_state.status.is_active = false;
-        app_state.status.latency_ms = 0.0;
-        app_state.status.route_mode = String::new();
-        app_state.status.mc_connected = false;
-        app_state.status.error = None;
-        app_state.voice_config.enabled = false;
-        app_state.voice_status = VoiceStatus::default();
-    }
+/// Turn the per-band AGC off for absolute band levels (e.g. a pre-rendered
+/// set), or back on
+#[tauri::command]
+fn set_agc(engine: State<'_, McavEngine>, enabled: bool) {
+    engine.set_agc(enabled)
+}
 
-    Ok(())
+/// Keep the show's intensity steady across preset switches by matching the
+/// new preset's average peak to the old one's
+#[tauri::command]
+fn set_normalize_across_presets(engine: State<'_, McavEngine>, enabled: bool) {
+    engine.set_normalize_across_presets(enabled)
 }
 
-/// Enable or disable voice audio streaming
+/// Add each band's rise since the last frame, times `emphasis` (0-4), on
+/// top of the smoothed level for a punchier look; 0 turns it off
 #[tauri::command]
-async fn set_voice_streaming(
-    app_handle: AppHandle,
-    state: State<'_, AppStateWrapper>,
-    enabled: bool,
-) -> Result<(), String> {
-    let (voice_streamer, tx, voice_config) = {
-        let mut app_state = state.0.lock();
-        app_state.voice_config.enabled = enabled;
-
-        let streamer = app_state.voice_streamer.clone();
-        let tx = app_state.client.as_ref().and_then(|c| c.get_tx_clone());
-        let config = app_state.voice_config.clone();
-        (streamer, tx, config)
-    };
+fn set_transient_emphasis(engine: State<'_, McavEngine>, emphasis: f32) -> Result<(), DjError> {
+    engine.set_transient_emphasis(emphasis)
+}
 
-    // Update streamer state
-    if let Some(ref streamer) = voice_streamer {
-        streamer.set_enabled(enabled);
-    }
+/// Retune the band edges: six increasing frequencies in Hz, e.g.
+/// `[20, 90, 250, 2000, 8000, 20000]` splits sub from low bass
+#[tauri::command]
+fn set_band_edges(engine: State<'_, McavEngine>, edges: [f32; 6]) -> Result<(), DjError> {
+    engine.set_band_edges(edges)
+}
 
-    // Send voice_config message to VJ server
-    if let Some(tx) = tx {
-        let msg = protocol::VoiceConfigMessage::new(
-            enabled,
-            voice_config.channel_type.clone(),
-            voice_config.distance,
-            voice_config.zone.clone(),
-        );
-        if let Ok(json) = serde_json::to_string(&msg) {
-            let _ = tx.send(Message::Text(json.into())).await;
-        }
-    }
+/// Names of the MIDI input ports currently available (empty in builds
+/// without MIDI support)
+#[tauri::command]
+fn list_midi_ports() -> Vec<String> {
+    midi::list_ports()
+}
+
+/// Replace the MIDI controller mapping
+#[tauri::command]
+fn set_midi_mapping(engine: State<'_, McavEngine>, mapping: midi::MidiMapping) {
+    engine.set_midi_mapping(mapping)
+}
 
-    // Emit updated voice status
-    {
-        let app_state = state.0.lock();
-        let _ = app_handle.emit("voice-status", &app_state.voice_status);
+/// Open a MIDI input (the first one when `port` is omitted) or close it;
+/// returns the open port's name. Every message is emitted as `midi-event`.
+#[tauri::command]
+async fn enable_midi(
+    engine: State<'_, McavEngine>,
+    enabled: bool,
+    port: Option<String>,
+) -> Result<Option<String>, DjError> {
+    if !enabled {
+        engine.disable_midi();
+        return Ok(None);
     }
+    engine.enable_midi(port.as_deref()).await.map(Some)
+}
 
-    Ok(())
+/// Send bands, beats, BPM and peak over OSC to `host:port`, `rate` times a
+/// second (1-120)
+#[tauri::command]
+fn enable_osc(
+    engine: State<'_, McavEngine>,
+    host: String,
+    port: u16,
+    rate: u32,
+) -> Result<(), DjError> {
+    engine.enable_osc(&host, port, rate)
 }
 
-/// Get current voice streaming status
+/// Stop the OSC output
 #[tauri::command]
-fn get_voice_status(state: State<'_, AppStateWrapper>) -> VoiceStatus {
-    let app_state = state.0.lock();
-    app_state.voice_status.clone()
+fn disable_osc(engine: State<'_, McavEngine>) {
+    engine.disable_osc()
 }
 
-/// Update voice streaming configuration
+/// Stream analysis frames as JSON to WebSocket clients on
+/// `ws://127.0.0.1:<port>` (0 picks a free port); returns the port
 #[tauri::command]
-async fn set_voice_config(
-    state: State<'_, AppStateWrapper>,
-    channel_type: String,
-    distance: f64,
-) -> Result<(), String> {
-    let tx = {
-        let mut app_state = state.0.lock();
-        app_state.voice_config.channel_type = channel_type.clone();
-        app_state.voice_config.distance = distance;
+async fn enable_analysis_server(engine: State<'_, McavEngine>, port: u16) -> Result<u16, DjError> {
+    engine.enable_analysis_server(port).await
+}
+
+/// Stop the local analysis stream
+#[tauri::command]
+fn disable_analysis_server(engine: State<'_, McavEngine>) {
+    engine.disable_analysis_server()
+}
+
+/// Path of the settings file in the app config dir
+fn settings_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map(|dir| dir.join(settings::SETTINGS_FILE))
+        .map_err(|e| e.to_string())
+}
+
+/// Export the active preset (with live band trim), beat assist and capture
+/// options as shareable JSON
+#[tauri::command]
+fn export_config(engine: State<'_, McavEngine>) -> Result<String, DjError> {
+    serde_json::to_string_pretty(&engine.export_setup())
+        .map_err(|e| DjError::Internal(e.to_string()))
+}
+
+/// Apply a setup JSON from `export_config`, e.g. one another DJ shared
+#[tauri::command]
+fn import_config(engine: State<'_, McavEngine>, json: String) -> Result<(), DjError> {
+    let setup = settings::SharedSetup::parse(&json).map_err(DjError::InvalidInput)?;
+    engine.import_setup(setup)
+}
+
+/// Reload settings from disk, apply them and return them as applied
+#[tauri::command]
+fn load_settings(engine: State<'_, McavEngine>) -> Result<Settings, DjError> {
+    engine.load_settings()
+}
+
+/// Apply settings and write them to disk; out-of-range values are clamped.
+/// Returns the settings as applied.
+#[tauri::command]
+fn save_settings(engine: State<'_, McavEngine>, settings: Settings) -> Result<Settings, DjError> {
+    engine.save_settings(settings)
+}
+
+/// Block an extra term in the content filter and save it
+#[tauri::command]
+fn add_blocked_word(engine: State<'_, McavEngine>, word: String) -> content_filter::WordLists {
+    content_filter::global().write().add_blocked_word(&word);
+    save_filter_words(&engine)
+}
+
+/// Whitelist a term in the content filter and save it
+#[tauri::command]
+fn add_allowed_word(engine: State<'_, McavEngine>, word: String) -> content_filter::WordLists {
+    content_filter::global().write().add_allowed_word(&word);
+    save_filter_words(&engine)
+}
+
+/// Persist settings after a word list change and return the new lists
+fn save_filter_words(engine: &McavEngine) -> content_filter::WordLists {
+    let settings = Settings::from_state(&engine.state().lock());
+    engine.persist_settings(&settings);
+    settings.filter_words
+}
 
-        app_state.client.as_ref().and_then(|c| c.get_tx_clone())
+/// Run a Lua pattern offline against fixture audio and return the
+/// entities of each frame, or the Lua error
+#[tauri::command]
+async fn preview_pattern(
+    source: String,
+    fixture: patterns::PreviewFixture,
+    entity_count: Option<u32>,
+    lib_source: Option<String>,
+) -> Result<Vec<Vec<serde_json::Value>>, String> {
+    let config = patterns::PatternConfig {
+        entity_count: entity_count.unwrap_or(patterns::PatternConfig::default().entity_count),
+        ..Default::default()
     };
+    // Lua runs on a blocking thread so a slow script can't stall the runtime
+    tokio::task::spawn_blocking(move || {
+        patterns::preview_pattern(&source, lib_source.as_deref(), &fixture, config)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    // Send updated voice_config to server if connected
-    if let Some(tx) = tx {
-        let config = {
-            let app_state = state.0.lock();
-            app_state.voice_config.clone()
-        };
-        let msg = protocol::VoiceConfigMessage::new(
-            config.enabled,
-            config.channel_type,
-            config.distance,
-            config.zone,
-        );
-        if let Ok(json) = serde_json::to_string(&msg) {
-            let _ = tx.send(Message::Text(json.into())).await;
-        }
-    }
+/// Get the synthetic beat assist settings
+#[tauri::command]
+fn get_beat_assist_config(engine: State<'_, McavEngine>) -> state::BeatAssistConfig {
+    engine.beat_assist_config()
+}
 
-    Ok(())
+/// Update the synthetic beat assist and save it; out-of-range values are
+/// clamped. Returns the config as applied.
+#[tauri::command]
+fn set_beat_assist_config(
+    engine: State<'_, McavEngine>,
+    config: state::BeatAssistConfig,
+) -> state::BeatAssistConfig {
+    engine.set_beat_assist_config(config)
 }
 
-/// List available audio presets
+/// Get the bridge frame rate (VJ frames per second)
 #[tauri::command]
-fn list_presets() -> Vec<AudioPreset> {
-    audio::get_presets()
+fn get_bridge_fps(state: State<'_, AppStateWrapper>) -> f64 {
+    1.0 / state.0.lock().bridge_tick.as_secs_f64()
 }
 
-/// Get the currently active preset name
+/// Set the bridge frame rate (20-120fps); a running bridge picks it up on
+/// its next tick.
+///
+/// This paces VJ frames and UI events only. Direct Minecraft publishing is
+/// off, and the VJ server paces its own Minecraft stream independently.
 #[tauri::command]
-fn get_current_preset(state: State<'_, AppStateWrapper>) -> String {
-    state.0.lock().active_preset.clone()
+fn set_bridge_fps(engine: State<'_, McavEngine>, fps: u32) -> Result<(), DjError> {
+    engine.set_bridge_fps(fps)
 }
 
-/// Apply an audio preset by name
+/// Low-power mode: halve the bridge frame rate and the UI event rates, for
+/// long sets on battery
 #[tauri::command]
-fn set_preset(state: State<'_, AppStateWrapper>, name: String) -> Result<String, String> {
-    let preset = audio::get_preset(&name).ok_or_else(|| format!("Unknown preset: {}", name))?;
-    let mut app_state = state.0.lock();
-    if let Some(ref capture) = app_state.audio_capture {
-        capture.analyzer().lock().apply_preset(&preset);
-    }
-    app_state.active_preset = preset.name.clone();
+fn set_low_power(engine: State<'_, McavEngine>, enabled: bool) {
+    engine.set_low_power(enabled)
+}
 
-    // Send preferred preset to VJ server so it persists across DJ swaps
-    if let Some(tx) = app_state.client.as_ref().and_then(|c| c.get_tx_clone())
-        && let Ok(json) = serde_json::to_string(&serde_json::json!({
-            "type": "set_my_preset",
-            "preset": &preset.name
-        }))
-    {
-        let _ = tx.try_send(Message::Text(json.into()));
-    }
+/// Cap the entity count the server asks for (1-512). A capped count shows in
+/// the status as requested vs effective, with an `entity-count-clamped` event.
+#[tauri::command]
+fn set_max_entity_count(engine: State<'_, McavEngine>, max: u32) -> Result<(), DjError> {
+    engine.set_max_entity_count(max)
+}
+
+/// Hide mirrored entities while their band is below `threshold` (0-1), so
+/// quiet passages thin out; `None` turns culling off
+#[tauri::command]
+fn set_visibility_threshold(
+    engine: State<'_, McavEngine>,
+    threshold: Option<f32>,
+) -> Result<(), DjError> {
+    engine.set_visibility_threshold(threshold)
+}
+
+/// Let the sent beat intensity fade over `release_ms` (20-2000) between
+/// beats instead of snapping to zero; `None` turns the envelope off
+#[tauri::command]
+fn set_beat_release(engine: State<'_, McavEngine>, release_ms: Option<u32>) -> Result<(), DjError> {
+    engine.set_beat_release(release_ms)
+}
 
-    Ok(preset.name)
+/// Drive everything from one band (0 = bass ... 4 = air) to check its
+/// mapping without muting the source; `None` restores all bands
+#[tauri::command]
+fn solo_band(engine: State<'_, McavEngine>, band: Option<usize>) -> Result<(), DjError> {
+    engine.set_solo_band(band)
+}
+
+/// Get the bridge loop counters from the last one-second window
+#[tauri::command]
+fn get_bridge_stats(state: State<'_, AppStateWrapper>) -> state::BridgeStats {
+    state.0.lock().bridge_stats.clone()
 }
 
 /// Get current connection status
@@ -960,50 +767,133 @@ fn get_status(state: State<'_, AppStateWrapper>) -> state::ConnectionStatus {
 
 /// Get current audio levels (5 bands + peak + beat info)
 #[tauri::command]
-fn get_audio_levels(state: State<'_, AppStateWrapper>) -> AudioLevels {
-    let mut app_state = state.0.lock();
-
-    // Get latest analysis from capture if running
-    if let Some(ref capture) = app_state.audio_capture {
-        let result = capture.get_analysis();
-        app_state.bands = result.bands;
-        app_state.peak = result.peak;
-        app_state.is_beat = result.is_beat;
-        app_state.beat_intensity = result.beat_intensity;
-        app_state.bpm = result.bpm;
-    }
-
-    AudioLevels {
-        bands: app_state.bands,
-        peak: app_state.peak,
-        is_beat: app_state.is_beat,
-        beat_intensity: app_state.beat_intensity,
-        bpm: app_state.bpm,
-    }
+fn get_audio_levels(engine: State<'_, McavEngine>) -> AudioLevels {
+    engine.audio_levels()
 }
 
-/// Audio levels response
+/// Bass lane readings for threshold calibration
 #[derive(Clone, serde::Serialize)]
-pub struct AudioLevels {
-    pub bands: [f32; 5],
-    pub peak: f32,
-    pub is_beat: bool,
-    pub beat_intensity: f32,
-    pub bpm: f32,
+pub struct BassLaneLevels {
+    pub instant_bass: f32,
+    pub instant_kick: bool,
+    pub raw_envelope: f32,
+    pub running_peak: f32,
 }
 
-/// Update the system tray tooltip based on connection status
-fn update_tray_tooltip(app: &AppHandle, connected: bool) {
+/// Get the bass lane's raw envelope and normalization peak, if capturing
+#[tauri::command]
+fn get_bass_lane_levels(state: State<'_, AppStateWrapper>) -> Option<BassLaneLevels> {
+    let app_state = state.0.lock();
+    let result = app_state.audio_capture.as_ref()?.get_analysis();
+    Some(BassLaneLevels {
+        instant_bass: result.instant_bass,
+        instant_kick: result.instant_kick,
+        raw_envelope: result.bass_raw_envelope,
+        running_peak: result.bass_running_peak,
+    })
+}
+
+/// Get the beat detector's live thresholds for a tuning screen, if capturing
+#[tauri::command]
+fn get_beat_debug(engine: State<'_, McavEngine>) -> Option<audio::BeatDebug> {
+    engine.beat_debug()
+}
+
+/// Time the analyzer over a synthetic signal `iterations` times without
+/// touching the live capture
+#[tauri::command]
+async fn benchmark_analysis(
+    engine: State<'_, McavEngine>,
+    iterations: usize,
+) -> Result<audio::AnalysisBenchmark, DjError> {
+    let engine = engine.inner().clone();
+    tokio::task::spawn_blocking(move || engine.benchmark_analysis(iterations))
+        .await
+        .map_err(|e| DjError::Internal(e.to_string()))?
+}
+
+/// Get the pattern scripts the server pushed and the one running locally
+#[tauri::command]
+fn get_patterns(engine: State<'_, McavEngine>) -> state::PatternList {
+    engine.patterns()
+}
+
+/// Get the features this build and platform support (Opus, per-app
+/// capture, Process Loopback) so the UI can hide the rest
+#[tauri::command]
+fn get_capabilities() -> engine::Capabilities {
+    engine::Capabilities::detect()
+}
+
+/// Get status, levels, voice, preset and capture info in one call
+#[tauri::command]
+fn get_snapshot(engine: State<'_, McavEngine>) -> AppSnapshot {
+    engine.snapshot()
+}
+
+/// Update the system tray tooltip from the connection status
+fn update_tray_tooltip(app: &AppHandle, tooltip: &str) {
     if let Some(tray) = app.tray_by_id("main-tray") {
-        let tooltip = if connected {
-            "MCAV DJ - Connected"
-        } else {
-            "MCAV DJ - Disconnected"
-        };
         let _ = tray.set_tooltip(Some(tooltip));
     }
 }
 
+/// Re-emit engine events as Tauri events named after the event variant,
+/// keeping the tray tooltip in step with the connection status
+async fn forward_engine_events(app: AppHandle, mut events: broadcast::Receiver<EngineEvent>) {
+    let mut tooltip = String::new();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::debug!("Dropped {} engine events (UI forwarder lagging)", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if let EngineEvent::DjStatus(ref status) = event {
+            let summary = status.tray_summary();
+            if summary != tooltip {
+                update_tray_tooltip(&app, &summary);
+                tooltip = summary;
+            }
+        }
+        if let Ok(serde_json::Value::Object(mut event)) = serde_json::to_value(&event)
+            && let (Some(serde_json::Value::String(name)), Some(payload)) =
+                (event.remove("event"), event.remove("payload"))
+        {
+            let _ = app.emit(&name, payload);
+        }
+    }
+}
+
+/// Longest quitting waits on the disconnect before exiting anyway
+const QUIT_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Disconnect first so `going_offline` reaches the server and the next DJ
+/// is promoted straight away, then exit
+fn quit_gracefully(app: &AppHandle) {
+    let app = app.clone();
+    let engine = app.state::<McavEngine>().inner().clone();
+    tauri::async_runtime::spawn(async move {
+        // Disconnect also stops capture and voice input
+        if tokio::time::timeout(QUIT_DISCONNECT_TIMEOUT, engine.disconnect())
+            .await
+            .is_err()
+        {
+            log::warn!("Disconnect timed out while quitting");
+        }
+        app.exit(0);
+    });
+}
+
+/// Recent log lines (info and above, oldest first) for a "copy
+/// diagnostics" button; `limit` defaults to everything kept
+#[tauri::command]
+fn get_logs(limit: Option<usize>) -> Vec<logs::LogLine> {
+    logs::recent(limit.unwrap_or(logs::LOG_CAPACITY))
+}
+
 /// Show the main window
 #[tauri::command]
 fn show_window(app: AppHandle) -> Result<(), String> {
@@ -1017,7 +907,7 @@ fn show_window(app: AppHandle) -> Result<(), String> {
 /// Initialize the Tauri application
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::init();
+    logs::init();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
@@ -1031,27 +921,99 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_updater::Builder::new().build())
-        .manage(AppStateWrapper(Arc::new(Mutex::new(AppState::default()))))
         .invoke_handler(tauri::generate_handler![
             list_audio_sources,
+            list_sources_filtered,
+            refresh_sources,
             connect_with_code,
+            connect_with_token,
             connect_direct,
+            test_connection,
             start_capture,
             stop_capture,
+            pause_capture,
+            resume_capture,
+            play_file,
+            pause_file,
+            seek_file,
+            set_file_looping,
+            get_file_playback,
             change_audio_source,
             get_capture_status,
             disconnect,
+            force_reconnect,
+            cancel_reconnect,
             get_status,
             get_audio_levels,
+            get_bass_lane_levels,
+            get_beat_debug,
+            benchmark_analysis,
+            get_patterns,
+            get_capabilities,
+            get_snapshot,
+            get_logs,
             set_voice_streaming,
             get_voice_status,
             set_voice_config,
+            get_zones,
+            set_voice_ducking,
+            set_voice_input_device,
             list_presets,
             get_current_preset,
             set_preset,
+            next_preset,
+            prev_preset,
+            set_preset_by_index,
+            set_band_trim,
+            reset_band_trim,
+            nudge_tempo_octave,
+            trigger_beat,
+            send_test_frame,
+            set_source_gain,
+            set_input_gain,
+            set_auto_normalize,
+            set_beat_source,
+            set_band_scale,
+            set_agc,
+            set_normalize_across_presets,
+            set_transient_emphasis,
+            export_config,
+            import_config,
+            set_band_edges,
+            list_midi_ports,
+            set_midi_mapping,
+            enable_midi,
+            enable_osc,
+            disable_osc,
+            enable_analysis_server,
+            disable_analysis_server,
+            load_settings,
+            save_settings,
+            add_blocked_word,
+            add_allowed_word,
+            get_bridge_stats,
+            preview_pattern,
+            get_bridge_fps,
+            set_bridge_fps,
+            set_low_power,
+            set_max_entity_count,
+            set_visibility_threshold,
+            solo_band,
+            set_beat_release,
+            get_beat_assist_config,
+            set_beat_assist_config,
             show_window,
         ])
         .setup(|app| {
+            // The engine needs the config dir, so it's created (and managed) here
+            let engine = McavEngine::new(settings_path(app.handle()).ok());
+            app.manage(AppStateWrapper(engine.state().clone()));
+            app.manage(engine.clone());
+            tauri::async_runtime::spawn(forward_engine_events(
+                app.handle().clone(),
+                engine.subscribe(),
+            ));
+
             // Create system tray menu
             let show_item = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
             let disconnect_item =
@@ -1073,18 +1035,15 @@ pub fn run() {
                         }
                     }
                     "disconnect" => {
-                        let state = app.state::<AppStateWrapper>();
-                        let is_connected = state.0.lock().status.connected;
+                        let engine = app.state::<McavEngine>().inner().clone();
+                        let is_connected = engine.state().lock().status.connected;
                         if is_connected {
-                            let app_clone = app.clone();
                             tauri::async_runtime::spawn(async move {
-                                let _ = disconnect(app_clone.state::<AppStateWrapper>()).await;
+                                engine.disconnect().await;
                             });
                         }
                     }
-                    "quit" => {
-                        app.exit(0);
-                    }
+                    "quit" => quit_gracefully(app),
                     _ => {}
                 })
                 .on_tray_icon_event(|tray, event| {
@@ -1099,29 +1058,45 @@ pub fn run() {
                 })
                 .build(app)?;
 
-            // Clean up on window close: stop capture and disconnect before exiting
+            // Restore the previous session's settings
+            if let Err(e) = engine.load_settings() {
+                log::warn!("Failed to load settings, using defaults: {}", e);
+            }
+
+            tauri::async_runtime::spawn(engine.clone().run_capture_monitor());
+
+            // Push source list updates when audio devices come and go
             {
-                let app_handle = app.handle().clone();
-                if let Some(window) = app.get_webview_window("main") {
-                    window.on_window_event(move |event| {
-                        if let tauri::WindowEvent::CloseRequested { .. } = event {
-                            let state = app_handle.state::<AppStateWrapper>();
-                            let mut app_state = state.0.lock();
-                            if let Some(capture) = app_state.audio_capture.take() {
-                                capture.stop();
-                            }
-                            app_state.voice_streamer = None;
-                            // Disconnect happens async; drop the lock first
-                            drop(app_state);
-                            let state_clone = app_handle.state::<AppStateWrapper>();
-                            tauri::async_runtime::block_on(async {
-                                let _ = disconnect(state_clone).await;
-                            });
-                        }
-                    });
+                let engine = engine.clone();
+                if let Err(e) = audio::spawn_device_watcher(move || {
+                    if let Err(e) = engine.refresh_sources() {
+                        log::warn!("Source rescan after device change failed: {}", e);
+                    }
+                }) {
+                    log::warn!("Failed to start audio device watcher: {}", e);
                 }
             }
 
+            // Closing the window exits the app: hold the close until the
+            // disconnect has gone out. Hiding or minimizing it slows the
+            // bridge's UI emits; both show up as focus or size changes.
+            if let Some(window) = app.get_webview_window("main") {
+                let handle = app.handle().clone();
+                let events_window = window.clone();
+                window.on_window_event(move |event| match event {
+                    tauri::WindowEvent::CloseRequested { api, .. } => {
+                        api.prevent_close();
+                        quit_gracefully(&handle);
+                    }
+                    tauri::WindowEvent::Focused(_) | tauri::WindowEvent::Resized(_) => {
+                        let visible = events_window.is_visible().unwrap_or(true)
+                            && !events_window.is_minimized().unwrap_or(false);
+                        handle.state::<McavEngine>().set_window_visible(visible);
+                    }
+                    _ => {}
+                });
+            }
+
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,139 @@
+//! In-memory log capture for bug reports
+//!
+//! `init` installs a logger that hands every record to `env_logger` as
+//! before and also keeps the last `LOG_CAPACITY` lines at info and above in
+//! a ring buffer, whatever `RUST_LOG` says. The frontend reads them with
+//! `get_logs` for its "copy diagnostics" button, so a DJ can attach
+//! connection, reconnect and capture history without a terminal.
+
+use log::{LevelFilter, Log, Metadata, Record};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+
+/// Lines kept in the ring buffer; the oldest are dropped first
+pub const LOG_CAPACITY: usize = 1000;
+
+/// Most verbose level captured, independent of `RUST_LOG`
+const CAPTURE_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// App-wide buffer behind `recent`
+static BUFFER: LazyLock<Mutex<LogBuffer>> =
+    LazyLock::new(|| Mutex::new(LogBuffer::new(LOG_CAPACITY)));
+
+/// One captured log record
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogLine {
+    /// Unix time in seconds
+    pub ts: f64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// Fixed-size ring of the most recent log lines
+#[derive(Debug)]
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append a line, dropping the oldest once full
+    pub fn push(&mut self, line: LogLine) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Up to `limit` of the newest lines, oldest first
+    pub fn recent(&self, limit: usize) -> Vec<LogLine> {
+        let skip = self.lines.len().saturating_sub(limit);
+        self.lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// `env_logger` plus the ring buffer
+struct CaptureLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for CaptureLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= CAPTURE_LEVEL || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if record.level() <= CAPTURE_LEVEL {
+            BUFFER.lock().push(LogLine {
+                ts: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+                level: record.level().to_string(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the capturing logger in place of `env_logger::init()`
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(CAPTURE_LEVEL);
+    if log::set_boxed_logger(Box::new(CaptureLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Up to `limit` of the newest captured lines, oldest first
+pub fn recent(limit: usize) -> Vec<LogLine> {
+    BUFFER.lock().recent(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(message: &str) -> LogLine {
+        LogLine {
+            ts: 0.0,
+            level: "INFO".to_string(),
+            target: "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn buffer_keeps_only_the_newest_lines() {
+        let mut buffer = LogBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(line(&i.to_string()));
+        }
+
+        let messages: Vec<_> = buffer.recent(10).into_iter().map(|l| l.message).collect();
+        assert_eq!(messages, ["2", "3", "4"]);
+        let newest: Vec<_> = buffer.recent(1).into_iter().map(|l| l.message).collect();
+        assert_eq!(newest, ["4"]);
+    }
+}
@@ -0,0 +1,321 @@
+//! MIDI controller input
+//!
+//! Maps notes and CCs from a hardware controller to client actions (preset
+//! switching, manual beats, voice, effects). Every incoming message is also
+//! surfaced as a `midi-event` so the UI can offer a learn mode.
+//!
+//! Port access needs the `midi` feature (midir); without it no ports are
+//! listed and enabling MIDI fails with a clear message.
+
+use serde::{Deserialize, Serialize};
+
+/// CC values at or above this count as a button press
+const CC_PRESS_THRESHOLD: u8 = 64;
+
+/// Kind of channel message we listen for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidiMessageKind {
+    NoteOn,
+    NoteOff,
+    ControlChange,
+}
+
+/// A parsed MIDI message, emitted as `midi-event`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MidiEvent {
+    pub port: String,
+    pub kind: MidiMessageKind,
+    /// 0-15
+    pub channel: u8,
+    /// Note or controller number
+    pub number: u8,
+    /// Velocity or CC value
+    pub value: u8,
+}
+
+impl MidiEvent {
+    /// Parse a raw message; anything but note on/off and CC is ignored.
+    /// Note on with velocity 0 is reported as note off, as most gear sends it.
+    pub fn parse(port: &str, bytes: &[u8]) -> Option<Self> {
+        let [status, number, value, ..] = *bytes else {
+            return None;
+        };
+        let kind = match status & 0xF0 {
+            0x90 if value > 0 => MidiMessageKind::NoteOn,
+            0x80 | 0x90 => MidiMessageKind::NoteOff,
+            0xB0 => MidiMessageKind::ControlChange,
+            _ => return None,
+        };
+        Some(Self {
+            port: port.to_string(),
+            kind,
+            channel: status & 0x0F,
+            number: number & 0x7F,
+            value: value & 0x7F,
+        })
+    }
+
+    /// Whether this is a button going down (note on, or a CC at or above
+    /// `CC_PRESS_THRESHOLD`)
+    fn is_press(&self) -> bool {
+        match self.kind {
+            MidiMessageKind::NoteOn => true,
+            MidiMessageKind::NoteOff => false,
+            MidiMessageKind::ControlChange => self.value >= CC_PRESS_THRESHOLD,
+        }
+    }
+}
+
+/// Control a binding listens to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MidiTrigger {
+    Note {
+        /// Any channel when unset
+        #[serde(default)]
+        channel: Option<u8>,
+        note: u8,
+    },
+    Cc {
+        /// Any channel when unset
+        #[serde(default)]
+        channel: Option<u8>,
+        controller: u8,
+    },
+}
+
+impl MidiTrigger {
+    fn matches(&self, event: &MidiEvent) -> bool {
+        let (channel, number, is_note) = match *self {
+            MidiTrigger::Note { channel, note } => (channel, note, true),
+            MidiTrigger::Cc {
+                channel,
+                controller,
+            } => (channel, controller, false),
+        };
+        let kind_matches = if is_note {
+            event.kind != MidiMessageKind::ControlChange
+        } else {
+            event.kind == MidiMessageKind::ControlChange
+        };
+        kind_matches && number == event.number && channel.is_none_or(|c| c == event.channel)
+    }
+}
+
+fn full_intensity() -> f32 {
+    1.0
+}
+
+/// What a binding does when its control is pressed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MidiAction {
+    NextPreset,
+    PrevPreset,
+    SetPreset {
+        name: String,
+    },
+    TriggerBeat {
+        #[serde(default = "full_intensity")]
+        intensity: f32,
+    },
+    ToggleVoice,
+    FireEffect {
+        effect: String,
+    },
+}
+
+/// One control-to-action binding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    pub trigger: MidiTrigger,
+    pub action: MidiAction,
+}
+
+/// The user's controller mapping; the first matching binding wins
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiMapping {
+    pub bindings: Vec<MidiBinding>,
+}
+
+impl MidiMapping {
+    /// Action bound to `event`, if it's a press of a mapped control
+    pub fn action_for(&self, event: &MidiEvent) -> Option<&MidiAction> {
+        if !event.is_press() {
+            return None;
+        }
+        self.bindings
+            .iter()
+            .find(|b| b.trigger.matches(event))
+            .map(|b| &b.action)
+    }
+}
+
+/// An open MIDI input port; closed when dropped
+pub struct MidiListener {
+    port: String,
+    #[cfg(feature = "midi")]
+    _connection: midir::MidiInputConnection<()>,
+}
+
+impl MidiListener {
+    /// Name of the open port
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+}
+
+/// Names of the MIDI input ports currently available
+#[cfg(feature = "midi")]
+pub fn list_ports() -> Vec<String> {
+    let Ok(input) = midir::MidiInput::new("mcav-dj-ports") else {
+        return Vec::new();
+    };
+    input
+        .ports()
+        .iter()
+        .filter_map(|p| input.port_name(p).ok())
+        .collect()
+}
+
+/// Names of the MIDI input ports currently available
+#[cfg(not(feature = "midi"))]
+pub fn list_ports() -> Vec<String> {
+    Vec::new()
+}
+
+/// Open `port` (the first available one when `None`) and call `on_event`
+/// for every note and CC message, on midir's thread
+#[cfg(feature = "midi")]
+pub fn open(
+    port: Option<&str>,
+    on_event: impl Fn(MidiEvent) + Send + 'static,
+) -> Result<MidiListener, String> {
+    let input = midir::MidiInput::new("mcav-dj").map_err(|e| e.to_string())?;
+    let ports = input.ports();
+    let (port, name) = ports
+        .iter()
+        .filter_map(|p| input.port_name(p).ok().map(|name| (p, name)))
+        .find(|(_, name)| port.is_none_or(|wanted| wanted == name))
+        .ok_or_else(|| match port {
+            Some(wanted) => format!("MIDI port not found: {}", wanted),
+            None => "No MIDI input ports available".to_string(),
+        })?;
+    let port_name = name.clone();
+    let connection = input
+        .connect(
+            port,
+            "mcav-dj-input",
+            move |_, bytes, _| {
+                if let Some(event) = MidiEvent::parse(&port_name, bytes) {
+                    on_event(event);
+                }
+            },
+            (),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(MidiListener {
+        port: name,
+        _connection: connection,
+    })
+}
+
+/// Open `port` (the first available one when `None`) and call `on_event`
+/// for every note and CC message, on midir's thread
+#[cfg(not(feature = "midi"))]
+pub fn open(
+    _port: Option<&str>,
+    _on_event: impl Fn(MidiEvent) + Send + 'static,
+) -> Result<MidiListener, String> {
+    Err("MIDI support is not included in this build (enable the `midi` feature)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(trigger: MidiTrigger, action: MidiAction) -> MidiBinding {
+        MidiBinding { trigger, action }
+    }
+
+    #[test]
+    fn parses_notes_and_ccs() {
+        let on = MidiEvent::parse("pad", &[0x91, 36, 100]).unwrap();
+        assert_eq!(on.kind, MidiMessageKind::NoteOn);
+        assert_eq!((on.channel, on.number, on.value), (1, 36, 100));
+
+        // Velocity-zero note on is a release
+        let off = MidiEvent::parse("pad", &[0x90, 36, 0]).unwrap();
+        assert_eq!(off.kind, MidiMessageKind::NoteOff);
+
+        let cc = MidiEvent::parse("pad", &[0xB0, 7, 127]).unwrap();
+        assert_eq!(cc.kind, MidiMessageKind::ControlChange);
+
+        // Pitch bend and short messages are ignored
+        assert!(MidiEvent::parse("pad", &[0xE0, 0, 64]).is_none());
+        assert!(MidiEvent::parse("pad", &[0xF8]).is_none());
+    }
+
+    #[test]
+    fn mapping_fires_on_press_only() {
+        let mapping = MidiMapping {
+            bindings: vec![
+                binding(
+                    MidiTrigger::Note {
+                        channel: Some(0),
+                        note: 36,
+                    },
+                    MidiAction::NextPreset,
+                ),
+                binding(
+                    MidiTrigger::Cc {
+                        channel: None,
+                        controller: 20,
+                    },
+                    MidiAction::FireEffect {
+                        effect: "strobe".to_string(),
+                    },
+                ),
+            ],
+        };
+        let event = |bytes: &[u8]| MidiEvent::parse("pad", bytes).unwrap();
+
+        assert_eq!(
+            mapping.action_for(&event(&[0x90, 36, 90])),
+            Some(&MidiAction::NextPreset)
+        );
+        assert_eq!(mapping.action_for(&event(&[0x80, 36, 0])), None);
+        // Wrong channel
+        assert_eq!(mapping.action_for(&event(&[0x91, 36, 90])), None);
+        // A CC with the same number isn't the note
+        assert_eq!(mapping.action_for(&event(&[0xB0, 36, 127])), None);
+
+        assert!(mapping.action_for(&event(&[0xB5, 20, 127])).is_some());
+        assert_eq!(mapping.action_for(&event(&[0xB5, 20, 10])), None);
+    }
+
+    #[test]
+    fn mapping_deserializes_from_ui_json() {
+        let mapping: MidiMapping = serde_json::from_str(
+            r#"{"bindings": [
+                {"trigger": {"type": "note", "note": 40}, "action": {"action": "trigger_beat"}},
+                {"trigger": {"type": "cc", "channel": 2, "controller": 1},
+                 "action": {"action": "set_preset", "name": "edm"}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            mapping.bindings[0].action,
+            MidiAction::TriggerBeat { intensity: 1.0 }
+        );
+        assert_eq!(
+            mapping.bindings[1].trigger,
+            MidiTrigger::Cc {
+                channel: Some(2),
+                controller: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! OSC output of analysis data
+//!
+//! Sends bands, beats, BPM and peak from the bridge loop to a lighting rig
+//! or any other OSC receiver over UDP:
+//!
+//! - `/mcav/bands` five floats, bass to air
+//! - `/mcav/beat` int (1 on a beat, else 0) and float intensity
+//! - `/mcav/bpm` float
+//! - `/mcav/peak` float
+//!
+//! Encoding needs the `osc` feature (rosc); without it enabling OSC fails
+//! with a clear message.
+
+use crate::audio::AnalysisResult;
+use parking_lot::Mutex;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Accepted send rates in Hz
+pub const OSC_RATE_RANGE: std::ops::RangeInclusive<u32> = 1..=120;
+
+/// One send's worth of analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(feature = "osc"), allow(dead_code))]
+struct OscFrame {
+    bands: [f32; 5],
+    is_beat: bool,
+    beat_intensity: f32,
+    bpm: f32,
+    peak: f32,
+}
+
+#[derive(Default)]
+struct SendState {
+    last_sent: Option<Instant>,
+    /// Strongest beat seen since the last send, so a rate below the
+    /// bridge's doesn't swallow beats
+    pending_beat: Option<f32>,
+}
+
+/// UDP sender for analysis frames, rate limited to the configured rate
+pub struct OscSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+    interval: Duration,
+    state: Mutex<SendState>,
+}
+
+impl OscSender {
+    /// Resolve `host:port` and open a non-blocking socket that may also
+    /// send to broadcast addresses
+    pub fn new(host: &str, port: u16, rate_hz: u32) -> Result<Self, String> {
+        if !cfg!(feature = "osc") {
+            return Err(
+                "OSC support is not included in this build (enable the `osc` feature)".to_string(),
+            );
+        }
+        if !OSC_RATE_RANGE.contains(&rate_hz) {
+            return Err(format!(
+                "OSC rate must be {}-{} Hz",
+                OSC_RATE_RANGE.start(),
+                OSC_RATE_RANGE.end()
+            ));
+        }
+        let target = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid OSC target {}:{}: {}", host, port, e))?
+            .next()
+            .ok_or_else(|| format!("Invalid OSC target {}:{}", host, port))?;
+        let bind: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        socket.set_broadcast(true).map_err(|e| e.to_string())?;
+        Ok(Self {
+            socket,
+            target,
+            interval: Duration::from_secs_f64(1.0 / rate_hz as f64),
+            state: Mutex::new(SendState::default()),
+        })
+    }
+
+    /// Where frames go
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// Send this bridge tick's analysis if a send is due. Send errors are
+    /// logged and otherwise ignored, like dropped VJ frames.
+    pub fn send(&self, analysis: &AnalysisResult, is_beat: bool, beat_intensity: f32) {
+        let beat = {
+            let mut state = self.state.lock();
+            if is_beat {
+                state.pending_beat = Some(state.pending_beat.unwrap_or(0.0).max(beat_intensity));
+            }
+            let now = Instant::now();
+            if state
+                .last_sent
+                .is_some_and(|t| now.duration_since(t) < self.interval)
+            {
+                return;
+            }
+            state.last_sent = Some(now);
+            state.pending_beat.take()
+        };
+        let frame = OscFrame {
+            bands: analysis.bands,
+            is_beat: beat.is_some(),
+            beat_intensity: beat.unwrap_or(0.0),
+            bpm: analysis.bpm,
+            peak: analysis.peak,
+        };
+        for packet in encode(&frame) {
+            if let Err(e) = self.socket.send_to(&packet, self.target) {
+                log::debug!("OSC send to {} failed: {}", self.target, e);
+                break;
+            }
+        }
+    }
+}
+
+/// One encoded packet per OSC address
+#[cfg(feature = "osc")]
+fn encode(frame: &OscFrame) -> Vec<Vec<u8>> {
+    use rosc::{OscMessage, OscPacket, OscType};
+
+    let messages = [
+        (
+            "/mcav/bands",
+            frame.bands.iter().map(|&b| OscType::Float(b)).collect(),
+        ),
+        (
+            "/mcav/beat",
+            vec![
+                OscType::Int(frame.is_beat as i32),
+                OscType::Float(frame.beat_intensity),
+            ],
+        ),
+        ("/mcav/bpm", vec![OscType::Float(frame.bpm)]),
+        ("/mcav/peak", vec![OscType::Float(frame.peak)]),
+    ];
+    messages
+        .into_iter()
+        .filter_map(|(addr, args)| {
+            rosc::encoder::encode(&OscPacket::Message(OscMessage {
+                addr: addr.to_string(),
+                args,
+            }))
+            .ok()
+        })
+        .collect()
+}
+
+/// One encoded packet per OSC address
+#[cfg(not(feature = "osc"))]
+fn encode(_frame: &OscFrame) -> Vec<Vec<u8>> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "osc")]
+    #[test]
+    fn sends_all_addresses_and_latches_beats_between_sends() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let port = receiver.local_addr().unwrap().port();
+        let sender = OscSender::new("127.0.0.1", port, 1).unwrap();
+
+        let analysis = AnalysisResult {
+            bands: [0.9, 0.5, 0.4, 0.3, 0.2],
+            bpm: 128.0,
+            peak: 0.9,
+            ..Default::default()
+        };
+        let recv = || {
+            let mut buf = [0u8; 1024];
+            let len = receiver.recv(&mut buf).unwrap();
+            match rosc::decoder::decode_udp(&buf[..len]).unwrap().1 {
+                rosc::OscPacket::Message(msg) => msg,
+                other => panic!("unexpected packet {:?}", other),
+            }
+        };
+
+        sender.send(&analysis, false, 0.0);
+        let addrs: Vec<String> = (0..4).map(|_| recv().addr).collect();
+        assert_eq!(
+            addrs,
+            ["/mcav/bands", "/mcav/beat", "/mcav/bpm", "/mcav/peak"]
+        );
+
+        // Inside the 1 Hz interval: nothing goes out, but the beat is kept
+        sender.send(&analysis, true, 0.7);
+        sender.state.lock().last_sent = None;
+        sender.send(&analysis, false, 0.0);
+        recv();
+        let beat = recv();
+        assert_eq!(
+            beat.args,
+            vec![rosc::OscType::Int(1), rosc::OscType::Float(0.7)]
+        );
+    }
+
+    #[cfg(feature = "osc")]
+    #[test]
+    fn rejects_rates_out_of_range() {
+        assert!(OscSender::new("127.0.0.1", 9000, 0).is_err());
+        assert!(OscSender::new("127.0.0.1", 9000, 500).is_err());
+    }
+
+    #[cfg(not(feature = "osc"))]
+    #[test]
+    fn reports_missing_feature() {
+        assert!(
+            OscSender::new("127.0.0.1", 9000, 30)
+                .err()
+                .is_some_and(|e| e.contains("osc"))
+        );
+    }
+}
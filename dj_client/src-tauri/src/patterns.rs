@@ -2,10 +2,24 @@
 //!
 //! Loads .lua pattern scripts and executes them each frame to produce
 //! entity positions for batch_update messages to Minecraft.
+//!
+//! `preview_pattern` runs a script offline against fixture audio, so pattern
+//! authors can iterate without a VJ server or Minecraft.
 
+use crate::audio::AnalysisResult;
 use mlua::prelude::*;
+use serde::Deserialize;
 use std::collections::HashMap;
 
+/// Shared pattern utilities, bundled for offline previews
+const BUNDLED_LIB: &str = include_str!("../../../patterns/lib.lua");
+
+/// Upper bound on frames a single preview may run (10s at 60fps)
+pub const MAX_PREVIEW_FRAMES: u32 = 600;
+
+/// Frame interval the engine steps patterns with
+const FRAME_DT: f64 = 0.016;
+
 /// Pattern configuration matching VJ server's PatternConfig
 #[derive(Debug, Clone)]
 pub struct PatternConfig {
@@ -37,6 +51,11 @@ pub struct PatternEngine {
     band_sensitivity: [f32; 5],
     lib_source: Option<String>,
     pattern_loaded: bool,
+    /// Effect triggered from the VJ panel, passed to the next frame as
+    /// `audio.effect`
+    effect: Option<String>,
+    /// Hide entities whose band (after sensitivity) is below this level
+    visibility_threshold: Option<f32>,
 }
 
 impl Default for PatternEngine {
@@ -45,18 +64,46 @@ impl Default for PatternEngine {
     }
 }
 
+/// A fresh Lua state, or the error if Lua can't allocate one.
+///
+/// Patterns arrive from the network, so the state is sandboxed: only
+/// `math`, `string` and `table` are opened (no `os`, `io`, `package` or
+/// `debug`), and the base library's file loaders are removed.
+fn new_lua() -> Result<Lua, String> {
+    let lua = Lua::new_with(
+        LuaStdLib::MATH | LuaStdLib::STRING | LuaStdLib::TABLE,
+        LuaOptions::default(),
+    )
+    .map_err(|e| format!("Lua init error: {}", e))?;
+    let globals = lua.globals();
+    for name in ["dofile", "loadfile"] {
+        globals
+            .set(name, LuaValue::Nil)
+            .map_err(|e| format!("Lua init error: {}", e))?;
+    }
+    Ok(lua)
+}
+
 impl PatternEngine {
+    /// Panics if Lua can't start; the bridge uses `try_new`
     pub fn new() -> Self {
-        let lua = Lua::new();
-        Self {
-            lua,
+        Self::try_new().expect("Lua state")
+    }
+
+    /// Create an engine, or the error if the Lua state can't be created
+    /// (e.g. under memory pressure)
+    pub fn try_new() -> Result<Self, String> {
+        Ok(Self {
+            lua: new_lua()?,
             current_pattern: String::new(),
             loaded_patterns: HashMap::new(),
             config: PatternConfig::default(),
             band_sensitivity: [1.0; 5],
             lib_source: None,
             pattern_loaded: false,
-        }
+            effect: None,
+            visibility_threshold: None,
+        })
     }
 
     /// Load lib.lua shared utilities
@@ -83,7 +130,7 @@ impl PatternEngine {
             .clone();
 
         // Reset Lua state for clean pattern switch
-        self.lua = Lua::new();
+        self.lua = new_lua()?;
 
         // Reload lib
         if let Some(ref lib) = self.lib_source {
@@ -105,10 +152,38 @@ impl PatternEngine {
         Ok(())
     }
 
+    /// Name of the active pattern (empty until one is set)
+    pub fn current_pattern(&self) -> &str {
+        &self.current_pattern
+    }
+
+    /// Names of the stored pattern scripts, sorted
+    pub fn loaded_pattern_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.loaded_patterns.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn config(&self) -> &PatternConfig {
+        &self.config
+    }
+
     pub fn set_config(&mut self, config: PatternConfig) {
         self.config = config;
     }
 
+    /// Set the effect patterns see as `audio.effect` (nil when None)
+    pub fn set_effect(&mut self, effect: Option<String>) {
+        self.effect = effect;
+    }
+
+    /// Hide entities whose mapped band is below `threshold` (0-1), so they
+    /// drop out while their band is silent; `None` leaves visibility to the
+    /// pattern
+    pub fn set_visibility_threshold(&mut self, threshold: Option<f32>) {
+        self.visibility_threshold = threshold;
+    }
+
     pub fn set_band_sensitivity(&mut self, sensitivity: [f32; 5]) {
         self.band_sensitivity = sensitivity;
     }
@@ -116,14 +191,10 @@ impl PatternEngine {
     /// Calculate entities by running the current Lua pattern
     pub fn calculate_entities(
         &self,
-        analysis: &crate::audio::AnalysisResult,
+        analysis: &AnalysisResult,
         seq: u64,
     ) -> Vec<serde_json::Value> {
-        if !self.pattern_loaded {
-            return Vec::new();
-        }
-
-        match self.run_lua_pattern(analysis, seq) {
+        match self.try_calculate_entities(analysis, seq) {
             Ok(entities) => entities,
             Err(e) => {
                 log::warn!("Pattern calculation error: {}", e);
@@ -132,9 +203,22 @@ impl PatternEngine {
         }
     }
 
+    /// Like `calculate_entities`, but returns Lua errors instead of logging
+    /// them
+    pub fn try_calculate_entities(
+        &self,
+        analysis: &AnalysisResult,
+        seq: u64,
+    ) -> Result<Vec<serde_json::Value>, String> {
+        if !self.pattern_loaded {
+            return Ok(Vec::new());
+        }
+        self.run_lua_pattern(analysis, seq)
+    }
+
     fn run_lua_pattern(
         &self,
-        analysis: &crate::audio::AnalysisResult,
+        analysis: &AnalysisResult,
         _seq: u64,
     ) -> Result<Vec<serde_json::Value>, String> {
         let lua = &self.lua;
@@ -142,15 +226,19 @@ impl PatternEngine {
         // Build audio table with band sensitivity applied
         let audio_table = lua.create_table().map_err(|e| e.to_string())?;
         let bands_table = lua.create_table().map_err(|e| e.to_string())?;
-        for i in 0..5 {
-            let adjusted = (analysis.bands[i] * self.band_sensitivity[i]).clamp(0.0, 2.0);
+        let mut adjusted_bands = [0.0_f32; 5];
+        for (i, adjusted) in adjusted_bands.iter_mut().enumerate() {
+            *adjusted = (analysis.bands[i] * self.band_sensitivity[i]).clamp(0.0, 2.0);
             bands_table
-                .set(i + 1, adjusted as f64)
+                .set(i + 1, *adjusted as f64)
                 .map_err(|e| e.to_string())?; // Lua 1-indexed
         }
         audio_table
             .set("bands", bands_table)
             .map_err(|e| e.to_string())?;
+        audio_table
+            .set("sub_bass", analysis.sub_bass as f64)
+            .map_err(|e| e.to_string())?;
         audio_table
             .set("amplitude", analysis.peak as f64)
             .map_err(|e| e.to_string())?;
@@ -169,6 +257,23 @@ impl PatternEngine {
             .set("beat_intensity", analysis.beat_intensity as f64)
             .map_err(|e| e.to_string())?;
         audio_table.set("frame", _seq).map_err(|e| e.to_string())?;
+        let chroma_table = lua.create_table().map_err(|e| e.to_string())?;
+        for (i, value) in analysis.chroma.iter().enumerate() {
+            chroma_table
+                .set(i + 1, *value as f64)
+                .map_err(|e| e.to_string())?; // Lua 1-indexed, C first
+        }
+        audio_table
+            .set("chroma", chroma_table)
+            .map_err(|e| e.to_string())?;
+        audio_table
+            .set("dominant_pitch_class", analysis.dominant_pitch_class)
+            .map_err(|e| e.to_string())?;
+        if let Some(ref effect) = self.effect {
+            audio_table
+                .set("effect", effect.as_str())
+                .map_err(|e| e.to_string())?;
+        }
 
         // Build config table
         let config_table = lua.create_table().map_err(|e| e.to_string())?;
@@ -191,7 +296,7 @@ impl PatternEngine {
         // Call calculate(audio, config, dt)
         let calculate: LuaFunction = lua.globals().get("calculate").map_err(|e| e.to_string())?;
         let result: LuaTable = calculate
-            .call((audio_table, config_table, 0.016f64))
+            .call((audio_table, config_table, FRAME_DT))
             .map_err(|e| format!("calculate() error: {}", e))?;
 
         // Convert Lua table of entities to Vec<serde_json::Value>
@@ -205,7 +310,10 @@ impl PatternEngine {
             let z: f64 = entity.get("z").unwrap_or(0.5);
             let scale: f64 = entity.get("scale").unwrap_or(0.2);
             let band: i64 = entity.get("band").unwrap_or(0);
-            let visible: bool = entity.get("visible").unwrap_or(true);
+            let mut visible: bool = entity.get("visible").unwrap_or(true);
+            if let Some(threshold) = self.visibility_threshold {
+                visible &= adjusted_bands[band.clamp(0, 4) as usize] >= threshold;
+            }
 
             entities.push(serde_json::json!({
                 "id": id,
@@ -222,3 +330,242 @@ impl PatternEngine {
         Ok(entities)
     }
 }
+
+/// Audio to drive a pattern preview with
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PreviewFixture {
+    /// The given analysis frames, in order
+    Frames { frames: Vec<AnalysisResult> },
+    /// A generated four-on-the-floor sequence at `bpm`
+    Beats { bpm: f32, frames: u32 },
+}
+
+impl PreviewFixture {
+    /// The analysis frames this fixture stands for
+    pub fn frames(&self) -> Vec<AnalysisResult> {
+        match self {
+            Self::Frames { frames } => frames.clone(),
+            Self::Beats { bpm, frames } => beat_sequence(*bpm, *frames),
+        }
+    }
+}
+
+/// Synthetic analysis frames for a steady beat: a bass hit on every beat
+/// that decays until the next, with the upper bands trailing behind.
+pub fn beat_sequence(bpm: f32, frames: u32) -> Vec<AnalysisResult> {
+    let bpm = bpm.clamp(40.0, 240.0);
+    let beat_period = 60.0 / bpm as f64;
+    (0..frames)
+        .map(|i| {
+            let t = i as f64 * FRAME_DT;
+            let phase = ((t / beat_period) % 1.0) as f32;
+            // Beat on the first frame of each period
+            let is_beat = phase < (FRAME_DT / beat_period) as f32;
+            let envelope = (-phase * 6.0).exp();
+            AnalysisResult {
+                bands: [
+                    envelope,
+                    envelope * 0.7,
+                    0.4 + envelope * 0.2,
+                    0.3 * (1.0 - phase),
+                    0.2,
+                ],
+                peak: envelope,
+                is_beat,
+                beat_intensity: if is_beat { 0.9 } else { 0.0 },
+                bpm,
+                tempo_confidence: 1.0,
+                beat_phase: phase,
+                beat_phase_smooth: phase,
+                instant_bass: envelope,
+                instant_kick: is_beat,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Run a pattern script offline against `fixture` in a fresh engine.
+///
+/// Returns one entity list per frame. `lib_source` defaults to the bundled
+/// `lib.lua`; load and runtime errors are returned rather than logged.
+pub fn preview_pattern(
+    source: &str,
+    lib_source: Option<&str>,
+    fixture: &PreviewFixture,
+    config: PatternConfig,
+) -> Result<Vec<Vec<serde_json::Value>>, String> {
+    let frames = fixture.frames();
+    if frames.len() > MAX_PREVIEW_FRAMES as usize {
+        return Err(format!(
+            "Preview is limited to {} frames",
+            MAX_PREVIEW_FRAMES
+        ));
+    }
+
+    let mut engine = PatternEngine::try_new()?;
+    engine.set_config(config);
+    engine.load_lib(lib_source.unwrap_or(BUNDLED_LIB))?;
+    engine.load_pattern("preview", source);
+    engine.set_pattern("preview")?;
+
+    frames
+        .iter()
+        .enumerate()
+        .map(|(seq, analysis)| engine.try_calculate_entities(analysis, seq as u64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PULSE: &str = r#"
+        function calculate(audio, config, dt)
+            local entities = {}
+            for i = 1, config.entity_count do
+                entities[i] = {
+                    id = "block_" .. (i - 1),
+                    y = clamp(audio.bands[1], 0, 1),
+                    scale = audio.is_beat and config.max_scale or config.base_scale,
+                }
+            end
+            return entities
+        end
+    "#;
+
+    #[test]
+    fn preview_runs_pattern_over_beat_sequence() {
+        let fixture = PreviewFixture::Beats {
+            bpm: 120.0,
+            frames: 60,
+        };
+        let config = PatternConfig {
+            entity_count: 4,
+            ..Default::default()
+        };
+
+        let frames = preview_pattern(PULSE, None, &fixture, config).unwrap();
+
+        assert_eq!(frames.len(), 60);
+        assert!(frames.iter().all(|f| f.len() == 4));
+        let scale = |frame: usize| frames[frame][0]["scale"].as_f64().unwrap();
+        assert_eq!(scale(0), 1.0);
+        assert!((scale(1) - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn preview_surfaces_lua_errors() {
+        let fixture = PreviewFixture::Frames {
+            frames: vec![AnalysisResult::default()],
+        };
+        let broken = "function calculate(audio, config, dt) return nil + 1 end";
+        let syntax = "function calculate(";
+
+        let runtime = preview_pattern(broken, None, &fixture, PatternConfig::default());
+        assert!(runtime.unwrap_err().contains("calculate() error"));
+        let load = preview_pattern(syntax, None, &fixture, PatternConfig::default());
+        assert!(load.unwrap_err().contains("load error"));
+    }
+
+    #[test]
+    fn patterns_cannot_reach_the_host() {
+        let mut engine = PatternEngine::new();
+        engine.load_pattern(
+            "escape",
+            r#"
+            function calculate(audio, config, dt)
+                local sealed = (os and os.execute) == nil
+                    and io == nil and package == nil and debug == nil
+                    and require == nil and dofile == nil and loadfile == nil
+                return { { id = "block_0", visible = sealed, scale = math.max(0.5, 0.2) } }
+            end
+            "#,
+        );
+        engine.set_pattern("escape").unwrap();
+
+        let entities = engine
+            .try_calculate_entities(&AnalysisResult::default(), 0)
+            .unwrap();
+        assert_eq!(entities[0]["visible"], true);
+        assert_eq!(entities[0]["scale"], 0.5);
+    }
+
+    #[test]
+    fn lists_loaded_and_active_patterns() {
+        let mut engine = PatternEngine::new();
+        assert!(engine.loaded_pattern_names().is_empty());
+        assert_eq!(engine.current_pattern(), "");
+
+        let script = "function calculate(audio, config, dt) return {} end";
+        engine.load_pattern("wave", script);
+        engine.load_pattern("pulse", script);
+        engine.set_pattern("wave").unwrap();
+
+        assert_eq!(engine.loaded_pattern_names(), ["pulse", "wave"]);
+        assert_eq!(engine.current_pattern(), "wave");
+    }
+
+    #[test]
+    fn patterns_see_triggered_effect() {
+        let mut engine = PatternEngine::new();
+        engine.load_pattern(
+            "fx",
+            r#"
+            function calculate(audio, config, dt)
+                return { { id = "block_0", scale = audio.effect == "flash" and 1.0 or 0.2 } }
+            end
+            "#,
+        );
+        engine.set_pattern("fx").unwrap();
+        let scale = |engine: &PatternEngine| {
+            engine.calculate_entities(&AnalysisResult::default(), 0)[0]["scale"]
+                .as_f64()
+                .unwrap()
+        };
+
+        assert!((scale(&engine) - 0.2).abs() < 1e-6);
+        engine.set_effect(Some("flash".to_string()));
+        assert_eq!(scale(&engine), 1.0);
+    }
+
+    #[test]
+    fn visibility_threshold_hides_quiet_bands() {
+        let mut engine = PatternEngine::new();
+        engine.load_pattern(
+            "bands",
+            r#"
+            function calculate(audio, config, dt)
+                return { { id = "block_0", band = 0 }, { id = "block_1", band = 3 } }
+            end
+            "#,
+        );
+        engine.set_pattern("bands").unwrap();
+        let analysis = AnalysisResult {
+            bands: [0.8, 0.5, 0.4, 0.02, 0.0],
+            ..Default::default()
+        };
+        let visible = |engine: &PatternEngine| -> Vec<bool> {
+            engine
+                .calculate_entities(&analysis, 0)
+                .iter()
+                .map(|e| e["visible"].as_bool().unwrap())
+                .collect()
+        };
+
+        assert_eq!(visible(&engine), [true, true]);
+        engine.set_visibility_threshold(Some(0.1));
+        assert_eq!(visible(&engine), [true, false]);
+    }
+
+    #[test]
+    fn beat_sequence_beats_once_per_period() {
+        let frames = beat_sequence(120.0, 125);
+        let beats = frames.iter().filter(|f| f.is_beat).count();
+
+        // 2s at 120bpm
+        assert_eq!(beats, 4);
+        assert!(frames[0].is_beat);
+    }
+}
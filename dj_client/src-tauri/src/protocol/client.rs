@@ -1,9 +1,10 @@
 //! WebSocket client for VJ server communication
 
 use super::messages::*;
+use crate::audio::{AudioPreset, get_preset};
 use futures_util::{SinkExt, StreamExt};
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
@@ -22,6 +23,9 @@ pub enum ClientError {
     #[error("Authentication failed: {0}")]
     AuthenticationFailed(String),
 
+    #[error("Connection timeout")]
+    Timeout,
+
     #[error("WebSocket error: {0}")]
     WebSocketError(String),
 
@@ -56,6 +60,10 @@ pub struct DjClientConfig {
     /// DJ key (for credential auth)
     pub dj_key: Option<String>,
 
+    /// Bearer token (for token auth); takes precedence over the connect
+    /// code and credentials
+    pub auth_token: Option<String>,
+
     /// DJ session ID from coordinator (passed to VJ server in code_auth message for profile lookup)
     pub dj_session_id: Option<String>,
 
@@ -78,6 +86,7 @@ impl Default for DjClientConfig {
             connect_code: None,
             dj_id: None,
             dj_key: None,
+            auth_token: None,
             dj_session_id: None,
             max_reconnect_attempts: 10,
             reconnect_delay: 2.0,
@@ -86,6 +95,83 @@ impl Default for DjClientConfig {
     }
 }
 
+/// Heartbeats in a row left unacknowledged before the link counts as stale
+pub const MAX_MISSED_HEARTBEATS: u32 = 3;
+
+/// Heartbeat intervals a heartbeat may wait for its ack before it counts
+/// as missed
+const HEARTBEAT_ACK_TIMEOUT_INTERVALS: f64 = 2.0;
+
+/// Range a server-advertised heartbeat interval is held to (seconds)
+const SERVER_HEARTBEAT_INTERVAL_RANGE: std::ops::RangeInclusive<f64> = 0.5..=30.0;
+
+/// Effects queued for the bridge before further ones are dropped
+const MAX_PENDING_EFFECTS: usize = 16;
+
+/// Longest `disconnect` waits for `going_offline` to be flushed
+const GOING_OFFLINE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Clock samples kept for the offset estimate
+const CLOCK_SAMPLES: usize = 8;
+
+/// Estimate of the server clock relative to ours.
+///
+/// Each sample is a server timestamp taken somewhere between a local send
+/// and receive time, assumed to sit at the midpoint. Of the recent samples
+/// the one with the shortest round trip is trusted, since its midpoint
+/// error is smallest.
+#[derive(Debug, Clone, Default)]
+pub struct ClockOffsetEstimator {
+    /// `(offset_ms, rtt_ms)` pairs, newest last
+    samples: VecDeque<(f64, f64)>,
+}
+
+impl ClockOffsetEstimator {
+    /// Record `server_time` observed between local `sent` and `received`
+    /// (all unix seconds)
+    pub fn add(&mut self, server_time: f64, sent: f64, received: f64) {
+        let rtt_ms = ((received - sent) * 1000.0).max(0.0);
+        let offset_ms = (server_time - (sent + received) / 2.0) * 1000.0;
+        self.push(offset_ms, rtt_ms);
+    }
+
+    /// Record a server timestamp we only know the receive time of (the
+    /// handshake `clock_sync_request`). Off by the one-way delay, so any
+    /// round-trip sample replaces it.
+    pub fn add_one_way(&mut self, server_time: f64, received: f64) {
+        self.push((server_time - received) * 1000.0, f64::INFINITY);
+    }
+
+    fn push(&mut self, offset_ms: f64, rtt_ms: f64) {
+        if !offset_ms.is_finite() {
+            return;
+        }
+        self.samples.push_back((offset_ms, rtt_ms));
+        if self.samples.len() > CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    fn best(&self) -> Option<(f64, f64)> {
+        self.samples
+            .iter()
+            .copied()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Server time minus local time, in milliseconds
+    pub fn offset_ms(&self) -> Option<f64> {
+        self.best().map(|(offset, _)| offset)
+    }
+
+    /// Round trip of the sample behind `offset_ms`, if it had one
+    pub fn rtt_ms(&self) -> Option<f64> {
+        self.best()
+            .map(|(_, rtt)| rtt)
+            .filter(|rtt| rtt.is_finite())
+    }
+}
+
 /// Connection state
 #[derive(Debug, Clone)]
 pub struct ConnectionState {
@@ -94,19 +180,27 @@ pub struct ConnectionState {
     pub is_active: bool,
     pub dj_id: Option<String>,
     pub latency_ms: f32,
+    /// Smoothed change between consecutive latency samples
+    pub latency_jitter_ms: f32,
+    /// Server clock offset, from the clock sync and heartbeat echoes
+    pub clock: ClockOffsetEstimator,
     pub reconnect_attempts: u32,
     pub route_mode: String, // relay | dual
     pub mc_host: Option<String>,
     pub mc_port: Option<u16>,
     pub mc_zone: Option<String>,
     pub mc_entity_count: Option<u32>,
+    /// Route asked for entities to be mirrored into VJ audio frames
+    pub mirror_entities: bool,
+    /// Protocol version reported in `auth_success`, if any
+    pub server_protocol_version: Option<String>,
     // Voice status fields (populated by server voice_status messages)
     pub voice_available: bool,
     pub voice_streaming: bool,
     pub voice_channel_type: Option<String>,
     pub voice_connected_players: Option<u32>,
-    /// Preset name received from server (bridge task consumes this)
-    pub pending_preset: Option<String>,
+    /// Preset received from server (bridge task consumes this)
+    pub pending_preset: Option<AudioPreset>,
     /// Current pattern name
     pub current_pattern: String,
     /// Per-band sensitivity multipliers
@@ -121,6 +215,77 @@ pub struct ConnectionState {
     pub pending_config_change: Option<(u32, String)>,
     /// DJ roster update received from server (consumed by bridge task)
     pub pending_dj_roster: Option<serde_json::Value>,
+    /// Effects the VJ panel triggered, oldest first (consumed by bridge task)
+    pub pending_effects: Vec<String>,
+    /// Place in the rotation queue, from `queue_update`
+    pub queue_position: usize,
+    pub total_djs: usize,
+    pub active_dj_name: Option<String>,
+    /// Zones from the last `zone_list`; `None` until the server has sent one
+    pub zones: Option<Vec<ZoneInfo>>,
+    /// Number of `zone_list` replies received, so a request can tell when
+    /// its answer has arrived
+    pub zone_lists_received: u64,
+    /// Send times of heartbeats still waiting for their ack, oldest first
+    pub outstanding_heartbeats: VecDeque<f64>,
+    /// Heartbeats that were never acknowledged, over the connection
+    pub missed_heartbeats: u64,
+    /// Unacknowledged heartbeats since the last ack
+    pub consecutive_missed_heartbeats: u32,
+    /// Heartbeat interval the server asked for in `auth_success`
+    pub server_heartbeat_interval: Option<f64>,
+}
+
+impl ConnectionState {
+    /// Check `zone` against the server's zone list. Any zone passes while
+    /// the list is unknown, since older servers don't answer `list_zones`.
+    pub fn check_zone(&self, zone: &str) -> Result<(), String> {
+        match self.zones {
+            Some(ref zones) if !zones.iter().any(|z| z.name == zone) => Err(format!(
+                "Unknown zone '{}' (server has: {})",
+                zone,
+                zones
+                    .iter()
+                    .map(|z| z.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a heartbeat sent at `ts` (seconds), first counting those
+    /// older than `ack_timeout` as missed. Returns true once
+    /// `MAX_MISSED_HEARTBEATS` in a row went unacknowledged.
+    pub fn heartbeat_sent(&mut self, ts: f64, ack_timeout: f64) -> bool {
+        while self
+            .outstanding_heartbeats
+            .front()
+            .is_some_and(|&sent| ts - sent >= ack_timeout)
+        {
+            self.outstanding_heartbeats.pop_front();
+            self.missed_heartbeats += 1;
+            self.consecutive_missed_heartbeats += 1;
+        }
+        self.outstanding_heartbeats.push_back(ts);
+        self.consecutive_missed_heartbeats >= MAX_MISSED_HEARTBEATS
+    }
+
+    /// Settle the heartbeat an ack echoes (the oldest one if the server
+    /// doesn't echo timestamps), along with any sent before it
+    pub fn heartbeat_acked(&mut self, echo_ts: Option<f64>) {
+        let settled = match echo_ts {
+            Some(echo) => self
+                .outstanding_heartbeats
+                .iter()
+                .position(|&sent| (sent - echo).abs() < 1e-6)
+                .map_or(0, |i| i + 1),
+            None => 1,
+        };
+        self.outstanding_heartbeats
+            .drain(..settled.min(self.outstanding_heartbeats.len()));
+        self.consecutive_missed_heartbeats = 0;
+    }
 }
 
 impl Default for ConnectionState {
@@ -131,12 +296,16 @@ impl Default for ConnectionState {
             is_active: false,
             dj_id: None,
             latency_ms: 0.0,
+            latency_jitter_ms: 0.0,
+            clock: ClockOffsetEstimator::default(),
             reconnect_attempts: 0,
             route_mode: String::new(),
             mc_host: None,
             mc_port: None,
             mc_zone: None,
             mc_entity_count: None,
+            mirror_entities: false,
+            server_protocol_version: None,
             voice_available: false,
             voice_streaming: false,
             voice_channel_type: None,
@@ -149,6 +318,16 @@ impl Default for ConnectionState {
             pending_band_sensitivity: None,
             pending_config_change: None,
             pending_dj_roster: None,
+            pending_effects: Vec::new(),
+            queue_position: 0,
+            total_djs: 0,
+            active_dj_name: None,
+            zones: None,
+            zone_lists_received: 0,
+            outstanding_heartbeats: VecDeque::new(),
+            missed_heartbeats: 0,
+            consecutive_missed_heartbeats: 0,
+            server_heartbeat_interval: None,
         }
     }
 }
@@ -159,6 +338,8 @@ pub struct DjClient {
     state: Arc<Mutex<ConnectionState>>,
     tx: Option<mpsc::Sender<Message>>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    /// Writer task, awaited on disconnect so `going_offline` gets sent
+    writer_task: Mutex<Option<tokio::task::JoinHandle<()>>>,
     mc_connected: Arc<AtomicBool>,
 }
 
@@ -170,6 +351,7 @@ impl DjClient {
             state: Arc::new(Mutex::new(ConnectionState::default())),
             tx: None,
             shutdown_tx: None,
+            writer_task: Mutex::new(None),
             mc_connected: Arc::new(AtomicBool::new(false)),
         }
     }
@@ -206,7 +388,7 @@ impl DjClient {
             connect_async_with_config(&url, Some(ws_config), false),
         )
         .await
-        .map_err(|_| ClientError::ConnectionFailed("Connection timeout".to_string()))?
+        .map_err(|_| ClientError::Timeout)?
         .map_err(|e| ClientError::ConnectionFailed(e.to_string()))?;
 
         let (ws_stream, _) = ws_stream;
@@ -220,7 +402,16 @@ impl DjClient {
         self.shutdown_tx = Some(shutdown_tx);
 
         // Send authentication
-        let auth_msg = if let Some(ref code) = self.config.connect_code {
+        let auth_msg = if let Some(ref token) = self.config.auth_token {
+            // Bearer token authentication
+            serde_json::to_string(&TokenAuthMessage::new(
+                token.clone(),
+                self.config.dj_name.clone(),
+            ))
+            .map_err(|e| {
+                ClientError::SendError(format!("Failed to serialize auth message: {}", e))
+            })?
+        } else if let Some(ref code) = self.config.connect_code {
             // Code-based authentication
             serde_json::to_string(&CodeAuthMessage::new(
                 code.clone(),
@@ -242,7 +433,8 @@ impl DjClient {
             })?
         } else {
             return Err(ClientError::AuthenticationFailed(
-                "No credentials provided. Set a connect code or DJ ID/key in settings.".to_string(),
+                "No credentials provided. Set an auth token, connect code or DJ ID/key in settings."
+                    .to_string(),
             ));
         };
 
@@ -284,6 +476,8 @@ impl DjClient {
                                     if let Some(pattern_cfg) = auth.pattern_config.as_ref() {
                                         s.mc_entity_count = pattern_cfg.entity_count;
                                     }
+                                    s.server_protocol_version = auth.protocol_version.clone();
+                                    s.server_heartbeat_interval = auth.heartbeat_interval;
                                     log::info!(
                                         "Authenticated as {} (active: {})",
                                         auth.dj_name,
@@ -301,6 +495,11 @@ impl DjClient {
                                     .duration_since(std::time::UNIX_EPOCH)
                                     .unwrap_or_default()
                                     .as_secs_f64();
+                                if let Some(server_time) =
+                                    msg.get("server_time").and_then(|v| v.as_f64())
+                                {
+                                    self.state.lock().clock.add_one_way(server_time, now);
+                                }
                                 let response = ClockSyncResponse::new(now);
                                 let json = serde_json::to_string(&response).map_err(|e| {
                                     ClientError::SendError(format!(
@@ -350,10 +549,20 @@ impl DjClient {
 
         // Spawn message handling tasks
         let state = self.state.clone();
-        let heartbeat_interval = self.config.heartbeat_interval;
+        let heartbeat_interval = match self.state.lock().server_heartbeat_interval {
+            Some(interval) if interval.is_finite() => {
+                let interval = interval.clamp(
+                    *SERVER_HEARTBEAT_INTERVAL_RANGE.start(),
+                    *SERVER_HEARTBEAT_INTERVAL_RANGE.end(),
+                );
+                log::info!("Using server heartbeat interval of {:.1}s", interval);
+                interval
+            }
+            _ => self.config.heartbeat_interval,
+        };
 
         // Writer task
-        tokio::spawn(async move {
+        let writer = tokio::spawn(async move {
             loop {
                 tokio::select! {
                     Some(msg) = rx.recv() => {
@@ -372,6 +581,7 @@ impl DjClient {
                 }
             }
         });
+        *self.writer_task.lock() = Some(writer);
 
         // Reader task
         let state_reader = state.clone();
@@ -381,27 +591,20 @@ impl DjClient {
                 match msg {
                     Ok(Message::Text(ref text)) => {
                         match serde_json::from_str::<ServerMessage>(text) {
+                            Ok(ServerMessage::Unknown) => log::debug!(
+                                "Ignoring unknown server message type '{}'",
+                                message_type(text).unwrap_or_default()
+                            ),
                             Ok(server_msg) => {
                                 handle_server_message(&state_reader, &tx_reader, server_msg).await;
                             }
-                            Err(e) => {
-                                // Extract message type for logging without full payload
-                                let msg_type = serde_json::from_str::<serde_json::Value>(text)
-                                    .ok()
-                                    .and_then(|v| {
-                                        v.get("type").and_then(|t| t.as_str()).map(String::from)
-                                    });
-                                match msg_type {
-                                    Some(t) => log::debug!(
-                                        "Ignoring unhandled server message type '{}': {}",
-                                        t,
-                                        e
-                                    ),
-                                    None => {
-                                        log::warn!("Received malformed JSON from server: {}", e)
-                                    }
+                            // A known type with a bad payload, or not JSON at all
+                            Err(e) => match message_type(text) {
+                                Some(t) => log::debug!("Failed to parse '{}' message: {}", t, e),
+                                None => {
+                                    log::debug!("Received malformed message from server: {}", e)
                                 }
-                            }
+                            },
                         }
                     }
                     Ok(Message::Close(_)) => {
@@ -437,6 +640,26 @@ impl DjClient {
                     hb.latency_ms = Some(latency_ms as f64);
                 }
                 hb.mc_connected = Some(mc_connected_flag.load(Ordering::Relaxed));
+                // A link that stopped acking is dead even if the socket looks
+                // open; dropping `connected` lets the bridge reconnect
+                let stale = {
+                    let mut s = state_heartbeat.lock();
+                    let stale = s.heartbeat_sent(
+                        hb.ts,
+                        heartbeat_interval * HEARTBEAT_ACK_TIMEOUT_INTERVALS,
+                    );
+                    if stale {
+                        s.connected = false;
+                    }
+                    stale
+                };
+                if stale {
+                    log::warn!(
+                        "{} heartbeats in a row went unacknowledged; connection is stale",
+                        MAX_MISSED_HEARTBEATS
+                    );
+                    break;
+                }
                 let msg = match serde_json::to_string(&hb) {
                     Ok(json) => json,
                     Err(e) => {
@@ -483,11 +706,19 @@ impl DjClient {
         Ok(())
     }
 
-    /// Disconnect from server
+    /// Disconnect from server, waiting briefly for `going_offline` to go out
     pub async fn disconnect(&self) -> Result<(), ClientError> {
         if let Some(ref shutdown) = self.shutdown_tx {
             let _ = shutdown.send(()).await;
         }
+        let writer = self.writer_task.lock().take();
+        if let Some(writer) = writer
+            && tokio::time::timeout(GOING_OFFLINE_TIMEOUT, writer)
+                .await
+                .is_err()
+        {
+            log::warn!("Timed out sending going_offline to the server");
+        }
 
         let mut state = self.state.lock();
         state.connected = false;
@@ -501,6 +732,12 @@ impl DjClient {
         self.state.lock().clone()
     }
 
+    /// Server time minus local time in milliseconds, once a clock sync or
+    /// heartbeat echo has come back
+    pub fn server_time_offset_ms(&self) -> Option<f64> {
+        self.state.lock().clock.offset_ms()
+    }
+
     /// Check if connected
     pub fn is_connected(&self) -> bool {
         self.state.lock().connected
@@ -535,8 +772,8 @@ impl DjClient {
         }
     }
 
-    /// Take the pending preset name (if any) that was received from the server.
-    pub fn take_pending_preset(&self) -> Option<String> {
+    /// Take the pending preset (if any) that was received from the server.
+    pub fn take_pending_preset(&self) -> Option<AudioPreset> {
         self.state.lock().pending_preset.take()
     }
 
@@ -564,6 +801,11 @@ impl DjClient {
     pub fn take_pending_dj_roster(&self) -> Option<serde_json::Value> {
         self.state.lock().pending_dj_roster.take()
     }
+
+    /// Take effects triggered since the last call (called by bridge task)
+    pub fn take_pending_effects(&self) -> Vec<String> {
+        std::mem::take(&mut self.state.lock().pending_effects)
+    }
 }
 
 /// Handle incoming server messages
@@ -600,12 +842,13 @@ async fn handle_server_message(
             s.is_active = update.is_active;
             log::info!("Status update: active={}", update.is_active);
         }
-        ServerMessage::ClockSyncRequest(_req) => {
+        ServerMessage::ClockSyncRequest(req) => {
             // Respond to clock sync
             let now = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs_f64();
+            state.lock().clock.add_one_way(req.server_time, now);
 
             let response = ClockSyncResponse::new(now);
             match serde_json::to_string(&response) {
@@ -624,20 +867,32 @@ async fn handle_server_message(
                 .unwrap_or_default()
                 .as_secs_f64();
 
+            let mut s = state.lock();
+            s.heartbeat_acked(ack.echo_ts);
             let latency = if let Some(echo_ts) = ack.echo_ts {
+                s.clock.add(ack.server_time, echo_ts, now);
                 ((now - echo_ts) * 1000.0) as f32
             } else {
                 ((now - ack.server_time) * 1000.0) as f32
             };
-            state.lock().latency_ms = latency.max(0.0);
-        }
-        ServerMessage::PresetSync(ps) => {
-            // Extract preset name from the server payload
-            if let Some(name) = ps.preset.get("name").and_then(|v| v.as_str()) {
-                log::info!("Preset sync from server: {}", name);
-                state.lock().pending_preset = Some(name.to_string());
+            let latency = latency.max(0.0);
+            if s.latency_ms > 0.0 {
+                // RFC 3550-style interarrival jitter: 1/16 of each new deviation
+                let deviation = (latency - s.latency_ms).abs();
+                s.latency_jitter_ms += (deviation - s.latency_jitter_ms) / 16.0;
             }
+            s.latency_ms = latency;
         }
+        ServerMessage::PresetSync(ps) => match ps.audio_preset() {
+            Some(preset) => {
+                log::info!("Preset sync from server: {}", preset.name);
+                state.lock().pending_preset = Some(preset);
+            }
+            None => log::warn!(
+                "Ignoring unrecognised or invalid preset sync: {}",
+                ps.preset
+            ),
+        },
         ServerMessage::PatternSync(ps) => {
             log::info!("Pattern sync from VJ: {}", ps.pattern);
             let mut s = state.lock();
@@ -662,6 +917,11 @@ async fn handle_server_message(
         }
         ServerMessage::EffectTriggered(eff) => {
             log::info!("Effect triggered: {}", eff.effect);
+            let mut s = state.lock();
+            // A stalled bridge shouldn't let a strobe-happy VJ grow this forever
+            if s.pending_effects.len() < MAX_PENDING_EFFECTS {
+                s.pending_effects.push(eff.effect);
+            }
         }
         ServerMessage::BandSensitivitySync(bs) => {
             if bs.sensitivity.len() >= 5 {
@@ -686,6 +946,7 @@ async fn handle_server_message(
             s.mc_host = route.minecraft_host;
             s.mc_port = route.minecraft_port;
             s.mc_zone = route.zone;
+            s.mirror_entities = route.mirror_entities;
             s.mc_entity_count = route.entity_count.or_else(|| {
                 route
                     .pattern_config
@@ -708,9 +969,12 @@ async fn handle_server_message(
                 s.current_pattern = pattern.clone();
                 s.pending_pattern_change = Some(pattern.clone());
             }
-            if let Some(ref preset) = route.preset {
-                log::info!("Stream route includes preset: {}", preset);
-                s.pending_preset = Some(preset.clone());
+            if let Some(ref name) = route.preset {
+                log::info!("Stream route includes preset: {}", name);
+                // Keep a preset sync already queued over an unknown name
+                if let Some(preset) = get_preset(name) {
+                    s.pending_preset = Some(preset);
+                }
             }
         }
         ServerMessage::VoiceStatus(vs) => {
@@ -735,9 +999,38 @@ async fn handle_server_message(
                 state.lock().pending_dj_roster = Some(value);
             }
         }
+        ServerMessage::QueueUpdate(queue) => {
+            log::info!(
+                "Queue update: position {} of {}, live: {}",
+                queue.position,
+                queue.total,
+                queue.active_dj_name.as_deref().unwrap_or("nobody")
+            );
+            let mut s = state.lock();
+            s.queue_position = queue.position as usize;
+            s.total_djs = queue.total as usize;
+            s.active_dj_name = queue.active_dj_name;
+        }
+        ServerMessage::ZoneList(list) => {
+            log::info!("Zone list: {} zones", list.zones.len());
+            let mut s = state.lock();
+            s.zones = Some(list.zones);
+            s.zone_lists_received += 1;
+        }
+        // Logged by the reader, which still has the raw type
+        ServerMessage::Unknown => {}
     }
 }
 
+/// The `type` field of a raw server message, if it has one
+fn message_type(text: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(text)
+        .ok()?
+        .get("type")?
+        .as_str()
+        .map(String::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -753,6 +1046,7 @@ mod tests {
         assert_eq!(config.reconnect_delay, 2.0);
         assert_eq!(config.heartbeat_interval, 2.0);
         assert!(config.connect_code.is_none());
+        assert!(config.auth_token.is_none());
         assert!(config.dj_id.is_none());
         assert!(config.dj_key.is_none());
     }
@@ -778,6 +1072,69 @@ mod tests {
         assert!(!client.is_active());
     }
 
+    #[test]
+    fn clock_offset_trusts_the_shortest_round_trip() {
+        let mut clock = ClockOffsetEstimator::default();
+        assert_eq!(clock.offset_ms(), None);
+
+        // Server is 2s ahead; the handshake sample is off by the one-way delay
+        clock.add_one_way(102.05, 100.0);
+        assert!((clock.offset_ms().unwrap() - 2050.0).abs() < 1e-6);
+        assert_eq!(clock.rtt_ms(), None);
+
+        clock.add(112.3, 110.0, 110.4);
+        clock.add(122.05, 120.0, 120.1);
+        assert!((clock.offset_ms().unwrap() - 2000.0).abs() < 1e-6);
+        assert!((clock.rtt_ms().unwrap() - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn zone_check_uses_the_server_list_once_known() {
+        let mut state = ConnectionState::default();
+        assert!(state.check_zone("anything").is_ok());
+
+        state.zones = Some(vec![ZoneInfo {
+            name: "main".to_string(),
+            world: None,
+            entity_count: None,
+        }]);
+        assert!(state.check_zone("main").is_ok());
+        let err = state.check_zone("mian").unwrap_err();
+        assert!(err.contains("mian") && err.contains("main"));
+    }
+
+    #[test]
+    fn message_type_reads_the_type_field() {
+        assert_eq!(
+            message_type(r#"{"type": "new_thing"}"#).as_deref(),
+            Some("new_thing")
+        );
+        assert_eq!(message_type(r#"{"kind": "x"}"#), None);
+        assert_eq!(message_type("not json"), None);
+    }
+
+    #[test]
+    fn unacknowledged_heartbeats_mark_the_link_stale() {
+        let mut state = ConnectionState::default();
+        assert!(!state.heartbeat_sent(0.0, 4.0));
+        assert!(!state.heartbeat_sent(2.0, 4.0));
+        state.heartbeat_acked(Some(2.0));
+        assert!(state.outstanding_heartbeats.is_empty());
+        assert_eq!(state.missed_heartbeats, 0);
+
+        // Acks stop: each heartbeat two intervals old counts as missed
+        let stale: Vec<bool> = (2..8)
+            .map(|i| state.heartbeat_sent(i as f64 * 2.0, 4.0))
+            .collect();
+        assert_eq!(stale, [false, false, false, false, true, true]);
+        assert_eq!(state.missed_heartbeats, 4);
+
+        // One ack resets the streak, not the total
+        state.heartbeat_acked(None);
+        assert_eq!(state.consecutive_missed_heartbeats, 0);
+        assert_eq!(state.missed_heartbeats, 4);
+    }
+
     #[tokio::test]
     async fn disconnect_without_connection_succeeds() {
         let client = DjClient::new(DjClientConfig::default());
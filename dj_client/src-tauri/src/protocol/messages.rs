@@ -1,5 +1,6 @@
 //! Message types for VJ server protocol
 
+use crate::audio::{AudioPreset, get_preset};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -52,6 +53,48 @@ impl CodeAuthMessage {
     }
 }
 
+/// Bearer token authentication message, for servers behind SSO or a proxy
+/// that issue tokens (e.g. JWTs) instead of connect codes
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenAuthMessage {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub token: String,
+    pub dj_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct_mode: Option<bool>,
+}
+
+impl TokenAuthMessage {
+    pub fn new(token: String, dj_name: String) -> Self {
+        Self {
+            msg_type: "token_auth".to_string(),
+            token,
+            dj_name,
+            direct_mode: Some(true),
+        }
+    }
+}
+
+/// Normalize a connect code to the `XXXX-XXXX` form the server issues.
+///
+/// Accepts lower case and a missing dash (as typed into the code input);
+/// anything else is rejected before a socket is opened.
+pub fn normalize_connect_code(code: &str) -> Result<String, String> {
+    let compact: String = code
+        .trim()
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let dashes = code.matches('-').count();
+    let dash_ok = dashes == 0 || (dashes == 1 && code.trim().find('-') == Some(4));
+    if compact.len() != 8 || !dash_ok || !compact.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("Connect code must look like ABCD-EF12".to_string());
+    }
+    Ok(format!("{}-{}", &compact[..4], &compact[4..]))
+}
+
 /// Audio frame message sent to VJ server
 #[derive(Debug, Clone, Serialize)]
 pub struct AudioFrameMessage {
@@ -59,6 +102,8 @@ pub struct AudioFrameMessage {
     pub msg_type: &'static str,
     pub seq: u64,
     pub bands: [f32; 5],
+    /// Sub-bass rumble below the bass band (0-1)
+    pub sub_bass: f32,
     pub peak: f32,
     pub beat: bool,
     pub beat_i: f32,
@@ -73,6 +118,10 @@ pub struct AudioFrameMessage {
     /// Whether this DJ is using direct Minecraft connection mode
     #[serde(skip_serializing_if = "Option::is_none")]
     pub direct_mode: Option<bool>,
+    /// Locally computed entities, sent when the route asks for
+    /// `mirror_entities` so the browser preview matches Minecraft
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entities: Option<Vec<serde_json::Value>>,
 }
 
 impl AudioFrameMessage {
@@ -93,6 +142,7 @@ impl AudioFrameMessage {
             msg_type: "dj_audio_frame",
             seq,
             bands,
+            sub_bass: 0.0,
             peak,
             beat,
             beat_i: beat_intensity,
@@ -106,6 +156,7 @@ impl AudioFrameMessage {
                 .unwrap_or_default()
                 .as_secs_f64(),
             direct_mode: None,
+            entities: None,
         }
     }
 }
@@ -196,16 +247,22 @@ pub struct VoiceAudioMessage {
     pub msg_type: String,
     pub data: String,
     pub seq: u64,
-    pub codec: String,
+    pub codec: &'static str,
+    /// Music ducking level (0 = full music, 1 = music fully attenuated)
+    pub duck: f32,
+    /// 1 (mono) or 2 (interleaved stereo)
+    pub channels: u8,
 }
 
 impl VoiceAudioMessage {
-    pub fn new(data: String, seq: u64, codec: String) -> Self {
+    pub fn new(data: String, seq: u64, codec: &'static str, duck: f32, channels: u8) -> Self {
         Self {
             msg_type: "voice_audio".to_string(),
             data,
             seq,
             codec,
+            duck,
+            channels,
         }
     }
 }
@@ -233,6 +290,27 @@ impl VoiceConfigMessage {
     }
 }
 
+/// Ask the server for its visualization zones (answered with `zone_list`)
+#[derive(Debug, Clone, Serialize)]
+pub struct ListZonesMessage {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+}
+
+impl ListZonesMessage {
+    pub fn new() -> Self {
+        Self {
+            msg_type: "list_zones".to_string(),
+        }
+    }
+}
+
+impl Default for ListZonesMessage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // === Incoming Messages ===
 
 /// Server message types (incoming)
@@ -280,6 +358,17 @@ pub enum ServerMessage {
 
     #[serde(rename = "dj_roster")]
     DjRoster(DjRosterMessage),
+
+    #[serde(rename = "queue_update")]
+    QueueUpdate(QueueUpdateMessage),
+
+    #[serde(rename = "zone_list")]
+    ZoneList(ZoneListMessage),
+
+    /// A message type this client doesn't know yet (e.g. from a newer
+    /// server); ignored rather than treated as a parse error
+    #[serde(other)]
+    Unknown,
 }
 
 /// Auth success response
@@ -294,6 +383,12 @@ pub struct AuthSuccessMessage {
     pub pattern_config: Option<PatternConfigInfo>,
     #[serde(default)]
     pub route_mode: Option<String>,
+    /// Protocol version the server speaks (absent on older servers)
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+    /// Heartbeat interval the server prefers, in seconds
+    #[serde(default)]
+    pub heartbeat_interval: Option<f64>,
 }
 
 /// Auth error response
@@ -353,6 +448,30 @@ pub struct PresetSyncMessage {
     pub preset: serde_json::Value,
 }
 
+impl PresetSyncMessage {
+    /// The preset to apply: the server's full tuning if it sent one, else
+    /// the built-in preset it names. Tuning that fails
+    /// `AudioPreset::validate` is dropped.
+    pub fn audio_preset(&self) -> Option<AudioPreset> {
+        let mut payload = self.preset.clone();
+        if let Some(fields) = payload.as_object_mut() {
+            // Tuning broadcast without a preset name
+            fields
+                .entry("name")
+                .or_insert_with(|| serde_json::Value::from("custom"));
+        }
+        serde_json::from_value(payload)
+            .ok()
+            .or_else(|| {
+                self.preset
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .and_then(get_preset)
+            })
+            .filter(|preset: &AudioPreset| preset.validate().is_ok())
+    }
+}
+
 /// Effect triggered notification
 #[derive(Debug, Clone, Deserialize)]
 pub struct EffectTriggeredMessage {
@@ -396,6 +515,9 @@ pub struct StreamRouteMessage {
     pub band_sensitivity: Option<Vec<f32>>,
     #[serde(default)]
     pub preset: Option<String>,
+    /// Echo the DJ's computed entities in every `dj_audio_frame`
+    #[serde(default)]
+    pub mirror_entities: bool,
 }
 
 /// Voice status update from server
@@ -423,6 +545,36 @@ pub struct DjRosterMessage {
     pub rotation_interval_sec: u32,
 }
 
+/// This DJ's place in the rotation queue
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueUpdateMessage {
+    /// Place in the queue (1 = next up, 0 = live)
+    #[serde(default)]
+    pub position: u32,
+    /// DJs connected, including the live one
+    #[serde(default)]
+    pub total: u32,
+    #[serde(default)]
+    pub active_dj_name: Option<String>,
+}
+
+/// Zones the server can visualize, in reply to `list_zones`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneListMessage {
+    #[serde(default)]
+    pub zones: Vec<ZoneInfo>,
+}
+
+/// One visualization zone
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ZoneInfo {
+    pub name: String,
+    #[serde(default)]
+    pub world: Option<String>,
+    #[serde(default)]
+    pub entity_count: Option<u32>,
+}
+
 /// Single DJ entry in the roster
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DjRosterEntry {
@@ -439,6 +591,26 @@ pub struct DjRosterEntry {
 mod tests {
     use super::*;
 
+    #[test]
+    fn connect_code_is_normalized() {
+        assert_eq!(normalize_connect_code("BEAT-7K3M").unwrap(), "BEAT-7K3M");
+        assert_eq!(normalize_connect_code(" beat7k3m ").unwrap(), "BEAT-7K3M");
+    }
+
+    #[test]
+    fn malformed_connect_code_is_rejected() {
+        for code in [
+            "",
+            "BEAT-7K3",
+            "BEA-T7K3M",
+            "BEAT--7K3M",
+            "BEAT-7K3M!",
+            "BEAT 7K3M",
+        ] {
+            assert!(normalize_connect_code(code).is_err(), "accepted {code:?}");
+        }
+    }
+
     #[test]
     fn code_auth_message_serializes_expected_shape() {
         let msg = CodeAuthMessage::new("BEAT-7K3M".to_string(), "DJ Spark".to_string(), None);
@@ -495,7 +667,9 @@ mod tests {
         assert!((json["beat_phase"].as_f64().unwrap_or_default() - 0.2).abs() < 1e-6);
         assert!((json["i_bass"].as_f64().unwrap_or_default() - 0.6).abs() < 1e-6);
         assert_eq!(json["i_kick"], true);
+        assert_eq!(json["sub_bass"], 0.0);
         assert!(json["ts"].as_f64().unwrap_or(0.0) > 0.0);
+        assert!(json.get("entities").is_none());
     }
 
     #[test]
@@ -508,6 +682,15 @@ mod tests {
         assert!(msg.dj_send_time >= recv_time);
     }
 
+    #[test]
+    fn token_auth_serializes_with_type_tag() {
+        let msg = TokenAuthMessage::new("eyJhbGciOi.x.y".to_string(), "DJ Test".to_string());
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], "token_auth");
+        assert_eq!(json["token"], "eyJhbGciOi.x.y");
+        assert_eq!(json["dj_name"], "DJ Test");
+    }
+
     #[test]
     fn server_message_deserializes_auth_success_variant() {
         let input = r#"{
@@ -560,8 +743,97 @@ mod tests {
                     msg.pattern_config.as_ref().and_then(|cfg| cfg.entity_count),
                     Some(24)
                 );
+                assert!(!msg.mirror_entities);
             }
             _ => panic!("expected stream_route variant"),
         }
     }
+
+    #[test]
+    fn zone_list_deserializes_and_ignores_extra_fields() {
+        let input = r#"{
+          "type": "zone_list",
+          "zones": [
+            {"name": "main", "world": "world", "entity_count": 64, "origin": {"x": 0}},
+            {"name": "stage_left"}
+          ]
+        }"#;
+        match serde_json::from_str(input).expect("zone_list payload should deserialize") {
+            ServerMessage::ZoneList(msg) => {
+                assert_eq!(msg.zones.len(), 2);
+                assert_eq!(msg.zones[0].name, "main");
+                assert_eq!(msg.zones[0].entity_count, Some(64));
+                assert_eq!(msg.zones[1].world, None);
+            }
+            _ => panic!("expected zone_list variant"),
+        }
+    }
+
+    #[test]
+    fn unknown_message_type_is_recognized_not_rejected() {
+        let input = r#"{"type": "stage_lighting", "cue": 3}"#;
+        assert!(matches!(
+            serde_json::from_str::<ServerMessage>(input),
+            Ok(ServerMessage::Unknown)
+        ));
+        // A known type with a broken payload is still an error
+        let malformed = r#"{"type": "zone_list", "zones": 5}"#;
+        assert!(serde_json::from_str::<ServerMessage>(malformed).is_err());
+    }
+
+    #[test]
+    fn stream_route_reads_mirror_entities() {
+        let input = r#"{"type": "stream_route", "route_mode": "dual", "mirror_entities": true}"#;
+        match serde_json::from_str(input).expect("stream_route payload should deserialize") {
+            ServerMessage::StreamRoute(msg) => assert!(msg.mirror_entities),
+            _ => panic!("expected stream_route variant"),
+        }
+    }
+
+    #[test]
+    fn preset_sync_uses_sent_tuning_or_named_preset() {
+        let tuned = PresetSyncMessage {
+            preset: serde_json::json!({
+                "name": "club",
+                "attack": 0.6,
+                "release": 0.1,
+                "beat_threshold": 1.2,
+                "bass_weight": 0.8,
+                "band_sensitivity": [1.2, 1.0, 1.0, 0.9, 0.8],
+                "beat_sensitivity": 1.0,
+            }),
+        };
+        let preset = tuned.audio_preset().expect("full tuning should parse");
+        assert_eq!(preset.name, "club");
+        assert_eq!(preset.attack, 0.6);
+        assert_eq!(preset.band_sensitivity[0], 1.2);
+
+        let named = PresetSyncMessage {
+            preset: serde_json::json!({"name": "Chill"}),
+        };
+        assert_eq!(named.audio_preset().unwrap().name, "chill");
+
+        let unknown = PresetSyncMessage {
+            preset: serde_json::json!({"name": "no-such-preset"}),
+        };
+        assert!(unknown.audio_preset().is_none());
+
+        let mut invalid = tuned;
+        invalid.preset["attack"] = serde_json::json!(3.0);
+        assert!(invalid.audio_preset().is_none());
+    }
+
+    #[test]
+    fn queue_update_deserializes() {
+        let input =
+            r#"{"type": "queue_update", "position": 2, "total": 4, "active_dj_name": "DJ Nova"}"#;
+        match serde_json::from_str(input).expect("queue_update payload should deserialize") {
+            ServerMessage::QueueUpdate(msg) => {
+                assert_eq!(msg.position, 2);
+                assert_eq!(msg.total, 4);
+                assert_eq!(msg.active_dj_name.as_deref(), Some("DJ Nova"));
+            }
+            _ => panic!("expected queue_update variant"),
+        }
+    }
 }
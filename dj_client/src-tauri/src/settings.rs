@@ -0,0 +1,407 @@
+//! Persistent user settings
+//!
+//! The server, preset, audio source and voice configuration are stored as
+//! JSON in the Tauri app config dir, so a returning DJ starts where they
+//! left off instead of at `AppState::default()`. The content filter's
+//! custom word lists are saved here too.
+
+use crate::audio::{self, AudioPreset, BandScale, BeatSource};
+use crate::content_filter::{self, WordLists};
+use crate::midi::MidiMapping;
+use crate::state::{AppState, BeatAssistConfig};
+use crate::voice::VoiceConfig;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// File name inside the app config dir
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Settings errors
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Settings I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid settings file: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// User settings that survive restarts.
+///
+/// Missing fields fall back to their defaults, so older files keep loading
+/// as settings are added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server_host: String,
+    pub server_port: u16,
+    #[serde(deserialize_with = "preset_or_name")]
+    pub active_preset: AudioPreset,
+    pub band_trim: [f32; 5],
+    pub input_gain_db: f32,
+    pub auto_normalize: bool,
+    pub beat_source: BeatSource,
+    pub band_scale: BandScale,
+    pub db_floor: f32,
+    pub agc_enabled: bool,
+    pub normalize_across_presets: bool,
+    pub transient_emphasis: f32,
+    pub beat_release_ms: Option<u32>,
+    pub beat_assist: BeatAssistConfig,
+    pub band_edges: [f32; 6],
+    pub audio_source_id: Option<String>,
+    pub midi_mapping: MidiMapping,
+    pub voice_config: VoiceConfig,
+    pub filter_words: WordLists,
+    pub max_entity_count: u32,
+    pub visibility_threshold: Option<f32>,
+    pub low_power: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_state(&AppState::default())
+    }
+}
+
+impl Settings {
+    /// Snapshot the persisted fields of the app state, plus the word lists
+    /// of the app-wide content filter.
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            server_host: state.server_host.clone(),
+            server_port: state.server_port,
+            active_preset: state.active_preset.clone(),
+            band_trim: state.band_trim,
+            input_gain_db: state.input_gain_db,
+            auto_normalize: state.auto_normalize,
+            beat_source: state.beat_source,
+            band_scale: state.band_scale,
+            db_floor: state.db_floor,
+            agc_enabled: state.agc_enabled,
+            normalize_across_presets: state.normalize_across_presets,
+            transient_emphasis: state.transient_emphasis,
+            beat_release_ms: state.beat_release_ms,
+            beat_assist: state.beat_assist,
+            band_edges: state.band_edges,
+            audio_source_id: state.remembered_source_id.clone(),
+            midi_mapping: state.midi_mapping.clone(),
+            voice_config: state.voice_config.clone(),
+            filter_words: content_filter::global().read().word_lists().clone(),
+            max_entity_count: state.max_entity_count,
+            visibility_threshold: state.visibility_threshold,
+            low_power: state.low_power,
+        }
+    }
+
+    /// Copy these settings into the app state and the content filter.
+    ///
+    /// Whether voice is currently streaming is left alone, so loading
+    /// settings never turns the DJ's mic on by itself.
+    pub fn apply_to(&self, state: &mut AppState) {
+        state.server_host = self.server_host.clone();
+        state.server_port = self.server_port;
+        state.active_preset = self.active_preset.clone();
+        state.band_trim = self.band_trim;
+        state.input_gain_db = self.input_gain_db;
+        state.auto_normalize = self.auto_normalize;
+        state.beat_source = self.beat_source;
+        state.band_scale = self.band_scale;
+        state.db_floor = self.db_floor;
+        state.agc_enabled = self.agc_enabled;
+        state.normalize_across_presets = self.normalize_across_presets;
+        state.transient_emphasis = self.transient_emphasis;
+        state.beat_release_ms = self.beat_release_ms;
+        state.beat_assist = self.beat_assist.clamped();
+        state.band_edges = self.band_edges;
+        state.remembered_source_id = self.audio_source_id.clone();
+        state.midi_mapping = self.midi_mapping.clone();
+        state.max_entity_count = self.max_entity_count;
+        state.visibility_threshold = self.visibility_threshold;
+        state.low_power = self.low_power;
+        state.voice_config = VoiceConfig {
+            enabled: state.voice_config.enabled,
+            ..self.voice_config.clone()
+        };
+        content_filter::global()
+            .write()
+            .set_word_lists(&self.filter_words);
+    }
+
+    /// Load settings from `path`; a missing file yields the defaults.
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write settings to `path`, creating the directory if needed.
+    ///
+    /// Writes to a temporary file first so a crash mid-write can't leave a
+    /// truncated settings file behind.
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Read the active preset, or just its name as older settings files stored
+/// it. A name without a built-in preset falls back to the default one.
+fn preset_or_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<AudioPreset, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PresetOrName {
+        Preset(AudioPreset),
+        Name(String),
+    }
+
+    Ok(match PresetOrName::deserialize(deserializer)? {
+        PresetOrName::Preset(preset) => preset,
+        PresetOrName::Name(name) => audio::get_preset(&name).unwrap_or_else(|| {
+            log::warn!(
+                "Saved preset '{}' no longer exists, using the default",
+                name
+            );
+            AudioPreset::default()
+        }),
+    })
+}
+
+/// Format version written into exported setups; newer ones are refused
+pub const SETUP_FORMAT_VERSION: u32 = 1;
+
+/// A DJ's reactive setup, shared as JSON between DJs ("my techno setup"):
+/// the active preset with the live band trim, beat assist and the capture
+/// options. Server, device, MIDI and voice settings stay out, since they
+/// belong to one machine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedSetup {
+    pub version: u32,
+    pub preset: AudioPreset,
+    pub band_trim: [f32; 5],
+    pub beat_assist: BeatAssistConfig,
+    pub input_gain_db: f32,
+    pub auto_normalize: bool,
+    pub beat_source: BeatSource,
+    pub band_scale: BandScale,
+    pub db_floor: f32,
+    pub agc_enabled: bool,
+    pub band_edges: [f32; 6],
+    pub normalize_across_presets: bool,
+    /// Missing from setups exported before it was shared
+    #[serde(default)]
+    pub transient_emphasis: f32,
+}
+
+impl SharedSetup {
+    /// Snapshot the shareable fields of the app state
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            version: SETUP_FORMAT_VERSION,
+            preset: state.active_preset.clone(),
+            band_trim: state.band_trim,
+            beat_assist: state.beat_assist,
+            input_gain_db: state.input_gain_db,
+            auto_normalize: state.auto_normalize,
+            beat_source: state.beat_source,
+            band_scale: state.band_scale,
+            db_floor: state.db_floor,
+            agc_enabled: state.agc_enabled,
+            band_edges: state.band_edges,
+            normalize_across_presets: state.normalize_across_presets,
+            transient_emphasis: state.transient_emphasis,
+        }
+    }
+
+    /// Parse an exported setup, refusing ones from a newer format
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let setup: Self =
+            serde_json::from_str(json).map_err(|e| format!("Invalid setup file: {}", e))?;
+        if setup.version > SETUP_FORMAT_VERSION {
+            return Err(format!(
+                "Setup file version {} is newer than this app supports ({})",
+                setup.version, SETUP_FORMAT_VERSION
+            ));
+        }
+        Ok(setup)
+    }
+
+    /// Copy the setup into the app state; the preset becomes the active one
+    pub fn apply_to(&self, state: &mut AppState) {
+        state.active_preset = self.preset.clone();
+        state.band_trim = self.band_trim;
+        state.beat_assist = self.beat_assist;
+        state.input_gain_db = self.input_gain_db;
+        state.auto_normalize = self.auto_normalize;
+        state.beat_source = self.beat_source;
+        state.band_scale = self.band_scale;
+        state.db_floor = self.db_floor;
+        state.agc_enabled = self.agc_enabled;
+        state.band_edges = self.band_edges;
+        state.normalize_across_presets = self.normalize_across_presets;
+        state.transient_emphasis = self.transient_emphasis;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::{MidiAction, MidiBinding, MidiTrigger};
+    use std::path::PathBuf;
+
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!(
+                "mcav-settings-test-{}-{}",
+                name,
+                std::process::id()
+            ))
+            .join(SETTINGS_FILE)
+    }
+
+    #[test]
+    fn missing_file_loads_defaults() {
+        let path = scratch_path("missing");
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = scratch_path("round-trip");
+        let settings = Settings {
+            server_host: "vj.example.com".to_string(),
+            server_port: 9443,
+            active_preset: audio::get_preset("edm").unwrap(),
+            band_trim: [0.8, 1.0, 1.0, 1.0, 1.1],
+            input_gain_db: 6.0,
+            auto_normalize: true,
+            beat_source: BeatSource::Combined,
+            band_scale: BandScale::Db,
+            db_floor: -48.0,
+            agc_enabled: false,
+            band_edges: [20.0, 90.0, 250.0, 2000.0, 8000.0, 20000.0],
+            audio_source_id: Some("output:Speakers".to_string()),
+            midi_mapping: MidiMapping {
+                bindings: vec![MidiBinding {
+                    trigger: MidiTrigger::Note {
+                        channel: None,
+                        note: 36,
+                    },
+                    action: MidiAction::NextPreset,
+                }],
+            },
+            voice_config: VoiceConfig {
+                distance: 42.0,
+                ..Default::default()
+            },
+            filter_words: WordLists {
+                blocked: vec!["velvet rope".to_string()],
+                allowed: Vec::new(),
+            },
+            max_entity_count: 128,
+            visibility_threshold: Some(0.1),
+            low_power: true,
+            normalize_across_presets: true,
+            transient_emphasis: 0.5,
+            beat_release_ms: Some(250),
+            beat_assist: BeatAssistConfig {
+                enabled: false,
+                phase_window: 0.12,
+                ..Default::default()
+            },
+        };
+
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn partial_file_fills_in_defaults() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"server_port": 8080, "voice_config": {"distance": 5.0}}"#)
+                .unwrap();
+
+        assert_eq!(settings.server_port, 8080);
+        assert_eq!(settings.server_host, AppState::default().server_host);
+        assert_eq!(settings.voice_config.distance, 5.0);
+        assert_eq!(settings.voice_config.zone, VoiceConfig::default().zone);
+    }
+
+    #[test]
+    fn preset_names_from_older_files_load_the_built_in() {
+        let settings: Settings = serde_json::from_str(r#"{"active_preset": "Chill"}"#).unwrap();
+        assert_eq!(settings.active_preset, audio::get_preset("chill").unwrap());
+
+        let settings: Settings = serde_json::from_str(r#"{"active_preset": "gone"}"#).unwrap();
+        assert_eq!(settings.active_preset, AudioPreset::default());
+    }
+
+    #[test]
+    fn shared_setup_round_trips_and_refuses_newer_versions() {
+        let mut state = AppState {
+            active_preset: audio::get_preset("edm").unwrap(),
+            band_trim: [0.8, 1.0, 1.0, 1.0, 1.1],
+            beat_source: BeatSource::Combined,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&SharedSetup::from_state(&state)).unwrap();
+
+        state.active_preset = AudioPreset::default();
+        state.band_trim = [1.0; 5];
+        SharedSetup::parse(&json).unwrap().apply_to(&mut state);
+        assert_eq!(state.active_preset.name, "edm");
+        assert_eq!(state.band_trim, [0.8, 1.0, 1.0, 1.0, 1.1]);
+        assert_eq!(state.beat_source, BeatSource::Combined);
+
+        let newer = json.replace(
+            &format!("\"version\":{}", SETUP_FORMAT_VERSION),
+            "\"version\":99",
+        );
+        assert!(SharedSetup::parse(&newer).unwrap_err().contains("newer"));
+        assert!(SharedSetup::parse("{}").is_err());
+    }
+
+    #[test]
+    fn applying_settings_keeps_voice_enabled_state() {
+        let settings = Settings {
+            voice_config: VoiceConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut state = AppState::default();
+
+        settings.apply_to(&mut state);
+        assert!(!state.voice_config.enabled);
+    }
+
+    #[test]
+    fn applying_settings_clamps_beat_assist() {
+        let settings = Settings {
+            beat_assist: BeatAssistConfig {
+                min_tempo_confidence: 2.0,
+                phase_window: -0.1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut state = AppState::default();
+
+        settings.apply_to(&mut state);
+        assert_eq!(state.beat_assist.min_tempo_confidence, 1.0);
+        assert_eq!(state.beat_assist.phase_window, 0.0);
+    }
+}
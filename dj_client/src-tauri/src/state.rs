@@ -1,10 +1,18 @@
 //! Application state management
 
-use crate::audio::AudioCaptureHandle;
+use crate::analysis_server::AnalysisServer;
+use crate::audio::{
+    AnalysisResult, AudioCaptureHandle, AudioConfig, AudioPreset, BandScale, BeatSource,
+    DEFAULT_BAND_EDGES, VoiceInputHandle,
+};
+use crate::midi::{MidiListener, MidiMapping};
+use crate::osc::OscSender;
 use crate::protocol::DjClient;
 use crate::voice::{VoiceConfig, VoiceStatus, VoiceStreamer};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
@@ -14,14 +22,337 @@ pub struct ConnectionStatus {
     pub connected: bool,
     pub is_active: bool,
     pub latency_ms: f32,
+    /// Server time minus local time, once measured
+    pub server_time_offset_ms: Option<f64>,
     pub route_mode: String,
     pub mc_connected: bool,
     pub queue_position: usize,
     pub total_djs: usize,
     pub active_dj_name: Option<String>,
+    /// Connection health 0-100 (see `health_score`); 0 while disconnected
+    pub health: u8,
+    /// Entity count the server asked for, once known
+    pub requested_entity_count: Option<u32>,
+    /// Entity count actually rendered, after the local cap
+    pub effective_entity_count: Option<u32>,
     pub error: Option<String>,
 }
 
+impl ConnectionStatus {
+    /// One-line status for the tray tooltip, e.g.
+    /// `MCAV DJ - Live (dual, 34ms, MC ✓)`
+    pub fn tray_summary(&self) -> String {
+        if !self.connected {
+            return "MCAV DJ - Disconnected".to_string();
+        }
+        let state = if self.is_active {
+            "Live".to_string()
+        } else if self.queue_position > 0 {
+            format!("Queued #{}", self.queue_position)
+        } else {
+            "Connected".to_string()
+        };
+        let mut details = Vec::new();
+        if !self.route_mode.is_empty() {
+            details.push(self.route_mode.clone());
+        }
+        details.push(format!("{:.0}ms", self.latency_ms));
+        details.push(format!("MC {}", if self.mc_connected { "✓" } else { "✗" }));
+        format!("MCAV DJ - {} ({})", state, details.join(", "))
+    }
+}
+
+/// Most entities the client renders, and the default local cap
+pub const ENTITY_COUNT_LIMIT: u32 = 512;
+
+/// The server's requested entity count held to `1..=max`
+pub fn effective_entity_count(requested: u32, max: u32) -> u32 {
+    requested.clamp(1, max.max(1))
+}
+
+/// Leave only `band` active for calibration: the other bands (and the
+/// sub-bass, unless the bass band is soloed) are zeroed and the peak
+/// follows the soloed band
+pub fn solo_band(analysis: &mut AnalysisResult, band: usize) {
+    for (i, level) in analysis.bands.iter_mut().enumerate() {
+        if i != band {
+            *level = 0.0;
+        }
+    }
+    if band != 0 {
+        analysis.sub_bass = 0.0;
+    }
+    analysis.peak = analysis.bands.get(band).copied().unwrap_or(0.0);
+}
+
+/// The server asked for more entities than the local cap allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EntityCountClamp {
+    pub requested: u32,
+    pub effective: u32,
+}
+
+/// Patterns the server pushed to the local engine and the one running
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatternList {
+    pub loaded: Vec<String>,
+    pub active: Option<String>,
+}
+
+/// Single 0-100 connection health for a traffic-light display, from the
+/// heartbeat latency and jitter, the share of VJ frames dropped in the last
+/// stats window, and whether the bridge had to reconnect recently.
+///
+/// Each factor costs a capped number of points: latency up to 40 (from
+/// 50ms to 500ms), jitter up to 25 (from 5ms to 105ms), drops up to 25
+/// (at 10% dropped) and a recent reconnect 10.
+pub fn health_score(
+    latency_ms: f32,
+    jitter_ms: f32,
+    drop_ratio: f32,
+    reconnected_recently: bool,
+) -> u8 {
+    let latency_penalty = ((latency_ms - 50.0) / 450.0).clamp(0.0, 1.0) * 40.0;
+    let jitter_penalty = ((jitter_ms - 5.0) / 100.0).clamp(0.0, 1.0) * 25.0;
+    let drop_penalty = (drop_ratio / 0.1).clamp(0.0, 1.0) * 25.0;
+    let reconnect_penalty = if reconnected_recently { 10.0 } else { 0.0 };
+    let score = 100.0 - latency_penalty - jitter_penalty - drop_penalty - reconnect_penalty;
+    score.round().clamp(0.0, 100.0) as u8
+}
+
+/// User control over the bridge's reconnect loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeControl {
+    /// Skip the rest of the backoff and retry now
+    ReconnectNow,
+    /// Stop retrying and stay disconnected
+    CancelReconnect,
+}
+
+/// Synthetic beat assist applied by the bridge on top of detected beats
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeatAssistConfig {
+    /// Master switch; off sends only beats the analyzer detected
+    pub enabled: bool,
+    /// Minimum tempo confidence (0-1) before phase-predicted beats fire
+    pub min_tempo_confidence: f32,
+    /// Distance from the beat boundary (in beat phase, 0-0.5) inside which
+    /// a predicted beat may fire
+    pub phase_window: f32,
+    /// Count bass-lane kicks as beats
+    pub kick_assist: bool,
+}
+
+impl Default for BeatAssistConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_tempo_confidence: 0.60,
+            phase_window: 0.08,
+            kick_assist: true,
+        }
+    }
+}
+
+impl BeatAssistConfig {
+    /// Clamp the tunables to their valid ranges
+    pub fn clamped(self) -> Self {
+        Self {
+            min_tempo_confidence: self.min_tempo_confidence.clamp(0.0, 1.0),
+            phase_window: self.phase_window.clamp(0.0, 0.5),
+            ..self
+        }
+    }
+
+    /// The beat flag and intensity to send for `analysis`.
+    ///
+    /// Phase-aware assist: when tempo lock is strong and phase is near the
+    /// beat boundary, emit a conservative predicted beat so both VJ and
+    /// direct MC routes stay visually tight. `last_predicted_at` throttles
+    /// predictions to at most one per 0.6 beat periods.
+    pub fn apply(
+        &self,
+        analysis: &AnalysisResult,
+        now_secs: f64,
+        last_predicted_at: &mut f64,
+    ) -> (bool, f32) {
+        let mut is_beat = analysis.is_beat;
+        let mut intensity = analysis.beat_intensity;
+        if !self.enabled {
+            return (is_beat, intensity);
+        }
+
+        if !is_beat
+            && analysis.tempo_confidence >= self.min_tempo_confidence
+            && analysis.bpm >= 60.0
+        {
+            let beat_period = 60.0_f64 / analysis.bpm as f64;
+            let phase = analysis.beat_phase.clamp(0.0, 1.0);
+            let near_boundary = !(self.phase_window..=1.0 - self.phase_window).contains(&phase);
+            let can_fire = *last_predicted_at <= 0.0
+                || (now_secs - *last_predicted_at) >= (beat_period * 0.60);
+            if near_boundary && can_fire {
+                is_beat = true;
+                intensity =
+                    intensity.max((0.50 + analysis.tempo_confidence * 0.25).clamp(0.0, 1.0));
+                *last_predicted_at = now_secs;
+            }
+        }
+
+        // Use bass lane kick to supplement beat detection
+        if self.kick_assist && !is_beat && analysis.instant_kick {
+            is_beat = true;
+            intensity = intensity.max(0.5);
+        }
+
+        (is_beat, intensity)
+    }
+}
+
+/// Smallest move of the locked tempo reported as a change (BPM)
+const TEMPO_CHANGE_MIN_BPM: f32 = 4.0;
+
+/// Tempo confidence below which the tempo isn't considered locked
+const TEMPO_CHANGE_MIN_CONFIDENCE: f32 = 0.5;
+
+/// A significant shift of the locked tempo, e.g. across a song transition
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TempoChange {
+    pub old_bpm: f32,
+    pub new_bpm: f32,
+    pub confidence: f32,
+}
+
+/// Remembers the last reported tempo so the bridge can tell when the lock
+/// has moved
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TempoTracker {
+    reported_bpm: Option<f32>,
+}
+
+impl TempoTracker {
+    /// A change to report if `analysis` has a confident tempo more than
+    /// `TEMPO_CHANGE_MIN_BPM` from the last reported one. The first lock is
+    /// only remembered.
+    pub fn update(&mut self, analysis: &AnalysisResult) -> Option<TempoChange> {
+        if analysis.tempo_confidence < TEMPO_CHANGE_MIN_CONFIDENCE || analysis.bpm <= 0.0 {
+            return None;
+        }
+        let old_bpm = self.reported_bpm.replace(analysis.bpm);
+        match old_bpm {
+            Some(old_bpm) if (analysis.bpm - old_bpm).abs() > TEMPO_CHANGE_MIN_BPM => {
+                Some(TempoChange {
+                    old_bpm,
+                    new_bpm: analysis.bpm,
+                    confidence: analysis.tempo_confidence,
+                })
+            }
+            // Small drift: keep measuring from the last reported tempo
+            Some(old_bpm) => {
+                self.reported_bpm = Some(old_bpm);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+/// Beat intensity with an instant attack and exponential release, so it
+/// fades between beats instead of snapping from 1 to 0 and strobing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BeatEnvelope {
+    level: f32,
+    last_update: Option<Instant>,
+}
+
+impl BeatEnvelope {
+    /// Decay the held level over the time since the last call (falling to
+    /// about a third every `release`), then rise to `intensity` if higher
+    pub fn apply(&mut self, intensity: f32, release: Duration, now: Instant) -> f32 {
+        if let Some(last) = self.last_update {
+            let dt = now.saturating_duration_since(last).as_secs_f32();
+            self.level *= (-dt / release.as_secs_f32().max(1e-3)).exp();
+        }
+        self.last_update = Some(now);
+        self.level = self.level.max(intensity).min(1.0);
+        self.level
+    }
+}
+
+/// Bridge loop counters over the last one-second window
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct BridgeStats {
+    /// Length of the window the counts cover (ms)
+    pub window_ms: u32,
+    /// Bridge loop iterations per second (target ~60)
+    pub tick_rate: f32,
+    /// Longest gap between two ticks (ms)
+    pub max_tick_gap_ms: f32,
+    /// Longest wait for the app state lock at the start of a tick (ms)
+    pub max_lock_wait_ms: f32,
+    pub vj_frames_sent: u32,
+    /// Frames dropped because the send channel was full
+    pub vj_frames_dropped: u32,
+    /// Frames published straight to Minecraft (0 while direct publish is off)
+    pub mc_frames_sent: u32,
+    /// Ticks with no analysis to send (capture not running) and no keepalive
+    /// due
+    pub skipped_no_analysis: u32,
+    /// Ticks whose frame was held back by the idle keepalive
+    pub skipped_idle: u32,
+}
+
+/// Accumulates `BridgeStats` and hands them out once per window
+#[derive(Debug)]
+pub struct BridgeStatsWindow {
+    started: Instant,
+    last_tick: Option<Instant>,
+    ticks: u32,
+    /// Counters for the window in progress
+    pub counts: BridgeStats,
+}
+
+impl BridgeStatsWindow {
+    /// Length of one statistics window
+    pub const WINDOW: Duration = Duration::from_secs(1);
+
+    pub fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            last_tick: None,
+            ticks: 0,
+            counts: BridgeStats::default(),
+        }
+    }
+
+    /// Record the start of a bridge tick
+    pub fn record_tick(&mut self, now: Instant, lock_wait: Duration) {
+        if let Some(last) = self.last_tick {
+            let gap_ms = now.duration_since(last).as_secs_f32() * 1000.0;
+            self.counts.max_tick_gap_ms = self.counts.max_tick_gap_ms.max(gap_ms);
+        }
+        let wait_ms = lock_wait.as_secs_f32() * 1000.0;
+        self.counts.max_lock_wait_ms = self.counts.max_lock_wait_ms.max(wait_ms);
+        self.last_tick = Some(now);
+        self.ticks += 1;
+    }
+
+    /// Close the window if it has run its length, returning its stats
+    pub fn finish(&mut self, now: Instant) -> Option<BridgeStats> {
+        let elapsed = now.duration_since(self.started);
+        if elapsed < Self::WINDOW {
+            return None;
+        }
+        let mut stats = std::mem::take(&mut self.counts);
+        stats.window_ms = elapsed.as_millis() as u32;
+        stats.tick_rate = self.ticks as f32 / elapsed.as_secs_f32();
+        self.started = now;
+        self.ticks = 0;
+        Some(stats)
+    }
+}
+
 /// Application state
 pub struct AppState {
     /// WebSocket client
@@ -54,6 +385,10 @@ pub struct AppState {
     /// Connect code
     pub connect_code: Option<String>,
 
+    /// Bearer token from the last token connect, reused on reconnect (not
+    /// persisted)
+    pub auth_token: Option<String>,
+
     /// Server host
     pub server_host: String,
 
@@ -63,23 +398,129 @@ pub struct AppState {
     /// Selected audio source ID
     pub audio_source_id: Option<String>,
 
+    /// Device sources mixed in on top of `audio_source_id`
+    pub mix_source_ids: Vec<String>,
+
+    /// Per-source mix gains, reapplied when capture restarts
+    pub source_gains: HashMap<String, f32>,
+
+    /// Last source that captured successfully (persisted across launches)
+    pub remembered_source_id: Option<String>,
+
+    /// Why per-app capture fell back to system loopback, if it did
+    pub capture_fallback: Option<String>,
+
+    /// Pattern running in the bridge's local Lua engine, once the server
+    /// has sent scripts
+    pub local_pattern: Option<String>,
+
+    /// Pattern scripts loaded into the bridge's local Lua engine, sorted
+    pub loaded_patterns: Vec<String>,
+
     /// Shutdown signal sender for the bridge task
     pub bridge_shutdown_tx: Option<mpsc::Sender<()>>,
 
+    /// Reconnect controls for the bridge task
+    pub bridge_control_tx: Option<mpsc::Sender<BridgeControl>>,
+
     /// Handle for the bridge task (used to await completion on reconnect)
     pub bridge_task_handle: Option<JoinHandle<()>>,
 
     /// Voice audio streamer (shared with audio capture thread)
     pub voice_streamer: Option<Arc<VoiceStreamer>>,
 
+    /// Dedicated voice input (mic) feeding the voice streamer, if configured
+    pub voice_input: Option<VoiceInputHandle>,
+
     /// Voice streaming configuration
     pub voice_config: VoiceConfig,
 
     /// Voice streaming status (updated from server)
     pub voice_status: VoiceStatus,
 
-    /// Currently active audio preset name
-    pub active_preset: String,
+    /// Currently active audio preset: a built-in one, or one imported or
+    /// synced from the server that has no built-in to look up by name
+    pub active_preset: AudioPreset,
+
+    /// Per-band trim multiplied onto the preset's band sensitivity
+    pub band_trim: [f32; 5],
+
+    /// One-shot beat intensity queued by `trigger_beat`, consumed by the
+    /// next bridge frame
+    pub forced_beat: Option<f32>,
+
+    /// Effects fired locally (e.g. from a MIDI pad), consumed by the bridge
+    /// alongside the server's
+    pub local_effects: Vec<String>,
+
+    /// Pre-gain on the captured signal before analysis, in dB
+    pub input_gain_db: f32,
+
+    /// Slowly normalize the captured level before analysis
+    pub auto_normalize: bool,
+
+    /// Signal the beat detector listens to
+    pub beat_source: BeatSource,
+
+    /// Scale of the reported bands
+    pub band_scale: BandScale,
+
+    /// dBFS shown as 0 when `band_scale` is `Db`
+    pub db_floor: f32,
+
+    /// Per-band AGC in the analyzer
+    pub agc_enabled: bool,
+
+    /// Hold the average peak steady across preset switches
+    pub normalize_across_presets: bool,
+
+    /// Share of each band's rise added on top of the smoothed value
+    pub transient_emphasis: f32,
+
+    /// Release time of the beat intensity envelope; `None` sends the raw
+    /// per-frame intensity
+    pub beat_release_ms: Option<u32>,
+
+    /// Band edge frequencies in Hz
+    pub band_edges: [f32; 6],
+
+    /// Controller bindings applied to incoming MIDI
+    pub midi_mapping: MidiMapping,
+
+    /// Open MIDI input, while MIDI is enabled
+    pub midi_listener: Option<MidiListener>,
+
+    /// OSC output fed from the bridge loop, while enabled
+    pub osc_sender: Option<Arc<OscSender>>,
+
+    /// Local WebSocket analysis stream fed from the bridge loop, while enabled
+    pub analysis_server: Option<Arc<AnalysisServer>>,
+
+    /// Bridge counters from the last completed window
+    pub bridge_stats: BridgeStats,
+
+    /// Synthetic beat assist settings read by the bridge each tick
+    pub beat_assist: BeatAssistConfig,
+
+    /// Bridge tick interval (VJ frame rate); 16ms is ~60fps
+    pub bridge_tick: Duration,
+
+    /// Local cap on the server's entity count, for machines that can't
+    /// keep up with what the server asks for
+    pub max_entity_count: u32,
+
+    /// Band level below which mirrored entities are hidden; `None` leaves
+    /// visibility to the pattern
+    pub visibility_threshold: Option<f32>,
+
+    /// Main window hidden or minimized; the bridge slows its UI emits
+    pub ui_hidden: bool,
+
+    /// Band left active in the analysis the bridge sends, for calibration
+    pub solo_band: Option<usize>,
+
+    /// Halve the bridge's send and UI emit rates to save battery
+    pub low_power: bool,
 }
 
 impl Default for AppState {
@@ -95,15 +536,49 @@ impl Default for AppState {
             status: ConnectionStatus::default(),
             dj_name: String::new(),
             connect_code: None,
+            auth_token: None,
             server_host: "192.168.1.204".to_string(),
             server_port: 9000,
             audio_source_id: None,
+            mix_source_ids: Vec::new(),
+            source_gains: HashMap::new(),
+            remembered_source_id: None,
+            capture_fallback: None,
+            local_pattern: None,
+            loaded_patterns: Vec::new(),
             bridge_shutdown_tx: None,
+            bridge_control_tx: None,
             bridge_task_handle: None,
             voice_streamer: None,
+            voice_input: None,
             voice_config: VoiceConfig::default(),
             voice_status: VoiceStatus::default(),
-            active_preset: "auto".to_string(),
+            active_preset: AudioPreset::default(),
+            band_trim: [1.0; 5],
+            forced_beat: None,
+            local_effects: Vec::new(),
+            input_gain_db: 0.0,
+            auto_normalize: false,
+            beat_source: BeatSource::default(),
+            band_scale: BandScale::default(),
+            db_floor: AudioConfig::default().db_floor,
+            agc_enabled: true,
+            normalize_across_presets: false,
+            transient_emphasis: AudioConfig::default().transient_emphasis,
+            beat_release_ms: None,
+            band_edges: DEFAULT_BAND_EDGES,
+            midi_mapping: MidiMapping::default(),
+            midi_listener: None,
+            osc_sender: None,
+            analysis_server: None,
+            bridge_stats: BridgeStats::default(),
+            beat_assist: BeatAssistConfig::default(),
+            bridge_tick: Duration::from_millis(16),
+            max_entity_count: ENTITY_COUNT_LIMIT,
+            visibility_threshold: None,
+            ui_hidden: false,
+            solo_band: None,
+            low_power: false,
         }
     }
 }
@@ -140,12 +615,187 @@ mod tests {
         assert_eq!(state.bpm, 120.0);
         assert_eq!(state.server_host, "192.168.1.204");
         assert_eq!(state.server_port, 9000);
+        assert!(state.capture_fallback.is_none());
         assert!(state.bridge_shutdown_tx.is_none());
+        assert!(state.bridge_control_tx.is_none());
         assert!(state.bridge_task_handle.is_none());
         assert!(state.voice_streamer.is_none());
         assert!(!state.voice_config.enabled);
         assert_eq!(state.voice_config.channel_type, "static");
         assert!(!state.voice_status.available);
-        assert_eq!(state.active_preset, "auto");
+        assert_eq!(state.active_preset.name, "auto");
+    }
+
+    #[test]
+    fn bridge_stats_window_reports_rates_once_per_window() {
+        let start = Instant::now();
+        let mut window = BridgeStatsWindow::new(start);
+
+        for i in 0..30 {
+            let now = start + Duration::from_millis(i * 20);
+            window.record_tick(now, Duration::from_millis(if i == 7 { 3 } else { 0 }));
+            window.counts.vj_frames_sent += 1;
+            assert!(window.finish(now).is_none());
+        }
+        window.record_tick(start + Duration::from_millis(1000), Duration::ZERO);
+
+        let stats = window.finish(start + Duration::from_millis(1000)).unwrap();
+        assert_eq!(stats.window_ms, 1000);
+        assert!((stats.tick_rate - 31.0).abs() < 0.01);
+        assert!((stats.max_tick_gap_ms - 420.0).abs() < 0.01);
+        assert!((stats.max_lock_wait_ms - 3.0).abs() < 0.01);
+        assert_eq!(stats.vj_frames_sent, 30);
+        assert_eq!(window.counts, BridgeStats::default());
+    }
+
+    #[test]
+    fn tray_summary_shows_state_route_latency_and_mc() {
+        let mut status = ConnectionStatus::default();
+        assert_eq!(status.tray_summary(), "MCAV DJ - Disconnected");
+
+        status.connected = true;
+        status.latency_ms = 33.6;
+        status.queue_position = 2;
+        assert_eq!(status.tray_summary(), "MCAV DJ - Queued #2 (34ms, MC ✗)");
+
+        status.is_active = true;
+        status.route_mode = "dual".to_string();
+        status.mc_connected = true;
+        assert_eq!(status.tray_summary(), "MCAV DJ - Live (dual, 34ms, MC ✓)");
+    }
+
+    #[test]
+    fn health_score_penalizes_each_factor() {
+        assert_eq!(health_score(20.0, 1.0, 0.0, false), 100);
+        assert_eq!(health_score(275.0, 1.0, 0.0, false), 80);
+        assert_eq!(health_score(20.0, 55.0, 0.0, false), 88);
+        assert_eq!(health_score(20.0, 1.0, 0.05, false), 88);
+        assert_eq!(health_score(20.0, 1.0, 0.0, true), 90);
+        assert_eq!(health_score(2000.0, 500.0, 1.0, true), 0);
+    }
+
+    #[test]
+    fn effective_entity_count_respects_the_local_cap() {
+        assert_eq!(effective_entity_count(64, ENTITY_COUNT_LIMIT), 64);
+        assert_eq!(effective_entity_count(2000, ENTITY_COUNT_LIMIT), 512);
+        assert_eq!(effective_entity_count(512, 128), 128);
+        assert_eq!(effective_entity_count(0, 128), 1);
+        assert_eq!(effective_entity_count(16, 0), 1);
+    }
+
+    #[test]
+    fn solo_band_zeroes_the_other_bands() {
+        let mut analysis = AnalysisResult {
+            bands: [0.9, 0.5, 0.4, 0.3, 0.2],
+            sub_bass: 0.7,
+            peak: 0.9,
+            ..Default::default()
+        };
+        solo_band(&mut analysis, 2);
+        assert_eq!(analysis.bands, [0.0, 0.0, 0.4, 0.0, 0.0]);
+        assert_eq!(analysis.sub_bass, 0.0);
+        assert_eq!(analysis.peak, 0.4);
+
+        let mut bass = AnalysisResult {
+            bands: [0.9, 0.5, 0.4, 0.3, 0.2],
+            sub_bass: 0.7,
+            ..Default::default()
+        };
+        solo_band(&mut bass, 0);
+        assert_eq!(bass.bands, [0.9, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(bass.sub_bass, 0.7);
+    }
+
+    #[test]
+    fn beat_envelope_attacks_instantly_and_releases_smoothly() {
+        let release = Duration::from_millis(200);
+        let start = Instant::now();
+        let mut envelope = BeatEnvelope::default();
+
+        assert_eq!(envelope.apply(1.0, release, start), 1.0);
+        let after_release = envelope.apply(0.0, release, start + release);
+        assert!((after_release - (-1.0f32).exp()).abs() < 1e-4);
+        let later = envelope.apply(0.0, release, start + release * 3);
+        assert!(later < after_release && later > 0.0);
+
+        // A new beat is taken at once, even a weaker one
+        assert_eq!(envelope.apply(0.5, release, start + release * 3), 0.5);
+        assert_eq!(envelope.apply(1.5, release, start + release * 3), 1.0);
+    }
+
+    #[test]
+    fn tempo_tracker_reports_only_confident_large_moves() {
+        let tempo = |bpm: f32, tempo_confidence: f32| AnalysisResult {
+            bpm,
+            tempo_confidence,
+            ..Default::default()
+        };
+        let mut tracker = TempoTracker::default();
+
+        assert_eq!(tracker.update(&tempo(128.0, 0.2)), None);
+        // First lock is remembered, not reported
+        assert_eq!(tracker.update(&tempo(128.0, 0.8)), None);
+        // Drift under the threshold never adds up to a change
+        assert_eq!(tracker.update(&tempo(131.0, 0.8)), None);
+        assert_eq!(tracker.update(&tempo(124.5, 0.8)), None);
+        // Unconfident readings are ignored
+        assert_eq!(tracker.update(&tempo(90.0, 0.3)), None);
+        assert_eq!(
+            tracker.update(&tempo(140.0, 0.9)),
+            Some(TempoChange {
+                old_bpm: 128.0,
+                new_bpm: 140.0,
+                confidence: 0.9,
+            })
+        );
+        assert_eq!(tracker.update(&tempo(141.0, 0.9)), None);
+    }
+
+    fn locked_tempo_near_boundary() -> AnalysisResult {
+        AnalysisResult {
+            bpm: 120.0,
+            tempo_confidence: 0.8,
+            beat_phase: 0.97,
+            instant_kick: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn beat_assist_predicts_beat_near_phase_boundary() {
+        let mut last = 0.0;
+        let (is_beat, intensity) =
+            BeatAssistConfig::default().apply(&locked_tempo_near_boundary(), 10.0, &mut last);
+
+        assert!(is_beat);
+        assert!((intensity - 0.7).abs() < 1e-6);
+        assert_eq!(last, 10.0);
+    }
+
+    #[test]
+    fn disabled_beat_assist_passes_detected_beats_through() {
+        let config = BeatAssistConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let mut last = 0.0;
+
+        let (is_beat, intensity) = config.apply(&locked_tempo_near_boundary(), 10.0, &mut last);
+        assert!(!is_beat);
+        assert_eq!(intensity, 0.0);
+        assert_eq!(last, 0.0);
+    }
+
+    #[test]
+    fn beat_assist_respects_confidence_threshold_and_kick_switch() {
+        let config = BeatAssistConfig {
+            min_tempo_confidence: 0.9,
+            kick_assist: false,
+            ..Default::default()
+        };
+        let mut last = 0.0;
+
+        let (is_beat, _) = config.apply(&locked_tempo_near_boundary(), 10.0, &mut last);
+        assert!(!is_beat);
     }
 }
@@ -1,8 +1,11 @@
 //! Voice audio streaming for Simple Voice Chat integration
 //!
-//! Captures raw PCM audio, resamples to 48kHz mono i16, chunks into
-//! 960-sample (20ms) frames, Opus-encodes (with PCM fallback), and
-//! base64-encodes them for WebSocket transport.
+//! Captures raw PCM audio, resamples to 48kHz i16 (mono for speech, or
+//! stereo when streaming the music mix), chunks into frames (20ms by
+//! default, 2.5-40ms configurable), gates out silence, Opus-encodes (with PCM
+//! fallback), and base64-encodes them for WebSocket transport. Each frame
+//! carries a ducking level the server can use to attenuate the music
+//! while the DJ is talking.
 
 use base64::Engine;
 #[cfg(feature = "voice-opus")]
@@ -10,24 +13,86 @@ use opus::{Application, Channels, Encoder as OpusEncoder};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 
-/// Number of samples per voice frame (20ms at 48kHz)
+/// Number of samples per default voice frame (20ms at 48kHz). The queue
+/// limits below count frames of this size and scale for shorter frames.
 const VOICE_FRAME_SAMPLES: usize = 960;
 
+/// Frame durations (ms) Opus accepts that can be picked in `VoiceConfig`
+pub const VOICE_FRAME_SIZES_MS: [f32; 5] = [2.5, 5.0, 10.0, 20.0, 40.0];
+
+/// Encoder output buffer per 20ms of audio
+const MAX_PACKET_BYTES_PER_FRAME: usize = 4000;
+
+/// Codec this build sends with when the Opus encoder initializes
+const BUILD_CODEC: &str = if cfg!(feature = "voice-opus") {
+    "opus"
+} else {
+    "pcm"
+};
+
+/// Frame RMS below which DTX treats a frame as silence (about -60 dBFS)
+const DTX_SILENCE_RMS: f32 = 0.001;
+
+/// How often DTX still sends a silent frame to keep the stream alive (ms),
+/// matching Opus' own DTX cadence
+const DTX_KEEPALIVE_MS: f32 = 400.0;
+
 /// Maximum number of queued frames before dropping oldest
 const MAX_QUEUED_FRAMES: usize = 50;
 
+/// Queue depth the paced drain aims for (60ms of voice)
+const TARGET_QUEUED_FRAMES: usize = 3;
+
+/// Upper bound on frames released by a single paced drain
+const MAX_FRAMES_PER_DRAIN: usize = 8;
+
 /// Target sample rate for voice output
 const VOICE_SAMPLE_RATE: u32 = 48_000;
 
+/// Duration of one voice frame in milliseconds
+const VOICE_FRAME_MS: u32 = 20;
+
 /// Voice streaming configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VoiceConfig {
     pub enabled: bool,
     pub channel_type: String,
     pub distance: f64,
     pub zone: String,
+    /// RMS level (0-1) below which a frame counts as silence (0 disables the gate)
+    pub vad_threshold: f32,
+    /// How long silence must last before frames stop being queued (ms)
+    pub vad_hold_ms: u32,
+    /// How quickly the music ducks once speech starts (ms)
+    pub duck_attack_ms: u32,
+    /// How quickly the music recovers once speech stops (ms)
+    pub duck_release_ms: u32,
+    /// Maximum music attenuation while speaking (0-1, 0 disables ducking)
+    pub duck_depth: f32,
+    /// Input device to capture voice from (None = send the music mix)
+    pub voice_input_device: Option<String>,
+    /// Opus frame duration in ms, one of `VOICE_FRAME_SIZES_MS`. Shorter
+    /// frames cut latency, longer ones save bandwidth.
+    pub frame_ms: f32,
+    /// Keep left and right instead of downmixing to mono, for streaming
+    /// music rather than speech
+    pub stereo: bool,
+    /// Opus in-band forward error correction: each packet also carries a
+    /// low-bitrate copy of the previous frame, so a single lost packet can
+    /// be rebuilt. Costs bitrate (the main frame gets less of it) and only
+    /// helps when `packet_loss_perc` is above 0.
+    pub fec: bool,
+    /// Expected packet loss (0-100) the encoder plans for. Higher values
+    /// make frames less dependent on each other and give FEC more room, at
+    /// the price of quality on a clean link.
+    pub packet_loss_perc: u8,
+    /// Discontinuous transmission: near-silent frames are sent only every
+    /// 400ms. Saves most of the bandwidth between phrases even with the
+    /// noise gate off, but the receiver hears gaps rather than the room tone.
+    pub dtx: bool,
 }
 
 impl Default for VoiceConfig {
@@ -37,10 +102,60 @@ impl Default for VoiceConfig {
             channel_type: "static".to_string(),
             distance: 100.0,
             zone: "main".to_string(),
+            vad_threshold: 0.01,
+            vad_hold_ms: 500,
+            duck_attack_ms: 20,
+            duck_release_ms: 400,
+            duck_depth: 0.5,
+            voice_input_device: None,
+            frame_ms: VOICE_FRAME_MS as f32,
+            stereo: false,
+            fec: false,
+            packet_loss_perc: 0,
+            dtx: false,
         }
     }
 }
 
+impl VoiceConfig {
+    /// Clamp the levels to 0-1 and packet loss to 100%; an unsupported
+    /// frame size goes back to the default
+    pub fn clamped(self) -> Self {
+        Self {
+            vad_threshold: self.vad_threshold.clamp(0.0, 1.0),
+            duck_depth: self.duck_depth.clamp(0.0, 1.0),
+            packet_loss_perc: self.packet_loss_perc.min(100),
+            frame_ms: if frame_samples_for(self.frame_ms).is_some() {
+                self.frame_ms
+            } else {
+                VOICE_FRAME_MS as f32
+            },
+            ..self
+        }
+    }
+}
+
+/// Samples per frame at 48kHz for a frame duration in `VOICE_FRAME_SIZES_MS`
+pub fn frame_samples_for(frame_ms: f32) -> Option<usize> {
+    VOICE_FRAME_SIZES_MS
+        .contains(&frame_ms)
+        .then(|| (frame_ms * VOICE_SAMPLE_RATE as f32 / 1000.0) as usize)
+}
+
+/// An encoded voice frame ready to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceFrame {
+    /// Base64 Opus packet, or raw interleaved PCM with the `pcm` codec
+    pub data: String,
+    pub seq: u64,
+    /// "opus" or "pcm"
+    pub codec: &'static str,
+    /// Music ducking level when the frame was captured (0-1)
+    pub duck: f32,
+    /// 1 (mono) or 2 (interleaved stereo)
+    pub channels: u8,
+}
+
 /// Voice status information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoiceStatus {
@@ -48,6 +163,23 @@ pub struct VoiceStatus {
     pub streaming: bool,
     pub channel_type: String,
     pub connected_players: u32,
+    /// RMS level (0-1) of the most recent captured voice frame
+    pub level: f32,
+    /// Encoded frames waiting to be sent
+    pub queue_depth: u32,
+    /// Codec voice frames are sent with, "opus" or "pcm". Known from the
+    /// start of capture; "pcm" in builds without Opus or if it failed to
+    /// initialize.
+    pub codec: String,
+}
+
+/// Payload of the `voice-overrun` event
+#[derive(Debug, Clone, Serialize)]
+pub struct VoiceOverrun {
+    /// Frames dropped because the send queue was full
+    pub dropped_frames: u64,
+    /// Queue depth when the event was raised
+    pub queue_depth: u32,
 }
 
 impl Default for VoiceStatus {
@@ -57,6 +189,9 @@ impl Default for VoiceStatus {
             streaming: false,
             channel_type: "static".to_string(),
             connected_players: 0,
+            level: 0.0,
+            queue_depth: 0,
+            codec: BUILD_CODEC.to_string(),
         }
     }
 }
@@ -64,7 +199,7 @@ impl Default for VoiceStatus {
 /// Thread-safe voice audio streamer.
 ///
 /// Takes raw f32 audio samples from the capture callback, resamples to 48kHz
-/// mono i16, and chunks into 960-sample frames for voice chat transmission.
+/// mono i16, and chunks into frames for voice chat transmission.
 pub struct VoiceStreamer {
     /// Whether voice streaming is enabled
     enabled: AtomicBool,
@@ -72,11 +207,20 @@ pub struct VoiceStreamer {
     /// Sequence counter for voice frames
     seq: AtomicU64,
 
+    /// Frames dropped from a full queue since the last `take_dropped_frames`
+    dropped_frames: AtomicU64,
+
     /// Inner state protected by mutex
     inner: Mutex<VoiceStreamerInner>,
 
-    /// Source sample rate for resampling
-    source_sample_rate: u32,
+    /// Sample rate of the music mix this streamer was created for
+    mix_sample_rate: u32,
+
+    /// Sample rate of the samples currently being pushed
+    source_sample_rate: AtomicU32,
+
+    /// Whether a dedicated voice input is feeding this streamer
+    dedicated_input: AtomicBool,
 
     /// Source channel count for downmixing
     #[allow(dead_code)]
@@ -84,59 +228,113 @@ pub struct VoiceStreamer {
 }
 
 struct VoiceStreamerInner {
-    /// Residual f32 samples awaiting resampling (mono, source rate)
+    /// Residual f32 samples awaiting resampling (interleaved, source rate)
     residual: Vec<f32>,
 
-    /// Resampled i16 samples awaiting framing (48kHz mono)
+    /// Position of the next output sample past the start of `residual`,
+    /// in 1/48000ths of a source sample (see `plan_resample`)
+    resample_phase: u64,
+
+    /// Resampled i16 samples awaiting framing (48kHz, interleaved)
     frame_buffer: Vec<i16>,
 
-    /// Completed base64-encoded frames ready for sending
-    frames: VecDeque<String>,
+    /// Samples per channel per frame (48kHz), one of the
+    /// `VOICE_FRAME_SIZES_MS` sizes
+    frame_samples: usize,
+
+    /// Output channels: 1 (speech) or 2 (stereo music)
+    channels: usize,
+
+    /// Completed base64-encoded frames and their duck levels, ready for sending
+    frames: VecDeque<(String, f32)>,
 
     /// Opus encoder for compressing voice frames (None = PCM fallback)
     #[cfg(feature = "voice-opus")]
     opus_encoder: Option<OpusEncoder>,
 
     /// Codec identifier: "opus" or "pcm"
-    codec: String,
+    codec: &'static str,
+
+    /// Noise gate threshold (frame RMS, 0-1)
+    vad_threshold: f32,
+
+    /// Number of consecutive silent frames allowed before the gate closes
+    vad_hold_frames: usize,
+
+    /// Consecutive frames below the gate threshold
+    silent_frames: usize,
+
+    /// Per-frame envelope coefficient while the duck level rises
+    duck_attack_coef: f32,
+
+    /// Per-frame envelope coefficient while the duck level falls
+    duck_release_coef: f32,
+
+    /// Target duck level while speaking (0 = ducking disabled)
+    duck_depth: f32,
+
+    /// Current duck level (0-1)
+    duck: f32,
+
+    /// RMS level of the last completed frame, before gating (0-1)
+    level: f32,
+
+    /// Opus in-band FEC, re-applied when the encoder is recreated
+    fec: bool,
+
+    /// Expected packet loss (0-100) given to the encoder
+    packet_loss_perc: u8,
+
+    /// Whether near-silent frames are thinned out
+    dtx: bool,
+
+    /// Consecutive near-silent frames while DTX is on
+    dtx_silent_frames: usize,
 }
 
 impl VoiceStreamer {
     /// Create a new voice streamer for the given source format.
     pub fn new(source_sample_rate: u32, source_channels: u16) -> Self {
         #[cfg(feature = "voice-opus")]
-        let (opus_encoder, codec) = {
-            match OpusEncoder::new(48_000, Channels::Mono, Application::Audio) {
-                Ok(enc) => {
-                    log::info!("Opus encoder initialized for voice streaming");
-                    (Some(enc), "opus".to_string())
-                }
-                Err(e) => {
-                    log::warn!("Failed to create Opus encoder, falling back to PCM: {}", e);
-                    (None, "pcm".to_string())
-                }
-            }
-        };
+        let (opus_encoder, codec) = new_encoder(1);
         #[cfg(not(feature = "voice-opus"))]
         let codec = {
             log::info!(
                 "Voice streaming using PCM (build with voice-opus feature for Opus encoding)"
             );
-            "pcm".to_string()
+            "pcm"
         };
 
         Self {
             enabled: AtomicBool::new(false),
             seq: AtomicU64::new(0),
+            dropped_frames: AtomicU64::new(0),
             inner: Mutex::new(VoiceStreamerInner {
                 residual: Vec::with_capacity(4096),
+                resample_phase: 0,
                 frame_buffer: Vec::with_capacity(VOICE_FRAME_SAMPLES * 2),
+                frame_samples: VOICE_FRAME_SAMPLES,
+                channels: 1,
                 frames: VecDeque::with_capacity(MAX_QUEUED_FRAMES),
                 #[cfg(feature = "voice-opus")]
                 opus_encoder,
                 codec,
+                vad_threshold: 0.0,
+                vad_hold_frames: 0,
+                silent_frames: 0,
+                duck_attack_coef: 1.0,
+                duck_release_coef: 1.0,
+                duck_depth: 0.0,
+                duck: 0.0,
+                level: 0.0,
+                fec: false,
+                packet_loss_perc: 0,
+                dtx: false,
+                dtx_silent_frames: 0,
             }),
-            source_sample_rate,
+            mix_sample_rate: source_sample_rate,
+            source_sample_rate: AtomicU32::new(source_sample_rate),
+            dedicated_input: AtomicBool::new(false),
             source_channels: source_channels.max(1),
         }
     }
@@ -148,8 +346,12 @@ impl VoiceStreamer {
             // Clear buffered data and reset encoder state when disabled
             let mut inner = self.inner.lock();
             inner.residual.clear();
+            inner.resample_phase = 0;
             inner.frame_buffer.clear();
             inner.frames.clear();
+            inner.silent_frames = 0;
+            inner.duck = 0.0;
+            inner.level = 0.0;
             #[cfg(feature = "voice-opus")]
             if let Some(ref mut encoder) = inner.opus_encoder {
                 if let Err(e) = encoder.reset_state() {
@@ -164,47 +366,195 @@ impl VoiceStreamer {
         self.enabled.load(Ordering::Relaxed)
     }
 
+    /// Configure the noise gate.
+    ///
+    /// Frames whose RMS stays below `threshold` for longer than `hold_ms`
+    /// are not queued, so silence between songs costs no uplink bandwidth.
+    /// A threshold of 0 disables the gate.
+    pub fn set_vad(&self, threshold: f32, hold_ms: u32) {
+        let mut inner = self.inner.lock();
+        inner.vad_threshold = threshold.max(0.0);
+        inner.vad_hold_frames = (hold_ms as f32 / inner.frame_ms()).ceil() as usize;
+        inner.silent_frames = 0;
+    }
+
+    /// Configure the music ducking envelope.
+    ///
+    /// While the noise gate is open the duck level moves towards `depth`
+    /// with an `attack_ms` time constant, and back to 0 with `release_ms`
+    /// once speech stops. A depth of 0 disables ducking.
+    pub fn set_ducking(&self, attack_ms: u32, release_ms: u32, depth: f32) {
+        let mut inner = self.inner.lock();
+        let frame_ms = inner.frame_ms();
+        inner.duck_attack_coef = envelope_coef(attack_ms, frame_ms);
+        inner.duck_release_coef = envelope_coef(release_ms, frame_ms);
+        inner.duck_depth = depth.clamp(0.0, 1.0);
+        if inner.duck_depth == 0.0 {
+            inner.duck = 0.0;
+        }
+    }
+
+    /// Configure resilience to lossy networks: Opus in-band FEC, the packet
+    /// loss the encoder plans for (0-100), and DTX. FEC and the loss hint
+    /// only affect Opus; DTX applies to PCM too. See [`VoiceConfig`] for the
+    /// tradeoffs.
+    pub fn set_loss_resilience(&self, fec: bool, packet_loss_perc: u8, dtx: bool) {
+        let mut inner = self.inner.lock();
+        inner.fec = fec;
+        inner.packet_loss_perc = packet_loss_perc.min(100);
+        inner.dtx = dtx;
+        inner.dtx_silent_frames = 0;
+        #[cfg(feature = "voice-opus")]
+        {
+            let packet_loss_perc = inner.packet_loss_perc;
+            if let Some(ref mut encoder) = inner.opus_encoder {
+                configure_encoder(encoder, fec, packet_loss_perc);
+            }
+        }
+    }
+
+    /// Apply the frame size, gate, ducking and loss settings from a
+    /// [`VoiceConfig`].
+    pub fn apply_config(&self, config: &VoiceConfig) {
+        // Frame size first: the gate and envelope are counted in frames
+        self.set_frame_ms(config.frame_ms);
+        self.set_stereo(config.stereo);
+        self.set_loss_resilience(config.fec, config.packet_loss_perc, config.dtx);
+        self.set_vad(config.vad_threshold, config.vad_hold_ms);
+        self.set_ducking(
+            config.duck_attack_ms,
+            config.duck_release_ms,
+            config.duck_depth,
+        );
+    }
+
+    /// Change the frame duration. A size Opus doesn't accept falls back to
+    /// 20ms. Buffered audio is dropped and the encoder reset on a change.
+    fn set_frame_ms(&self, frame_ms: f32) {
+        let frame_samples = frame_samples_for(frame_ms).unwrap_or_else(|| {
+            log::warn!("Unsupported voice frame size {}ms, using 20ms", frame_ms);
+            VOICE_FRAME_SAMPLES
+        });
+        let mut inner = self.inner.lock();
+        if inner.frame_samples == frame_samples {
+            return;
+        }
+        inner.frame_samples = frame_samples;
+        inner.frame_buffer.clear();
+        inner.frames.clear();
+        #[cfg(feature = "voice-opus")]
+        if let Some(ref mut encoder) = inner.opus_encoder
+            && let Err(e) = encoder.reset_state()
+        {
+            log::warn!("Failed to reset Opus encoder state: {}", e);
+        }
+        log::info!("Voice frame size set to {}ms", inner.frame_ms());
+    }
+
+    /// Switch between mono and stereo output. Buffered audio is dropped and
+    /// the encoder recreated for the new channel count on a change.
+    fn set_stereo(&self, stereo: bool) {
+        let channels = if stereo { 2 } else { 1 };
+        let mut inner = self.inner.lock();
+        if inner.channels == channels {
+            return;
+        }
+        inner.channels = channels;
+        inner.residual.clear();
+        inner.resample_phase = 0;
+        inner.frame_buffer.clear();
+        inner.frames.clear();
+        #[cfg(feature = "voice-opus")]
+        {
+            let (mut encoder, codec) = new_encoder(channels);
+            if let Some(ref mut encoder) = encoder {
+                configure_encoder(encoder, inner.fec, inner.packet_loss_perc);
+            }
+            inner.opus_encoder = encoder;
+            inner.codec = codec;
+        }
+        log::info!(
+            "Voice streaming in {}",
+            if stereo { "stereo" } else { "mono" }
+        );
+    }
+
+    /// Switch between the music mix and a dedicated voice input.
+    ///
+    /// `Some(rate)` hands the streamer to a dedicated input (e.g. a headset
+    /// mic) running at `rate`; from then on [`push_mix_samples`] is ignored.
+    /// `None` goes back to the music mix at its original sample rate.
+    ///
+    /// [`push_mix_samples`]: Self::push_mix_samples
+    pub fn set_dedicated_input(&self, sample_rate: Option<u32>) {
+        let mut inner = self.inner.lock();
+        inner.residual.clear();
+        inner.resample_phase = 0;
+        inner.frame_buffer.clear();
+        self.source_sample_rate.store(
+            sample_rate.unwrap_or(self.mix_sample_rate),
+            Ordering::Relaxed,
+        );
+        self.dedicated_input
+            .store(sample_rate.is_some(), Ordering::Relaxed);
+    }
+
+    /// Feed samples from the music capture.
+    ///
+    /// Forwards to [`push_samples`](Self::push_samples) unless a dedicated
+    /// voice input has taken over.
+    pub fn push_mix_samples(&self, data: &[f32], channels: usize) {
+        if self.dedicated_input.load(Ordering::Relaxed) {
+            return;
+        }
+        self.push_samples(data, channels);
+    }
+
     /// Feed raw interleaved f32 samples from the audio capture callback.
     ///
     /// This method is designed to be called from the audio callback thread.
-    /// It downmixes to mono, resamples to 48kHz, converts to i16, and
-    /// chunks into 960-sample frames.
+    /// It downmixes to mono (or keeps the first two channels in stereo
+    /// mode), resamples to 48kHz, converts to i16, and chunks into frames of
+    /// the configured size.
     pub fn push_samples(&self, data: &[f32], channels: usize) {
         if !self.is_enabled() || data.is_empty() {
             return;
         }
 
         let channels = channels.max(1);
-
-        // Downmix to mono f32
-        let mono: Vec<f32> = data
-            .chunks(channels)
-            .map(|frame| {
-                let sum: f32 = frame.iter().sum();
-                sum / channels as f32
-            })
-            .collect();
-
         let mut inner = self.inner.lock();
+        let out_channels = inner.channels;
 
-        // Append to residual buffer
-        inner.residual.extend_from_slice(&mono);
-
-        // Resample from source rate to 48kHz
-        let resampled = resample(&inner.residual, self.source_sample_rate, VOICE_SAMPLE_RATE);
+        // Append to residual buffer, downmixed to mono or as left/right
+        // (a mono source is copied to both sides)
+        for frame in data.chunks(channels) {
+            if out_channels == 1 {
+                let sum: f32 = frame.iter().sum();
+                inner.residual.push(sum / channels as f32);
+            } else {
+                inner.residual.push(frame[0]);
+                inner.residual.push(*frame.get(1).unwrap_or(&frame[0]));
+            }
+        }
 
-        // Calculate how many source samples were consumed
-        // consumed = resampled.len() * source_rate / target_rate (approximately)
-        let consumed = if self.source_sample_rate == VOICE_SAMPLE_RATE {
-            resampled.len()
-        } else {
-            // For each output sample, we consumed source_rate/target_rate source samples
-            // More precisely: output_len = floor(input_len * target / source)
-            // so input_consumed = ceil(output_len * source / target)
-            let ratio = self.source_sample_rate as f64 / VOICE_SAMPLE_RATE as f64;
-            (resampled.len() as f64 * ratio).ceil() as usize
-        };
-        let consumed = consumed.min(inner.residual.len());
+        // Resample from source rate to 48kHz, carrying the fractional
+        // position into the next call
+        let source_sample_rate = self.source_sample_rate.load(Ordering::Relaxed);
+        let (output_len, consumed, phase) = plan_resample(
+            inner.residual.len() / out_channels,
+            source_sample_rate,
+            VOICE_SAMPLE_RATE,
+            inner.resample_phase,
+        );
+        let resampled = resample_interleaved(
+            &inner.residual,
+            out_channels,
+            (source_sample_rate, VOICE_SAMPLE_RATE),
+            inner.resample_phase,
+            output_len,
+        );
+        inner.resample_phase = phase;
+        let consumed = consumed * out_channels;
 
         // Convert f32 [-1,1] to i16 and append to frame buffer
         for &sample in &resampled {
@@ -216,14 +566,64 @@ impl VoiceStreamer {
         // Drain consumed source samples
         inner.residual.drain(..consumed);
 
-        // Extract complete 960-sample frames
-        while inner.frame_buffer.len() >= VOICE_FRAME_SAMPLES {
-            let frame_samples: Vec<i16> = inner.frame_buffer.drain(..VOICE_FRAME_SAMPLES).collect();
+        // Extract complete frames
+        let frame_len = inner.frame_samples * out_channels;
+        let max_queued = MAX_QUEUED_FRAMES * inner.frames_per_base_frame();
+        #[cfg(feature = "voice-opus")]
+        let max_packet_bytes =
+            MAX_PACKET_BYTES_PER_FRAME * inner.frame_samples.div_ceil(VOICE_FRAME_SAMPLES);
+        while inner.frame_buffer.len() >= frame_len {
+            let frame_samples: Vec<i16> = inner.frame_buffer.drain(..frame_len).collect();
+
+            // Noise gate: keep sending through the hold period, then stop
+            // queuing until the level comes back above the threshold
+            let rms = frame_rms(&frame_samples);
+            inner.level = rms;
+            let gate_open = if rms >= inner.vad_threshold {
+                inner.silent_frames = 0;
+                true
+            } else {
+                inner.silent_frames = inner.silent_frames.saturating_add(1);
+                inner.silent_frames <= inner.vad_hold_frames
+            };
+
+            // Ducking envelope follows the gate. Frames keep flowing after the
+            // gate closes until the release has brought the level back to 0,
+            // so the server always sees the music come back up.
+            let prev_duck = inner.duck;
+            let target = if gate_open { inner.duck_depth } else { 0.0 };
+            let coef = if target > prev_duck {
+                inner.duck_attack_coef
+            } else {
+                inner.duck_release_coef
+            };
+            let mut duck = prev_duck + (target - prev_duck) * coef;
+            if target == 0.0 && duck < 0.01 {
+                duck = 0.0;
+            }
+            inner.duck = duck;
+
+            if !gate_open && prev_duck == 0.0 {
+                continue;
+            }
+
+            // DTX: once the music is fully back up, near-silent frames only
+            // go out every DTX_KEEPALIVE_MS so the stream stays alive
+            if inner.dtx && rms < DTX_SILENCE_RMS && duck == 0.0 {
+                let keepalive = ((DTX_KEEPALIVE_MS / inner.frame_ms()).round() as usize).max(1);
+                let skip = inner.dtx_silent_frames % keepalive != 0;
+                inner.dtx_silent_frames += 1;
+                if skip {
+                    continue;
+                }
+            } else {
+                inner.dtx_silent_frames = 0;
+            }
 
             // Encode frame: Opus if available, otherwise raw PCM bytes
             #[cfg(feature = "voice-opus")]
             let encoded = if let Some(ref mut encoder) = inner.opus_encoder {
-                match encoder.encode_vec(&frame_samples, 4000) {
+                match encoder.encode_vec(&frame_samples, max_packet_bytes) {
                     Ok(opus_bytes) => base64::engine::general_purpose::STANDARD.encode(&opus_bytes),
                     Err(e) => {
                         log::warn!("Opus encode failed, sending PCM fallback: {}", e);
@@ -237,35 +637,170 @@ impl VoiceStreamer {
             let encoded = encode_pcm_frame(&frame_samples);
 
             // Push to frame queue, dropping oldest if full
-            if inner.frames.len() >= MAX_QUEUED_FRAMES {
+            if inner.frames.len() >= max_queued {
                 inner.frames.pop_front();
+                self.dropped_frames.fetch_add(1, Ordering::Relaxed);
             }
-            inner.frames.push_back(encoded);
+            inner.frames.push_back((encoded, duck));
         }
     }
 
-    /// Drain all ready frames as `(base64_data, sequence_number, codec)` tuples.
+    /// Drain all ready frames.
     ///
     /// When the Opus encoder is active, frames contain compressed Opus packets.
-    /// Otherwise they contain raw PCM (960 i16 samples = 1920 bytes LE).
-    pub fn drain_frames(&self) -> Vec<(String, u64, String)> {
+    /// Otherwise they contain raw interleaved PCM (960 i16 samples = 1920
+    /// bytes LE for a 20ms mono frame).
+    pub fn drain_frames(&self) -> Vec<VoiceFrame> {
+        self.drain_up_to(usize::MAX)
+    }
+
+    /// Drain a paced number of frames for one send tick.
+    ///
+    /// Releases one frame per call while the queue is at or below
+    /// `TARGET_QUEUED_FRAMES`, and more (up to `MAX_FRAMES_PER_DRAIN`) when
+    /// it has backed up, so a stall is worked off over a few ticks instead of
+    /// in one burst. Frames not released stay queued for the next call.
+    /// Frames shorter than 20ms are paced in 20ms groups.
+    pub fn drain_frames_paced(&self) -> Vec<VoiceFrame> {
+        let (depth, group) = {
+            let inner = self.inner.lock();
+            (inner.frames.len(), inner.frames_per_base_frame())
+        };
+        self.drain_up_to(paced_drain_count(depth.div_ceil(group)) * group)
+    }
+
+    fn drain_up_to(&self, max: usize) -> Vec<VoiceFrame> {
         let mut inner = self.inner.lock();
-        let codec = inner.codec.clone();
-        let mut result = Vec::with_capacity(inner.frames.len());
-        while let Some(frame) = inner.frames.pop_front() {
+        let codec = inner.codec;
+        let channels = inner.channels as u8;
+        let count = inner.frames.len().min(max);
+        let mut result = Vec::with_capacity(count);
+        for _ in 0..count {
+            let Some((frame, duck)) = inner.frames.pop_front() else {
+                break;
+            };
             let seq = self.seq.fetch_add(1, Ordering::Relaxed);
-            result.push((frame, seq, codec.clone()));
+            result.push(VoiceFrame {
+                data: frame,
+                seq,
+                codec,
+                duck,
+                channels,
+            });
         }
         result
     }
 
+    /// Codec frames are encoded with: "opus", or "pcm" without the
+    /// `voice-opus` feature or when the Opus encoder failed to initialize.
+    pub fn codec(&self) -> &'static str {
+        self.inner.lock().codec
+    }
+
+    /// Number of encoded frames waiting to be sent.
+    pub fn queue_depth(&self) -> usize {
+        self.inner.lock().frames.len()
+    }
+
+    /// Frames dropped from a full queue since the last call (resets the count).
+    pub fn take_dropped_frames(&self) -> u64 {
+        self.dropped_frames.swap(0, Ordering::Relaxed)
+    }
+
+    /// RMS level (0-1) of the most recent frame.
+    ///
+    /// Measured before the noise gate, so it keeps moving while the gate is
+    /// closed and can be used as a mic level meter.
+    pub fn current_level(&self) -> f32 {
+        self.inner.lock().level
+    }
+
     /// Get the current sequence number.
     pub fn current_seq(&self) -> u64 {
         self.seq.load(Ordering::Relaxed)
     }
 }
 
-/// Encode a PCM frame (960 i16 samples) to base64 little-endian bytes.
+impl VoiceStreamerInner {
+    /// Duration of one frame in milliseconds
+    fn frame_ms(&self) -> f32 {
+        self.frame_samples as f32 * 1000.0 / VOICE_SAMPLE_RATE as f32
+    }
+
+    /// Frames per 20ms, the unit the queue limits are counted in (1 for
+    /// 20ms frames and longer)
+    fn frames_per_base_frame(&self) -> usize {
+        (VOICE_FRAME_SAMPLES / self.frame_samples).max(1)
+    }
+}
+
+/// Opus encoder for `channels` (1 or 2), or `None` and the PCM codec if it
+/// can't be created
+#[cfg(feature = "voice-opus")]
+fn new_encoder(channels: usize) -> (Option<OpusEncoder>, &'static str) {
+    let layout = if channels == 2 {
+        Channels::Stereo
+    } else {
+        Channels::Mono
+    };
+    match OpusEncoder::new(VOICE_SAMPLE_RATE, layout, Application::Audio) {
+        Ok(enc) => {
+            log::info!("Opus encoder initialized for voice streaming");
+            (Some(enc), "opus")
+        }
+        Err(e) => {
+            log::warn!("Failed to create Opus encoder, falling back to PCM: {}", e);
+            (None, "pcm")
+        }
+    }
+}
+
+/// Apply the FEC and expected-loss settings to an Opus encoder
+#[cfg(feature = "voice-opus")]
+fn configure_encoder(encoder: &mut OpusEncoder, fec: bool, packet_loss_perc: u8) {
+    if let Err(e) = encoder.set_inband_fec(fec) {
+        log::warn!("Failed to set Opus in-band FEC: {}", e);
+    }
+    if let Err(e) = encoder.set_packet_loss_perc(packet_loss_perc as i32) {
+        log::warn!("Failed to set Opus packet loss percentage: {}", e);
+    }
+}
+
+/// Root-mean-square level of an i16 frame, normalized to 0-1.
+fn frame_rms(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum_sq: f64 = samples
+        .iter()
+        .map(|&s| {
+            let v = s as f64 / 32768.0;
+            v * v
+        })
+        .sum();
+    (sum_sq / samples.len() as f64).sqrt() as f32
+}
+
+/// How many frames a paced drain releases at the given queue depth.
+fn paced_drain_count(depth: usize) -> usize {
+    if depth <= TARGET_QUEUED_FRAMES {
+        depth.min(1)
+    } else {
+        (1 + (depth - TARGET_QUEUED_FRAMES).div_ceil(2)).min(MAX_FRAMES_PER_DRAIN)
+    }
+}
+
+/// One-pole envelope coefficient for a time constant of `time_ms`,
+/// applied once per voice frame of `frame_ms`. A time of 0 jumps straight
+/// to the target.
+fn envelope_coef(time_ms: u32, frame_ms: f32) -> f32 {
+    if time_ms == 0 {
+        return 1.0;
+    }
+    1.0 - (-frame_ms / time_ms as f32).exp()
+}
+
+/// Encode a PCM frame of i16 samples to base64 little-endian bytes.
 fn encode_pcm_frame(samples: &[i16]) -> String {
     let mut bytes = Vec::with_capacity(samples.len() * 2);
     for sample in samples {
@@ -274,38 +809,100 @@ fn encode_pcm_frame(samples: &[i16]) -> String {
     base64::engine::general_purpose::STANDARD.encode(&bytes)
 }
 
-/// Simple linear interpolation resampler.
+/// Plan one streaming resample step over `input_len` buffered samples.
 ///
-/// Resamples mono f32 samples from `from_rate` to `to_rate`.
-fn resample(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-    if input.is_empty() {
-        return Vec::new();
+/// Output sample `n` sits at source position `(phase + n * from) / to`,
+/// with `phase` carried over from the previous step, so the fractional
+/// position is never rounded away and the output keeps exact pace with the
+/// input however the audio is chunked. Every output position inside the
+/// buffer is produced, so the whole buffer is consumed once there is any
+/// output and nothing accumulates.
+///
+/// Returns `(output_len, consumed, next_phase)`: samples to produce, source
+/// samples to drop from the front of the buffer, and the position of the
+/// next output past the new buffer start (below `from`).
+fn plan_resample(
+    input_len: usize,
+    from_rate: u32,
+    to_rate: u32,
+    phase: u64,
+) -> (usize, usize, u64) {
+    if from_rate == to_rate {
+        return (input_len, input_len, 0);
     }
+    let (from, to) = (from_rate as u64, to_rate as u64);
+    // Every output whose left neighbour is buffered
+    let limit = input_len as u64 * to;
+    let output_len = limit.saturating_sub(phase).div_ceil(from);
+    let end = phase + output_len * from;
+    let consumed = (end / to).min(input_len as u64);
+    (output_len as usize, consumed as usize, end - consumed * to)
+}
 
-    if from_rate == to_rate {
-        return input.to_vec();
+/// Resample interleaved f32 samples with `channels` channels, one channel
+/// at a time, as planned by [`plan_resample`].
+fn resample_interleaved(
+    input: &[f32],
+    channels: usize,
+    (from_rate, to_rate): (u32, u32),
+    phase: u64,
+    output_len: usize,
+) -> Vec<f32> {
+    if channels <= 1 {
+        return resample_from(input, from_rate, to_rate, phase, output_len);
+    }
+    let per_channel: Vec<Vec<f32>> = (0..channels)
+        .map(|c| {
+            let channel: Vec<f32> = input.iter().skip(c).step_by(channels).copied().collect();
+            resample_from(&channel, from_rate, to_rate, phase, output_len)
+        })
+        .collect();
+    let frames = per_channel.iter().map(Vec::len).min().unwrap_or(0);
+    let mut output = Vec::with_capacity(frames * channels);
+    for i in 0..frames {
+        output.extend(per_channel.iter().map(|channel| channel[i]));
     }
+    output
+}
 
-    let ratio = from_rate as f64 / to_rate as f64;
-    let output_len = ((input.len() as f64) / ratio).floor() as usize;
+/// Resample a whole mono buffer from `from_rate` to `to_rate` in one go.
+#[cfg(test)]
+fn resample(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    let output_len = input.len() * to_rate as usize / from_rate as usize;
+    resample_from(input, from_rate, to_rate, 0, output_len)
+}
 
-    if output_len == 0 {
+/// Produce `output_len` mono samples at `to_rate` from `input` at
+/// `from_rate`, starting `phase / to_rate` source samples in.
+///
+/// Uses Catmull-Rom cubic interpolation when built with the
+/// `voice-hq-resample` feature, linear interpolation otherwise.
+fn resample_from(
+    input: &[f32],
+    from_rate: u32,
+    to_rate: u32,
+    phase: u64,
+    output_len: usize,
+) -> Vec<f32> {
+    if input.is_empty() || output_len == 0 {
         return Vec::new();
     }
 
+    if from_rate == to_rate {
+        return input[..output_len.min(input.len())].to_vec();
+    }
+
+    let (from, to) = (from_rate as u64, to_rate as u64);
     let mut output = Vec::with_capacity(output_len);
-    for i in 0..output_len {
-        let src_pos = i as f64 * ratio;
-        let src_idx = src_pos.floor() as usize;
-        let frac = (src_pos - src_idx as f64) as f32;
-
-        let sample = if src_idx + 1 < input.len() {
-            input[src_idx] * (1.0 - frac) + input[src_idx + 1] * frac
-        } else if src_idx < input.len() {
-            input[src_idx]
-        } else {
-            0.0
-        };
+    for i in 0..output_len as u64 {
+        let src_pos = phase + i * from;
+        let src_idx = (src_pos / to) as usize;
+        let frac = (src_pos % to) as f32 / to as f32;
+
+        #[cfg(feature = "voice-hq-resample")]
+        let sample = cubic_sample(input, src_idx, frac);
+        #[cfg(not(feature = "voice-hq-resample"))]
+        let sample = linear_sample(input, src_idx, frac);
 
         output.push(sample);
     }
@@ -313,6 +910,43 @@ fn resample(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
     output
 }
 
+/// Linear interpolation between `input[idx]` and `input[idx + 1]`.
+fn linear_sample(input: &[f32], idx: usize, frac: f32) -> f32 {
+    if idx + 1 < input.len() {
+        input[idx] * (1.0 - frac) + input[idx + 1] * frac
+    } else if idx < input.len() {
+        input[idx]
+    } else {
+        0.0
+    }
+}
+
+/// Catmull-Rom interpolation between `input[idx]` and `input[idx + 1]`.
+///
+/// The sample before the buffer start is clamped to the first sample; near
+/// the end, where there is no second sample of lookahead, it falls back to
+/// linear interpolation.
+#[cfg(feature = "voice-hq-resample")]
+fn cubic_sample(input: &[f32], idx: usize, frac: f32) -> f32 {
+    if idx + 2 >= input.len() {
+        return linear_sample(input, idx, frac);
+    }
+
+    let p0 = input[idx.saturating_sub(1)];
+    let p1 = input[idx];
+    let p2 = input[idx + 1];
+    let p3 = input[idx + 2];
+
+    let t = frac;
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -345,12 +979,12 @@ mod tests {
         assert_eq!(frames.len(), 1);
 
         // Verify codec field is set
-        let codec = &frames[0].2;
+        let codec = frames[0].codec;
         assert!(codec == "opus" || codec == "pcm");
 
         // Verify base64 decodes successfully
         let decoded = base64::engine::general_purpose::STANDARD
-            .decode(&frames[0].0)
+            .decode(&frames[0].data)
             .unwrap();
         if codec == "pcm" {
             // PCM: 960 i16 samples = 1920 bytes
@@ -361,6 +995,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn codec_is_known_before_any_frames() {
+        let streamer = VoiceStreamer::new(48000, 2);
+        #[cfg(not(feature = "voice-opus"))]
+        assert_eq!(streamer.codec(), "pcm");
+        #[cfg(feature = "voice-opus")]
+        assert!(streamer.codec() == "opus" || streamer.codec() == "pcm");
+        assert!(streamer.drain_frames().is_empty());
+    }
+
     #[test]
     fn voice_streamer_sequence_increments() {
         let streamer = VoiceStreamer::new(48000, 1);
@@ -371,10 +1015,10 @@ mod tests {
 
         let frames = streamer.drain_frames();
         assert_eq!(frames.len(), 2);
-        assert_eq!(frames[0].1, 0);
-        assert_eq!(frames[1].1, 1);
+        assert_eq!(frames[0].seq, 0);
+        assert_eq!(frames[1].seq, 1);
         // Both frames should have the same codec
-        assert_eq!(frames[0].2, frames[1].2);
+        assert_eq!(frames[0].codec, frames[1].codec);
     }
 
     #[test]
@@ -390,6 +1034,33 @@ mod tests {
 
         let frames = streamer.drain_frames();
         assert!(frames.len() <= MAX_QUEUED_FRAMES);
+        assert_eq!(streamer.take_dropped_frames(), 10);
+        assert_eq!(streamer.take_dropped_frames(), 0);
+    }
+
+    #[test]
+    fn paced_drain_scales_with_queue_depth() {
+        assert_eq!(paced_drain_count(0), 0);
+        assert_eq!(paced_drain_count(1), 1);
+        assert_eq!(paced_drain_count(TARGET_QUEUED_FRAMES), 1);
+        assert_eq!(paced_drain_count(TARGET_QUEUED_FRAMES + 4), 3);
+        assert_eq!(paced_drain_count(MAX_QUEUED_FRAMES), MAX_FRAMES_PER_DRAIN);
+    }
+
+    #[test]
+    fn paced_drain_keeps_remaining_frames_queued() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+        streamer.push_samples(&vec![0.1f32; 960 * 10], 1);
+
+        let first = streamer.drain_frames_paced();
+        assert_eq!(first.len(), 5);
+        assert_eq!(streamer.queue_depth(), 5);
+
+        // Sequence numbers continue across drains
+        let rest = streamer.drain_frames();
+        assert_eq!(rest[0].seq, first.last().unwrap().seq + 1);
+        assert_eq!(streamer.queue_depth(), 0);
     }
 
     #[test]
@@ -415,6 +1086,120 @@ mod tests {
         assert_eq!(output.len(), 200);
     }
 
+    /// Feed `chunks` through `plan_resample` like `push_samples` does and
+    /// return (total input, total output, largest residual seen)
+    fn stream_resample_plan(from: u32, chunks: impl Iterator<Item = usize>) -> (u64, u64, usize) {
+        let (mut total_in, mut total_out, mut max_residual) = (0u64, 0u64, 0usize);
+        let (mut residual, mut phase) = (0usize, 0u64);
+        for chunk in chunks {
+            total_in += chunk as u64;
+            residual += chunk;
+            let (out, consumed, next_phase) = plan_resample(residual, from, 48000, phase);
+            assert!(consumed <= residual);
+            total_out += out as u64;
+            residual -= consumed;
+            phase = next_phase;
+            max_residual = max_residual.max(residual);
+        }
+        (total_in, total_out, max_residual)
+    }
+
+    #[test]
+    fn resample_plan_keeps_pace_over_a_long_stream() {
+        // One hour of 10ms callbacks, plus ragged chunk sizes at odd ratios
+        for (from, chunks) in [
+            (44100, vec![441usize; 360_000]),
+            (96000, vec![960; 360_000]),
+            (44100, (0..360_000).map(|i| 400 + i % 97).collect()),
+            (22050, (0..360_000).map(|i| 100 + i % 331).collect()),
+            (192000, (0..360_000).map(|i| 1 + i % 13).collect()),
+        ] {
+            let (total_in, total_out, max_residual) =
+                stream_resample_plan(from, chunks.into_iter());
+            // Output matches the input duration to within one sample
+            let expected = total_in as f64 * 48000.0 / from as f64;
+            assert!(
+                (total_out as f64 - expected).abs() <= 1.0,
+                "{} Hz: {} out for {} expected",
+                from,
+                total_out,
+                expected
+            );
+            assert!(
+                max_residual <= 1,
+                "{} Hz residual grew to {}",
+                from,
+                max_residual
+            );
+        }
+    }
+
+    #[test]
+    fn resample_plan_passes_48k_through() {
+        assert_eq!(plan_resample(480, 48000, 48000, 0), (480, 480, 0));
+    }
+
+    #[test]
+    fn chunked_resample_matches_one_shot() {
+        let input: Vec<f32> = (0..4410).map(|i| (i as f32 * 0.01).sin()).collect();
+        let whole = resample(&input, 44100, 48000);
+        let mut chunked = Vec::new();
+        let (mut residual, mut phase) = (Vec::new(), 0u64);
+        for chunk in input.chunks(333) {
+            residual.extend_from_slice(chunk);
+            let (out, consumed, next_phase) = plan_resample(residual.len(), 44100, 48000, phase);
+            chunked.extend(resample_from(&residual, 44100, 48000, phase, out));
+            residual.drain(..consumed);
+            phase = next_phase;
+        }
+        // Only the edge samples of each chunk may differ (no right neighbour)
+        let close = whole
+            .iter()
+            .zip(&chunked)
+            .filter(|(a, b)| (*a - *b).abs() < 1e-3)
+            .count();
+        assert!(close >= whole.len() - 4410 / 333 - 1);
+        assert!((chunked.len() as i64 - whole.len() as i64).abs() <= 1);
+    }
+
+    #[test]
+    fn resample_preserves_linear_ramp() {
+        let input: Vec<f32> = (0..441).map(|i| i as f32 / 441.0).collect();
+        let output = resample(&input, 44100, 48000);
+        // The last output position lies past the final input sample
+        for (i, &sample) in output.iter().enumerate().take(output.len() - 1) {
+            let expected = (i as f64 * 44100.0 / 48000.0 / 441.0) as f32;
+            assert!(
+                (sample - expected).abs() < 1e-4,
+                "sample {} = {}",
+                i,
+                sample
+            );
+        }
+    }
+
+    #[cfg(feature = "voice-hq-resample")]
+    #[test]
+    fn cubic_resample_tracks_sine_closer_than_linear() {
+        // 5kHz sine at 44.1kHz -> 48kHz, compared against the ideal signal
+        let freq = 5000.0f64;
+        let input: Vec<f32> = (0..4410)
+            .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / 44100.0).sin() as f32)
+            .collect();
+        let ratio = 44100.0 / 48000.0;
+        let mut cubic_err = 0.0f64;
+        let mut linear_err = 0.0f64;
+        for (i, &sample) in resample(&input, 44100, 48000).iter().enumerate().skip(1) {
+            let pos = i as f64 * ratio;
+            let ideal = (2.0 * std::f64::consts::PI * freq * pos / 44100.0).sin();
+            let idx = pos.floor() as usize;
+            let linear = linear_sample(&input, idx, (pos - idx as f64) as f32);
+            cubic_err += (sample as f64 - ideal).powi(2);
+            linear_err += (linear as f64 - ideal).powi(2);
+        }
+        assert!(cubic_err < linear_err / 4.0);
+    }
+
     #[test]
     fn voice_streamer_stereo_downmix() {
         let streamer = VoiceStreamer::new(48000, 2);
@@ -439,6 +1224,204 @@ mod tests {
         assert_eq!(config.channel_type, "static");
         assert_eq!(config.distance, 100.0);
         assert_eq!(config.zone, "main");
+        assert_eq!(config.vad_threshold, 0.01);
+        assert_eq!(config.vad_hold_ms, 500);
+        assert_eq!(config.duck_attack_ms, 20);
+        assert_eq!(config.duck_release_ms, 400);
+        assert_eq!(config.duck_depth, 0.5);
+        assert!(config.voice_input_device.is_none());
+        assert_eq!(config.frame_ms, 20.0);
+    }
+
+    #[test]
+    fn frame_size_sets_samples_per_frame_and_pacing() {
+        assert_eq!(frame_samples_for(2.5), Some(120));
+        assert_eq!(frame_samples_for(40.0), Some(1920));
+        assert_eq!(frame_samples_for(15.0), None);
+
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+        streamer.apply_config(&VoiceConfig {
+            frame_ms: 10.0,
+            vad_threshold: 0.0,
+            ..Default::default()
+        });
+        streamer.push_samples(&vec![0.1f32; 960], 1);
+        assert_eq!(streamer.queue_depth(), 2);
+        // Two 10ms frames make up one 20ms pacing step
+        let frames = streamer.drain_frames_paced();
+        assert_eq!(frames.len(), 2);
+        #[cfg(not(feature = "voice-opus"))]
+        {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(&frames[0].data)
+                .unwrap();
+            assert_eq!(decoded.len(), 480 * 2);
+        }
+
+        // Unsupported sizes fall back to 20ms
+        streamer.apply_config(&VoiceConfig {
+            frame_ms: 7.0,
+            vad_threshold: 0.0,
+            ..Default::default()
+        });
+        streamer.push_samples(&vec![0.1f32; 960], 1);
+        assert_eq!(streamer.queue_depth(), 1);
+    }
+
+    #[test]
+    fn dtx_sends_silence_only_every_keepalive_interval() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+        streamer.apply_config(&VoiceConfig {
+            dtx: true,
+            fec: true,
+            packet_loss_perc: 150,
+            vad_threshold: 0.0,
+            duck_depth: 0.0,
+            ..Default::default()
+        });
+        assert_eq!(streamer.inner.lock().packet_loss_perc, 100);
+
+        // 1s of silence at 20ms frames: one keepalive per 400ms
+        streamer.push_samples(&vec![0.0f32; 960 * 50], 1);
+        assert_eq!(streamer.drain_frames().len(), 3);
+
+        // Sound goes out frame by frame
+        streamer.push_samples(&vec![0.1f32; 960 * 5], 1);
+        assert_eq!(streamer.drain_frames().len(), 5);
+    }
+
+    #[test]
+    fn stereo_keeps_left_and_right() {
+        let streamer = VoiceStreamer::new(48000, 2);
+        streamer.set_enabled(true);
+        streamer.apply_config(&VoiceConfig {
+            stereo: true,
+            vad_threshold: 0.0,
+            ..Default::default()
+        });
+
+        // 960 stereo frames make one 20ms frame of 960 samples per channel
+        let samples: Vec<f32> = (0..960).flat_map(|_| [0.5f32, -0.25]).collect();
+        streamer.push_samples(&samples, 2);
+        let frames = streamer.drain_frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].channels, 2);
+        #[cfg(not(feature = "voice-opus"))]
+        {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(&frames[0].data)
+                .unwrap();
+            assert_eq!(decoded.len(), 960 * 2 * 2);
+            let left = i16::from_le_bytes([decoded[0], decoded[1]]);
+            let right = i16::from_le_bytes([decoded[2], decoded[3]]);
+            assert!(left > 0 && right < 0);
+        }
+
+        // Back to mono for speech
+        streamer.apply_config(&VoiceConfig {
+            vad_threshold: 0.0,
+            ..Default::default()
+        });
+        streamer.push_samples(&samples, 2);
+        assert_eq!(streamer.drain_frames()[0].channels, 1);
+    }
+
+    #[test]
+    fn dedicated_input_replaces_music_mix() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+
+        streamer.push_mix_samples(&vec![0.1f32; 960], 1);
+        assert_eq!(streamer.drain_frames().len(), 1);
+
+        // Mic at 24kHz: mix samples are ignored, mic samples are resampled
+        streamer.set_dedicated_input(Some(24000));
+        streamer.push_mix_samples(&vec![0.1f32; 960], 1);
+        assert!(streamer.drain_frames().is_empty());
+        streamer.push_samples(&vec![0.1f32; 480], 1);
+        assert_eq!(streamer.drain_frames().len(), 1);
+
+        streamer.set_dedicated_input(None);
+        streamer.push_mix_samples(&vec![0.1f32; 960], 1);
+        assert_eq!(streamer.drain_frames().len(), 1);
+    }
+
+    #[test]
+    fn ducking_disabled_by_default() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+
+        streamer.push_samples(&vec![0.1f32; 960 * 3], 1);
+        assert!(streamer.drain_frames().iter().all(|f| f.duck == 0.0));
+    }
+
+    #[test]
+    fn ducking_attacks_while_speaking_and_releases_to_zero() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+        streamer.set_vad(0.01, 0);
+        streamer.set_ducking(0, 100, 0.8);
+
+        streamer.push_samples(&vec![0.1f32; 960 * 2], 1);
+        let frames = streamer.drain_frames();
+        assert!(frames.iter().all(|f| (f.duck - 0.8).abs() < 1e-6));
+
+        // Silence: the gate closes, but frames keep coming until the
+        // release has finished, ending on a duck level of exactly 0
+        streamer.push_samples(&vec![0.0f32; 960 * 50], 1);
+        let frames = streamer.drain_frames();
+        assert!(!frames.is_empty() && frames.len() < 50);
+        assert!(frames.windows(2).all(|w| w[1].duck < w[0].duck));
+        assert_eq!(frames.last().unwrap().duck, 0.0);
+    }
+
+    #[test]
+    fn vad_stops_queuing_after_hold_period() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+        streamer.set_vad(0.01, 100); // 5 frames of hold
+
+        // 10 frames of silence: only the 5 hold frames are queued
+        streamer.push_samples(&vec![0.0f32; 960 * 10], 1);
+        assert_eq!(streamer.drain_frames().len(), 5);
+
+        // Audio above the threshold reopens the gate immediately
+        streamer.push_samples(&vec![0.1f32; 960 * 2], 1);
+        assert_eq!(streamer.drain_frames().len(), 2);
+    }
+
+    #[test]
+    fn vad_zero_threshold_disables_gate() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        streamer.set_enabled(true);
+        streamer.set_vad(0.0, 0);
+
+        streamer.push_samples(&vec![0.0f32; 960 * 10], 1);
+        assert_eq!(streamer.drain_frames().len(), 10);
+    }
+
+    #[test]
+    fn frame_rms_of_full_scale_square_wave_is_one() {
+        let frame: Vec<i16> = (0..960)
+            .map(|i| if i % 2 == 0 { i16::MAX } else { i16::MIN })
+            .collect();
+        assert!((frame_rms(&frame) - 1.0).abs() < 0.001);
+        assert_eq!(frame_rms(&[0; 960]), 0.0);
+    }
+
+    #[test]
+    fn current_level_tracks_last_frame() {
+        let streamer = VoiceStreamer::new(48000, 1);
+        assert_eq!(streamer.current_level(), 0.0);
+        streamer.set_enabled(true);
+
+        streamer.push_samples(&vec![0.5f32; 960], 1);
+        assert!((streamer.current_level() - 0.5).abs() < 0.001);
+
+        streamer.set_enabled(false);
+        assert_eq!(streamer.current_level(), 0.0);
     }
 
     #[test]
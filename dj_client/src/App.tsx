@@ -62,7 +62,7 @@ function App() {
       // Ctrl/Cmd + R - Refresh audio sources
       if (modifierKey && event.key === 'r') {
         event.preventDefault();
-        void audioSources.loadAudioSources();
+        void audioSources.refreshAudioSources();
       }
 
       // Ctrl/Cmd + T - Toggle test audio
@@ -77,11 +77,16 @@ export default function ConnectedView({
           sources={audioSources.audioSources}
           value={audioSources.selectedSource}
           onChange={handleSourceChange}
-          onRefresh={audioSources.loadAudioSources}
+          onRefresh={audioSources.refreshAudioSources}
         />
         {connection.captureMode && connection.captureMode.mode === 'process_loopback' && (
           <span className="capture-info">{connection.captureMode.name}</span>
         )}
+        {connection.captureMode && connection.captureMode.mode === 'lost' && (
+          <span className="capture-info capture-lost">
+            {connection.captureMode.reason} - pick another source
+          </span>
+        )}
         <button
           className={`btn voice-toggle ${connection.voiceEnabled ? 'voice-on' : 'voice-off'}`}
           onClick={connection.handleToggleVoice}
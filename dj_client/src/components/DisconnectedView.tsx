@@ -39,7 +39,7 @@ export default function DisconnectedView({
         selectedSource={audioSources.selectedSource}
         onSourceChange={audioSources.setSelectedSource}
         audioSources={audioSources.audioSources}
-        onRefreshSources={audioSources.loadAudioSources}
+        onRefreshSources={audioSources.refreshAudioSources}
         directConnect={connection.directConnect}
         onDirectConnectChange={connection.setDirectConnect}
         serverHost={connection.serverHost}
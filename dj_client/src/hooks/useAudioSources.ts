@@ -1,5 +1,6 @@
-import { useState, useRef } from 'react';
+import { useState, useRef, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import type { AudioSource, AudioLevels } from '../types';
 
 export interface UseAudioSourcesReturn {
@@ -8,6 +9,7 @@ export interface UseAudioSourcesReturn {
   setSelectedSource: (source: string | null) => void;
   isTestingAudio: boolean;
   loadAudioSources: () => Promise<void>;
+  refreshAudioSources: () => Promise<void>;
   handleSourceChange: (sourceId: string | null, isConnected: boolean) => Promise<void>;
   handleStartTest: () => Promise<void>;
   handleStopTest: () => Promise<void>;
@@ -21,34 +23,56 @@ export function useAudioSources(): UseAudioSourcesReturn {
   const testIntervalRef = useRef<ReturnType<typeof setInterval> | null>(null);
   const testTimeoutRef = useRef<ReturnType<typeof setTimeout> | null>(null);
 
-  const loadAudioSources = async () => {
-    try {
-      const sources = await invoke<AudioSource[]>('list_audio_sources');
-      setAudioSources(sources);
-      if (sources.length === 0) {
-        setSelectedSource(null);
-        return;
+  const applySources = (sources: AudioSource[]) => {
+    setAudioSources(sources);
+    if (sources.length === 0) {
+      setSelectedSource(null);
+      return;
+    }
+
+    setSelectedSource((currentSelected) => {
+      const selectedStillExists = currentSelected
+        ? sources.some((source) => source.id === currentSelected)
+        : false;
+      if (selectedStillExists) {
+        return currentSelected;
       }
 
-      setSelectedSource((currentSelected) => {
-        const selectedStillExists = currentSelected
-          ? sources.some((source) => source.id === currentSelected)
-          : false;
-        if (selectedStillExists) {
-          return currentSelected;
-        }
+      const savedSourceId = localStorage.getItem('mcav.audioSource');
+      const savedSourceExists = savedSourceId
+        ? sources.some((source) => source.id === savedSourceId)
+        : false;
+      return savedSourceExists ? savedSourceId : sources[0].id;
+    });
+  };
 
-        const savedSourceId = localStorage.getItem('mcav.audioSource');
-        const savedSourceExists = savedSourceId
-          ? sources.some((source) => source.id === savedSourceId)
-          : false;
-        return savedSourceExists ? savedSourceId : sources[0].id;
-      });
+  const loadAudioSources = async () => {
+    try {
+      applySources(await invoke<AudioSource[]>('list_audio_sources'));
     } catch (e) {
       console.error('Failed to load audio sources:', e);
     }
   };
 
+  // Forces a rescan; the backend also broadcasts the result as sources-changed
+  const refreshAudioSources = async () => {
+    try {
+      applySources(await invoke<AudioSource[]>('refresh_sources'));
+    } catch (e) {
+      console.error('Failed to refresh audio sources:', e);
+    }
+  };
+
+  // Device hot-plug updates pushed by the backend
+  useEffect(() => {
+    const unlisten = listen<AudioSource[]>('sources-changed', (event) => {
+      applySources(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn()).catch(() => {});
+    };
+  }, []);
+
   const handleSourceChange = async (sourceId: string | null, isConnected: boolean) => {
     setSelectedSource(sourceId);
     if (sourceId) {
@@ -117,6 +141,7 @@ export function useAudioSources(): UseAudioSourcesReturn {
     setSelectedSource,
     isTestingAudio,
     loadAudioSources,
+    refreshAudioSources,
     handleSourceChange,
     handleStartTest,
     handleStopTest,
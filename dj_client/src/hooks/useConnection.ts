@@ -16,6 +16,7 @@ import {
   DEFAULT_CONNECTION_STATUS,
   DEFAULT_VOICE_STATUS,
   DEFAULT_AUDIO_DATA,
+  isDjError,
 } from '../types';
 
 export interface UseConnectionReturn {
@@ -256,10 +257,17 @@ export function useConnection(auth: UseAuthReturn): UseConnectionReturn {
 
       setStatus((prev) => ({ ...prev, connected: true }));
     } catch (e) {
-      const errStr = String(e);
+      const djError = isDjError(e) ? e : null;
+      const errStr = djError ? djError.message : String(e);
       let errorMessage = errStr;
 
-      if (e instanceof api.ApiError) {
+      if (djError) {
+        if (djError.code === 'timeout' || djError.code === 'network') {
+          errorMessage = "Can't reach server. Check that the VJ server is running.";
+        } else if (djError.code === 'auth') {
+          errorMessage = 'Authentication failed. Ask your VJ operator for a new code.';
+        }
+      } else if (e instanceof api.ApiError) {
         if (e.status === 404) {
           errorMessage = 'Connect code not found. Check the code and try again.';
         } else if (e.status === 409) {
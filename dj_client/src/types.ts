@@ -8,11 +8,19 @@ export interface ConnectionStatus {
   connected: boolean;
   is_active: boolean;
   latency_ms: number;
+  /** Server time minus local time, once measured */
+  server_time_offset_ms: number | null;
   route_mode: string;
   mc_connected: boolean;
   queue_position: number;
   total_djs: number;
   active_dj_name: string | null;
+  /** Connection health 0-100; 0 while disconnected */
+  health: number;
+  /** Entity count the server asked for, once known */
+  requested_entity_count: number | null;
+  /** Entity count actually rendered, after the local cap */
+  effective_entity_count: number | null;
   error: string | null;
 }
 
@@ -22,6 +30,91 @@ export interface AudioLevels {
   is_beat: boolean;
   beat_intensity: number;
   bpm: number;
+  is_paused: boolean;
+}
+
+export interface BeatEvent {
+  intensity: number;
+  bpm: number;
+  beat_phase: number;
+  /** Unix time in seconds */
+  ts: number;
+}
+
+/** Emitted as `tempo-changed` when the locked tempo moves by more than 4 BPM */
+export interface TempoChange {
+  old_bpm: number;
+  new_bpm: number;
+  confidence: number;
+}
+
+/** Emitted as `entity-count-clamped` when the server asks for more entities
+ * than the local cap */
+export interface EntityCountClamp {
+  requested: number;
+  effective: number;
+}
+
+/** From `get_capabilities`: what this build and platform support */
+export interface Capabilities {
+  opus: boolean;
+  per_app_capture: boolean;
+  process_loopback: boolean;
+  platform: string;
+}
+
+/** From `get_patterns`: scripts the server pushed and the one running */
+export interface PatternList {
+  loaded: string[];
+  active: string | null;
+}
+
+/** Emitted as `clipping-detected` (at most every 10s) while the analyzed
+ * input sits at full scale; lowering the input gain usually fixes it */
+export interface ClippingWarning {
+  input_gain_db: number;
+}
+
+export interface BassLaneLevels {
+  instant_bass: number;
+  instant_kick: boolean;
+  raw_envelope: number;
+  running_peak: number;
+}
+
+export interface BeatDebug {
+  bass: number;
+  avg: number;
+  bass_threshold: number;
+  bass_flux: number;
+  flux_mean: number;
+  flux_std: number;
+  flux_threshold: number;
+  beat_threshold: number;
+  kind: 'none' | 'onset' | 'predicted';
+}
+
+/** Per-call analyzer timing from `benchmark_analysis`, in microseconds */
+export interface AnalysisBenchmark {
+  iterations: number;
+  fft_size: number;
+  sample_rate: number;
+  mean_us: number;
+  p99_us: number;
+  max_us: number;
+}
+
+/** Scale of the reported bands; `db` maps the dB floor..0 dBFS onto 0-1 */
+export type BandScale = 'linear' | 'db';
+
+/** Signal the beat detector listens to */
+export type BeatSource = 'bass_band' | 'bass_lane' | 'combined';
+
+/** A visualization zone on the connected server, from `get_zones` */
+export interface ZoneInfo {
+  name: string;
+  world: string | null;
+  entity_count: number | null;
 }
 
 export interface VoiceStatus {
@@ -29,15 +122,148 @@ export interface VoiceStatus {
   streaming: boolean;
   channel_type: string;
   connected_players: number;
+  /** Mic level (0-1) of the latest voice frame */
+  level: number;
+  /** Encoded frames waiting to be sent */
+  queue_depth: number;
+  /** Voice codec, known as soon as capture starts */
+  codec: 'opus' | 'pcm';
 }
 
 export interface CaptureMode {
-  mode: 'pending' | 'system_loopback' | 'process_loopback' | 'input_device';
+  mode:
+    | 'pending'
+    | 'system_loopback'
+    | 'process_loopback'
+    | 'input_device'
+    | 'file_playback'
+    | 'lost';
   fallback_reason?: string;
+  path?: string;
+  reason?: string;
   pid?: number;
   name?: string;
 }
 
+export interface MixSource {
+  source_id: string;
+  gain: number;
+}
+
+export interface CaptureStatus {
+  active: boolean;
+  source_id: string | null;
+  capture_mode: CaptureMode | null;
+  /** Sources summed before analysis, primary first */
+  mix: MixSource[];
+  /** Why per-app capture fell back to all system audio, if it did */
+  capture_fallback: string | null;
+}
+
+/** Playback state of a `file:` source */
+export interface FilePlaybackStatus {
+  path: string;
+  position_secs: number;
+  duration_secs: number | null;
+  paused: boolean;
+  looping: boolean;
+  ended: boolean;
+}
+
+/** Structured error from the connect and capture commands */
+export interface DjError {
+  code:
+    | 'auth'
+    | 'timeout'
+    | 'network'
+    | 'content_rejected'
+    | 'invalid_input'
+    | 'already_connected'
+    | 'not_connected'
+    | 'audio_device'
+    | 'source_not_found'
+    | 'capture_not_running'
+    | 'internal';
+  message: string;
+}
+
+export function isDjError(e: unknown): e is DjError {
+  return typeof e === 'object' && e !== null && 'code' in e && 'message' in e;
+}
+
+export interface ConnectionTestResult {
+  reachable: boolean;
+  code_accepted: boolean;
+  connect_ms: number;
+  protocol_version: string | null;
+  error: string | null;
+}
+
+export type PreviewFixture =
+  | { kind: 'frames'; frames: Record<string, unknown>[] }
+  | { kind: 'beats'; bpm: number; frames: number };
+
+export interface BeatAssistConfig {
+  enabled: boolean;
+  min_tempo_confidence: number;
+  phase_window: number;
+  kick_assist: boolean;
+}
+
+export interface BridgeStats {
+  window_ms: number;
+  tick_rate: number;
+  max_tick_gap_ms: number;
+  max_lock_wait_ms: number;
+  vj_frames_sent: number;
+  vj_frames_dropped: number;
+  mc_frames_sent: number;
+  skipped_no_analysis: number;
+  skipped_idle: number;
+}
+
+/** A note or CC from the open MIDI port, emitted as `midi-event` */
+export interface MidiEvent {
+  port: string;
+  kind: 'note_on' | 'note_off' | 'control_change';
+  channel: number;
+  number: number;
+  value: number;
+}
+
+export type MidiTrigger =
+  | { type: 'note'; channel?: number | null; note: number }
+  | { type: 'cc'; channel?: number | null; controller: number };
+
+export type MidiAction =
+  | { action: 'next_preset' }
+  | { action: 'prev_preset' }
+  | { action: 'set_preset'; name: string }
+  | { action: 'trigger_beat'; intensity?: number }
+  | { action: 'toggle_voice' }
+  | { action: 'fire_effect'; effect: string };
+
+export interface MidiMapping {
+  bindings: { trigger: MidiTrigger; action: MidiAction }[];
+}
+
+/** A captured log line from `get_logs` */
+export interface LogLine {
+  /** Unix time in seconds */
+  ts: number;
+  level: 'ERROR' | 'WARN' | 'INFO';
+  target: string;
+  message: string;
+}
+
+export interface AppSnapshot {
+  status: ConnectionStatus;
+  audio_levels: AudioLevels;
+  voice_status: VoiceStatus;
+  active_preset: string;
+  capture_info: CaptureStatus;
+}
+
 export interface RosterDJ {
   dj_id: string;
   dj_name: string;
@@ -64,11 +290,15 @@ export const DEFAULT_CONNECTION_STATUS: ConnectionStatus = {
   connected: false,
   is_active: false,
   latency_ms: 0,
+  server_time_offset_ms: null,
   route_mode: '',
   mc_connected: false,
   queue_position: 0,
   total_djs: 0,
   active_dj_name: null,
+  health: 0,
+  requested_entity_count: null,
+  effective_entity_count: null,
   error: null,
 };
 
@@ -77,6 +307,9 @@ export const DEFAULT_VOICE_STATUS: VoiceStatus = {
   streaming: false,
   channel_type: 'static',
   connected_players: 0,
+  level: 0,
+  queue_depth: 0,
+  codec: 'pcm',
 };
 
 export const DEFAULT_AUDIO_DATA: AudioData = {